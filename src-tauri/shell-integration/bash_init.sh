@@ -82,6 +82,47 @@ __aiterm_emit_host() {
     printf "\033]633;H;%s\007" "$(__aiterm_get_hostname)"
 }
 
+# Escape a value for OSC 633 (backslash, semicolon and control characters)
+__aiterm_escape_value() {
+    local v="$1"
+    v="${v//\\/\\\\}"
+    v="${v//;/\\x3b}"
+    v="${v//$'\n'/\\x0a}"
+    v="${v//$'\a'/\\x07}"
+    v="${v//$'\e'/\\x1b}"
+    printf '%s' "$v"
+}
+
+# Report the command line (OSC 633;E) so the backend can track command blocks
+__aiterm_emit_command() {
+    local cmd="$1"
+    [ -n "$cmd" ] || return 0
+    printf "\033]633;E;%s\007" "$(__aiterm_escape_value "$cmd")"
+}
+
+__aiterm_emit_cwd() {
+    printf "\033]633;P;Cwd=%s\007" "$(__aiterm_escape_value "$PWD")"
+}
+
+# Percent-encode a path for a file:// URL, byte by byte
+__aiterm_urlencode_path() {
+    local LC_ALL=C
+    local s="$1" out="" c i
+    for ((i = 0; i < ${#s}; i++)); do
+        c="${s:i:1}"
+        case "$c" in
+            [A-Za-z0-9/._~-]) out+="$c" ;;
+            *) out+="$(printf '%%%02X' "'$c")" ;;
+        esac
+    done
+    printf '%s' "$out"
+}
+
+# Report the cwd as OSC 7 (file://host/path), understood by other terminals too
+__aiterm_emit_osc7() {
+    printf "\033]7;file://%s%s\007" "$(__aiterm_get_hostname)" "$(__aiterm_urlencode_path "$PWD")"
+}
+
 # Emit RemoteHost OSC sequence for SSH detection
 __aiterm_emit_remote_host() {
     if [ -n "$SSH_CONNECTION" ] || [ -n "$SSH_CLIENT" ] || [ -n "$SSH_TTY" ]; then
@@ -115,6 +156,8 @@ __aiterm_emit_remote_host() {
 __aiterm_mark_prompt() { 
     __aiterm_emit "A"
     __aiterm_emit_remote_host  # Update SSH state on every prompt
+    __aiterm_emit_cwd
+    __aiterm_emit_osc7
 }
 __aiterm_mark_output_start() { __aiterm_emit "C"; }
 __aiterm_mark_done() { local ret=${1:-$?}; __aiterm_emit "D;${ret}"; }
@@ -675,12 +718,24 @@ if [ -n "$BASH_VERSION" ]; then
     # This is critical for proper line wrapping, especially in SSH sessions
     shopt -s checkwinsize 2>/dev/null || true
     
+    # Newest history entry as "number<TAB>command line"
+    __aiterm_last_history() {
+        local entry number
+        entry="$(HISTTIMEFORMAT= builtin history 1 2>/dev/null)"
+        entry="${entry#"${entry%%[![:space:]]*}"}"
+        number="${entry%%[!0-9]*}"
+        [ -n "$number" ] || return 0
+        printf '%s\t%s' "$number" "${entry#"$number"[[:space:]][[:space:]]}"
+    }
+
     __aiterm_prompt_wrapper() {
         local ret=$?
         __AITERM_IN_PROMPT=1
         __aiterm_mark_done "$ret"
         __aiterm_mark_prompt
         __aiterm_emit_host
+        __AITERM_HISTORY_SEEN="$(__aiterm_last_history)"
+        __AITERM_HISTORY_SEEN="${__AITERM_HISTORY_SEEN%%$'\t'*}"
         __AITERM_COMMAND_STARTED=
         __AITERM_IN_PROMPT=
     }
@@ -715,9 +770,22 @@ if [ -n "$BASH_VERSION" ]; then
         case "$BASH_COMMAND" in
             __aiterm_prompt_wrapper*|__aiterm_preexec*|__aiterm_mark_*|__aiterm_emit*) return ;;
         esac
+        # The whole line (`make && ./run`) is the history entry added since
+        # the prompt. A line kept out of history (ignorespace, ignoredups,
+        # history off) leaves only BASH_COMMAND, the first simple command.
+        local cmd="" entry
+        entry="$(__aiterm_last_history)"
+        if [ -n "$entry" ] && [ "${entry%%$'\t'*}" != "$__AITERM_HISTORY_SEEN" ]; then
+            cmd="${entry#*$'\t'}"
+        fi
+        [ -n "$cmd" ] || cmd="$BASH_COMMAND"
         __AITERM_COMMAND_STARTED=1
+        __aiterm_emit_command "$cmd"
         __aiterm_mark_output_start
     }
+    # The rest of the rc files runs before the first prompt; none of it is
+    # a command the user typed
+    __AITERM_COMMAND_STARTED=1
     trap '__aiterm_preexec' DEBUG
 elif [ -n "$ZSH_VERSION" ]; then
     if [ -z "$__AITERM_ZSH_HOOKS" ]; then
@@ -727,6 +795,7 @@ elif [ -n "$ZSH_VERSION" ]; then
         __aiterm_preexec() {
             if [ -n "$__AITERM_COMMAND_STARTED" ]; then return; fi
             __AITERM_COMMAND_STARTED=1
+            __aiterm_emit_command "$1"
             __aiterm_mark_output_start
         }
         add-zsh-hook precmd __aiterm_precmd
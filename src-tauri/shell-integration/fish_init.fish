@@ -0,0 +1,81 @@
+# AI Terminal OSC 133 Shell Integration for fish
+#
+# Loaded with `fish --init-command`, which runs after the user's config.fish,
+# so these handlers are installed on top of whatever the user configured.
+# Emits the same sequences as bash_init.sh:
+#   133;A/C/D      prompt, output start and command finished
+#   633;E/P/H      command line, working directory, hostname
+#   1337;RemoteHost SSH detection
+#   7;file://      working directory as a URL
+
+if not set -q __aiterm_integration_loaded
+    set -g __aiterm_integration_loaded 1
+
+    # Advertise to downstream shells
+    set -gx TERM_PROGRAM aiterminal
+
+    function __aiterm_emit
+        printf '\e]133;%s\a' $argv[1]
+    end
+
+    function __aiterm_get_hostname
+        if not set -q __aiterm_hostname
+            set -g __aiterm_hostname (hostname -f 2>/dev/null; or hostname 2>/dev/null; or echo unknown)
+        end
+        printf '%s' $__aiterm_hostname[1]
+    end
+
+    # Escape a value for OSC 633 (backslash, semicolon and control characters)
+    function __aiterm_escape_value
+        set -l lines (string replace -a -- '\\' '\\\\' "$argv[1]" \
+            | string replace -a -- ';' '\\x3b' \
+            | string replace -a -- \a '\\x07' \
+            | string replace -a -- \e '\\x1b')
+        string join -- '\x0a' $lines
+    end
+
+    function __aiterm_emit_remote_host
+        if set -q SSH_CONNECTION; or set -q SSH_CLIENT; or set -q SSH_TTY
+            set -l current_user $USER
+            test -n "$current_user"; or set current_user (whoami 2>/dev/null; or echo unknown)
+            set -l depth 0
+            set -q __AITERM_SSH_DEPTH; and set depth $__AITERM_SSH_DEPTH
+
+            # SSH_CONNECTION is "client_ip client_port server_ip server_port"
+            set -l remote_ip
+            if set -q SSH_CONNECTION
+                set remote_ip (string split ' ' -- $SSH_CONNECTION)[3]
+            end
+
+            if test -n "$remote_ip"
+                printf '\e]1337;RemoteHost=%s@%s:%s;Depth=%d\a' $current_user (__aiterm_get_hostname) $remote_ip $depth
+            else
+                printf '\e]1337;RemoteHost=%s@%s;Depth=%d\a' $current_user (__aiterm_get_hostname) $depth
+            end
+        else
+            printf '\e]1337;RemoteHost=;Depth=0\a'
+        end
+    end
+
+    function __aiterm_postexec --on-event fish_postexec
+        set -g __aiterm_last_status $status
+    end
+
+    function __aiterm_prompt --on-event fish_prompt
+        set -l ret 0
+        set -q __aiterm_last_status; and set ret $__aiterm_last_status
+        __aiterm_emit "D;$ret"
+        __aiterm_emit A
+        __aiterm_emit_remote_host
+        printf '\e]633;P;Cwd=%s\a' (__aiterm_escape_value $PWD)
+        printf '\e]633;H;%s\a' (__aiterm_get_hostname)
+        printf '\e]7;file://%s%s\a' (__aiterm_get_hostname) (string escape --style=url -- $PWD)
+    end
+
+    function __aiterm_preexec --on-event fish_preexec
+        if test -n "$argv[1]"
+            printf '\e]633;E;%s\a' (__aiterm_escape_value $argv[1])
+        end
+        __aiterm_emit C
+    end
+end
@@ -7,7 +7,6 @@
 mod autocomplete;
 mod chat;
 mod context_index;
-mod health_check;
 mod history;
 mod keychain;
 mod models;
@@ -19,6 +18,7 @@ mod security;
 mod sessions;
 mod settings;
 mod ssh;
+mod terminal_profiles;
 mod tools;
 mod utils;
 
@@ -47,13 +47,18 @@ use keychain::{
     save_api_key_to_keychain,
 };
 pub use models::AppState;
+#[cfg(unix)]
+pub use pty::{run_pty_daemon, DAEMON_ARG};
 use preview::{get_preview_content, open_preview_window, read_preview_file, stop_preview_watcher};
-use pty::{check_pty_health, close_pty, focus_terminal, get_active_terminal, get_pty_cwd, get_pty_info, resize_pty, spawn_pty, write_to_pty};
+use pty::{ack_pty_output, add_pty_to_group, attach_pty_channel, cancel_command_capture, cancel_paste, check_pty_health, close_pty, connect_ssh_profile, connect_ssh_profile_native, create_input_group, delete_input_group, detach_pty_channel, focus_terminal, get_active_terminal, get_command_blocks, get_last_command, get_pty_cwd, get_pty_info, get_pty_process, get_ssh_latency_stats, get_terminal_context, get_tmux_windows, list_input_groups, list_recordings, load_triggers, paste_to_pty, read_pty_scrollback, reload_triggers, remove_pty_from_group, replay_recording, resize_pty, run_command_capture, save_triggers, search_pty_scrollback, set_pty_output_storm_mode, set_replay_speed, spawn_pty, spawn_pty_with_profile, start_pty_recording, stop_pty_recording, stop_replay, write_to_group, write_to_pty};
+#[cfg(unix)]
+use pty::{attach_daemon_session, detach_pty, list_daemon_sessions};
 use quick_actions::{load_quick_actions, save_quick_actions};
 use secret_scanner::scan_content_for_secrets;
 use sessions::{clear_session_state, has_saved_session, load_session_state, save_session_state};
 use settings::{delete_api_key, get_api_key, load_settings, save_api_key, save_settings};
-use ssh::{get_ssh_config_hosts, load_ssh_profiles, save_ssh_profiles};
+use ssh::{download_file, export_ssh_config, export_ssh_profile_bundle, get_ssh_config_hosts, import_ssh_config_hosts, import_ssh_profile_bundle, list_port_forwards, load_ssh_profiles, resolve_ssh_host, save_ssh_profiles, sftp_list_dir, sftp_read_file, start_port_forwards, stop_port_forwards, upload_file};
+use terminal_profiles::{load_terminal_profiles, save_terminal_profiles};
 use tauri::Emitter;
 use tools::{
     analyze_error_tool, append_to_file_tool, calculate_tool, check_port_tool, diff_files_tool,
@@ -61,8 +66,8 @@ use tools::{
     get_current_directory_tool, get_env_var_tool, get_file_info_tool, get_git_branch_tool,
     get_git_diff_tool, get_shell_history_tool, get_system_info_tool, git_status_tool,
     grep_in_files_tool, list_directory_tool, list_file_backups_tool, make_directory_tool,
-    read_file_tool, read_multiple_files_tool, replace_in_file_tool, search_files_tool,
-    tail_file_tool, undo_file_change_tool, web_search_tool, write_file_tool,
+    read_multiple_files_tool, replace_in_file_tool, search_files_tool, undo_file_change_tool,
+    web_search_tool, write_file_tool,
 };
 #[tauri::command]
 async fn context_index_sync(
@@ -229,6 +234,10 @@ pub fn run() {
         )))
         .setup(|app| {
             preview::init_preview_watchers(&app.handle());
+            {
+                use tauri::Manager;
+                pty::init_triggers(&app.state::<AppState>());
+            }
             
             // Disable press-and-hold accent menu for all windows
             #[cfg(target_os = "macos")]
@@ -257,16 +266,56 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             emit_event,
             measure_pty_latency,
+            get_ssh_latency_stats,
             get_pty_info,
+            get_terminal_context,
             get_pty_cwd,
             check_pty_health,
+            get_command_blocks,
+            get_last_command,
+            read_pty_scrollback,
+            search_pty_scrollback,
+            run_command_capture,
+            cancel_command_capture,
+            start_pty_recording,
+            stop_pty_recording,
+            list_recordings,
+            replay_recording,
+            set_replay_speed,
+            stop_replay,
+            attach_pty_channel,
+            detach_pty_channel,
+            ack_pty_output,
+            set_pty_output_storm_mode,
             focus_terminal,
             get_active_terminal,
             get_shell_history,
             spawn_pty,
+            spawn_pty_with_profile,
+            connect_ssh_profile,
+            connect_ssh_profile_native,
+            get_pty_process,
             write_to_pty,
+            paste_to_pty,
+            cancel_paste,
             resize_pty,
             close_pty,
+            get_tmux_windows,
+            create_input_group,
+            add_pty_to_group,
+            remove_pty_from_group,
+            delete_input_group,
+            list_input_groups,
+            write_to_group,
+            load_triggers,
+            save_triggers,
+            reload_triggers,
+            #[cfg(unix)]
+            list_daemon_sessions,
+            #[cfg(unix)]
+            attach_daemon_session,
+            #[cfg(unix)]
+            detach_pty,
             load_settings,
             save_settings,
             get_api_key,
@@ -277,8 +326,22 @@ pub fn run() {
             delete_api_key_from_keychain,
             check_keychain_available,
             get_ssh_config_hosts,
+            resolve_ssh_host,
             save_ssh_profiles,
             load_ssh_profiles,
+            export_ssh_config,
+            export_ssh_profile_bundle,
+            import_ssh_config_hosts,
+            import_ssh_profile_bundle,
+            start_port_forwards,
+            stop_port_forwards,
+            list_port_forwards,
+            sftp_list_dir,
+            sftp_read_file,
+            upload_file,
+            download_file,
+            save_terminal_profiles,
+            load_terminal_profiles,
             load_quick_actions,
             save_quick_actions,
             save_session_state,
@@ -292,7 +355,7 @@ pub fn run() {
             test_ai_connection,
             ai_chat,
             ai_chat_stream,
-            read_file_tool,
+            tools::filesystem::read_file_tool,
             get_file_info_tool,
             read_multiple_files_tool,
             grep_in_files_tool,
@@ -309,7 +372,7 @@ pub fn run() {
             find_process_tool,
             check_port_tool,
             get_system_info_tool,
-            tail_file_tool,
+            tools::filesystem::tail_file_tool,
             make_directory_tool,
             get_git_diff_tool,
             get_shell_history_tool,
@@ -333,7 +396,15 @@ pub fn run() {
             context_index_query,
             context_index_clear,
         ])
-        .run(tauri::generate_context!())
+        .build(tauri::generate_context!())
+        .map(|app| {
+            app.run(|app, event| {
+                if let tauri::RunEvent::Exit = event {
+                    use tauri::Manager;
+                    ssh::stop_all_port_forwards(&app.state::<AppState>());
+                }
+            })
+        })
         .map_err(|e| {
             eprintln!("Fatal error: Failed to run application: {}", e);
             std::process::exit(1);
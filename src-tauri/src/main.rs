@@ -2,5 +2,15 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    // The same executable doubles as the persistent PTY daemon
+    #[cfg(unix)]
+    if std::env::args().nth(1).as_deref() == Some(aiterminal_lib::DAEMON_ARG) {
+        if let Err(e) = aiterminal_lib::run_pty_daemon() {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     aiterminal_lib::run()
 }
@@ -1,14 +1,59 @@
 use portable_pty::{Child, MasterPty};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::Write;
-use std::sync::atomic::AtomicU32;
+use std::sync::atomic::{AtomicBool, AtomicU32};
 use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
 
 // PTY and buffering constants
 pub const PTY_BUFFER_SIZE: usize = 8192; // 8KB for PTY read buffer
 pub const MAX_STREAM_BUFFER_SIZE: usize = 1024 * 1024; // 1MB limit for SSE stream buffer
+pub const MAX_COMMAND_BLOCKS: usize = 500; // Finished command blocks kept per PTY
+pub const MAX_SCROLLBACK_LINES: usize = 10_000; // Lines of server-side scrollback kept per PTY
+pub const MAX_SCROLLBACK_LINE_LEN: usize = 4096; // Characters kept per scrollback line
+pub const OUTPUT_FRAME_MS: u64 = 12; // Minimum interval between output emits per PTY
+pub const OUTPUT_MAX_FRAME_BYTES: usize = 256 * 1024; // Emit early once a frame reaches this size
+pub const FLOW_HIGH_WATERMARK: usize = 1024 * 1024; // Pause reading above this many unacked bytes
+pub const FLOW_LOW_WATERMARK: usize = 256 * 1024; // Resume reading below this many unacked bytes
+pub const FLOW_ACK_TIMEOUT_MS: u64 = 10_000; // Stop throttling if the frontend stops acking
+pub const STORM_BYTES_PER_SEC: usize = 4 * 1024 * 1024; // Throughput that counts as an output storm
+pub const STORM_SUSTAIN_MS: u64 = 2_000; // How long throughput must stay high before suppressing
+pub const STORM_COOLDOWN_MS: u64 = 1_000; // How long throughput must stay low before resuming
+pub const MAX_CAPTURE_OUTPUT_BYTES: usize = 1024 * 1024; // 1MB of output returned by run_command_capture
+pub const PROCESS_POLL_MS: u64 = 1_000; // How often each PTY's foreground process is checked
+pub const MAX_PROCESS_TREE_DEPTH: usize = 8; // Child levels reported by get_pty_process
+pub const DAEMON_MAX_FRAME_BYTES: usize = 16 * 1024 * 1024; // Largest frame accepted on the daemon socket
+pub const DAEMON_REPLAY_BYTES: usize = 1024 * 1024; // Raw output kept per daemon session for reattach
+pub const DAEMON_REQUEST_TIMEOUT_MS: u64 = 5_000; // How long the app waits for a daemon reply
+pub const DAEMON_START_TIMEOUT_MS: u64 = 3_000; // How long the app waits for a new daemon to listen
+pub const DAEMON_IDLE_EXIT_SECS: u64 = 60; // Daemon exits after this long with no sessions or clients
+pub const TRIGGER_DEFAULT_COOLDOWN_MS: u64 = 1_000; // Minimum gap between firings of one trigger on one PTY
+pub const MAX_TRIGGER_HITS_PER_READ: usize = 16; // Trigger firings handled per PTY read, the rest are dropped
+pub const LATENCY_PROBE_INTERVAL_MS: u64 = 5_000; // Gap between TCP probes of an SSH host
+pub const LATENCY_PROBE_TIMEOUT_MS: u64 = 5_000; // A probe slower than this counts as lost
+pub const LATENCY_HISTORY_SIZE: usize = 120; // Probes kept per PTY for latency stats (10 min)
+pub const SSH_HEALTH_MIN_INTERVAL_SECS: u64 = 5; // Shortest health check interval a profile can ask for
+pub const SSH_BANNER_TIMEOUT_MS: u64 = 5_000; // A host that sends no SSH banner by then fails the health check
+pub const SSH_HEALTH_FAILURES_TO_DISCONNECT: u32 = 3; // Failed checks in a row, with no PTY output meanwhile and the PTY failing, that count as a disconnect
+pub const SSH_ESCAPE_GRACE_MS: u64 = 1_000; // Wait after typing `~.` to a hung ssh before typing the ssh command again
+pub const SSH_RECONNECT_POLL_MS: u64 = 1_000; // Gap between checks for the remote shell while reconnecting
+pub const SSH_RECONNECT_TIMEOUT_MS: u64 = 60_000; // An auto-reconnect that has not reached the remote shell by then is given up
+pub const PASTE_CHUNK_BYTES: usize = 1024; // Input above this is written in paced chunks
+pub const PASTE_CHUNK_DELAY_MS: u64 = 5; // Pause between paste chunks so the tty input queue can drain
+pub const PASTE_PROGRESS_THRESHOLD_BYTES: usize = 64 * 1024; // Pastes above this emit pty-paste-progress events
+pub const PASTE_PROGRESS_INTERVAL_MS: u64 = 100; // Minimum gap between progress events of one paste
+pub const PORT_FORWARD_CHECK_INTERVAL_MS: u64 = 5_000; // Gap between health checks of an active port forward
+pub const PORT_FORWARD_START_TIMEOUT_MS: u64 = 15_000; // A forward whose local port is not bound by then is restarted
+pub const PORT_FORWARD_BACKOFF_START_MS: u64 = 1_000; // First restart delay; doubles after each failed attempt
+pub const PORT_FORWARD_BACKOFF_MAX_MS: u64 = 60_000; // Cap for the restart delay
+pub const PORT_FORWARD_STABLE_MS: u64 = 30_000; // A forward active this long restarts with the first delay again
+pub const TRANSFER_CHUNK_BYTES: usize = 64 * 1024; // Read/write size of file transfers to and from SSH hosts
+pub const TRANSFER_PROGRESS_INTERVAL_MS: u64 = 200; // Minimum gap between transfer-progress events of one transfer
+#[cfg(feature = "native-ssh")]
+pub const NATIVE_SSH_CONNECT_TIMEOUT_MS: u64 = 15_000; // TCP connect, handshake and auth of the in-process SSH client
+#[cfg(feature = "native-ssh")]
+pub const NATIVE_SSH_POLL_MAX_MS: u64 = 16; // Longest sleep between polls of an idle native SSH channel
 
 // Network and timeout constants
 pub const HTTP_TIMEOUT_SECS: u64 = 120;
@@ -54,6 +99,9 @@ pub struct AiSettings {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TerminalSettings {
     pub max_markers: u16,
+    /// Run shells in the background PTY daemon so they survive app restarts
+    #[serde(default)]
+    pub persistent_sessions: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -142,6 +190,7 @@ impl Default for AppSettings {
             },
             terminal: TerminalSettings {
                 max_markers: DEFAULT_MAX_MARKERS,
+                persistent_sessions: false,
             },
             autocomplete: AutocompleteSettings::default(),
             streaming: StreamingSettings::default(),
@@ -155,6 +204,9 @@ pub struct PtySession {
     pub child: Option<Box<dyn Child + Send + Sync>>,
     pub reader_handle: Option<JoinHandle<()>>,
     pub ssh_session: Option<SshSessionInfo>,
+    pub tmux_pane: Option<crate::pty::TmuxPaneRef>, // Set when this PTY is a tmux control-mode pane
+    pub remote_fs: Option<Arc<dyn crate::ssh::RemoteFs>>, // SFTP on the same connection when this PTY is a native SSH channel
+    pub ssh_profile_id: Option<String>, // Saved SSH profile this PTY was opened from
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -164,7 +216,6 @@ pub struct SshSessionInfo {
     pub remote_port: u16,
     pub connection_time: u64, // Unix timestamp
     pub last_latency_ms: Option<u64>,
-    pub latency_monitor_handle: Option<()>, // Placeholder for thread handle (can't serialize JoinHandle)
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -174,6 +225,19 @@ pub enum ContextConfidence {
     Low,    // Complex nesting or unclear
 }
 
+/// One SSH level in a PTY's host stack (local -> login node -> compute node)
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteHop {
+    pub user: String,
+    pub host: String,
+    pub ip: Option<String>,
+    pub depth: u32,
+    /// False when the shell did not report `Depth=N` and the level was inferred
+    pub depth_reported: bool,
+    pub cwd: Option<String>,
+    pub connected_at: u64, // Unix timestamp
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct TerminalContext {
     pub terminal_id: u32,
@@ -183,6 +247,7 @@ pub struct TerminalContext {
     pub confidence: ContextConfidence,
     pub connection_depth: u32, // 0=local, 1=ssh, 2=nested, etc.
     pub last_updated: u64,     // Unix timestamp
+    pub hops: Vec<RemoteHop>,  // Outermost first; empty when local
 }
 
 impl TerminalContext {
@@ -198,6 +263,7 @@ impl TerminalContext {
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs(),
+            hops: Vec::new(),
         }
     }
 }
@@ -206,6 +272,7 @@ impl TerminalContext {
 #[derive(Debug, Clone)]
 pub struct FileBackup {
     pub path: String,
+    pub host: Option<String>, // Remote host the file is on (None = this machine)
     pub content: String,
     pub timestamp: u64,
 }
@@ -222,10 +289,27 @@ pub struct AppState {
     pub keychain_lock: Mutex<()>,
     pub ssh_sessions: Arc<Mutex<HashMap<u32, SshSessionInfo>>>, // PTY ID -> SSH info, wrapped in Arc for thread sharing
     pub terminal_contexts: Arc<Mutex<HashMap<u32, TerminalContext>>>, // PTY ID -> Context
+    pub pty_cwds: Arc<Mutex<HashMap<u32, crate::pty::PtyCwd>>>, // PTY ID -> last working directory the shell reported
+    pub latency_monitors: Arc<Mutex<HashMap<u32, crate::pty::LatencyMonitor>>>, // PTY ID -> SSH latency probe task and history
+    pub paste_queues: Mutex<HashMap<u32, Arc<crate::pty::PasteQueue>>>, // PTY ID -> ordered, paced input writer
+    pub bracketed_paste: Arc<Mutex<HashSet<u32>>>, // PTY IDs whose foreground app enabled bracketed paste (mode 2004)
+    pub port_forwards: Mutex<HashMap<String, crate::ssh::PortForwardSupervisor>>, // "profileId/forwardId" -> background forward and its status
     pub context_index: Mutex<crate::context_index::ContextIndex>,
     pub file_backups: Mutex<Vec<FileBackup>>, // Stack of file backups for undo functionality
     pub pty_last_output: Arc<Mutex<HashMap<u32, u64>>>, // PTY ID -> last output timestamp (ms since epoch)
+    pub command_blocks: Arc<Mutex<HashMap<u32, crate::pty::CommandTracker>>>, // PTY ID -> OSC 133 command history
+    pub pty_scrollback: Arc<Mutex<HashMap<u32, crate::pty::ScrollbackBuffer>>>, // PTY ID -> plain-text output history
+    pub capture_runs: Mutex<HashMap<String, Arc<AtomicBool>>>, // Run ID -> cancel flag for headless captures
+    pub pty_recordings: Arc<Mutex<HashMap<u32, crate::pty::PtyRecorder>>>, // PTY ID -> active asciicast recorder
+    pub replays: Arc<Mutex<HashMap<u32, Arc<crate::pty::ReplayControl>>>>, // Replay pane ID -> playback controls
+    pub pty_channels: Arc<Mutex<HashMap<u32, tauri::ipc::Channel<tauri::ipc::InvokeResponseBody>>>>, // PTY ID -> raw output channel
+    pub pty_output: Arc<Mutex<HashMap<u32, Arc<crate::pty::OutputControl>>>>, // PTY ID -> flow control and storm state
     pub active_terminal: AtomicU32, // Currently focused terminal ID (0 = none)
+    pub tmux_gateways: Mutex<HashMap<u32, Arc<crate::pty::TmuxGateway>>>, // Gateway PTY ID -> tmux control-mode client
+    pub input_groups: Mutex<HashMap<String, crate::pty::InputGroup>>, // Group ID -> PTYs sharing broadcast input
+    pub triggers: Arc<Mutex<Arc<crate::pty::TriggerSet>>>, // Compiled output triggers, replaced when rules are saved
+    #[cfg(unix)]
+    pub pty_daemon: Mutex<Option<Arc<crate::pty::DaemonClient>>>, // Connection to the persistent PTY daemon
 }
 
 impl AppState {
@@ -237,10 +321,27 @@ impl AppState {
             keychain_lock: Mutex::new(()),
             ssh_sessions: Arc::new(Mutex::new(HashMap::new())),
             terminal_contexts: Arc::new(Mutex::new(HashMap::new())),
+            pty_cwds: Arc::new(Mutex::new(HashMap::new())),
+            latency_monitors: Arc::new(Mutex::new(HashMap::new())),
+            paste_queues: Mutex::new(HashMap::new()),
+            bracketed_paste: Arc::new(Mutex::new(HashSet::new())),
+            port_forwards: Mutex::new(HashMap::new()),
             context_index: Mutex::new(crate::context_index::ContextIndex::default()),
             file_backups: Mutex::new(Vec::new()),
             pty_last_output: Arc::new(Mutex::new(HashMap::new())),
+            command_blocks: Arc::new(Mutex::new(HashMap::new())),
+            pty_scrollback: Arc::new(Mutex::new(HashMap::new())),
+            capture_runs: Mutex::new(HashMap::new()),
+            pty_recordings: Arc::new(Mutex::new(HashMap::new())),
+            replays: Arc::new(Mutex::new(HashMap::new())),
+            pty_channels: Arc::new(Mutex::new(HashMap::new())),
+            pty_output: Arc::new(Mutex::new(HashMap::new())),
             active_terminal: AtomicU32::new(0),
+            tmux_gateways: Mutex::new(HashMap::new()),
+            input_groups: Mutex::new(HashMap::new()),
+            triggers: Arc::new(Mutex::new(Arc::new(crate::pty::TriggerSet::empty()))),
+            #[cfg(unix)]
+            pty_daemon: Mutex::new(None),
         }
     }
 }
@@ -0,0 +1,436 @@
+use super::command_blocks::CommandTracker;
+use super::integration::{
+    configure_shell_command, fish_quote, is_nushell, nu_quote, setup_integration_scripts,
+};
+use super::osc_parser::OscScanner;
+use super::scrollback::ScrollbackBuffer;
+use super::shell::resolve_shell;
+use crate::models::{AppState, MAX_CAPTURE_OUTPUT_BYTES, PTY_BUFFER_SIZE};
+use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem};
+use serde::Serialize;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+const DEFAULT_CAPTURE_TIMEOUT_MS: u64 = 120_000;
+const MAX_CAPTURE_TIMEOUT_MS: u64 = 30 * 60 * 1000;
+/// How long the shell gets to print its first integrated prompt
+const PROMPT_TIMEOUT_MS: u64 = 15_000;
+/// Granularity of timeout and cancellation checks
+const POLL_INTERVAL_MS: u64 = 50;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureStatus {
+    /// The shell reported 133;D for the command
+    Completed,
+    TimedOut,
+    Cancelled,
+    /// The shell exited before the command finished (e.g. the command ran `exit`)
+    Exited,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CaptureResult {
+    pub run_id: String,
+    pub status: CaptureStatus,
+    /// Terminal output with escape sequences stripped. A PTY merges stdout and
+    /// stderr, so both appear here in the order they were written.
+    pub output: String,
+    /// Exit code from 133;D (None unless status is `completed`)
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    /// True if output exceeded MAX_CAPTURE_OUTPUT_BYTES and the start was dropped
+    pub truncated: bool,
+}
+
+/// Run a command in a hidden PTY and capture its output.
+///
+/// The shell is started exactly like a visible terminal (same integration
+/// scripts), so aliases, functions and environment match what the user sees.
+/// Pass a `run_id` to be able to abort the run with `cancel_command_capture`.
+#[tauri::command]
+pub async fn run_command_capture(
+    command: String,
+    cwd: Option<String>,
+    timeout_ms: Option<u64>,
+    run_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<CaptureResult, String> {
+    if command.trim().is_empty() {
+        return Err("Command cannot be empty".to_string());
+    }
+
+    let run_id = run_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let cancelled = Arc::new(AtomicBool::new(false));
+    {
+        let mut runs = state
+            .capture_runs
+            .lock()
+            .map_err(|e| format!("Failed to acquire capture lock: {}", e))?;
+        if runs.contains_key(&run_id) {
+            return Err(format!("Capture {} is already running", run_id));
+        }
+        runs.insert(run_id.clone(), cancelled.clone());
+    }
+
+    let timeout = Duration::from_millis(
+        timeout_ms
+            .unwrap_or(DEFAULT_CAPTURE_TIMEOUT_MS)
+            .clamp(1, MAX_CAPTURE_TIMEOUT_MS),
+    );
+    let task_run_id = run_id.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        run_capture(task_run_id, &command, cwd.as_deref(), timeout, &cancelled)
+    })
+    .await
+    .map_err(|e| format!("Capture task failed: {}", e));
+
+    if let Ok(mut runs) = state.capture_runs.lock() {
+        runs.remove(&run_id);
+    }
+
+    result?
+}
+
+/// Abort a running capture. Returns false if no capture with that ID is running.
+#[tauri::command]
+pub fn cancel_command_capture(run_id: String, state: State<AppState>) -> Result<bool, String> {
+    let runs = state
+        .capture_runs
+        .lock()
+        .map_err(|e| format!("Failed to acquire capture lock: {}", e))?;
+    match runs.get(&run_id) {
+        Some(flag) => {
+            flag.store(true, Ordering::SeqCst);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Raw PTY output kept for slicing by stream offset, bounded from the front
+struct OutputWindow {
+    data: Vec<u8>,
+    /// Absolute stream offset of `data[0]`
+    base: u64,
+    truncated_before: Option<u64>,
+}
+
+impl OutputWindow {
+    fn push(&mut self, chunk: &[u8]) {
+        self.data.extend_from_slice(chunk);
+        // Keep twice the limit so the prompt and echo never crowd out output
+        let limit = MAX_CAPTURE_OUTPUT_BYTES * 2;
+        if self.data.len() > limit {
+            let excess = self.data.len() - limit;
+            self.data.drain(..excess);
+            self.base += excess as u64;
+            self.truncated_before = Some(self.base);
+        }
+    }
+
+    /// Bytes in [start, end), plus whether anything before `end` was dropped
+    fn slice(&self, start: u64, end: u64) -> (&[u8], bool) {
+        let end = end.clamp(self.base, self.base + self.data.len() as u64);
+        let mut from = start.clamp(self.base, end);
+        let mut truncated = self.truncated_before.is_some_and(|t| t > start);
+        if end - from > MAX_CAPTURE_OUTPUT_BYTES as u64 {
+            from = end - MAX_CAPTURE_OUTPUT_BYTES as u64;
+            truncated = true;
+        }
+        (
+            &self.data[(from - self.base) as usize..(end - self.base) as usize],
+            truncated,
+        )
+    }
+}
+
+/// Wrap multi-line input so the shell runs it as one command (one 133;C/D pair)
+fn command_line(command: &str, shell: &str) -> String {
+    let command = command.trim_end_matches(['\r', '\n']);
+    if !command.contains('\n') {
+        return command.to_string();
+    }
+    let lines: Vec<&str> = command
+        .split('\n')
+        .map(|line| line.trim_end_matches('\r'))
+        .collect();
+    if shell.ends_with("bash") || shell.ends_with("zsh") {
+        let mut quoted = String::from("eval $'");
+        for ch in lines.join("\n").chars() {
+            match ch {
+                '\\' => quoted.push_str("\\\\"),
+                '\'' => quoted.push_str("\\'"),
+                '\n' => quoted.push_str("\\n"),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('\'');
+        quoted
+    } else if shell.ends_with("fish") {
+        // An unquoted \n between quoted pieces is a newline in the same token
+        let pieces: Vec<String> = lines.iter().map(|line| fish_quote(line)).collect();
+        format!("eval {}", pieces.join("\\n"))
+    } else if is_nushell(shell) {
+        // nushell has no eval; run the script in a child of the same binary
+        let script = nu_quote(&lines.join("\n")).replace('\n', "\\n");
+        format!("^$nu.current-exe -c {}", script)
+    } else {
+        // POSIX sh has no $'...'; a single-quoted string may span lines
+        format!("eval '{}'", lines.join("\n").replace('\'', "'\\''"))
+    }
+}
+
+fn strip_ansi(raw: &[u8]) -> String {
+    let mut buffer = ScrollbackBuffer::new();
+    buffer.push_str(&String::from_utf8_lossy(raw));
+    buffer.last_lines(usize::MAX).join("\n")
+}
+
+pub(crate) fn run_capture(
+    run_id: String,
+    command: &str,
+    cwd: Option<&str>,
+    timeout: Duration,
+    cancelled: &AtomicBool,
+) -> Result<CaptureResult, String> {
+    let started = Instant::now();
+    let deadline = started + timeout;
+
+    let pty_system = NativePtySystem::default();
+    // Wide enough that typical output is not wrapped by the line discipline
+    let pair = pty_system
+        .openpty(PtySize {
+            rows: 50,
+            cols: 400,
+            pixel_width: 0,
+            pixel_height: 0,
+        })
+        .map_err(|e| e.to_string())?;
+
+    let shell = resolve_shell();
+    let mut cmd = CommandBuilder::new(&shell);
+    let config_dir = setup_integration_scripts();
+    configure_shell_command(&mut cmd, &shell, config_dir.as_ref());
+    // Nothing is there to page through or answer interactive prompts
+    cmd.env("PAGER", "cat");
+    cmd.env("GIT_PAGER", "cat");
+    cmd.env("HISTFILE", "/dev/null");
+    if let Some(dir) = cwd {
+        let dir = shellexpand::tilde(dir).to_string();
+        if !std::path::Path::new(&dir).is_dir() {
+            return Err(format!("Working directory does not exist: {}", dir));
+        }
+        cmd.cwd(dir);
+    }
+
+    let mut child = pair.slave.spawn_command(cmd).map_err(|e| e.to_string())?;
+    drop(pair.slave);
+
+    let mut reader = pair.master.try_clone_reader().map_err(|e| e.to_string())?;
+    let mut writer = pair.master.take_writer().map_err(|e| e.to_string())?;
+
+    let (tx, rx) = mpsc::channel::<Vec<u8>>();
+    let reader_handle = std::thread::spawn(move || {
+        let mut buf = [0u8; PTY_BUFFER_SIZE];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if tx.send(buf[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    let mut scanner = OscScanner::new();
+    let mut tracker = CommandTracker::new();
+    let mut window = OutputWindow {
+        data: Vec::new(),
+        base: 0,
+        truncated_before: None,
+    };
+    let prompt_deadline = started + Duration::from_millis(PROMPT_TIMEOUT_MS);
+    let mut command_sent = false;
+    let mut output_start: Option<u64> = None;
+    let mut finished = None;
+
+    let status = loop {
+        if cancelled.load(Ordering::SeqCst) {
+            break Ok(CaptureStatus::Cancelled);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            break Ok(CaptureStatus::TimedOut);
+        }
+        if !command_sent && now >= prompt_deadline {
+            break Err(format!(
+                "Shell {} did not report a prompt; shell integration may be unavailable",
+                shell
+            ));
+        }
+
+        let chunk = match rx.recv_timeout(Duration::from_millis(POLL_INTERVAL_MS)) {
+            Ok(chunk) => chunk,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break Ok(CaptureStatus::Exited),
+        };
+        window.push(&chunk);
+
+        let mut prompt_seen = false;
+        for seq in scanner.feed(&chunk) {
+            let is_prompt = matches!(seq.code, 133 | 633) && seq.payload == "A";
+            if let Some(block) = tracker.handle_osc(&seq) {
+                if command_sent {
+                    finished = Some(block);
+                }
+            }
+            if is_prompt {
+                prompt_seen = true;
+            }
+            if output_start.is_none() && command_sent {
+                output_start = tracker.running().map(|block| block.output_start);
+            }
+        }
+
+        if finished.is_some() {
+            break Ok(CaptureStatus::Completed);
+        }
+        if prompt_seen && !command_sent {
+            let line = format!("{}\r", command_line(command, &shell));
+            if let Err(e) = writer
+                .write_all(line.as_bytes())
+                .and_then(|_| writer.flush())
+            {
+                break Err(format!("Failed to write command: {}", e));
+            }
+            command_sent = true;
+        }
+    };
+
+    // Every way out of the loop, errors included, reaps the shell and its reader
+    let _ = child.kill();
+    let _ = child.wait();
+    drop(writer);
+    drop(pair.master);
+    let _ = reader_handle.join();
+    let status = status?;
+
+    let (output, truncated, exit_code, duration_ms) = match (&finished, output_start) {
+        (Some(block), _) => {
+            let (raw, truncated) = window.slice(
+                block.output_start,
+                block.output_end.unwrap_or(block.output_start),
+            );
+            (
+                strip_ansi(raw),
+                truncated,
+                block.exit_code,
+                block.duration_ms.unwrap_or_default(),
+            )
+        }
+        (None, Some(start)) => {
+            let end = window.base + window.data.len() as u64;
+            let (raw, truncated) = window.slice(start, end);
+            (
+                strip_ansi(raw),
+                truncated,
+                None,
+                started.elapsed().as_millis() as u64,
+            )
+        }
+        (None, None) => (
+            String::new(),
+            false,
+            None,
+            started.elapsed().as_millis() as u64,
+        ),
+    };
+
+    Ok(CaptureResult {
+        run_id,
+        status,
+        output,
+        exit_code,
+        duration_ms,
+        truncated,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line_wraps_multiline_input() {
+        assert_eq!(command_line("ls -la\n", "/bin/bash"), "ls -la");
+        let script = "echo 'a'\necho \\b";
+        assert_eq!(
+            command_line(script, "/bin/bash"),
+            r"eval $'echo \'a\'\necho \\b'"
+        );
+        assert_eq!(
+            command_line(script, "/usr/bin/fish"),
+            r"eval 'echo \'a\''\n'echo \\b'"
+        );
+        assert_eq!(
+            command_line(script, "/usr/bin/nu"),
+            r#"^$nu.current-exe -c "echo 'a'\necho \\b""#
+        );
+        assert_eq!(
+            command_line(script, "/bin/sh"),
+            "eval 'echo '\\''a'\\''\necho \\b'"
+        );
+    }
+
+    #[test]
+    fn test_output_window_slices_by_stream_offset() {
+        let mut window = OutputWindow {
+            data: Vec::new(),
+            base: 0,
+            truncated_before: None,
+        };
+        window.push(b"$ ls\r\n");
+        window.push(b"a.txt\r\n$ ");
+        let (raw, truncated) = window.slice(6, 13);
+        assert_eq!(raw, b"a.txt\r\n");
+        assert!(!truncated);
+        assert_eq!(strip_ansi(raw), "a.txt");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_capture_reports_output_exit_code_and_timeout() {
+        let (_home_guard, _home_dir) = crate::tests::helpers::with_test_home();
+        let cancelled = AtomicBool::new(false);
+
+        let result = run_capture(
+            "test".to_string(),
+            "echo hello; ls /aiterminal_missing_dir",
+            Some("/tmp"),
+            Duration::from_secs(20),
+            &cancelled,
+        )
+        .unwrap();
+        assert_eq!(result.status, CaptureStatus::Completed);
+        assert_eq!(result.exit_code, Some(2));
+        assert!(result.output.starts_with("hello\n"));
+
+        let result = run_capture(
+            "test".to_string(),
+            "echo start; sleep 5",
+            None,
+            Duration::from_secs(2),
+            &cancelled,
+        )
+        .unwrap();
+        assert_eq!(result.status, CaptureStatus::TimedOut);
+        assert_eq!(result.output, "start");
+    }
+}
@@ -0,0 +1,269 @@
+use super::osc_parser::{current_timestamp_ms, unescape_osc_value, OscSequence};
+use crate::models::MAX_COMMAND_BLOCKS;
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// A single shell command delimited by OSC 133/633 marks
+#[derive(Serialize, Debug, Clone)]
+pub struct CommandBlock {
+    /// Sequence number of the block within its PTY
+    pub id: u64,
+    /// Command line as reported by OSC 633;E (None if the shell did not report it)
+    pub command: Option<String>,
+    /// Working directory reported at the prompt
+    pub cwd: Option<String>,
+    /// Stream offset of the prompt start (133;A)
+    pub prompt_offset: u64,
+    /// Stream offset where command output begins (just after 133;C)
+    pub output_start: u64,
+    /// Stream offset where command output ends (start of 133;D)
+    pub output_end: Option<u64>,
+    /// Exit code from 133;D (None while running or if not reported)
+    pub exit_code: Option<i32>,
+    /// Start time in ms since epoch
+    pub started_at: u64,
+    /// Completion time in ms since epoch (None while running)
+    pub finished_at: Option<u64>,
+    /// Wall-clock duration in ms (None while running)
+    pub duration_ms: Option<u64>,
+}
+
+/// Prompt/command state between marks
+#[derive(Debug, Default)]
+struct PendingBlock {
+    prompt_offset: u64,
+    command: Option<String>,
+    cwd: Option<String>,
+    output_start: Option<u64>,
+    started_at: Option<u64>,
+}
+
+/// Per-PTY command boundary tracker fed by the reader thread
+#[derive(Debug, Default)]
+pub struct CommandTracker {
+    blocks: VecDeque<CommandBlock>,
+    pending: Option<PendingBlock>,
+    next_id: u64,
+    cwd: Option<String>,
+    in_repl: bool,
+}
+
+impl CommandTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply an OSC sequence. Returns the finished block when a command completes.
+    pub fn handle_osc(&mut self, seq: &OscSequence) -> Option<CommandBlock> {
+        match seq.code {
+            133 | 633 => {
+                let mut parts = seq.payload.splitn(2, ';');
+                let mark = parts.next().unwrap_or("");
+                let rest = parts.next().unwrap_or("");
+                self.handle_mark(mark, rest, seq)
+            }
+            1337 => {
+                // Python/R REPL prompts emit their own 133 marks nested inside the
+                // outer shell command; ignore them so the outer block stays intact.
+                if let Some(value) = seq
+                    .payload
+                    .strip_prefix("PythonREPL=")
+                    .or_else(|| seq.payload.strip_prefix("RREPL="))
+                {
+                    self.in_repl = value == "1";
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn handle_mark(&mut self, mark: &str, rest: &str, seq: &OscSequence) -> Option<CommandBlock> {
+        if self.in_repl && matches!(mark, "A" | "B" | "C" | "D") {
+            return None;
+        }
+
+        match mark {
+            "A" => {
+                // A prompt without a preceding D closes any running block
+                let finished = self.finish(seq.start, None);
+                self.pending = Some(PendingBlock {
+                    prompt_offset: seq.start,
+                    cwd: self.cwd.clone(),
+                    ..Default::default()
+                });
+                finished
+            }
+            "C" => {
+                let pending = self.pending.get_or_insert_with(|| PendingBlock {
+                    prompt_offset: seq.start,
+                    ..Default::default()
+                });
+                if pending.output_start.is_none() {
+                    pending.output_start = Some(seq.end);
+                    pending.started_at = Some(current_timestamp_ms());
+                    if pending.cwd.is_none() {
+                        pending.cwd = self.cwd.clone();
+                    }
+                }
+                None
+            }
+            "D" => {
+                let exit_code = rest
+                    .split(';')
+                    .next()
+                    .and_then(|code| code.trim().parse::<i32>().ok());
+                self.finish(seq.start, exit_code)
+            }
+            "E" => {
+                let command = rest.split(';').next().map(unescape_osc_value);
+                if let Some(pending) = self.pending.as_mut() {
+                    pending.command = command.filter(|c| !c.trim().is_empty());
+                }
+                None
+            }
+            "P" => {
+                if let Some(cwd) = rest.strip_prefix("Cwd=") {
+                    let cwd = unescape_osc_value(cwd);
+                    if let Some(pending) = self.pending.as_mut() {
+                        if pending.output_start.is_none() {
+                            pending.cwd = Some(cwd.clone());
+                        }
+                    }
+                    self.cwd = Some(cwd);
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Close the pending block if it produced output; prompts that never reached
+    /// 133;C (empty command lines) are discarded.
+    fn finish(&mut self, end_offset: u64, exit_code: Option<i32>) -> Option<CommandBlock> {
+        let pending = self.pending.take()?;
+        let output_start = pending.output_start?;
+
+        let finished_at = current_timestamp_ms();
+        let started_at = pending.started_at.unwrap_or(finished_at);
+        let block = CommandBlock {
+            id: self.next_id,
+            command: pending.command,
+            cwd: pending.cwd,
+            prompt_offset: pending.prompt_offset,
+            output_start,
+            output_end: Some(end_offset.max(output_start)),
+            exit_code,
+            started_at,
+            finished_at: Some(finished_at),
+            duration_ms: Some(finished_at.saturating_sub(started_at)),
+        };
+        self.next_id += 1;
+
+        self.blocks.push_back(block.clone());
+        while self.blocks.len() > MAX_COMMAND_BLOCKS {
+            self.blocks.pop_front();
+        }
+
+        Some(block)
+    }
+
+    /// The command currently running, if any
+    pub fn running(&self) -> Option<CommandBlock> {
+        let pending = self.pending.as_ref()?;
+        let output_start = pending.output_start?;
+        Some(CommandBlock {
+            id: self.next_id,
+            command: pending.command.clone(),
+            cwd: pending.cwd.clone(),
+            prompt_offset: pending.prompt_offset,
+            output_start,
+            output_end: None,
+            exit_code: None,
+            started_at: pending.started_at.unwrap_or_default(),
+            finished_at: None,
+            duration_ms: None,
+        })
+    }
+
+    /// Finished blocks, oldest first
+    pub fn blocks(&self) -> impl Iterator<Item = &CommandBlock> {
+        self.blocks.iter()
+    }
+
+    pub fn last_finished(&self) -> Option<&CommandBlock> {
+        self.blocks.back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pty::osc_parser::OscScanner;
+
+    fn feed(
+        tracker: &mut CommandTracker,
+        scanner: &mut OscScanner,
+        data: &[u8],
+    ) -> Vec<CommandBlock> {
+        scanner
+            .feed(data)
+            .iter()
+            .filter_map(|seq| tracker.handle_osc(seq))
+            .collect()
+    }
+
+    #[test]
+    fn test_tracks_command_lifecycle() {
+        let mut tracker = CommandTracker::new();
+        let mut scanner = OscScanner::new();
+
+        let done = feed(
+            &mut tracker,
+            &mut scanner,
+            b"\x1b]633;P;Cwd=/home/u\x07\x1b]133;A\x07$ \x1b]633;E;false\x07\x1b]133;C\x07",
+        );
+        assert!(done.is_empty());
+        assert_eq!(tracker.running().unwrap().command.as_deref(), Some("false"));
+
+        let done = feed(
+            &mut tracker,
+            &mut scanner,
+            b"oops\n\x1b]133;D;1\x07\x1b]133;A\x07",
+        );
+        assert_eq!(done.len(), 1);
+        let block = &done[0];
+        assert_eq!(block.exit_code, Some(1));
+        assert_eq!(block.cwd.as_deref(), Some("/home/u"));
+        assert_eq!(block.output_end.unwrap() - block.output_start, 5);
+        assert!(tracker.running().is_none());
+    }
+
+    #[test]
+    fn test_empty_prompt_is_not_a_block() {
+        let mut tracker = CommandTracker::new();
+        let mut scanner = OscScanner::new();
+        let done = feed(
+            &mut tracker,
+            &mut scanner,
+            b"\x1b]133;A\x07$ \x1b]133;D;0\x07\x1b]133;A\x07",
+        );
+        assert!(done.is_empty());
+        assert_eq!(tracker.blocks().count(), 0);
+    }
+
+    #[test]
+    fn test_repl_marks_do_not_split_outer_command() {
+        let mut tracker = CommandTracker::new();
+        let mut scanner = OscScanner::new();
+        let done = feed(
+            &mut tracker,
+            &mut scanner,
+            b"\x1b]133;A\x07\x1b]633;E;python3\x07\x1b]133;C\x07\x1b]1337;PythonREPL=1\x07\
+              \x1b]133;A;py=1\x07\x1b]133;C;py=1\x07\x1b]133;D;0;py=1\x07\x1b]1337;PythonREPL=0\x07\
+              \x1b]133;D;0\x07",
+        );
+        assert_eq!(done.len(), 1);
+        assert_eq!(done[0].command.as_deref(), Some("python3"));
+    }
+}
@@ -1,9 +1,13 @@
-use super::PtyInfo;
-use crate::models::AppState;
+use super::output::OutputStatus;
+use super::scrollback::ScrollbackMatch;
+use super::context::PtyCwd;
+use super::{CommandBlock, PtyInfo};
+use crate::models::{AppState, ContextConfidence, TerminalContext};
 use portable_pty::PtySize;
 use serde::Serialize;
 use std::io::Write;
 use std::sync::atomic::Ordering;
+use tauri::ipc::{Channel, InvokeResponseBody};
 use tauri::State;
 
 /// Terminal health status
@@ -17,6 +21,8 @@ pub struct TerminalHealth {
     pub ms_since_last_output: Option<u64>,
     /// Overall health status: "healthy", "idle", "unresponsive", "dead"
     pub status: String,
+    /// Output flow control and storm state (None once the reader has exited)
+    pub output: Option<OutputStatus>,
 }
 
 #[tauri::command]
@@ -59,26 +65,48 @@ pub fn get_pty_info(id: u32, state: State<AppState>) -> Result<PtyInfo, String>
     }
 }
 
+/// Tauri command: which machine the PTY's shell is on, including every hop
+/// of a nested SSH session
 #[tauri::command]
-pub fn write_to_pty(id: u32, data: String, state: State<AppState>) -> Result<(), String> {
-    let mut ptys = state
+pub fn get_terminal_context(id: u32, state: State<AppState>) -> Result<TerminalContext, String> {
+    if !state
         .ptys
         .lock()
-        .map_err(|e| format!("Failed to acquire PTY lock: {}", e))?;
-
-    if let Some(session) = ptys.get_mut(&id) {
-        session
-            .writer
-            .write_all(data.as_bytes())
-            .map_err(|e| format!("Failed to write to PTY {}: {}", id, e))?;
-        session
-            .writer
-            .flush()
-            .map_err(|e| format!("Failed to flush PTY {}: {}", id, e))?;
-        Ok(())
-    } else {
-        Err(format!("PTY {} not found", id))
+        .map_err(|e| format!("Failed to acquire PTY lock: {}", e))?
+        .contains_key(&id)
+    {
+        return Err(format!("PTY {} not found", id));
     }
+
+    let contexts = state
+        .terminal_contexts
+        .lock()
+        .map_err(|e| format!("Failed to acquire terminal context lock: {}", e))?;
+    Ok(contexts
+        .get(&id)
+        .cloned()
+        .unwrap_or_else(|| TerminalContext {
+            // No shell integration report yet
+            confidence: ContextConfidence::Low,
+            ..TerminalContext::new_local(id)
+        }))
+}
+
+/// Tauri command: write input to a PTY. Large input (e.g. a paste) is
+/// written in paced chunks in the background, with later input queued behind it.
+#[tauri::command]
+pub fn write_to_pty(
+    id: u32,
+    data: String,
+    window: tauri::Window,
+    state: State<AppState>,
+) -> Result<(), String> {
+    super::paste::paste_queue(id, &state)?.write(
+        id,
+        data.into_bytes(),
+        super::paste::progress_emitter(window, id),
+    )?;
+    Ok(())
 }
 
 #[tauri::command]
@@ -104,17 +132,72 @@ pub fn resize_pty(id: u32, rows: u16, cols: u16, state: State<AppState>) {
             pixel_height: 0,
         }) {
             eprintln!("Failed to resize PTY: {}", e);
+            return;
+        }
+    }
+    drop(ptys);
+
+    if let Ok(mut recordings) = state.pty_recordings.lock() {
+        if let Some(recorder) = recordings.get_mut(&id) {
+            recorder.resize(cols, rows);
         }
     }
 }
 
 #[tauri::command]
 pub fn close_pty(id: u32, state: State<AppState>) {
+    // Wake the reader if it is paused by flow control so it can see EOF
+    if let Ok(mut outputs) = state.pty_output.lock() {
+        if let Some(control) = outputs.remove(&id) {
+            control.close();
+        }
+    }
+
     // Remove SSH session tracking if exists
     if let Ok(mut sessions) = state.ssh_sessions.lock() {
         sessions.remove(&id);
     }
 
+    if let Ok(mut contexts) = state.terminal_contexts.lock() {
+        contexts.remove(&id);
+    }
+
+    if let Ok(mut cwds) = state.pty_cwds.lock() {
+        cwds.remove(&id);
+    }
+
+    super::latency::stop_latency_monitor(id, &state.latency_monitors);
+
+    if let Ok(mut queues) = state.paste_queues.lock() {
+        if let Some(queue) = queues.remove(&id) {
+            queue.close();
+        }
+    }
+
+    if let Ok(mut bracketed) = state.bracketed_paste.lock() {
+        bracketed.remove(&id);
+    }
+
+    if let Ok(mut trackers) = state.command_blocks.lock() {
+        trackers.remove(&id);
+    }
+
+    if let Ok(mut scrollback) = state.pty_scrollback.lock() {
+        scrollback.remove(&id);
+    }
+
+    if let Ok(mut recordings) = state.pty_recordings.lock() {
+        if let Some(recorder) = recordings.remove(&id) {
+            recorder.finish();
+        }
+    }
+
+    if let Ok(mut channels) = state.pty_channels.lock() {
+        channels.remove(&id);
+    }
+
+    super::groups::remove_from_groups(id, &state);
+
     let session = {
         let mut ptys = match state.ptys.lock() {
             Ok(p) => p,
@@ -166,8 +249,13 @@ pub fn close_pty(id: u32, state: State<AppState>) {
     }
 }
 
+/// Tauri command: working directory of the PTY's shell.
+///
+/// Prefers what the shell last reported (OSC 7 or OSC 633;P), which is the
+/// remote directory during SSH sessions. Otherwise the local process's cwd is
+/// used, unless the PTY is known to be on a remote host.
 #[tauri::command]
-pub fn get_pty_cwd(id: u32, state: State<AppState>) -> Result<String, String> {
+pub fn get_pty_cwd(id: u32, state: State<AppState>) -> Result<PtyCwd, String> {
     let ptys = state
         .ptys
         .lock()
@@ -177,6 +265,35 @@ pub fn get_pty_cwd(id: u32, state: State<AppState>) -> Result<String, String> {
         .get(&id)
         .ok_or_else(|| format!("PTY {} not found", id))?;
 
+    if let Some(cwd) = state
+        .pty_cwds
+        .lock()
+        .map_err(|e| format!("Failed to acquire cwd lock: {}", e))?
+        .get(&id)
+    {
+        return Ok(cwd.clone());
+    }
+
+    // The local process is the ssh client; its cwd says nothing about the remote side
+    if let Some(ssh) = state
+        .ssh_sessions
+        .lock()
+        .map_err(|e| format!("Failed to acquire SSH session lock: {}", e))?
+        .get(&id)
+    {
+        return Err(format!(
+            "PTY {} is on remote host {} and has not reported its working directory",
+            id, ssh.remote_host
+        ));
+    }
+
+    let local = |path: String, source: &str| PtyCwd {
+        path,
+        host: None,
+        remote: false,
+        source: source.to_string(),
+    };
+
     // Get the PID of the shell process
     if let Some(child) = &session.child {
         // On Unix systems, we can try to read the cwd from /proc or use lsof
@@ -202,7 +319,7 @@ pub fn get_pty_cwd(id: u32, state: State<AppState>) -> Result<String, String> {
                 // Parse lsof output: looking for "n<path>" line
                 for line in output_str.lines() {
                     if line.starts_with('n') {
-                        return Ok(line[1..].to_string());
+                        return Ok(local(line[1..].to_string(), "process"));
                     }
                 }
             }
@@ -214,7 +331,7 @@ pub fn get_pty_cwd(id: u32, state: State<AppState>) -> Result<String, String> {
             if let Some(pid) = child.process_id() {
                 let cwd_link = format!("/proc/{}/cwd", pid);
                 if let Ok(cwd) = fs::read_link(&cwd_link) {
-                    return Ok(cwd.to_string_lossy().to_string());
+                    return Ok(local(cwd.to_string_lossy().to_string(), "process"));
                 }
             }
         }
@@ -222,7 +339,7 @@ pub fn get_pty_cwd(id: u32, state: State<AppState>) -> Result<String, String> {
 
     // Fallback: return the app's current directory
     std::env::current_dir()
-        .map(|p| p.to_string_lossy().to_string())
+        .map(|p| local(p.to_string_lossy().to_string(), "fallback"))
         .map_err(|e| format!("Failed to get current directory: {}", e))
 }
 
@@ -230,6 +347,10 @@ pub fn get_pty_cwd(id: u32, state: State<AppState>) -> Result<String, String> {
 /// Returns health status including process state, writability, and idle time
 #[tauri::command]
 pub fn check_pty_health(id: u32, state: State<AppState>) -> Result<TerminalHealth, String> {
+    pty_health(id, &state)
+}
+
+pub(super) fn pty_health(id: u32, state: &AppState) -> Result<TerminalHealth, String> {
     let now_ms = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap()
@@ -291,14 +412,193 @@ pub fn check_pty_health(id: u32, state: State<AppState>) -> Result<TerminalHealt
         "healthy".to_string()
     };
 
+    let output = state
+        .pty_output
+        .lock()
+        .map_err(|e| format!("Failed to acquire output lock: {}", e))?
+        .get(&id)
+        .map(|control| control.status());
+
     Ok(TerminalHealth {
         process_alive,
         writable,
         ms_since_last_output,
         status,
+        output,
     })
 }
 
+fn ensure_pty_exists(id: u32, state: &State<AppState>) -> Result<(), String> {
+    let ptys = state
+        .ptys
+        .lock()
+        .map_err(|e| format!("Failed to acquire PTY lock: {}", e))?;
+
+    if ptys.contains_key(&id) {
+        Ok(())
+    } else {
+        Err(format!("PTY {} not found", id))
+    }
+}
+
+/// Get command blocks for a PTY, oldest first
+/// Includes the currently running command (exit_code/finished_at unset) as the last entry
+#[tauri::command]
+pub fn get_command_blocks(
+    id: u32,
+    limit: Option<usize>,
+    state: State<AppState>,
+) -> Result<Vec<CommandBlock>, String> {
+    ensure_pty_exists(id, &state)?;
+
+    let trackers = state
+        .command_blocks
+        .lock()
+        .map_err(|e| format!("Failed to acquire command block lock: {}", e))?;
+
+    let Some(tracker) = trackers.get(&id) else {
+        return Ok(Vec::new());
+    };
+
+    let mut blocks: Vec<CommandBlock> = tracker.blocks().cloned().collect();
+    blocks.extend(tracker.running());
+
+    if let Some(limit) = limit {
+        let skip = blocks.len().saturating_sub(limit);
+        blocks.drain(..skip);
+    }
+
+    Ok(blocks)
+}
+
+/// Get the most recently finished command for a PTY
+#[tauri::command]
+pub fn get_last_command(id: u32, state: State<AppState>) -> Result<Option<CommandBlock>, String> {
+    ensure_pty_exists(id, &state)?;
+
+    let trackers = state
+        .command_blocks
+        .lock()
+        .map_err(|e| format!("Failed to acquire command block lock: {}", e))?;
+
+    Ok(trackers
+        .get(&id)
+        .and_then(|tracker| tracker.last_finished().cloned()))
+}
+
+/// Read the last lines of plain-text output captured for a PTY
+#[tauri::command]
+pub fn read_pty_scrollback(
+    id: u32,
+    last_lines: Option<usize>,
+    state: State<AppState>,
+) -> Result<String, String> {
+    use crate::models::MAX_SCROLLBACK_LINES;
+
+    let count = last_lines.unwrap_or(100).min(MAX_SCROLLBACK_LINES);
+
+    let scrollback = state
+        .pty_scrollback
+        .lock()
+        .map_err(|e| format!("Failed to acquire scrollback lock: {}", e))?;
+
+    if let Some(buffer) = scrollback.get(&id) {
+        return Ok(buffer.last_lines(count).join("\n"));
+    }
+    drop(scrollback);
+
+    // PTY exists but has not produced output yet
+    ensure_pty_exists(id, &state)?;
+    Ok(String::new())
+}
+
+/// Search a PTY's scrollback with a regex, most recent matches first
+#[tauri::command]
+pub fn search_pty_scrollback(
+    id: u32,
+    regex: String,
+    max_results: Option<usize>,
+    state: State<AppState>,
+) -> Result<Vec<ScrollbackMatch>, String> {
+    let pattern = regex::RegexBuilder::new(&regex)
+        .size_limit(1024 * 1024)
+        .build()
+        .map_err(|e| format!("Invalid regex: {}", e))?;
+    let max_results = max_results.unwrap_or(50).min(500);
+
+    ensure_pty_exists(id, &state)?;
+
+    let scrollback = state
+        .pty_scrollback
+        .lock()
+        .map_err(|e| format!("Failed to acquire scrollback lock: {}", e))?;
+
+    Ok(scrollback
+        .get(&id)
+        .map(|buffer| buffer.search(&pattern, max_results))
+        .unwrap_or_default())
+}
+
+/// Stream raw PTY output over an IPC channel instead of `pty-data:{id}` events.
+/// Frames are the bytes exactly as read, so the frontend decodes UTF-8 itself.
+#[tauri::command]
+pub fn attach_pty_channel(
+    id: u32,
+    channel: Channel<InvokeResponseBody>,
+    state: State<AppState>,
+) -> Result<(), String> {
+    ensure_pty_exists(id, &state)?;
+    state
+        .pty_channels
+        .lock()
+        .map_err(|e| format!("Failed to acquire channel lock: {}", e))?
+        .insert(id, channel);
+    Ok(())
+}
+
+/// Revert to `pty-data:{id}` events
+#[tauri::command]
+pub fn detach_pty_channel(id: u32, state: State<AppState>) -> Result<(), String> {
+    state
+        .pty_channels
+        .lock()
+        .map_err(|e| format!("Failed to acquire channel lock: {}", e))?
+        .remove(&id);
+    Ok(())
+}
+
+/// Acknowledge output frames the frontend has finished rendering.
+/// The first ack turns on flow control for the PTY.
+#[tauri::command]
+pub fn ack_pty_output(id: u32, frames: usize, state: State<AppState>) -> Result<(), String> {
+    let outputs = state
+        .pty_output
+        .lock()
+        .map_err(|e| format!("Failed to acquire output lock: {}", e))?;
+    if let Some(control) = outputs.get(&id) {
+        control.ack(frames);
+    }
+    Ok(())
+}
+
+/// Suppress display of sustained output floods, summarizing them when they end
+#[tauri::command]
+pub fn set_pty_output_storm_mode(
+    id: u32,
+    enabled: bool,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let outputs = state
+        .pty_output
+        .lock()
+        .map_err(|e| format!("Failed to acquire output lock: {}", e))?;
+    let control = outputs
+        .get(&id)
+        .ok_or_else(|| format!("PTY {} not found", id))?;
+    control.set_storm_mode(enabled);
+    Ok(())
+}
+
 /// Set the currently active/focused terminal
 #[tauri::command]
 pub fn focus_terminal(id: u32, state: State<AppState>) {
@@ -0,0 +1,313 @@
+//! Which machine a PTY's shell is on.
+//!
+//! The shell integration reports `OSC 1337;RemoteHost=user@host:ip;Depth=N`
+//! at every prompt (`RemoteHost=;Depth=0` when local) and its working
+//! directory as `OSC 633;P;Cwd=...` and `OSC 7;file://host/path`. The reader
+//! keeps a stack of SSH hops from these reports, so exiting a nested session
+//! pops back to the level below instead of dropping to local.
+
+use super::osc_parser::{
+    current_timestamp, parse_cwd_url, parse_remote_host, unescape_osc_value, OscSequence,
+    RemoteHost,
+};
+use crate::models::{ContextConfidence, RemoteHop, TerminalContext};
+use serde::Serialize;
+
+/// Working directory of a PTY's shell, as returned by `get_pty_cwd`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PtyCwd {
+    pub path: String,
+    /// Machine the path is on, when known
+    pub host: Option<String>,
+    /// The path is on a remote machine, not this one's filesystem
+    pub remote: bool,
+    /// "osc7", "shell" (OSC 633;P), "process" or "fallback"
+    pub source: String,
+}
+
+pub struct HostStack {
+    /// Outermost hop first
+    hops: Vec<RemoteHop>,
+    /// Working directory reported while local
+    local_cwd: Option<String>,
+    /// Last working directory report, from either OSC 7 or OSC 633;P
+    cwd: Option<PtyCwd>,
+    /// This machine's name as its own shell reports it in OSC 7
+    local_host: Option<String>,
+    /// Set once any report arrived; before that the shell may lack integration
+    reported: bool,
+}
+
+impl HostStack {
+    pub fn new() -> Self {
+        Self {
+            hops: Vec::new(),
+            local_cwd: None,
+            cwd: None,
+            local_host: None,
+            reported: false,
+        }
+    }
+
+    /// Apply one OSC sequence. Returns true if it was a host or cwd report.
+    pub fn handle_osc(&mut self, seq: &OscSequence) -> bool {
+        match seq.code {
+            1337 => match parse_remote_host(&seq.payload) {
+                Some(Some(remote)) => self.enter(remote),
+                Some(None) => self.hops.clear(),
+                None => return false,
+            },
+            633 => {
+                let Some(cwd) = seq.payload.strip_prefix("P;Cwd=") else {
+                    return false;
+                };
+                let cwd = unescape_osc_value(cwd);
+                let top = self.hops.last();
+                self.cwd = Some(PtyCwd {
+                    path: cwd.clone(),
+                    host: top.map(|hop| hop.host.clone()).or(self.local_host.clone()),
+                    remote: top.is_some(),
+                    source: "shell".to_string(),
+                });
+                self.set_hop_cwd(cwd);
+            }
+            7 => {
+                let Some((host, path)) = parse_cwd_url(&seq.payload) else {
+                    return false;
+                };
+                let host = Some(host).filter(|host| !host.is_empty() && host != "localhost");
+                let remote = match (self.hops.last(), &host, &self.local_host) {
+                    (Some(_), _, _) => true,
+                    // A remote shell that reports OSC 7 but not RemoteHost
+                    (None, Some(host), Some(local)) => !host.eq_ignore_ascii_case(local),
+                    (None, Some(host), None) => {
+                        self.local_host = Some(host.clone());
+                        false
+                    }
+                    (None, None, _) => false,
+                };
+                self.cwd = Some(PtyCwd {
+                    path: path.clone(),
+                    host: host.or_else(|| self.hops.last().map(|hop| hop.host.clone())),
+                    remote,
+                    source: "osc7".to_string(),
+                });
+                if !remote || !self.hops.is_empty() {
+                    self.set_hop_cwd(path);
+                }
+            }
+            _ => return false,
+        }
+        self.reported = true;
+        true
+    }
+
+    fn set_hop_cwd(&mut self, cwd: String) {
+        match self.hops.last_mut() {
+            Some(hop) => hop.cwd = Some(cwd),
+            None => self.local_cwd = Some(cwd),
+        }
+    }
+
+    fn enter(&mut self, remote: RemoteHost) {
+        let same_host = |hop: &RemoteHop| {
+            hop.user == remote.user && hop.host.eq_ignore_ascii_case(&remote.host)
+        };
+
+        let depth = match remote.depth.filter(|depth| *depth > 0) {
+            Some(depth) => {
+                // A prompt at a lower level means the deeper sessions exited
+                if let Some(hop) = self.hops.iter_mut().find(|hop| hop.depth == depth) {
+                    if same_host(hop) {
+                        hop.ip = remote.ip.or(hop.ip.take());
+                        hop.depth_reported = true;
+                        self.hops.retain(|hop| hop.depth <= depth);
+                        return;
+                    }
+                }
+                self.hops.retain(|hop| hop.depth < depth);
+                (depth, true)
+            }
+            // Plain `ssh` or a hand-sourced integration: infer the level
+            None => {
+                if let Some(pos) = self.hops.iter().rposition(same_host) {
+                    self.hops.truncate(pos + 1);
+                    return;
+                }
+                (self.hops.last().map_or(1, |hop| hop.depth + 1), false)
+            }
+        };
+
+        self.hops.push(RemoteHop {
+            user: remote.user,
+            host: remote.host,
+            ip: remote.ip,
+            depth: depth.0,
+            depth_reported: depth.1,
+            cwd: None,
+            connected_at: current_timestamp(),
+        });
+    }
+
+    /// Last reported working directory
+    pub fn cwd(&self) -> Option<&PtyCwd> {
+        self.cwd.as_ref()
+    }
+
+    /// Outermost hop, the one this machine connected to
+    pub fn first(&self) -> Option<&RemoteHop> {
+        self.hops.first()
+    }
+
+    /// Innermost hop, i.e. the machine commands run on
+    pub fn top(&self) -> Option<&RemoteHop> {
+        self.hops.last()
+    }
+
+    pub fn context(&self, terminal_id: u32) -> TerminalContext {
+        let confidence = if !self.reported {
+            ContextConfidence::Low
+        } else if self
+            .hops
+            .iter()
+            .enumerate()
+            .any(|(i, hop)| hop.depth != i as u32 + 1)
+        {
+            // Levels were skipped, e.g. hops without shell integration
+            ContextConfidence::Low
+        } else if self.hops.iter().any(|hop| !hop.depth_reported) {
+            ContextConfidence::Medium
+        } else {
+            ContextConfidence::High
+        };
+
+        let top = self.top();
+        TerminalContext {
+            terminal_id,
+            is_ssh: top.is_some(),
+            remote_host: top.map(|hop| hop.host.clone()),
+            remote_cwd: top.and_then(|hop| hop.cwd.clone()),
+            confidence,
+            connection_depth: top.map_or(0, |hop| hop.depth),
+            last_updated: current_timestamp(),
+            hops: self.hops.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn osc(code: u32, payload: &str) -> OscSequence {
+        OscSequence {
+            code,
+            payload: payload.to_string(),
+            start: 0,
+            end: 0,
+        }
+    }
+
+    fn hosts(stack: &HostStack) -> Vec<(String, u32)> {
+        stack
+            .hops
+            .iter()
+            .map(|hop| (hop.host.clone(), hop.depth))
+            .collect()
+    }
+
+    #[test]
+    fn test_nested_hops_push_and_pop() {
+        let mut stack = HostStack::new();
+        assert!(matches!(
+            stack.context(1).confidence,
+            ContextConfidence::Low
+        ));
+
+        assert!(stack.handle_osc(&osc(633, "P;Cwd=/Users/me")));
+        assert!(stack.handle_osc(&osc(1337, "RemoteHost=;Depth=0")));
+        assert_eq!(stack.context(1).connection_depth, 0);
+
+        stack.handle_osc(&osc(1337, "RemoteHost=me@login1:10.0.0.5;Depth=1"));
+        stack.handle_osc(&osc(633, "P;Cwd=/home/me"));
+        stack.handle_osc(&osc(1337, "RemoteHost=me@node042;Depth=2"));
+        stack.handle_osc(&osc(633, "P;Cwd=/scratch/me"));
+        let context = stack.context(1);
+        assert_eq!(context.connection_depth, 2);
+        assert_eq!(context.remote_host.as_deref(), Some("node042"));
+        assert_eq!(context.remote_cwd.as_deref(), Some("/scratch/me"));
+        assert!(matches!(context.confidence, ContextConfidence::High));
+
+        // `exit` on the compute node: the login node prompts again
+        stack.handle_osc(&osc(1337, "RemoteHost=me@login1:10.0.0.5;Depth=1"));
+        assert_eq!(hosts(&stack), vec![("login1".to_string(), 1)]);
+        assert_eq!(stack.context(1).remote_cwd.as_deref(), Some("/home/me"));
+
+        stack.handle_osc(&osc(1337, "RemoteHost=;Depth=0"));
+        assert!(!stack.context(1).is_ssh);
+        assert_eq!(stack.local_cwd.as_deref(), Some("/Users/me"));
+        assert!(!stack.handle_osc(&osc(1337, "PythonREPL=1")));
+    }
+
+    #[test]
+    fn test_osc7_cwd_marks_remote_paths() {
+        let mut stack = HostStack::new();
+        stack.handle_osc(&osc(1337, "RemoteHost=;Depth=0"));
+        stack.handle_osc(&osc(7, "file://laptop.local/Users/me/My%20Docs"));
+        let cwd = stack.cwd().unwrap();
+        assert_eq!(cwd.path, "/Users/me/My Docs");
+        assert!(!cwd.remote);
+        assert_eq!(cwd.source, "osc7");
+
+        // Remote shell that reports OSC 7 on its own, without RemoteHost
+        stack.handle_osc(&osc(7, "file://gpu7/data"));
+        assert!(stack.cwd().unwrap().remote);
+        assert_eq!(stack.cwd().unwrap().host.as_deref(), Some("gpu7"));
+        assert_eq!(stack.local_cwd.as_deref(), Some("/Users/me/My Docs"));
+
+        stack.handle_osc(&osc(1337, "RemoteHost=me@login1;Depth=1"));
+        stack.handle_osc(&osc(633, "P;Cwd=/home/me"));
+        assert_eq!(
+            stack.cwd(),
+            Some(&PtyCwd {
+                path: "/home/me".to_string(),
+                host: Some("login1".to_string()),
+                remote: true,
+                source: "shell".to_string(),
+            })
+        );
+        stack.handle_osc(&osc(7, "file:///home/me/src"));
+        assert_eq!(stack.cwd().unwrap().host.as_deref(), Some("login1"));
+        assert_eq!(stack.context(1).remote_cwd.as_deref(), Some("/home/me/src"));
+    }
+
+    #[test]
+    fn test_untracked_depth_is_inferred() {
+        let mut stack = HostStack::new();
+        stack.handle_osc(&osc(1337, "RemoteHost=me@login1;Depth=1"));
+        // Shell on the next host did not get the depth passed along
+        stack.handle_osc(&osc(1337, "RemoteHost=me@gpu7;Depth=0"));
+        assert_eq!(
+            hosts(&stack),
+            vec![("login1".to_string(), 1), ("gpu7".to_string(), 2)]
+        );
+        assert!(matches!(
+            stack.context(1).confidence,
+            ContextConfidence::Medium
+        ));
+        // Same host again stays at the same level
+        stack.handle_osc(&osc(1337, "RemoteHost=me@gpu7;Depth=0"));
+        assert_eq!(stack.context(1).connection_depth, 2);
+        // Back on the login node
+        stack.handle_osc(&osc(1337, "RemoteHost=me@login1"));
+        assert_eq!(hosts(&stack), vec![("login1".to_string(), 1)]);
+
+        // Reported depth with a missing level in between
+        stack.handle_osc(&osc(1337, "RemoteHost=me@inner;Depth=3"));
+        assert_eq!(stack.context(1).connection_depth, 3);
+        assert!(matches!(
+            stack.context(1).confidence,
+            ContextConfidence::Low
+        ));
+    }
+}
@@ -0,0 +1,507 @@
+use super::protocol::{
+    decode_data, encode_control, encode_data, read_frame, ClientMessage, DaemonMessage, Request,
+    Response, SessionInfo, FRAME_CONTROL, FRAME_DATA,
+};
+use crate::models::DAEMON_REQUEST_TIMEOUT_MS;
+use portable_pty::{Child, ChildKiller, CommandBuilder, ExitStatus, MasterPty, PtySize};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::Duration;
+
+/// How a daemon session ended, from this app's point of view
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SessionEnd {
+    Exited(Option<u32>),
+    /// Detached or the daemon connection dropped; the process may still run
+    Disconnected,
+}
+
+#[derive(Debug, Default)]
+struct EndState {
+    end: Mutex<Option<SessionEnd>>,
+    changed: Condvar,
+}
+
+impl EndState {
+    fn set(&self, end: SessionEnd) {
+        if let Ok(mut current) = self.end.lock() {
+            current.get_or_insert(end);
+            self.changed.notify_all();
+        }
+    }
+
+    fn get(&self) -> Option<SessionEnd> {
+        self.end.lock().ok().and_then(|end| *end)
+    }
+
+    fn wait(&self) -> Option<SessionEnd> {
+        let mut end = self.end.lock().ok()?;
+        while end.is_none() {
+            end = self.changed.wait(end).ok()?;
+        }
+        *end
+    }
+}
+
+/// Connection from the app to the PTY daemon
+pub struct DaemonClient {
+    stream: Mutex<UnixStream>,
+    next_seq: AtomicU64,
+    pending: Mutex<HashMap<u64, mpsc::Sender<Result<Response, String>>>>,
+    outputs: Mutex<HashMap<u32, mpsc::Sender<Vec<u8>>>>,
+    ends: Mutex<HashMap<u32, Arc<EndState>>>,
+    connected: AtomicBool,
+}
+
+/// Handles for a daemon session, shaped like a local PTY so the rest of the
+/// PTY module does not need to know where the shell runs
+pub struct DaemonPty {
+    pub session: u32,
+    pub master: Box<dyn MasterPty + Send>,
+    pub child: Box<dyn Child + Send + Sync>,
+    pub reader: Box<dyn Read + Send>,
+}
+
+impl DaemonClient {
+    pub fn connect(socket_path: &Path) -> io::Result<Arc<Self>> {
+        let stream = UnixStream::connect(socket_path)?;
+        let reader = stream.try_clone()?;
+        let client = Arc::new(Self {
+            stream: Mutex::new(stream),
+            next_seq: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+            outputs: Mutex::new(HashMap::new()),
+            ends: Mutex::new(HashMap::new()),
+            connected: AtomicBool::new(true),
+        });
+
+        let dispatcher = client.clone();
+        std::thread::spawn(move || dispatcher.dispatch(reader));
+        Ok(client)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::SeqCst)
+    }
+
+    /// Close the connection. Sessions keep running in the daemon.
+    pub fn disconnect(&self) {
+        if let Ok(stream) = self.stream.lock() {
+            let _ = stream.shutdown(std::net::Shutdown::Both);
+        }
+    }
+
+    /// Route daemon frames to waiting requests and session readers
+    fn dispatch(&self, mut reader: UnixStream) {
+        while let Ok(Some((kind, payload))) = read_frame(&mut reader) {
+            match kind {
+                FRAME_CONTROL => match serde_json::from_slice::<DaemonMessage>(&payload) {
+                    Ok(DaemonMessage::Response { seq, result }) => {
+                        let waiter = self.pending.lock().ok().and_then(|mut p| p.remove(&seq));
+                        if let Some(waiter) = waiter {
+                            let _ = waiter.send(result);
+                        }
+                    }
+                    Ok(DaemonMessage::Exit { session, exit_code }) => {
+                        self.end_session(session, SessionEnd::Exited(exit_code));
+                    }
+                    Err(e) => eprintln!("[ptyd] Invalid message from daemon: {}", e),
+                },
+                FRAME_DATA => {
+                    if let Some((session, bytes)) = decode_data(&payload) {
+                        if let Ok(outputs) = self.outputs.lock() {
+                            if let Some(output) = outputs.get(&session) {
+                                let _ = output.send(bytes.to_vec());
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // Daemon gone: fail pending requests and end every session reader
+        self.connected.store(false, Ordering::SeqCst);
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.clear();
+        }
+        let sessions: Vec<u32> = self
+            .ends
+            .lock()
+            .map(|ends| ends.keys().copied().collect())
+            .unwrap_or_default();
+        for session in sessions {
+            self.end_session(session, SessionEnd::Disconnected);
+        }
+    }
+
+    /// Dropping the output sender gives the session reader EOF
+    fn end_session(&self, session: u32, end: SessionEnd) {
+        if let Ok(mut outputs) = self.outputs.lock() {
+            outputs.remove(&session);
+        }
+        let state = self
+            .ends
+            .lock()
+            .ok()
+            .and_then(|mut ends| ends.remove(&session));
+        if let Some(state) = state {
+            state.set(end);
+        }
+    }
+
+    fn send_frame(&self, frame: &[u8]) -> Result<(), String> {
+        let mut stream = self
+            .stream
+            .lock()
+            .map_err(|e| format!("Failed to acquire daemon socket lock: {}", e))?;
+        stream
+            .write_all(frame)
+            .map_err(|e| format!("Failed to write to PTY daemon: {}", e))
+    }
+
+    fn request(&self, request: Request) -> Result<Response, String> {
+        let seq = self.next_seq.fetch_add(1, Ordering::SeqCst);
+        let (tx, rx) = mpsc::channel();
+        self.pending
+            .lock()
+            .map_err(|e| format!("Failed to acquire daemon request lock: {}", e))?
+            .insert(seq, tx);
+
+        let frame = encode_control(&ClientMessage { seq, request })
+            .map_err(|e| format!("Failed to encode daemon request: {}", e))?;
+        if let Err(e) = self.send_frame(&frame) {
+            if let Ok(mut pending) = self.pending.lock() {
+                pending.remove(&seq);
+            }
+            return Err(e);
+        }
+
+        match rx.recv_timeout(Duration::from_millis(DAEMON_REQUEST_TIMEOUT_MS)) {
+            Ok(result) => result,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                if let Ok(mut pending) = self.pending.lock() {
+                    pending.remove(&seq);
+                }
+                Err("PTY daemon did not respond".to_string())
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                Err("PTY daemon connection closed".to_string())
+            }
+        }
+    }
+
+    /// Start `cmd` in a daemon-owned PTY and attach to it
+    pub fn spawn(
+        self: &Arc<Self>,
+        cmd: &CommandBuilder,
+        size: PtySize,
+    ) -> Result<DaemonPty, String> {
+        let argv = cmd
+            .get_argv()
+            .iter()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect();
+        let env = cmd
+            .iter_full_env_as_str()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let cwd = cmd.get_cwd().map(|dir| dir.to_string_lossy().to_string());
+
+        match self.request(Request::Spawn {
+            argv,
+            env,
+            cwd,
+            rows: size.rows,
+            cols: size.cols,
+        })? {
+            Response::Spawned { session, .. } => self.attach(session),
+            other => Err(format!("Unexpected daemon response: {:?}", other)),
+        }
+    }
+
+    /// Attach to an existing session. Output starts with the session's replay buffer.
+    pub fn attach(self: &Arc<Self>, session: u32) -> Result<DaemonPty, String> {
+        // Register before asking, so the replay that follows the reply has somewhere to go
+        let (tx, rx) = mpsc::channel();
+        let end = Arc::new(EndState::default());
+        self.outputs
+            .lock()
+            .map_err(|e| format!("Failed to acquire daemon output lock: {}", e))?
+            .insert(session, tx);
+        self.ends
+            .lock()
+            .map_err(|e| format!("Failed to acquire daemon session lock: {}", e))?
+            .insert(session, end.clone());
+
+        let info = match self.request(Request::Attach { session }) {
+            Ok(Response::Attached { info }) => info,
+            Ok(other) => {
+                self.end_session(session, SessionEnd::Disconnected);
+                return Err(format!("Unexpected daemon response: {:?}", other));
+            }
+            Err(e) => {
+                self.end_session(session, SessionEnd::Disconnected);
+                return Err(e);
+            }
+        };
+
+        Ok(DaemonPty {
+            session,
+            master: Box::new(DaemonMaster {
+                client: self.clone(),
+                session,
+                size: Mutex::new(PtySize {
+                    rows: info.rows,
+                    cols: info.cols,
+                    pixel_width: 0,
+                    pixel_height: 0,
+                }),
+            }),
+            child: Box::new(DaemonChild {
+                client: self.clone(),
+                session,
+                pid: info.pid,
+                end,
+            }),
+            reader: Box::new(DaemonReader {
+                rx,
+                chunk: Vec::new(),
+                pos: 0,
+            }),
+        })
+    }
+
+    /// Stop receiving output for a session; its process keeps running in the daemon
+    pub fn detach(&self, session: u32) -> Result<(), String> {
+        let result = self.request(Request::Detach { session }).map(|_| ());
+        self.end_session(session, SessionEnd::Disconnected);
+        result
+    }
+
+    pub fn list(&self) -> Result<Vec<SessionInfo>, String> {
+        match self.request(Request::List)? {
+            Response::Sessions { sessions } => Ok(sessions),
+            other => Err(format!("Unexpected daemon response: {:?}", other)),
+        }
+    }
+
+    fn write(&self, session: u32, bytes: &[u8]) -> io::Result<()> {
+        if !self.is_connected() {
+            return Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "PTY daemon connection closed",
+            ));
+        }
+        self.send_frame(&encode_data(session, bytes))
+            .map_err(io::Error::other)
+    }
+}
+
+/// Session output forwarded by the dispatcher; EOF once the session ends
+struct DaemonReader {
+    rx: mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for DaemonReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+struct DaemonWriter {
+    client: Arc<DaemonClient>,
+    session: u32,
+}
+
+impl Write for DaemonWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.client.write(self.session, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `MasterPty` whose terminal lives in the daemon
+pub struct DaemonMaster {
+    client: Arc<DaemonClient>,
+    session: u32,
+    size: Mutex<PtySize>,
+}
+
+impl DaemonMaster {
+    pub fn session(&self) -> u32 {
+        self.session
+    }
+
+    pub fn client(&self) -> &Arc<DaemonClient> {
+        &self.client
+    }
+}
+
+impl MasterPty for DaemonMaster {
+    fn resize(&self, size: PtySize) -> Result<(), anyhow::Error> {
+        self.client
+            .request(Request::Resize {
+                session: self.session,
+                rows: size.rows,
+                cols: size.cols,
+            })
+            .map_err(io::Error::other)?;
+        if let Ok(mut current) = self.size.lock() {
+            *current = size;
+        }
+        Ok(())
+    }
+
+    fn get_size(&self) -> Result<PtySize, anyhow::Error> {
+        Ok(*self
+            .size
+            .lock()
+            .map_err(|e| io::Error::other(e.to_string()))?)
+    }
+
+    /// Output arrives through `DaemonPty::reader`; a session has exactly one
+    fn try_clone_reader(&self) -> Result<Box<dyn Read + Send>, anyhow::Error> {
+        Err(io::Error::other("Daemon sessions have a single reader").into())
+    }
+
+    fn take_writer(&self) -> Result<Box<dyn Write + Send>, anyhow::Error> {
+        Ok(Box::new(DaemonWriter {
+            client: self.client.clone(),
+            session: self.session,
+        }))
+    }
+
+    fn process_group_leader(&self) -> Option<i32> {
+        match self.client.request(Request::ForegroundPid {
+            session: self.session,
+        }) {
+            Ok(Response::Pid { pid }) => pid.and_then(|pid| i32::try_from(pid).ok()),
+            _ => None,
+        }
+    }
+
+    fn as_raw_fd(&self) -> Option<std::os::unix::io::RawFd> {
+        None
+    }
+
+    fn tty_name(&self) -> Option<std::path::PathBuf> {
+        None
+    }
+}
+
+/// `Child` for a process owned by the daemon
+struct DaemonChild {
+    client: Arc<DaemonClient>,
+    session: u32,
+    pid: Option<u32>,
+    end: Arc<EndState>,
+}
+
+impl std::fmt::Debug for DaemonChild {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DaemonChild")
+            .field("session", &self.session)
+            .field("pid", &self.pid)
+            .finish()
+    }
+}
+
+/// Detached sessions report success so callers don't treat them as crashed
+fn exit_status(end: SessionEnd) -> ExitStatus {
+    match end {
+        SessionEnd::Exited(code) => ExitStatus::with_exit_code(code.unwrap_or(1)),
+        SessionEnd::Disconnected => ExitStatus::with_exit_code(0),
+    }
+}
+
+impl Child for DaemonChild {
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        Ok(self.end.get().map(exit_status))
+    }
+
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        self.end
+            .wait()
+            .map(exit_status)
+            .ok_or_else(|| io::Error::other("Daemon session state lost"))
+    }
+
+    fn process_id(&self) -> Option<u32> {
+        self.pid
+    }
+}
+
+impl ChildKiller for DaemonChild {
+    fn kill(&mut self) -> io::Result<()> {
+        if self.end.get().is_some() {
+            return Ok(());
+        }
+        self.client
+            .request(Request::Kill {
+                session: self.session,
+            })
+            .map(|_| ())
+            .map_err(io::Error::other)
+    }
+
+    fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+        Box::new(DaemonKiller {
+            client: self.client.clone(),
+            session: self.session,
+        })
+    }
+}
+
+struct DaemonKiller {
+    client: Arc<DaemonClient>,
+    session: u32,
+}
+
+impl std::fmt::Debug for DaemonKiller {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DaemonKiller")
+            .field("session", &self.session)
+            .finish()
+    }
+}
+
+impl ChildKiller for DaemonKiller {
+    fn kill(&mut self) -> io::Result<()> {
+        self.client
+            .request(Request::Kill {
+                session: self.session,
+            })
+            .map(|_| ())
+            .map_err(io::Error::other)
+    }
+
+    fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+        Box::new(DaemonKiller {
+            client: self.client.clone(),
+            session: self.session,
+        })
+    }
+}
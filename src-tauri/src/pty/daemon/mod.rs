@@ -0,0 +1,236 @@
+//! Optional background daemon that owns PTYs so shells survive app restarts.
+//!
+//! The app runs its own executable with `--pty-daemon` and talks to it over a
+//! Unix socket (see `protocol`). Daemon sessions are wrapped in `MasterPty`
+//! and `Child` implementations, so `write_to_pty`, `resize_pty` and
+//! `close_pty` work on them unchanged.
+
+mod client;
+mod protocol;
+mod server;
+
+pub use client::{DaemonClient, DaemonMaster};
+pub use protocol::SessionInfo;
+
+use super::spawn::{next_pty_id, register_pty};
+use crate::models::{AppState, DAEMON_START_TIMEOUT_MS};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+const DAEMON_SOCKET_FILE: &str = ".config/aiterminal/ptyd.sock";
+
+/// Argument that makes the app executable run as the PTY daemon
+pub const DAEMON_ARG: &str = "--pty-daemon";
+
+fn get_socket_path() -> Result<PathBuf, String> {
+    let home =
+        std::env::var("HOME").map_err(|_| "Could not determine HOME directory".to_string())?;
+    Ok(PathBuf::from(home).join(DAEMON_SOCKET_FILE))
+}
+
+/// Entry point for `aiterminal --pty-daemon`
+pub fn run_pty_daemon() -> Result<(), String> {
+    server::serve(&get_socket_path()?)
+}
+
+fn persistent_sessions_enabled() -> bool {
+    crate::settings::get_config_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| serde_json::from_str::<crate::models::AppSettings>(&content).ok())
+        .is_some_and(|settings| settings.terminal.persistent_sessions)
+}
+
+fn start_daemon() -> Result<(), String> {
+    use std::os::unix::process::CommandExt;
+
+    let exe =
+        std::env::current_exe().map_err(|e| format!("Failed to locate app executable: {}", e))?;
+    // Own process group, so the daemon isn't signalled along with the app
+    std::process::Command::new(exe)
+        .arg(DAEMON_ARG)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .process_group(0)
+        .spawn()
+        .map_err(|e| format!("Failed to start PTY daemon: {}", e))?;
+    Ok(())
+}
+
+/// Connect to the daemon, starting it if needed
+fn connect_daemon(state: &AppState, start: bool) -> Result<Arc<DaemonClient>, String> {
+    let mut daemon = state
+        .pty_daemon
+        .lock()
+        .map_err(|e| format!("Failed to acquire daemon lock: {}", e))?;
+    if let Some(client) = daemon.as_ref().filter(|client| client.is_connected()) {
+        return Ok(client.clone());
+    }
+
+    let socket_path = get_socket_path()?;
+    let client = match DaemonClient::connect(&socket_path) {
+        Ok(client) => client,
+        Err(_) if start => {
+            start_daemon()?;
+            let deadline = Instant::now() + Duration::from_millis(DAEMON_START_TIMEOUT_MS);
+            loop {
+                match DaemonClient::connect(&socket_path) {
+                    Ok(client) => break client,
+                    Err(e) if Instant::now() >= deadline => {
+                        return Err(format!("PTY daemon did not start: {}", e));
+                    }
+                    Err(_) => std::thread::sleep(Duration::from_millis(50)),
+                }
+            }
+        }
+        Err(e) => return Err(format!("PTY daemon is not running: {}", e)),
+    };
+
+    *daemon = Some(client.clone());
+    Ok(client)
+}
+
+/// The daemon connection to spawn through, or None when persistent sessions are off
+pub fn daemon_for_spawn(state: &AppState) -> Result<Option<Arc<DaemonClient>>, String> {
+    if !persistent_sessions_enabled() {
+        return Ok(None);
+    }
+    connect_daemon(state, true).map(Some)
+}
+
+/// Daemon session behind a PTY, if it is daemon-owned
+fn daemon_session(state: &AppState, id: u32) -> Result<Option<(Arc<DaemonClient>, u32)>, String> {
+    let ptys = state
+        .ptys
+        .lock()
+        .map_err(|e| format!("Failed to acquire PTY lock: {}", e))?;
+    let session = ptys
+        .get(&id)
+        .ok_or_else(|| format!("PTY {} not found", id))?;
+    let master: &dyn portable_pty::MasterPty = session.master.as_ref();
+    Ok(master
+        .downcast_ref::<DaemonMaster>()
+        .map(|master| (master.client().clone(), master.session())))
+}
+
+/// Tauri command: sessions running in the daemon (empty if it isn't running)
+#[tauri::command]
+pub fn list_daemon_sessions(state: State<AppState>) -> Result<Vec<SessionInfo>, String> {
+    match connect_daemon(&state, false) {
+        Ok(client) => client.list(),
+        Err(_) => Ok(Vec::new()),
+    }
+}
+
+/// Tauri command: attach a daemon session to a new PTY ID. The session's
+/// recent output is replayed first.
+#[tauri::command]
+pub fn attach_daemon_session(
+    window: tauri::Window,
+    state: State<AppState>,
+    session: u32,
+) -> Result<u32, String> {
+    let client = connect_daemon(&state, false)?;
+    let pty = client.attach(session)?;
+
+    let id = next_pty_id(&state)?;
+    register_pty(
+        window,
+        &state,
+        id,
+        pty.master,
+        pty.child,
+        pty.reader,
+        None,
+        Vec::new(),
+        false,
+    )?;
+    Ok(id)
+}
+
+/// Tauri command: close a PTY's tab but leave its daemon session running, so
+/// it can be attached again later. Local PTYs are closed as usual.
+#[tauri::command]
+pub fn detach_pty(id: u32, state: State<AppState>) -> Result<(), String> {
+    if let Some((client, session)) = daemon_session(&state, id)? {
+        client.detach(session)?;
+    }
+    super::commands::close_pty(id, state);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    fn read_until(reader: &mut dyn Read, needle: &str) -> String {
+        let mut seen = String::new();
+        let mut buf = [0u8; 1024];
+        while !seen.contains(needle) {
+            let n = reader.read(&mut buf).unwrap();
+            assert!(n > 0, "EOF before {:?}; got {:?}", needle, seen);
+            seen.push_str(&String::from_utf8_lossy(&buf[..n]));
+        }
+        seen
+    }
+
+    #[test]
+    fn test_daemon_spawn_detach_reattach_and_kill() {
+        let socket = std::env::temp_dir().join(format!("aiterm-ptyd-{}.sock", std::process::id()));
+        let server_socket = socket.clone();
+        let server = std::thread::spawn(move || {
+            server::serve_with_idle_timeout(&server_socket, Duration::from_millis(300))
+        });
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let client = loop {
+            match DaemonClient::connect(&socket) {
+                Ok(client) => break client,
+                Err(_) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(20))
+                }
+                Err(e) => panic!("daemon did not start: {}", e),
+            }
+        };
+
+        let mut cmd = portable_pty::CommandBuilder::new("/bin/cat");
+        cmd.cwd("/tmp");
+        let size = portable_pty::PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 0,
+            pixel_height: 0,
+        };
+        let mut pty = client.spawn(&cmd, size).unwrap();
+        let mut writer = pty.master.take_writer().unwrap();
+        writer.write_all(b"hello daemon\n").unwrap();
+        read_until(&mut pty.reader, "hello daemon");
+
+        pty.master
+            .resize(portable_pty::PtySize { rows: 40, ..size })
+            .unwrap();
+        client.detach(pty.session).unwrap();
+        assert!(pty.child.try_wait().unwrap().is_some());
+
+        // A fresh connection sees the session and its output again
+        let other = DaemonClient::connect(&socket).unwrap();
+        let sessions = other.list().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].rows, 40);
+        let mut again = other.attach(pty.session).unwrap();
+        read_until(&mut again.reader, "hello daemon");
+
+        again.child.kill().unwrap();
+        let mut rest = Vec::new();
+        again.reader.read_to_end(&mut rest).unwrap();
+        again.child.wait().unwrap();
+        assert!(other.list().unwrap().is_empty());
+
+        client.disconnect();
+        other.disconnect();
+        server.join().unwrap().unwrap();
+        assert!(!socket.exists());
+    }
+}
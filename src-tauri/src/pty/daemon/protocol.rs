@@ -0,0 +1,182 @@
+//! Wire format between the app and the PTY daemon.
+//!
+//! Every frame is `[u32 big-endian length][u8 kind][payload]`, where the length
+//! covers the kind byte and payload. Control frames carry JSON; data frames carry
+//! `[u32 big-endian session][raw bytes]` (PTY input from the app, PTY output
+//! from the daemon) so terminal traffic is never re-encoded.
+
+use crate::models::DAEMON_MAX_FRAME_BYTES;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read};
+
+pub const FRAME_CONTROL: u8 = 1;
+pub const FRAME_DATA: u8 = 2;
+
+/// Request from the app, answered by a `DaemonMessage::Response` with the same `seq`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ClientMessage {
+    pub seq: u64,
+    pub request: Request,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// Start a process in a new PTY. Does not attach.
+    Spawn {
+        argv: Vec<String>,
+        env: Vec<(String, String)>,
+        cwd: Option<String>,
+        rows: u16,
+        cols: u16,
+    },
+    /// Receive the session's replay buffer followed by live output
+    Attach {
+        session: u32,
+    },
+    /// Stop receiving output; the process keeps running
+    Detach {
+        session: u32,
+    },
+    Resize {
+        session: u32,
+        rows: u16,
+        cols: u16,
+    },
+    Kill {
+        session: u32,
+    },
+    /// Foreground process group of the session's terminal
+    ForegroundPid {
+        session: u32,
+    },
+    List,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Spawned { session: u32, pid: Option<u32> },
+    Attached { info: SessionInfo },
+    Pid { pid: Option<u32> },
+    Sessions { sessions: Vec<SessionInfo> },
+    Done,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DaemonMessage {
+    Response {
+        seq: u64,
+        result: Result<Response, String>,
+    },
+    /// The session's process exited; no more output follows
+    Exit {
+        session: u32,
+        exit_code: Option<u32>,
+    },
+}
+
+/// A PTY owned by the daemon
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SessionInfo {
+    pub session: u32,
+    pub pid: Option<u32>,
+    pub argv: Vec<String>,
+    pub cwd: Option<String>,
+    pub rows: u16,
+    pub cols: u16,
+    /// Start time in ms since epoch
+    pub created_at: u64,
+    /// Number of app connections currently attached
+    pub attached_clients: usize,
+}
+
+/// Build a complete frame, so it can be queued and written in one call
+pub fn encode_frame(kind: u8, payload: &[u8]) -> Vec<u8> {
+    let len = (payload.len() + 1) as u32;
+    let mut frame = Vec::with_capacity(payload.len() + 5);
+    frame.extend_from_slice(&len.to_be_bytes());
+    frame.push(kind);
+    frame.extend_from_slice(payload);
+    frame
+}
+
+pub fn encode_control<T: Serialize>(message: &T) -> io::Result<Vec<u8>> {
+    let json = serde_json::to_vec(message).map_err(io::Error::other)?;
+    Ok(encode_frame(FRAME_CONTROL, &json))
+}
+
+pub fn encode_data(session: u32, bytes: &[u8]) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(bytes.len() + 4);
+    payload.extend_from_slice(&session.to_be_bytes());
+    payload.extend_from_slice(bytes);
+    encode_frame(FRAME_DATA, &payload)
+}
+
+pub fn decode_data(payload: &[u8]) -> Option<(u32, &[u8])> {
+    let session = u32::from_be_bytes(payload.get(..4)?.try_into().ok()?);
+    Some((session, &payload[4..]))
+}
+
+/// Read one frame. Returns None on a clean EOF between frames.
+pub fn read_frame(reader: &mut impl Read) -> io::Result<Option<(u8, Vec<u8>)>> {
+    let mut len_buf = [0u8; 4];
+    match reader.read_exact(&mut len_buf) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let len = u32::from_be_bytes(len_buf) as usize;
+    if len == 0 || len > DAEMON_MAX_FRAME_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid frame length {}", len),
+        ));
+    }
+
+    let mut frame = vec![0u8; len];
+    reader.read_exact(&mut frame)?;
+    let kind = frame[0];
+    frame.remove(0);
+    Ok(Some((kind, frame)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_round_trip() {
+        let mut wire = Vec::new();
+        let request = ClientMessage {
+            seq: 7,
+            request: Request::Resize {
+                session: 3,
+                rows: 40,
+                cols: 120,
+            },
+        };
+        wire.extend(encode_control(&request).unwrap());
+        wire.extend(encode_data(3, b"ls\r"));
+
+        let mut reader = wire.as_slice();
+        let (kind, payload) = read_frame(&mut reader).unwrap().unwrap();
+        assert_eq!(kind, FRAME_CONTROL);
+        let decoded: ClientMessage = serde_json::from_slice(&payload).unwrap();
+        assert_eq!(decoded, request);
+
+        let (kind, payload) = read_frame(&mut reader).unwrap().unwrap();
+        assert_eq!(kind, FRAME_DATA);
+        assert_eq!(decode_data(&payload), Some((3, &b"ls\r"[..])));
+
+        assert!(read_frame(&mut reader).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_rejects_oversized_frame() {
+        let wire = u32::MAX.to_be_bytes();
+        assert!(read_frame(&mut wire.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,437 @@
+use super::protocol::{
+    decode_data, encode_control, encode_data, read_frame, ClientMessage, DaemonMessage, Request,
+    Response, SessionInfo, FRAME_CONTROL, FRAME_DATA,
+};
+use crate::models::{DAEMON_IDLE_EXIT_SECS, DAEMON_REPLAY_BYTES, PTY_BUFFER_SIZE};
+use portable_pty::{Child, CommandBuilder, MasterPty, NativePtySystem, PtySize, PtySystem};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Output and attachment state of a session, guarded together so a new client
+/// gets the replay and then live output with nothing lost or duplicated
+struct SessionOutput {
+    replay: Vec<u8>,
+    clients: HashMap<u64, mpsc::Sender<Vec<u8>>>,
+    size: PtySize,
+}
+
+impl SessionOutput {
+    fn push(&mut self, data: &[u8]) {
+        self.replay.extend_from_slice(data);
+        if self.replay.len() > DAEMON_REPLAY_BYTES {
+            // Drop whole lines so the replay doesn't start mid escape sequence
+            let excess = self.replay.len() - DAEMON_REPLAY_BYTES;
+            let cut = self.replay[excess..]
+                .iter()
+                .position(|b| *b == b'\n')
+                .map(|pos| excess + pos + 1)
+                .unwrap_or(excess);
+            self.replay.drain(..cut);
+        }
+    }
+}
+
+struct Session {
+    id: u32,
+    pid: Option<u32>,
+    argv: Vec<String>,
+    cwd: Option<String>,
+    created_at: u64,
+    master: Mutex<Box<dyn MasterPty + Send>>,
+    writer: Mutex<Box<dyn Write + Send>>,
+    child: Mutex<Box<dyn Child + Send + Sync>>,
+    output: Mutex<SessionOutput>,
+}
+
+impl Session {
+    fn info(&self) -> SessionInfo {
+        let (rows, cols, attached_clients) = match self.output.lock() {
+            Ok(output) => (output.size.rows, output.size.cols, output.clients.len()),
+            Err(_) => (0, 0, 0),
+        };
+        SessionInfo {
+            session: self.id,
+            pid: self.pid,
+            argv: self.argv.clone(),
+            cwd: self.cwd.clone(),
+            rows,
+            cols,
+            created_at: self.created_at,
+            attached_clients,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Daemon {
+    sessions: Mutex<HashMap<u32, Arc<Session>>>,
+    next_session: AtomicU32,
+    next_client: AtomicU64,
+    connected_clients: AtomicUsize,
+}
+
+impl Daemon {
+    fn session(&self, id: u32) -> Result<Arc<Session>, String> {
+        self.sessions
+            .lock()
+            .map_err(|e| format!("Failed to acquire session lock: {}", e))?
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| format!("Session {} not found", id))
+    }
+}
+
+/// Serve PTY sessions on `socket_path` until the daemon has had no sessions and
+/// no clients for `DAEMON_IDLE_EXIT_SECS`
+pub fn serve(socket_path: &Path) -> Result<(), String> {
+    serve_with_idle_timeout(socket_path, Duration::from_secs(DAEMON_IDLE_EXIT_SECS))
+}
+
+pub(crate) fn serve_with_idle_timeout(socket_path: &Path, idle: Duration) -> Result<(), String> {
+    if socket_path.exists() {
+        if UnixStream::connect(socket_path).is_ok() {
+            return Err(format!(
+                "PTY daemon already running at {}",
+                socket_path.display()
+            ));
+        }
+        // Left over from a daemon that crashed
+        std::fs::remove_file(socket_path)
+            .map_err(|e| format!("Failed to remove stale socket: {}", e))?;
+    }
+    if let Some(parent) = socket_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create socket directory: {}", e))?;
+    }
+
+    let listener =
+        UnixListener::bind(socket_path).map_err(|e| format!("Failed to bind socket: {}", e))?;
+    std::fs::set_permissions(socket_path, std::fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("Failed to set socket permissions: {}", e))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| format!("Failed to configure socket: {}", e))?;
+
+    let daemon = Arc::new(Daemon::default());
+    let mut idle_since = Instant::now();
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let _ = stream.set_nonblocking(false);
+                let daemon = daemon.clone();
+                std::thread::spawn(move || handle_client(daemon, stream));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => eprintln!("[ptyd] Accept failed: {}", e),
+        }
+
+        let busy = daemon.connected_clients.load(Ordering::SeqCst) > 0
+            || daemon
+                .sessions
+                .lock()
+                .map(|sessions| !sessions.is_empty())
+                .unwrap_or(true);
+        if busy {
+            idle_since = Instant::now();
+        } else if idle_since.elapsed() >= idle {
+            break;
+        }
+    }
+
+    let _ = std::fs::remove_file(socket_path);
+    Ok(())
+}
+
+fn send_message(tx: &mpsc::Sender<Vec<u8>>, message: &DaemonMessage) {
+    if let Ok(frame) = encode_control(message) {
+        let _ = tx.send(frame);
+    }
+}
+
+fn handle_client(daemon: Arc<Daemon>, stream: UnixStream) {
+    let client_id = daemon.next_client.fetch_add(1, Ordering::SeqCst);
+    daemon.connected_clients.fetch_add(1, Ordering::SeqCst);
+
+    // All frames to this client go through one queue so they never interleave
+    let (tx, rx) = mpsc::channel::<Vec<u8>>();
+    let writer_handle = stream.try_clone().ok().map(|mut out| {
+        std::thread::spawn(move || {
+            for frame in rx {
+                if out.write_all(&frame).is_err() {
+                    break;
+                }
+            }
+        })
+    });
+
+    let mut input = stream;
+    while let Ok(Some((kind, payload))) = read_frame(&mut input) {
+        match kind {
+            FRAME_CONTROL => {
+                if let Ok(message) = serde_json::from_slice::<ClientMessage>(&payload) {
+                    handle_request(&daemon, client_id, &tx, message);
+                }
+            }
+            FRAME_DATA => {
+                if let Some((session, bytes)) = decode_data(&payload) {
+                    if let Ok(session) = daemon.session(session) {
+                        if let Ok(mut writer) = session.writer.lock() {
+                            let _ = writer.write_all(bytes).and_then(|_| writer.flush());
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Connection closed: detach from everything, keep the processes running
+    if let Ok(sessions) = daemon.sessions.lock() {
+        for session in sessions.values() {
+            if let Ok(mut output) = session.output.lock() {
+                output.clients.remove(&client_id);
+            }
+        }
+    }
+    daemon.connected_clients.fetch_sub(1, Ordering::SeqCst);
+    drop(tx);
+    if let Some(handle) = writer_handle {
+        let _ = handle.join();
+    }
+}
+
+fn handle_request(
+    daemon: &Arc<Daemon>,
+    client_id: u64,
+    tx: &mpsc::Sender<Vec<u8>>,
+    message: ClientMessage,
+) {
+    let seq = message.seq;
+    let respond = |result: Result<Response, String>| {
+        send_message(tx, &DaemonMessage::Response { seq, result });
+    };
+
+    match message.request {
+        Request::Spawn {
+            argv,
+            env,
+            cwd,
+            rows,
+            cols,
+        } => respond(spawn_session(daemon, argv, env, cwd, rows, cols)),
+        Request::Attach { session } => {
+            let session = match daemon.session(session) {
+                Ok(session) => session,
+                Err(e) => return respond(Err(e)),
+            };
+            let info = session.info();
+            let Ok(mut output) = session.output.lock() else {
+                return respond(Err("Failed to acquire session output lock".to_string()));
+            };
+            // Response, replay and registration happen under the output lock,
+            // so the reader thread cannot slip live output in between
+            respond(Ok(Response::Attached { info }));
+            if !output.replay.is_empty() {
+                let _ = tx.send(encode_data(session.id, &output.replay));
+            }
+            output.clients.insert(client_id, tx.clone());
+        }
+        Request::Detach { session } => {
+            let result = daemon.session(session).map(|session| {
+                if let Ok(mut output) = session.output.lock() {
+                    output.clients.remove(&client_id);
+                }
+                Response::Done
+            });
+            respond(result);
+        }
+        Request::Resize {
+            session,
+            rows,
+            cols,
+        } => {
+            let result = daemon.session(session).and_then(|session| {
+                let size = PtySize {
+                    rows,
+                    cols,
+                    pixel_width: 0,
+                    pixel_height: 0,
+                };
+                session
+                    .master
+                    .lock()
+                    .map_err(|e| format!("Failed to acquire master lock: {}", e))?
+                    .resize(size)
+                    .map_err(|e| format!("Failed to resize PTY: {}", e))?;
+                if let Ok(mut output) = session.output.lock() {
+                    output.size = size;
+                }
+                Ok(Response::Done)
+            });
+            respond(result);
+        }
+        Request::Kill { session } => {
+            let result = daemon.session(session).and_then(|session| {
+                session
+                    .child
+                    .lock()
+                    .map_err(|e| format!("Failed to acquire child lock: {}", e))?
+                    .kill()
+                    .map_err(|e| format!("Failed to kill session: {}", e))?;
+                Ok(Response::Done)
+            });
+            respond(result);
+        }
+        Request::ForegroundPid { session } => {
+            let result = daemon.session(session).map(|session| {
+                let pid = session.master.lock().ok().and_then(|master| {
+                    master
+                        .process_group_leader()
+                        .and_then(|pgid| u32::try_from(pgid).ok())
+                });
+                Response::Pid { pid }
+            });
+            respond(result);
+        }
+        Request::List => {
+            let result = daemon
+                .sessions
+                .lock()
+                .map_err(|e| format!("Failed to acquire session lock: {}", e))
+                .map(|sessions| {
+                    let mut sessions: Vec<SessionInfo> =
+                        sessions.values().map(|session| session.info()).collect();
+                    sessions.sort_by_key(|info| info.session);
+                    Response::Sessions { sessions }
+                });
+            respond(result);
+        }
+    }
+}
+
+fn spawn_session(
+    daemon: &Arc<Daemon>,
+    argv: Vec<String>,
+    env: Vec<(String, String)>,
+    cwd: Option<String>,
+    rows: u16,
+    cols: u16,
+) -> Result<Response, String> {
+    let program = argv.first().ok_or("Spawn request has no program")?;
+    let size = PtySize {
+        rows,
+        cols,
+        pixel_width: 0,
+        pixel_height: 0,
+    };
+    let pair = NativePtySystem::default()
+        .openpty(size)
+        .map_err(|e| format!("Failed to open PTY: {}", e))?;
+
+    // The app sends its full environment so the shell matches a local spawn
+    let mut cmd = CommandBuilder::new(program);
+    cmd.args(&argv[1..]);
+    cmd.env_clear();
+    for (key, value) in env {
+        cmd.env(key, value);
+    }
+    if let Some(dir) = &cwd {
+        cmd.cwd(dir);
+    }
+
+    let child = pair
+        .slave
+        .spawn_command(cmd)
+        .map_err(|e| format!("Failed to spawn {}: {}", program, e))?;
+    let reader = pair
+        .master
+        .try_clone_reader()
+        .map_err(|e| format!("Failed to clone PTY reader: {}", e))?;
+    let writer = pair
+        .master
+        .take_writer()
+        .map_err(|e| format!("Failed to take PTY writer: {}", e))?;
+
+    let id = daemon.next_session.fetch_add(1, Ordering::SeqCst);
+    let pid = child.process_id();
+    let session = Arc::new(Session {
+        id,
+        pid,
+        argv,
+        cwd,
+        created_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64,
+        master: Mutex::new(pair.master),
+        writer: Mutex::new(writer),
+        child: Mutex::new(child),
+        output: Mutex::new(SessionOutput {
+            replay: Vec::new(),
+            clients: HashMap::new(),
+            size,
+        }),
+    });
+
+    daemon
+        .sessions
+        .lock()
+        .map_err(|e| format!("Failed to acquire session lock: {}", e))?
+        .insert(id, session.clone());
+
+    let daemon = daemon.clone();
+    std::thread::spawn(move || pump_output(daemon, session, reader));
+
+    Ok(Response::Spawned { session: id, pid })
+}
+
+/// Copy PTY output into the replay buffer and to attached clients until the
+/// process exits, then report the exit and drop the session
+fn pump_output(daemon: Arc<Daemon>, session: Arc<Session>, mut reader: Box<dyn Read + Send>) {
+    let mut buf = [0u8; PTY_BUFFER_SIZE];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(n) if n > 0 => {
+                if let Ok(mut output) = session.output.lock() {
+                    output.push(&buf[..n]);
+                    let frame = encode_data(session.id, &buf[..n]);
+                    output
+                        .clients
+                        .retain(|_, client| client.send(frame.clone()).is_ok());
+                }
+            }
+            _ => break,
+        }
+    }
+
+    let exit_code = session
+        .child
+        .lock()
+        .ok()
+        .and_then(|mut child| child.wait().ok())
+        .map(|status| status.exit_code());
+
+    if let Ok(mut sessions) = daemon.sessions.lock() {
+        sessions.remove(&session.id);
+    }
+    if let Ok(mut output) = session.output.lock() {
+        for client in output.clients.values() {
+            send_message(
+                client,
+                &DaemonMessage::Exit {
+                    session: session.id,
+                    exit_code,
+                },
+            );
+        }
+        output.clients.clear();
+    }
+}
@@ -0,0 +1,244 @@
+use super::commands::pty_health;
+use super::paste::{paste_queue, progress_emitter};
+use crate::models::AppState;
+use serde::Serialize;
+use tauri::State;
+
+/// PTYs that receive the same input
+#[derive(Serialize, Debug, Clone)]
+pub struct InputGroup {
+    pub id: String,
+    pub name: Option<String>,
+    /// Member PTY IDs, in the order input is delivered
+    pub members: Vec<u32>,
+}
+
+/// Outcome of `write_to_group` for one member
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct GroupWriteResult {
+    pub id: u32,
+    /// "written", "skipped" (closed or exited) or "failed"
+    pub status: String,
+    pub error: Option<String>,
+}
+
+impl GroupWriteResult {
+    fn new(id: u32, status: &str, error: Option<String>) -> Self {
+        Self {
+            id,
+            status: status.to_string(),
+            error,
+        }
+    }
+}
+
+fn ensure_pty_exists(id: u32, state: &AppState) -> Result<(), String> {
+    let ptys = state
+        .ptys
+        .lock()
+        .map_err(|e| format!("Failed to acquire PTY lock: {}", e))?;
+    if ptys.contains_key(&id) {
+        Ok(())
+    } else {
+        Err(format!("PTY {} not found", id))
+    }
+}
+
+/// Tauri command: create an input group, optionally with initial members
+#[tauri::command]
+pub fn create_input_group(
+    name: Option<String>,
+    members: Option<Vec<u32>>,
+    state: State<AppState>,
+) -> Result<InputGroup, String> {
+    let mut group = InputGroup {
+        id: uuid::Uuid::new_v4().to_string(),
+        name,
+        members: Vec::new(),
+    };
+    for id in members.unwrap_or_default() {
+        ensure_pty_exists(id, &state)?;
+        if !group.members.contains(&id) {
+            group.members.push(id);
+        }
+    }
+
+    state
+        .input_groups
+        .lock()
+        .map_err(|e| format!("Failed to acquire input group lock: {}", e))?
+        .insert(group.id.clone(), group.clone());
+    Ok(group)
+}
+
+/// Tauri command: add a PTY to a group (no-op if it is already a member)
+#[tauri::command]
+pub fn add_pty_to_group(
+    group_id: String,
+    id: u32,
+    state: State<AppState>,
+) -> Result<InputGroup, String> {
+    ensure_pty_exists(id, &state)?;
+    let mut groups = state
+        .input_groups
+        .lock()
+        .map_err(|e| format!("Failed to acquire input group lock: {}", e))?;
+    let group = groups
+        .get_mut(&group_id)
+        .ok_or_else(|| format!("Input group {} not found", group_id))?;
+    if !group.members.contains(&id) {
+        group.members.push(id);
+    }
+    Ok(group.clone())
+}
+
+/// Tauri command: remove a PTY from a group
+#[tauri::command]
+pub fn remove_pty_from_group(
+    group_id: String,
+    id: u32,
+    state: State<AppState>,
+) -> Result<InputGroup, String> {
+    let mut groups = state
+        .input_groups
+        .lock()
+        .map_err(|e| format!("Failed to acquire input group lock: {}", e))?;
+    let group = groups
+        .get_mut(&group_id)
+        .ok_or_else(|| format!("Input group {} not found", group_id))?;
+    group.members.retain(|member| *member != id);
+    Ok(group.clone())
+}
+
+/// Tauri command: delete a group. Returns false if it did not exist.
+#[tauri::command]
+pub fn delete_input_group(group_id: String, state: State<AppState>) -> Result<bool, String> {
+    Ok(state
+        .input_groups
+        .lock()
+        .map_err(|e| format!("Failed to acquire input group lock: {}", e))?
+        .remove(&group_id)
+        .is_some())
+}
+
+#[tauri::command]
+pub fn list_input_groups(state: State<AppState>) -> Result<Vec<InputGroup>, String> {
+    let groups = state
+        .input_groups
+        .lock()
+        .map_err(|e| format!("Failed to acquire input group lock: {}", e))?;
+    let mut groups: Vec<InputGroup> = groups.values().cloned().collect();
+    groups.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(groups)
+}
+
+/// Tauri command: write the same input to every member of a group.
+///
+/// Members whose process has exited are skipped. Each write goes through the
+/// member's paste queue, so large input is paced and lands behind a paste
+/// that is still running. The group lock is held while queueing, so input
+/// from concurrent calls reaches every member in the same order.
+#[tauri::command]
+pub fn write_to_group(
+    group_id: String,
+    data: String,
+    window: tauri::Window,
+    state: State<AppState>,
+) -> Result<Vec<GroupWriteResult>, String> {
+    let groups = state
+        .input_groups
+        .lock()
+        .map_err(|e| format!("Failed to acquire input group lock: {}", e))?;
+    let members = groups
+        .get(&group_id)
+        .map(|group| group.members.clone())
+        .ok_or_else(|| format!("Input group {} not found", group_id))?;
+
+    let health: Vec<Result<String, String>> = members
+        .iter()
+        .map(|id| pty_health(*id, &state).map(|health| health.status))
+        .collect();
+
+    Ok(fan_out(&members, &health, |id| {
+        paste_queue(id, &state)?
+            .write(
+                id,
+                data.as_bytes().to_vec(),
+                progress_emitter(window.clone(), id),
+            )
+            .map(|_| ())
+    }))
+}
+
+/// Write to each live member in order. `health` holds each member's
+/// `check_pty_health` status, or the error if the PTY is gone.
+fn fan_out(
+    members: &[u32],
+    health: &[Result<String, String>],
+    mut write: impl FnMut(u32) -> Result<(), String>,
+) -> Vec<GroupWriteResult> {
+    members
+        .iter()
+        .zip(health)
+        .map(|(&id, health)| match health {
+            Err(e) => GroupWriteResult::new(id, "skipped", Some(e.clone())),
+            Ok(status) if status == "dead" => {
+                GroupWriteResult::new(id, "skipped", Some(format!("PTY {} has exited", id)))
+            }
+            Ok(_) => match write(id) {
+                Ok(()) => GroupWriteResult::new(id, "written", None),
+                Err(e) => GroupWriteResult::new(id, "failed", Some(e)),
+            },
+        })
+        .collect()
+}
+
+/// Drop a closed PTY from every group
+pub(super) fn remove_from_groups(id: u32, state: &AppState) {
+    if let Ok(mut groups) = state.input_groups.lock() {
+        for group in groups.values_mut() {
+            group.members.retain(|member| *member != id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fan_out_skips_dead_members_in_order() {
+        let members = [3, 1, 7, 2];
+        let health = vec![
+            Ok("healthy".to_string()),
+            Ok("dead".to_string()),
+            Err("PTY 7 not found".to_string()),
+            Ok("idle".to_string()),
+        ];
+        let mut written = Vec::new();
+        let results = fan_out(&members, &health, |id| {
+            written.push(id);
+            if id == 2 {
+                Err("Failed to write to PTY 2: broken pipe".to_string())
+            } else {
+                Ok(())
+            }
+        });
+
+        assert_eq!(written, vec![3, 2]);
+        let statuses: Vec<(u32, &str)> = results
+            .iter()
+            .map(|result| (result.id, result.status.as_str()))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (3, "written"),
+                (1, "skipped"),
+                (7, "skipped"),
+                (2, "failed")
+            ]
+        );
+        assert_eq!(results[2].error.as_deref(), Some("PTY 7 not found"));
+    }
+}
@@ -0,0 +1,599 @@
+//! Health checks for PTYs connected from an SSH profile.
+//!
+//! A profile with `healthCheckInterval` set gets a tokio task per PTY that, at
+//! that interval, connects to the host ssh talks to (its first jump host, if
+//! any) and waits for the SSH banner. A failed check marks the session
+//! degraded. Failed checks alone never disconnect it, since an idle session
+//! prints nothing either: that takes ssh exiting with a connection error, or
+//! failures that keep coming while the PTY prints nothing and has stopped
+//! taking input or reached EOF. Hosts behind a ProxyCommand are not probed.
+//! Each change is emitted as an `ssh-degraded`, `ssh-recovered` or
+//! `ssh-disconnected` event.
+//!
+//! Profiles with `autoReconnect` then type their ssh command into the PTY
+//! again (telling a hung ssh to quit with `~.` first). Once the remote shell
+//! reports back, it is taken to the last remote directory and
+//! `ssh-reconnected` is emitted. Sessions of the in-process client end with
+//! their channel, so they are not reconnected.
+
+use super::commands::pty_health;
+use super::latency::{measure_tcp_latency, profile_probe_target, ProbeTarget};
+use super::osc_parser::current_timestamp;
+use super::paste::{paste_queue, shell_quote};
+use crate::models::{
+    AppState, SSH_BANNER_TIMEOUT_MS, SSH_ESCAPE_GRACE_MS, SSH_HEALTH_FAILURES_TO_DISCONNECT,
+    SSH_HEALTH_MIN_INTERVAL_SECS, SSH_RECONNECT_POLL_MS, SSH_RECONNECT_TIMEOUT_MS,
+};
+use crate::ssh::{find_ssh_profile, parse_ssh_config, ssh_command_line, SSHProfile};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+use tokio::io::AsyncReadExt;
+
+/// Bytes read while looking for the banner; servers may send other lines
+/// before it (RFC 4253, section 4.2)
+const MAX_BANNER_BYTES: usize = 8192;
+
+/// Trailing PTY lines searched for ssh's last message
+const SSH_EXIT_SCAN_LINES: usize = 5;
+
+/// Parts of the messages ssh prints when it loses the connection
+const LOST_CONNECTION_MESSAGES: &[&str] = &[
+    "closed by remote host",
+    "broken pipe",
+    "connection reset",
+    "not responding",
+    "timed out",
+    "network is unreachable",
+    "no route to host",
+];
+
+/// Payload of the `ssh-degraded`, `ssh-recovered`, `ssh-disconnected` and
+/// `ssh-reconnected` events
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SshHealthEvent {
+    pub pty_id: u32,
+    pub profile_id: String,
+    /// `host:port` the checks connect to; None behind a ProxyCommand
+    pub target: Option<String>,
+    pub reason: Option<String>,
+    pub consecutive_failures: u32,
+    pub last_latency_ms: Option<u64>,
+    /// The profile asks to be alerted when the connection drops
+    pub alert: bool,
+    /// The profile's ssh command is being run again
+    pub reconnecting: bool,
+}
+
+/// What one round of checks saw
+struct Observation {
+    process_alive: bool,
+    /// `connection_time` of the PTY's SSH session; None while it is local
+    session_since: Option<u64>,
+    /// Connect time, or why the check failed; None when nothing was probed
+    probe: Option<Result<u64, String>>,
+    ms_since_output: Option<u64>,
+    /// The PTY stopped taking input or its reader reached EOF
+    pty_failed: bool,
+    /// How ssh exited, when it did: Ok when the connection was closed on
+    /// request, Err with its message when it was lost
+    ssh_exit: Option<Result<(), String>>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Verdict {
+    /// The remote shell came up
+    Connected,
+    Degraded(String),
+    Recovered,
+    Disconnected(String),
+    /// The session ended on request; nothing to report
+    Ended,
+}
+
+/// Turns observations into state changes
+struct HealthTracker {
+    interval_ms: u64,
+    /// Sessions detected before this time (Unix seconds) belong to an
+    /// earlier connection
+    connected_after: u64,
+    armed: bool,
+    degraded: bool,
+    failures: u32,
+    last_failure: Option<String>,
+}
+
+impl HealthTracker {
+    fn new(interval_ms: u64) -> Self {
+        Self {
+            interval_ms,
+            connected_after: 0,
+            armed: false,
+            degraded: false,
+            failures: 0,
+            last_failure: None,
+        }
+    }
+
+    /// Wait for a new session, detected at or after `after`
+    fn expect_reconnect(&mut self, after: u64) {
+        *self = Self {
+            connected_after: after,
+            ..Self::new(self.interval_ms)
+        };
+    }
+
+    fn observe(&mut self, observation: Observation) -> Option<Verdict> {
+        let on_remote = observation.process_alive
+            && observation
+                .session_since
+                .is_some_and(|since| since >= self.connected_after);
+        if !self.armed {
+            if on_remote {
+                self.armed = true;
+                return Some(Verdict::Connected);
+            }
+            return (!observation.process_alive).then_some(Verdict::Ended);
+        }
+
+        if !on_remote {
+            self.armed = false;
+            return Some(match observation.ssh_exit {
+                Some(Ok(())) => Verdict::Ended,
+                Some(Err(message)) => Verdict::Disconnected(message),
+                None => self
+                    .last_failure
+                    .take()
+                    .map_or(Verdict::Ended, Verdict::Disconnected),
+            });
+        }
+
+        match observation.probe {
+            Some(Err(failure)) => {
+                self.failures += 1;
+                self.last_failure = Some(failure.clone());
+                // Nothing printed since before the first failed check
+                let silent_ms = self.interval_ms * self.failures as u64;
+                let stalled = observation
+                    .ms_since_output
+                    .is_none_or(|idle| idle >= silent_ms);
+                if stalled
+                    && observation.pty_failed
+                    && self.failures >= SSH_HEALTH_FAILURES_TO_DISCONNECT
+                {
+                    self.armed = false;
+                    return Some(Verdict::Disconnected(format!(
+                        "{} (no output for {} s)",
+                        failure,
+                        silent_ms / 1000
+                    )));
+                }
+                if self.degraded {
+                    return None;
+                }
+                self.degraded = true;
+                Some(Verdict::Degraded(failure))
+            }
+            _ => {
+                self.failures = 0;
+                self.last_failure = None;
+                if !self.degraded {
+                    return None;
+                }
+                self.degraded = false;
+                Some(Verdict::Recovered)
+            }
+        }
+    }
+}
+
+/// Connect to the target and wait for its SSH identification line. Returns
+/// the connect time in milliseconds.
+async fn probe_ssh(target: &ProbeTarget) -> Result<u64, String> {
+    let (mut stream, latency_ms) = measure_tcp_latency(target).await?;
+    let read_banner = async {
+        let mut banner = Vec::new();
+        let mut buf = [0u8; 512];
+        while banner.len() < MAX_BANNER_BYTES {
+            let n = stream
+                .read(&mut buf)
+                .await
+                .map_err(|e| format!("Failed to read: {}", e))?;
+            if n == 0 {
+                return Err("Connection closed".to_string());
+            }
+            banner.extend_from_slice(&buf[..n]);
+            if banner
+                .split(|byte| *byte == b'\n')
+                .any(|line| line.starts_with(b"SSH-"))
+            {
+                return Ok(());
+            }
+        }
+        Err("No identification line".to_string())
+    };
+    match tokio::time::timeout(Duration::from_millis(SSH_BANNER_TIMEOUT_MS), read_banner).await {
+        Ok(Ok(())) => Ok(latency_ms),
+        Ok(Err(e)) => Err(format!(
+            "No SSH banner from {}:{}: {}",
+            target.host, target.port, e
+        )),
+        Err(_) => Err(format!(
+            "No SSH banner from {}:{} within {} s",
+            target.host,
+            target.port,
+            SSH_BANNER_TIMEOUT_MS / 1000
+        )),
+    }
+}
+
+/// How ssh said goodbye, going by the PTY's last lines
+fn ssh_exit(lines: &[String]) -> Option<Result<(), String>> {
+    for line in lines.iter().rev() {
+        let line = line.trim();
+        let lower = line.to_lowercase();
+        if LOST_CONNECTION_MESSAGES
+            .iter()
+            .any(|message| lower.contains(message))
+        {
+            return Some(Err(line.to_string()));
+        }
+        if line.starts_with("Connection to ") && line.ends_with(" closed.") {
+            return Some(Ok(()));
+        }
+    }
+    None
+}
+
+fn last_ssh_exit(state: &AppState, id: u32) -> Option<Result<(), String>> {
+    let scrollback = state.pty_scrollback.lock().ok()?;
+    ssh_exit(&scrollback.get(&id)?.last_lines(SSH_EXIT_SCAN_LINES))
+}
+
+/// The directory the remote shell last reported
+fn remote_cwd(state: &AppState, id: u32) -> Option<String> {
+    state
+        .pty_cwds
+        .lock()
+        .ok()?
+        .get(&id)
+        .filter(|cwd| cwd.remote)
+        .map(|cwd| cwd.path.clone())
+}
+
+/// Type the profile's ssh command into the PTY again, after telling a hung
+/// ssh to quit. Returns when it was typed (Unix seconds).
+async fn reconnect_profile(
+    state: &AppState,
+    id: u32,
+    profile_id: &str,
+    hung: bool,
+) -> Result<u64, String> {
+    let command = ssh_command_line(&find_ssh_profile(profile_id)?)?;
+    if hung {
+        // ssh's escape for closing the connection, recognized at line start
+        paste_queue(id, state)?.write(id, b"\r~.".to_vec(), |_| {})?;
+        tokio::time::sleep(Duration::from_millis(SSH_ESCAPE_GRACE_MS)).await;
+    }
+    let typed_at = current_timestamp();
+    paste_queue(id, state)?.write(id, format!("{}\r", command).into_bytes(), |_| {})?;
+    Ok(typed_at)
+}
+
+/// A reconnect waiting for the remote shell
+struct PendingReconnect {
+    cwd: Option<String>,
+    deadline: Instant,
+}
+
+/// Start health checks for a PTY connected from `profile`, if the profile
+/// sets an interval. `native` sessions run on the in-process client.
+pub(super) fn spawn_health_monitor(
+    window: tauri::Window,
+    id: u32,
+    profile: &SSHProfile,
+    native: bool,
+) {
+    let interval_secs = match profile.health_check_interval {
+        Some(secs) if secs > 0 => (secs as u64).max(SSH_HEALTH_MIN_INTERVAL_SECS),
+        _ => return,
+    };
+    let profile = profile.clone();
+    tauri::async_runtime::spawn(async move {
+        let config = tokio::task::spawn_blocking(|| parse_ssh_config().unwrap_or_default())
+            .await
+            .unwrap_or_default();
+        let target = profile_probe_target(&profile, &config);
+        run_health_monitor(window, id, profile, target, interval_secs, native).await;
+    });
+}
+
+/// Check the PTY until it closes or its session ends
+async fn run_health_monitor(
+    window: tauri::Window,
+    id: u32,
+    profile: SSHProfile,
+    target: Option<ProbeTarget>,
+    interval_secs: u64,
+    native: bool,
+) {
+    let interval = Duration::from_secs(interval_secs);
+    let mut tracker = HealthTracker::new(interval_secs * 1000);
+    let mut last_latency_ms = None;
+    let mut reconnect: Option<PendingReconnect> = None;
+    let emit = |event: &str, reason: Option<String>, failures: u32, latency, reconnecting| {
+        let payload = SshHealthEvent {
+            pty_id: id,
+            profile_id: profile.id.clone(),
+            target: target
+                .as_ref()
+                .map(|target| format!("{}:{}", target.host, target.port)),
+            reason,
+            consecutive_failures: failures,
+            last_latency_ms: latency,
+            alert: profile.alert_on_disconnect.unwrap_or(false),
+            reconnecting,
+        };
+        let _ = window.emit(event, payload);
+    };
+
+    loop {
+        let delay = match reconnect {
+            Some(_) => Duration::from_millis(SSH_RECONNECT_POLL_MS),
+            None => interval,
+        };
+        tokio::time::sleep(delay).await;
+
+        let state = window.state::<AppState>();
+        // The PTY was closed
+        let Ok(health) = pty_health(id, &state) else {
+            break;
+        };
+        let session_since = state
+            .ssh_sessions
+            .lock()
+            .ok()
+            .and_then(|sessions| sessions.get(&id).map(|info| info.connection_time));
+        let probe = match &target {
+            Some(target) if tracker.armed && health.process_alive && session_since.is_some() => {
+                Some(probe_ssh(target).await)
+            }
+            _ => None,
+        };
+        if let Some(Ok(latency_ms)) = &probe {
+            last_latency_ms = Some(*latency_ms);
+        }
+        // The in-process client leaves no ssh messages to go by
+        let ssh_exit = if !native && (session_since.is_none() || !health.process_alive) {
+            last_ssh_exit(&state, id)
+        } else {
+            None
+        };
+
+        let verdict = tracker.observe(Observation {
+            process_alive: health.process_alive,
+            session_since,
+            probe,
+            ms_since_output: health.ms_since_last_output,
+            pty_failed: !health.writable || health.output.is_none(),
+            ssh_exit,
+        });
+        let failures = tracker.failures;
+        match verdict {
+            None => {
+                if reconnect
+                    .as_ref()
+                    .is_some_and(|pending| Instant::now() >= pending.deadline)
+                {
+                    let reason = format!(
+                        "Reconnecting did not reach the remote shell within {} s",
+                        SSH_RECONNECT_TIMEOUT_MS / 1000
+                    );
+                    emit("ssh-disconnected", Some(reason), 0, last_latency_ms, false);
+                    break;
+                }
+            }
+            Some(Verdict::Connected) => {
+                if let Some(pending) = reconnect.take() {
+                    if let Some(cwd) = pending.cwd {
+                        let command = format!("cd {}\r", shell_quote(&cwd));
+                        if let Err(e) = paste_queue(id, &state)
+                            .and_then(|queue| queue.write(id, command.into_bytes(), |_| {}))
+                        {
+                            eprintln!("[SSH {id}] Failed to restore working directory: {}", e);
+                        }
+                    }
+                    emit("ssh-reconnected", None, 0, last_latency_ms, false);
+                }
+            }
+            Some(Verdict::Degraded(reason)) => emit(
+                "ssh-degraded",
+                Some(reason),
+                failures,
+                last_latency_ms,
+                false,
+            ),
+            Some(Verdict::Recovered) => {
+                emit("ssh-recovered", None, failures, last_latency_ms, false)
+            }
+            Some(Verdict::Ended) => break,
+            Some(Verdict::Disconnected(reason)) => {
+                let reconnecting =
+                    !native && health.process_alive && profile.auto_reconnect.unwrap_or(false);
+                emit(
+                    "ssh-disconnected",
+                    Some(reason),
+                    failures,
+                    last_latency_ms,
+                    reconnecting,
+                );
+                if !reconnecting {
+                    break;
+                }
+
+                let cwd = remote_cwd(&state, id);
+                match reconnect_profile(&state, id, &profile.id, session_since.is_some()).await {
+                    Ok(typed_at) => {
+                        tracker.expect_reconnect(typed_at);
+                        reconnect = Some(PendingReconnect {
+                            cwd,
+                            deadline: Instant::now()
+                                + Duration::from_millis(SSH_RECONNECT_TIMEOUT_MS),
+                        });
+                    }
+                    Err(e) => {
+                        let reason = format!("Failed to reconnect: {}", e);
+                        emit("ssh-disconnected", Some(reason), 0, last_latency_ms, false);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    fn observe(
+        tracker: &mut HealthTracker,
+        session_since: Option<u64>,
+        probe: Option<Result<u64, String>>,
+        ms_since_output: u64,
+    ) -> Option<Verdict> {
+        tracker.observe(Observation {
+            process_alive: true,
+            session_since,
+            probe,
+            ms_since_output: Some(ms_since_output),
+            pty_failed: false,
+            ssh_exit: None,
+        })
+    }
+
+    #[test]
+    fn test_tracker_degrades_then_disconnects_when_stalled() {
+        let mut tracker = HealthTracker::new(10_000);
+        let down = || Some(Err("Connecting to gpu:22 timed out".to_string()));
+
+        // Waits for the remote shell before judging anything
+        assert_eq!(observe(&mut tracker, None, None, 0), None);
+        assert_eq!(
+            observe(&mut tracker, Some(100), None, 0),
+            Some(Verdict::Connected)
+        );
+        assert_eq!(observe(&mut tracker, Some(100), Some(Ok(12)), 0), None);
+
+        // Checks fail while output keeps coming: degraded, never disconnected
+        assert_eq!(
+            observe(&mut tracker, Some(100), down(), 500),
+            Some(Verdict::Degraded(
+                "Connecting to gpu:22 timed out".to_string()
+            ))
+        );
+        for _ in 0..3 {
+            assert_eq!(observe(&mut tracker, Some(100), down(), 500), None);
+        }
+        assert_eq!(
+            observe(&mut tracker, Some(100), Some(Ok(15)), 500),
+            Some(Verdict::Recovered)
+        );
+
+        // Failures with a silent PTY that still takes input: an idle session
+        // behind an unreachable probe, so only degraded
+        observe(&mut tracker, Some(100), down(), 10_000);
+        observe(&mut tracker, Some(100), down(), 20_000);
+        assert_eq!(observe(&mut tracker, Some(100), down(), 30_000), None);
+
+        // Once the PTY fails as well, the session is gone
+        assert_eq!(
+            tracker.observe(Observation {
+                process_alive: true,
+                session_since: Some(100),
+                probe: down(),
+                ms_since_output: Some(40_000),
+                pty_failed: true,
+                ssh_exit: None,
+            }),
+            Some(Verdict::Disconnected(
+                "Connecting to gpu:22 timed out (no output for 40 s)".to_string()
+            ))
+        );
+
+        // The old session does not count as the reconnected one
+        tracker.expect_reconnect(200);
+        assert_eq!(observe(&mut tracker, Some(100), None, 0), None);
+        assert_eq!(
+            observe(&mut tracker, Some(205), None, 0),
+            Some(Verdict::Connected)
+        );
+
+        // Leaving the host on request ends monitoring quietly
+        let left = |ssh_exit| {
+            let mut tracker = HealthTracker::new(10_000);
+            observe(&mut tracker, Some(1), None, 0);
+            tracker.observe(Observation {
+                process_alive: true,
+                session_since: None,
+                probe: None,
+                ms_since_output: Some(0),
+                pty_failed: false,
+                ssh_exit,
+            })
+        };
+        let lines = |lines: &[&str]| {
+            lines
+                .iter()
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            left(ssh_exit(&lines(&[
+                "logout",
+                "Connection to gpu closed.",
+                "$ "
+            ]))),
+            Some(Verdict::Ended)
+        );
+        assert_eq!(
+            left(ssh_exit(&lines(&[
+                "client_loop: send disconnect: Broken pipe",
+                "$ "
+            ]))),
+            Some(Verdict::Disconnected(
+                "client_loop: send disconnect: Broken pipe".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_probe_requires_ssh_banner() {
+        async fn serve(greeting: &'static [u8]) -> ProbeTarget {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            tokio::spawn(async move {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let _ = socket.write_all(greeting).await;
+            });
+            ProbeTarget {
+                host: "127.0.0.1".to_string(),
+                port,
+                jump_host: None,
+            }
+        }
+
+        let ssh = serve(b"Welcome\r\nSSH-2.0-OpenSSH_9.6\r\n").await;
+        assert!(probe_ssh(&ssh).await.is_ok());
+
+        let http = serve(b"HTTP/1.1 400 Bad Request\r\n\r\n").await;
+        let error = probe_ssh(&http).await.unwrap_err();
+        assert!(
+            error.starts_with("No SSH banner from 127.0.0.1:"),
+            "{}",
+            error
+        );
+    }
+}
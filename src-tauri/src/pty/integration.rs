@@ -11,44 +11,276 @@ pub fn setup_integration_scripts() -> Option<PathBuf> {
     let bash_init_path = config_dir.join("bash_init.sh");
     let ssh_helper_path = config_dir.join("ssh_helper.sh");
     let zsh_rc_path = config_dir.join(".zshrc");
+    let fish_init_path = config_dir.join("fish_init.fish");
+    let nu_init_path = config_dir.join("nu_init.nu");
 
     // Use embedded shell scripts from build time
     let bash_script = include_str!("../../shell-integration/bash_init.sh");
     let ssh_helper_script = include_str!("../../shell-integration/ssh_helper.sh");
     let zsh_rc = include_str!("../../shell-integration/zshrc");
+    let fish_init = include_str!("../../shell-integration/fish_init.fish");
+    let nu_init = include_str!("../../shell-integration/nu_init.nu");
 
     std::fs::write(&bash_init_path, bash_script).ok()?;
     std::fs::write(&ssh_helper_path, ssh_helper_script).ok()?;
     std::fs::write(&zsh_rc_path, zsh_rc).ok()?;
+    std::fs::write(&fish_init_path, fish_init).ok()?;
+    std::fs::write(&nu_init_path, nu_init).ok()?;
 
     Some(config_dir)
 }
 
+/// Whether the shell has an integration script that reports prompts
+pub fn supports_integration(shell: &str) -> bool {
+    shell.ends_with("bash")
+        || shell.ends_with("zsh")
+        || shell.ends_with("fish")
+        || is_nushell(shell)
+}
+
+pub(super) fn is_nushell(shell: &str) -> bool {
+    Path::new(shell)
+        .file_name()
+        .is_some_and(|name| name == "nu")
+}
+
 /// Configure shell command with integration scripts and environment
 pub fn configure_shell_command(
     cmd: &mut CommandBuilder,
     shell: &str,
     config_dir: Option<&PathBuf>,
+) {
+    configure_shell_launch(cmd, shell, config_dir, true, None);
+}
+
+/// Configure shell command with explicit interactive/login behaviour.
+///
+/// `login` of None keeps the defaults: bash and zsh start interactive with
+/// integration (bash_init.sh already sources the login profiles), fish,
+/// nushell and other shells start as login shells.
+pub fn configure_shell_launch(
+    cmd: &mut CommandBuilder,
+    shell: &str,
+    config_dir: Option<&PathBuf>,
+    interactive: bool,
+    login: Option<bool>,
 ) {
     // Set environment for color support
     cmd.env("TERM", "xterm-256color");
     cmd.env("COLORTERM", "truecolor");
     cmd.env("CLICOLOR", "1");
 
+    if cfg!(target_os = "windows") {
+        return;
+    }
+
+    if !interactive {
+        // No integration without an interactive prompt
+        if login == Some(true) {
+            cmd.args(["-l"]);
+        }
+        return;
+    }
+
     // Configure shell-specific integration
-    if !cfg!(target_os = "windows") {
-        if let Some(config_dir) = config_dir {
-            if shell.ends_with("bash") {
-                let bash_init_path = config_dir.join("bash_init.sh");
-                cmd.args(["--rcfile", bash_init_path.to_string_lossy().as_ref(), "-i"]);
-            } else if shell.ends_with("zsh") {
-                cmd.env("ZDOTDIR", config_dir.to_string_lossy().as_ref());
-                cmd.args(["-i"]);
-            } else {
+    match config_dir {
+        Some(config_dir) if shell.ends_with("bash") => {
+            let bash_init_path = config_dir.join("bash_init.sh");
+            cmd.args(["--rcfile", bash_init_path.to_string_lossy().as_ref(), "-i"]);
+        }
+        Some(config_dir) if shell.ends_with("zsh") => {
+            cmd.env("ZDOTDIR", config_dir.to_string_lossy().as_ref());
+            cmd.args(["-i"]);
+            if login == Some(true) {
                 cmd.args(["-l"]);
             }
-        } else {
-            cmd.args(["-l"]);
         }
+        Some(config_dir) if shell.ends_with("fish") => {
+            // --init-command runs after config.fish, so our handlers are added last
+            let fish_init_path = config_dir.join("fish_init.fish");
+            let init = format!("source {}", fish_quote(&fish_init_path.to_string_lossy()));
+            cmd.args(["--init-command", init.as_str(), "-i"]);
+            if login != Some(false) {
+                cmd.args(["-l"]);
+            }
+        }
+        Some(config_dir) if is_nushell(shell) => {
+            // --execute runs after env.nu/config.nu and then starts the REPL
+            let nu_init_path = config_dir.join("nu_init.nu");
+            let init = format!("source {}", nu_quote(&nu_init_path.to_string_lossy()));
+            cmd.args(["--execute", init.as_str()]);
+            if login != Some(false) {
+                cmd.args(["-l"]);
+            }
+        }
+        _ => {
+            if login != Some(false) {
+                cmd.args(["-l"]);
+            }
+        }
+    }
+}
+
+/// Single-quote a string for fish (only `\\` and `\'` are special)
+pub(super) fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Double-quote a string for nushell
+pub(super) fn nu_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::pty::osc_parser::{parse_cwd_url, OscScanner};
+    use crate::pty::CommandTracker;
+    use portable_pty::{NativePtySystem, PtySize, PtySystem};
+    use std::io::{Read, Write};
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    fn find_shell(name: &str) -> Option<String> {
+        let path = std::env::var_os("PATH")?;
+        std::env::split_paths(&path)
+            .map(|dir| dir.join(name))
+            .find(|candidate| candidate.is_file())
+            .map(|candidate| candidate.to_string_lossy().to_string())
+    }
+
+    /// Spawn `name` with integration, run one command and check the marks it
+    /// reports. Fails when the shell is not installed; tests for shells that
+    /// are not always present are `#[ignore]`d and run with `--ignored`.
+    fn assert_shell_reports_marks(name: &str) {
+        let shell = find_shell(name).unwrap_or_else(|| panic!("{} is not installed", name));
+        let (_home_guard, _home_dir) = crate::tests::helpers::with_test_home();
+        let config_dir = setup_integration_scripts();
+        assert!(config_dir.is_some());
+
+        let pair = NativePtySystem::default()
+            .openpty(PtySize {
+                rows: 40,
+                cols: 120,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .unwrap();
+        let mut cmd = CommandBuilder::new(&shell);
+        configure_shell_command(&mut cmd, &shell, config_dir.as_ref());
+        cmd.cwd("/tmp");
+        let mut child = pair.slave.spawn_command(cmd).unwrap();
+        drop(pair.slave);
+
+        let mut reader = pair.master.try_clone_reader().unwrap();
+        let mut writer = pair.master.take_writer().unwrap();
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 4096];
+            while let Ok(n) = reader.read(&mut buf) {
+                if n == 0 || tx.send(buf[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut scanner = OscScanner::new();
+        let mut tracker = CommandTracker::new();
+        let mut saw_remote_host = false;
+        let mut osc7_path = None;
+        let mut sent = false;
+        let mut block = None;
+        let deadline = Instant::now() + Duration::from_secs(20);
+        while block.is_none() && Instant::now() < deadline {
+            let Ok(data) = rx.recv_timeout(Duration::from_millis(200)) else {
+                continue;
+            };
+            for seq in scanner.feed(&data) {
+                saw_remote_host |= seq.code == 1337 && seq.payload.starts_with("RemoteHost=");
+                if seq.code == 7 {
+                    osc7_path = parse_cwd_url(&seq.payload).map(|(_, path)| path);
+                }
+                // Output from rc files can close a block before the first prompt
+                let finished = tracker.handle_osc(&seq);
+                if sent {
+                    block = block.or(finished);
+                } else if matches!(seq.code, 133 | 633) && seq.payload == "A" {
+                    // A pipeline, so the whole line is reported and not just
+                    // its first command
+                    writer.write_all(b"echo aiterm-ok | cat\r").unwrap();
+                    sent = true;
+                }
+            }
+        }
+        let _ = writer.write_all(b"exit\r");
+        let _ = child.kill();
+
+        let block = block.unwrap_or_else(|| panic!("{} reported no finished command", name));
+        assert_eq!(block.command.as_deref(), Some("echo aiterm-ok | cat"));
+        assert_eq!(block.exit_code, Some(0));
+        assert!(block.cwd.is_some());
+        assert!(saw_remote_host);
+        // macOS may report /private/tmp
+        assert!(osc7_path.is_some_and(|path| path.ends_with("/tmp")));
+    }
+
+    #[test]
+    fn test_launch_args_for_fish_and_nushell() {
+        let config_dir = PathBuf::from("/home/me/.config/aiterminal");
+
+        let mut cmd = CommandBuilder::new("/usr/bin/fish");
+        configure_shell_command(&mut cmd, "/usr/bin/fish", Some(&config_dir));
+        let argv: Vec<_> = cmd.get_argv().iter().map(|a| a.to_string_lossy()).collect();
+        assert_eq!(
+            argv[1..],
+            [
+                "--init-command",
+                "source '/home/me/.config/aiterminal/fish_init.fish'",
+                "-i",
+                "-l"
+            ]
+        );
+
+        let mut cmd = CommandBuilder::new("/usr/bin/nu");
+        configure_shell_launch(
+            &mut cmd,
+            "/usr/bin/nu",
+            Some(&config_dir),
+            true,
+            Some(false),
+        );
+        let argv: Vec<_> = cmd.get_argv().iter().map(|a| a.to_string_lossy()).collect();
+        assert_eq!(
+            argv[1..],
+            [
+                "--execute",
+                "source \"/home/me/.config/aiterminal/nu_init.nu\""
+            ]
+        );
+        assert!(supports_integration("/usr/bin/nu"));
+        assert!(!supports_integration("/usr/bin/menu"));
+    }
+
+    #[test]
+    fn test_bash_spawn_reports_marks() {
+        assert_shell_reports_marks("bash");
+    }
+
+    #[test]
+    #[ignore = "needs zsh installed"]
+    fn test_zsh_spawn_reports_marks() {
+        assert_shell_reports_marks("zsh");
+    }
+
+    #[test]
+    #[ignore = "needs fish installed"]
+    fn test_fish_spawn_reports_marks() {
+        assert_shell_reports_marks("fish");
+    }
+
+    #[test]
+    #[ignore = "needs nushell (nu) installed"]
+    fn test_nushell_spawn_reports_marks() {
+        assert_shell_reports_marks("nu");
     }
 }
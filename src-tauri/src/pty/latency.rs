@@ -0,0 +1,555 @@
+//! SSH latency monitoring.
+//!
+//! One tokio task per PTY probes the machine the local ssh client actually
+//! talks to: the first hop, or its ProxyJump host when there is one, on the
+//! port from `~/.ssh/config` or the matching SSH profile. Probes are plain TCP
+//! connects on a side channel, so the user's session is never touched.
+
+use crate::models::{
+    AppState, RemoteHop, SshSessionInfo, LATENCY_HISTORY_SIZE, LATENCY_PROBE_INTERVAL_MS,
+    LATENCY_PROBE_TIMEOUT_MS,
+};
+use crate::ssh::{parse_ssh_config, read_ssh_profiles, ConnectionType, SSHConfigHost, SSHProfile};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::State;
+
+use super::osc_parser::current_timestamp;
+
+/// Host and port the latency probes connect to
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProbeTarget {
+    pub host: String,
+    pub port: u16,
+    /// Set when the SSH host sits behind a ProxyJump and the jump host is probed instead
+    pub jump_host: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LatencySample {
+    pub timestamp: u64,
+    /// None when the probe failed or timed out
+    pub latency_ms: Option<u64>,
+}
+
+/// Rolling latency statistics returned by `get_ssh_latency_stats`
+#[derive(Serialize, Debug, Clone)]
+pub struct LatencyStats {
+    pub remote_host: String,
+    /// None until `~/.ssh/config` and the profiles have been read
+    pub target: Option<ProbeTarget>,
+    pub sent: usize,
+    pub lost: usize,
+    pub loss_percent: f64,
+    pub last_ms: Option<u64>,
+    pub min_ms: Option<u64>,
+    pub avg_ms: Option<u64>,
+    pub p95_ms: Option<u64>,
+    /// Oldest first, at most `LATENCY_HISTORY_SIZE`
+    pub samples: Vec<LatencySample>,
+}
+
+struct LatencyHistory {
+    samples: VecDeque<LatencySample>,
+}
+
+impl LatencyHistory {
+    fn new() -> Self {
+        Self {
+            samples: VecDeque::new(),
+        }
+    }
+
+    fn record(&mut self, sample: LatencySample) {
+        self.samples.push_back(sample);
+        while self.samples.len() > LATENCY_HISTORY_SIZE {
+            self.samples.pop_front();
+        }
+    }
+
+    fn stats(&self, remote_host: &str, target: Option<&ProbeTarget>) -> LatencyStats {
+        let mut ok: Vec<u64> = self.samples.iter().filter_map(|s| s.latency_ms).collect();
+        ok.sort_unstable();
+        let sent = self.samples.len();
+        let lost = sent - ok.len();
+        // Nearest-rank percentile
+        let p95 = (ok.len() * 95).div_ceil(100).max(1);
+
+        LatencyStats {
+            remote_host: remote_host.to_string(),
+            target: target.cloned(),
+            sent,
+            lost,
+            loss_percent: if sent == 0 {
+                0.0
+            } else {
+                lost as f64 * 100.0 / sent as f64
+            },
+            last_ms: self.samples.back().and_then(|s| s.latency_ms),
+            min_ms: ok.first().copied(),
+            avg_ms: (!ok.is_empty()).then(|| ok.iter().sum::<u64>() / ok.len() as u64),
+            p95_ms: ok.get(p95 - 1).copied(),
+            samples: self.samples.iter().cloned().collect(),
+        }
+    }
+}
+
+/// State shared between a monitor task and `get_ssh_latency_stats`
+struct MonitorShared {
+    remote_host: String,
+    target: Option<ProbeTarget>,
+    history: LatencyHistory,
+}
+
+/// A running latency monitor for one PTY
+pub struct LatencyMonitor {
+    /// First hop the probes are derived from
+    hop: (String, Option<String>),
+    shared: Arc<Mutex<MonitorShared>>,
+    task: tauri::async_runtime::JoinHandle<()>,
+}
+
+type Monitors = Arc<Mutex<HashMap<u32, LatencyMonitor>>>;
+
+/// Start monitoring for a PTY whose first hop is `first`, replacing a monitor
+/// for a different hop. Moving between nested hosts keeps the running monitor
+/// and its history, since the probes still go to the same first hop.
+pub(super) fn start_latency_monitor(
+    id: u32,
+    first: &RemoteHop,
+    remote_host: &str,
+    monitors: &Monitors,
+    ssh_sessions: &Arc<Mutex<HashMap<u32, SshSessionInfo>>>,
+) {
+    let Ok(mut monitors) = monitors.lock() else {
+        return;
+    };
+    let hop = (first.host.clone(), first.ip.clone());
+    if let Some(monitor) = monitors.get(&id) {
+        if monitor.hop == hop {
+            if let Ok(mut shared) = monitor.shared.lock() {
+                shared.remote_host = remote_host.to_string();
+            }
+            return;
+        }
+    }
+
+    let shared = Arc::new(Mutex::new(MonitorShared {
+        remote_host: remote_host.to_string(),
+        target: None,
+        history: LatencyHistory::new(),
+    }));
+    let task = tauri::async_runtime::spawn(run_monitor(
+        id,
+        hop.clone(),
+        shared.clone(),
+        ssh_sessions.clone(),
+    ));
+    if let Some(old) = monitors.insert(id, LatencyMonitor { hop, shared, task }) {
+        old.task.abort();
+    }
+}
+
+/// Stop a PTY's monitor, if any. The task is aborted, not left to notice.
+pub(super) fn stop_latency_monitor(id: u32, monitors: &Monitors) {
+    let monitor = monitors.lock().ok().and_then(|mut m| m.remove(&id));
+    if let Some(monitor) = monitor {
+        monitor.task.abort();
+    }
+}
+
+async fn run_monitor(
+    id: u32,
+    (host, ip): (String, Option<String>),
+    shared: Arc<Mutex<MonitorShared>>,
+    ssh_sessions: Arc<Mutex<HashMap<u32, SshSessionInfo>>>,
+) {
+    let first_host = host.clone();
+    let target = tokio::task::spawn_blocking(move || {
+        let config = parse_ssh_config().unwrap_or_default();
+        let profiles = read_ssh_profiles().unwrap_or_default();
+        resolve_probe_target(&host, ip.as_deref(), &config, &profiles)
+    })
+    .await;
+    let Ok(target) = target else {
+        return;
+    };
+    if let Ok(mut shared) = shared.lock() {
+        shared.target = Some(target.clone());
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_millis(LATENCY_PROBE_INTERVAL_MS));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let latency_ms = probe(&target).await;
+
+        if let Ok(mut shared) = shared.lock() {
+            shared.history.record(LatencySample {
+                timestamp: current_timestamp(),
+                latency_ms,
+            });
+        }
+        if let Ok(mut sessions) = ssh_sessions.lock() {
+            if let Some(info) = sessions.get_mut(&id) {
+                if latency_ms.is_some() {
+                    info.last_latency_ms = latency_ms;
+                }
+                if target.jump_host.is_none() && info.remote_host == first_host {
+                    info.remote_port = target.port;
+                }
+            }
+        }
+    }
+}
+
+/// Time a TCP connect, including name resolution
+async fn probe(target: &ProbeTarget) -> Option<u64> {
+    measure_tcp_latency(target).await.ok().map(|(_, ms)| ms)
+}
+
+/// Connect to the target, timing the connect including name resolution
+pub(super) async fn measure_tcp_latency(
+    target: &ProbeTarget,
+) -> Result<(tokio::net::TcpStream, u64), String> {
+    let start = Instant::now();
+    let connect = tokio::net::TcpStream::connect((target.host.as_str(), target.port));
+    match tokio::time::timeout(Duration::from_millis(LATENCY_PROBE_TIMEOUT_MS), connect).await {
+        Ok(Ok(stream)) => Ok((stream, start.elapsed().as_millis() as u64)),
+        Ok(Err(e)) => Err(format!(
+            "Failed to connect to {}:{}: {}",
+            target.host, target.port, e
+        )),
+        Err(_) => Err(format!(
+            "Connecting to {}:{} timed out",
+            target.host, target.port
+        )),
+    }
+}
+
+/// Connection settings found for a host
+#[derive(Default)]
+struct HostSettings {
+    hostname: Option<String>,
+    port: Option<u16>,
+    proxy_jump: Option<String>,
+}
+
+/// Work out where to probe for a first hop reported as `host` (with the
+/// server address from `SSH_CONNECTION` as `ip`)
+fn resolve_probe_target(
+    host: &str,
+    ip: Option<&str>,
+    config: &[SSHConfigHost],
+    profiles: &[SSHProfile],
+) -> ProbeTarget {
+    let settings = find_host_settings(host, config, profiles);
+    target_for(host, ip, settings, config)
+}
+
+/// Where to probe for a saved profile: its host, or the first jump host.
+/// None when ssh connects through a ProxyCommand, whose path a TCP probe
+/// cannot follow.
+pub(super) fn profile_probe_target(
+    profile: &SSHProfile,
+    config: &[SSHConfigHost],
+) -> Option<ProbeTarget> {
+    let in_profile = profile
+        .ssh_options
+        .iter()
+        .flatten()
+        .any(|option| sets_proxy_command(option));
+    let (host, settings) = match (&profile.connection_type, &profile.manual_config) {
+        (ConnectionType::Manual, Some(manual)) => (
+            manual.hostname.as_str(),
+            HostSettings {
+                hostname: Some(manual.hostname.clone()),
+                port: manual.port,
+                proxy_jump: manual.proxy_jump.clone(),
+            },
+        ),
+        _ => {
+            let alias = profile.ssh_config_host.as_deref().unwrap_or_default();
+            let settings = config
+                .iter()
+                .find(|entry| entry.host.eq_ignore_ascii_case(alias))
+                .map(config_settings)
+                .unwrap_or_default();
+            (alias, settings)
+        }
+    };
+    let in_config = config
+        .iter()
+        .filter(|entry| entry.host.eq_ignore_ascii_case(host))
+        .filter_map(|entry| entry.options.as_ref()?.get("proxycommand"))
+        .any(|command| !command.trim().eq_ignore_ascii_case("none"));
+    if in_profile || in_config {
+        return None;
+    }
+    Some(target_for(host, None, settings, config))
+}
+
+/// A custom ssh option like `-o ProxyCommand=...` (other than `none`)
+fn sets_proxy_command(option: &str) -> bool {
+    let lower = option.to_ascii_lowercase();
+    lower.find("proxycommand").is_some_and(|at| {
+        let value = lower[at + "proxycommand".len()..]
+            .trim_start_matches(|c: char| c == '=' || c.is_whitespace());
+        !value.is_empty() && !value.starts_with("none")
+    })
+}
+
+fn target_for(
+    host: &str,
+    ip: Option<&str>,
+    settings: HostSettings,
+    config: &[SSHConfigHost],
+) -> ProbeTarget {
+    // The ssh client only talks to the jump host; the target is behind it
+    if let Some((jump, jump_port)) = settings.proxy_jump.as_deref().and_then(parse_first_jump) {
+        let jump_settings = config
+            .iter()
+            .find(|entry| entry.host.eq_ignore_ascii_case(&jump))
+            .map(config_settings)
+            .unwrap_or_default();
+        return ProbeTarget {
+            host: jump_settings.hostname.unwrap_or_else(|| jump.clone()),
+            port: jump_port.or(jump_settings.port).unwrap_or(22),
+            jump_host: Some(jump),
+        };
+    }
+
+    // The server address from SSH_CONNECTION is how the server sees itself,
+    // which behind NAT or on a private cloud network this machine cannot reach
+    let known_host = (!host.is_empty() && host != "unknown").then(|| host.to_string());
+    ProbeTarget {
+        host: settings
+            .hostname
+            .or(known_host)
+            .or_else(|| ip.map(str::to_string))
+            .unwrap_or_else(|| host.to_string()),
+        port: settings.port.unwrap_or(22),
+        jump_host: None,
+    }
+}
+
+fn config_settings(entry: &SSHConfigHost) -> HostSettings {
+    HostSettings {
+        hostname: entry.hostname.clone(),
+        port: entry.port,
+        proxy_jump: entry.proxy_jump.clone(),
+    }
+}
+
+/// Find the config entry or manual profile for a host name reported by the
+/// remote shell: one whose alias or HostName is that name. Short names are
+/// not compared, since `login1` of one site says nothing about another's.
+fn find_host_settings(
+    host: &str,
+    config: &[SSHConfigHost],
+    profiles: &[SSHProfile],
+) -> HostSettings {
+    let matches = |name: &str| name.eq_ignore_ascii_case(host);
+
+    let from_config = config
+        .iter()
+        .find(|entry| matches(&entry.host) || entry.hostname.as_deref().is_some_and(matches));
+    if let Some(entry) = from_config {
+        return config_settings(entry);
+    }
+    let from_profile = profiles
+        .iter()
+        .filter_map(|profile| profile.manual_config.as_ref())
+        .find(|manual| matches(&manual.hostname));
+    if let Some(manual) = from_profile {
+        return HostSettings {
+            hostname: Some(manual.hostname.clone()),
+            port: manual.port,
+            proxy_jump: manual.proxy_jump.clone(),
+        };
+    }
+    HostSettings::default()
+}
+
+/// First host of a ProxyJump list: `[user@]host[:port]`, `ssh://...` or `none`
+fn parse_first_jump(proxy_jump: &str) -> Option<(String, Option<u16>)> {
+    let first = proxy_jump.split(',').next()?.trim();
+    if first.is_empty() || first.eq_ignore_ascii_case("none") {
+        return None;
+    }
+    let first = first.strip_prefix("ssh://").unwrap_or(first);
+    let host_port = first.rsplit_once('@').map_or(first, |(_, rest)| rest);
+
+    // [v6::addr]:port
+    if let Some(rest) = host_port.strip_prefix('[') {
+        let (host, after) = rest.split_once(']')?;
+        let port = after.strip_prefix(':').and_then(|port| port.parse().ok());
+        return Some((host.to_string(), port));
+    }
+    match host_port.split_once(':') {
+        Some((host, port)) => Some((host.to_string(), port.parse().ok())),
+        None => Some((host_port.to_string(), None)),
+    }
+}
+
+/// Tauri command: rolling latency statistics for an SSH PTY, or None when
+/// the PTY is local
+#[tauri::command]
+pub fn get_ssh_latency_stats(
+    id: u32,
+    state: State<AppState>,
+) -> Result<Option<LatencyStats>, String> {
+    let monitors = state
+        .latency_monitors
+        .lock()
+        .map_err(|e| format!("Failed to acquire latency monitor lock: {}", e))?;
+    let Some(monitor) = monitors.get(&id) else {
+        return Ok(None);
+    };
+    let shared = monitor
+        .shared
+        .lock()
+        .map_err(|e| format!("Failed to acquire latency stats lock: {}", e))?;
+    Ok(Some(
+        shared
+            .history
+            .stats(&shared.remote_host, shared.target.as_ref()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_host(
+        host: &str,
+        hostname: Option<&str>,
+        port: Option<u16>,
+        jump: Option<&str>,
+    ) -> SSHConfigHost {
+        SSHConfigHost {
+            host: host.to_string(),
+            hostname: hostname.map(str::to_string),
+            user: None,
+            port,
+            identity_file: None,
+            proxy_jump: jump.map(str::to_string),
+            options: None,
+            warnings: None,
+        }
+    }
+
+    #[test]
+    fn test_probe_target_uses_config_port_and_jump_host() {
+        let config = vec![
+            config_host("bastion", Some("bastion.example.com"), Some(2222), None),
+            config_host("cluster", Some("login1.cluster.edu"), Some(2200), None),
+            config_host("gpu", Some("10.1.2.3"), None, Some("me@bastion")),
+        ];
+
+        // Shell reports the HostName of a host configured under an alias; the
+        // server address it sees (behind NAT here) is not probed
+        assert_eq!(
+            resolve_probe_target("login1.cluster.edu", Some("192.0.2.10"), &config, &[]),
+            ProbeTarget {
+                host: "login1.cluster.edu".to_string(),
+                port: 2200,
+                jump_host: None,
+            }
+        );
+        // The same short name at another site is a different host
+        assert_eq!(
+            resolve_probe_target("login1.other.org", Some("198.51.100.7"), &config, &[]),
+            ProbeTarget {
+                host: "login1.other.org".to_string(),
+                port: 22,
+                jump_host: None,
+            }
+        );
+        // Only a shell that could not name its host leaves the address
+        assert_eq!(
+            resolve_probe_target("unknown", Some("198.51.100.7"), &config, &[]).host,
+            "198.51.100.7"
+        );
+        assert_eq!(
+            resolve_probe_target("gpu", Some("10.1.2.3"), &config, &[]),
+            ProbeTarget {
+                host: "bastion.example.com".to_string(),
+                port: 2222,
+                jump_host: Some("bastion".to_string()),
+            }
+        );
+        assert_eq!(resolve_probe_target("unknown", None, &config, &[]).port, 22);
+
+        assert_eq!(
+            parse_first_jump("ssh://me@[2001:db8::1]:2022,other"),
+            Some(("2001:db8::1".to_string(), Some(2022)))
+        );
+        assert_eq!(parse_first_jump("none"), None);
+    }
+
+    #[test]
+    fn test_profile_behind_proxy_command_is_not_probed() {
+        let profile =
+            |json: serde_json::Value| -> SSHProfile { serde_json::from_value(json).unwrap() };
+        let manual = profile(serde_json::json!({
+            "id": "p1",
+            "name": "Lab",
+            "connectionType": "manual",
+            "manualConfig": { "hostname": "lab.example.com", "username": "me", "port": 2200 }
+        }));
+        assert_eq!(
+            profile_probe_target(&manual, &[]),
+            Some(ProbeTarget {
+                host: "lab.example.com".to_string(),
+                port: 2200,
+                jump_host: None,
+            })
+        );
+
+        let mut proxied = manual.clone();
+        proxied.ssh_options = Some(vec![
+            "-o ProxyCommand=cloudflared access ssh --hostname %h".to_string()
+        ]);
+        assert_eq!(profile_probe_target(&proxied, &[]), None);
+        proxied.ssh_options = Some(vec!["-o ProxyCommand=none".to_string()]);
+        assert!(profile_probe_target(&proxied, &[]).is_some());
+
+        let mut entry = config_host("tunnel", Some("10.0.0.5"), None, None);
+        entry.options = Some(HashMap::from([(
+            "proxycommand".to_string(),
+            "ssh -W %h:%p bastion".to_string(),
+        )]));
+        let from_config = profile(serde_json::json!({
+            "id": "p2",
+            "name": "Tunnel",
+            "connectionType": "ssh-config",
+            "sshConfigHost": "tunnel"
+        }));
+        assert_eq!(profile_probe_target(&from_config, &[entry]), None);
+    }
+
+    #[test]
+    fn test_history_stats_are_rolling() {
+        let mut history = LatencyHistory::new();
+        assert_eq!(history.stats("h", None).p95_ms, None);
+
+        for i in 0..(LATENCY_HISTORY_SIZE + 20) as u64 {
+            history.record(LatencySample {
+                timestamp: i,
+                // Every tenth probe is lost; the rest take 1..=100 ms
+                latency_ms: (i % 10 != 0).then_some(i % 100 + 1),
+            });
+        }
+        let stats = history.stats("h", None);
+        assert_eq!(stats.sent, LATENCY_HISTORY_SIZE);
+        assert_eq!(stats.samples[0].timestamp, 20);
+        assert_eq!(stats.lost, LATENCY_HISTORY_SIZE / 10);
+        assert_eq!(stats.loss_percent, 10.0);
+        assert_eq!(stats.min_ms, Some(2));
+        assert_eq!(stats.p95_ms, Some(95));
+        assert_eq!(stats.last_ms, Some(40));
+    }
+}
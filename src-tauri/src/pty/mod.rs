@@ -1,14 +1,46 @@
 // PTY module - Terminal pseudo-terminal management
+mod capture;
+mod command_blocks;
 mod commands;
+mod context;
+#[cfg(unix)]
+mod daemon;
+mod groups;
+mod health;
 mod integration;
+mod latency;
 mod osc_parser;
+mod output;
+mod paste;
+mod process;
 mod reader;
+mod recording;
+mod remote;
+mod scrollback;
 mod shell;
 mod spawn;
+mod startup;
+mod tmux;
+mod triggers;
+mod utf8_stream;
 
 // Re-export public interfaces
-pub use commands::{check_pty_health, close_pty, focus_terminal, get_active_terminal, get_pty_cwd, get_pty_info, resize_pty, write_to_pty};
-pub use spawn::spawn_pty;
+pub use capture::{cancel_command_capture, run_command_capture};
+pub use command_blocks::{CommandBlock, CommandTracker};
+pub use commands::{ack_pty_output, attach_pty_channel, check_pty_health, close_pty, detach_pty_channel, focus_terminal, get_active_terminal, get_command_blocks, get_last_command, get_pty_cwd, get_pty_info, get_terminal_context, read_pty_scrollback, resize_pty, search_pty_scrollback, set_pty_output_storm_mode, write_to_pty};
+pub use context::PtyCwd;
+#[cfg(unix)]
+pub use daemon::{attach_daemon_session, detach_pty, list_daemon_sessions, run_pty_daemon, DaemonClient, DAEMON_ARG};
+pub use groups::{add_pty_to_group, create_input_group, delete_input_group, list_input_groups, remove_pty_from_group, write_to_group, InputGroup};
+pub use latency::{get_ssh_latency_stats, LatencyMonitor};
+pub use output::OutputControl;
+pub use paste::{cancel_paste, paste_to_pty, PasteQueue};
+pub use process::get_pty_process;
+pub use recording::{list_recordings, replay_recording, set_replay_speed, start_pty_recording, stop_pty_recording, stop_replay, PtyRecorder, ReplayControl};
+pub use scrollback::ScrollbackBuffer;
+pub use spawn::{connect_ssh_profile, connect_ssh_profile_native, spawn_pty, spawn_pty_with_profile};
+pub use tmux::{get_tmux_windows, TmuxGateway, TmuxPaneRef};
+pub use triggers::{init_triggers, load_triggers, reload_triggers, save_triggers, TriggerSet};
 
 // Re-export PtyInfo for backward compatibility
 use serde::{Deserialize, Serialize};
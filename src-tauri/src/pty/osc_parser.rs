@@ -1,59 +1,246 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
-/// Parse RemoteHost OSC sequence
-/// Format: ESC]1337;RemoteHost=user@host:ip;Depth=N BEL
-/// Returns Some((user, host, ip)) if SSH, None if local
-pub fn parse_remote_host_osc(data: &str) -> Option<Option<(String, String, Option<String>)>> {
-    // Look for OSC 1337 RemoteHost sequence
-    let prefix = "\x1b]1337;RemoteHost=";
-    if let Some(start) = data.find(prefix) {
-        let after_prefix = &data[start + prefix.len()..]; // Skip the prefix
-
-        // Find terminator (BEL \x07 or ST \x1b\\)
-        let end = after_prefix
-            .find('\x07')
-            .or_else(|| after_prefix.find("\x1b\\"))
-            .unwrap_or(after_prefix.len());
-
-        let value = &after_prefix[..end];
-
-        // Split off optional parameters like ;Depth=N
-        let remote_info = value.split(';').next().unwrap_or(value);
-
-        if remote_info.is_empty() || remote_info == "local" {
-            // Explicitly local
-            return Some(None);
+/// Maximum OSC payload we buffer (PreviewFile payloads can be large; anything
+/// beyond this is dropped rather than held in memory)
+const MAX_OSC_LEN: usize = 64 * 1024;
+
+/// A complete OSC sequence extracted from the PTY output stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscSequence {
+    /// Numeric OSC code (133, 633, 1337, ...)
+    pub code: u32,
+    /// Everything after `code;` up to (not including) the terminator
+    pub payload: String,
+    /// Absolute stream offset of the ESC that introduced the sequence
+    pub start: u64,
+    /// Absolute stream offset just past the terminator
+    pub end: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScanState {
+    Ground,
+    Escape,
+    Osc,
+    OscEscape,
+}
+
+/// Incremental OSC scanner.
+///
+/// PTY reads are arbitrary 8KB chunks, so an OSC sequence can straddle two
+/// reads. The scanner keeps partial sequences between calls to `feed` and
+/// tracks the absolute byte offset of the stream so callers can map marks
+/// back to output ranges.
+pub struct OscScanner {
+    state: ScanState,
+    buf: Vec<u8>,
+    overflow: bool,
+    start: u64,
+    offset: u64,
+}
+
+impl OscScanner {
+    pub fn new() -> Self {
+        Self {
+            state: ScanState::Ground,
+            buf: Vec::new(),
+            overflow: false,
+            start: 0,
+            offset: 0,
+        }
+    }
+
+    /// Feed a chunk of raw PTY output, returning every OSC sequence completed
+    /// within it
+    pub fn feed(&mut self, data: &[u8]) -> Vec<OscSequence> {
+        let mut sequences = Vec::new();
+
+        for &byte in data {
+            match self.state {
+                ScanState::Ground => {
+                    if byte == 0x1b {
+                        self.state = ScanState::Escape;
+                        self.start = self.offset;
+                    }
+                }
+                ScanState::Escape => self.on_escape_byte(byte),
+                ScanState::Osc => match byte {
+                    0x07 => {
+                        if let Some(seq) = self.finish() {
+                            sequences.push(seq);
+                        }
+                    }
+                    0x1b => self.state = ScanState::OscEscape,
+                    // CAN / SUB abort the sequence
+                    0x18 | 0x1a => self.state = ScanState::Ground,
+                    _ => {
+                        if self.buf.len() < MAX_OSC_LEN {
+                            self.buf.push(byte);
+                        } else {
+                            self.overflow = true;
+                        }
+                    }
+                },
+                ScanState::OscEscape => {
+                    if byte == b'\\' {
+                        if let Some(seq) = self.finish() {
+                            sequences.push(seq);
+                        }
+                    } else {
+                        // ESC without ST aborts the OSC and starts a new escape
+                        self.start = self.offset - 1;
+                        self.on_escape_byte(byte);
+                    }
+                }
+            }
+            self.offset += 1;
+        }
+
+        sequences
+    }
+
+    fn on_escape_byte(&mut self, byte: u8) {
+        match byte {
+            b']' => {
+                self.state = ScanState::Osc;
+                self.buf.clear();
+                self.overflow = false;
+            }
+            0x1b => {
+                self.state = ScanState::Escape;
+                self.start = self.offset;
+            }
+            _ => self.state = ScanState::Ground,
         }
+    }
+
+    fn finish(&mut self) -> Option<OscSequence> {
+        self.state = ScanState::Ground;
+        if self.overflow {
+            return None;
+        }
+
+        let raw = String::from_utf8_lossy(&self.buf);
+        let (code, payload) = match raw.split_once(';') {
+            Some((code, payload)) => (code, payload),
+            None => (raw.as_ref(), ""),
+        };
+        let code = code.parse::<u32>().ok()?;
+
+        Some(OscSequence {
+            code,
+            payload: payload.to_string(),
+            start: self.start,
+            end: self.offset + 1,
+        })
+    }
+}
+
+impl Default for OscScanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Remote side of an OSC 1337 RemoteHost report
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteHost {
+    pub user: String,
+    pub host: String,
+    pub ip: Option<String>,
+    /// SSH nesting level from `;Depth=N`; 0 or None when the shell does not track it
+    pub depth: Option<u32>,
+}
+
+/// Parse the value of an OSC 1337 RemoteHost sequence
+/// Format: ESC]1337;RemoteHost=user@host:ip;Depth=N BEL
+/// Takes the payload after `1337;`.
+/// Returns Some(RemoteHost) if SSH, None if local
+pub fn parse_remote_host(payload: &str) -> Option<Option<RemoteHost>> {
+    let value = payload.strip_prefix("RemoteHost=")?;
+
+    // Split off optional parameters like ;Depth=N
+    let mut params = value.split(';');
+    let remote_info = params.next().unwrap_or(value);
+    let depth = params
+        .find_map(|param| param.strip_prefix("Depth="))
+        .and_then(|depth| depth.trim().parse().ok());
 
-        // Parse user@host:ip
-        if let Some(at_pos) = remote_info.find('@') {
-            let user = remote_info[..at_pos].to_string();
-            let rest = &remote_info[at_pos + 1..];
-
-            // Check for :ip suffix
-            if let Some(colon_pos) = rest.rfind(':') {
-                let host = rest[..colon_pos].to_string();
-                let ip = rest[colon_pos + 1..].to_string();
-                return Some(Some((user, host, Some(ip))));
-            } else {
-                return Some(Some((user, rest.to_string(), None)));
+    if remote_info.is_empty() || remote_info == "local" {
+        // Explicitly local
+        return Some(None);
+    }
+
+    // Parse user@host:ip
+    let (user, rest) = match remote_info.find('@') {
+        Some(at_pos) => (
+            remote_info[..at_pos].to_string(),
+            &remote_info[at_pos + 1..],
+        ),
+        // Just hostname, use current user
+        None => (
+            std::env::var("USER").unwrap_or_else(|_| "unknown".to_string()),
+            remote_info,
+        ),
+    };
+
+    // Check for :ip suffix
+    let (host, ip) = match rest.rfind(':') {
+        Some(colon_pos) => (
+            rest[..colon_pos].to_string(),
+            Some(rest[colon_pos + 1..].to_string()),
+        ),
+        None => (rest.to_string(), None),
+    };
+    Some(Some(RemoteHost {
+        user,
+        host,
+        ip,
+        depth,
+    }))
+}
+
+/// Parse an OSC 7 working-directory report: `file://host/path` with the
+/// path percent-encoded. Returns the host (possibly empty) and the path.
+pub fn parse_cwd_url(payload: &str) -> Option<(String, String)> {
+    let rest = payload.strip_prefix("file://")?;
+    let slash = rest.find('/')?;
+    let path = urlencoding::decode_binary(&rest.as_bytes()[slash..]);
+    Some((
+        rest[..slash].to_string(),
+        String::from_utf8_lossy(&path).into_owned(),
+    ))
+}
+
+/// Decode a value escaped for OSC 633 (`\\` for backslash, `\xNN` for bytes)
+pub fn unescape_osc_value(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 1 < bytes.len() {
+            if bytes[i + 1] == b'\\' {
+                out.push(b'\\');
+                i += 2;
+                continue;
             }
-        } else {
-            // Just hostname, use current user
-            let user = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
-
-            // Check for :ip suffix
-            if let Some(colon_pos) = remote_info.rfind(':') {
-                let host = remote_info[..colon_pos].to_string();
-                let ip = remote_info[colon_pos + 1..].to_string();
-                return Some(Some((user, host, Some(ip))));
-            } else {
-                return Some(Some((user, remote_info.to_string(), None)));
+            if bytes[i + 1] == b'x'
+                && i + 3 < bytes.len()
+                && bytes[i + 2].is_ascii_hexdigit()
+                && bytes[i + 3].is_ascii_hexdigit()
+            {
+                let hex = std::str::from_utf8(&bytes[i + 2..i + 4]).unwrap_or("00");
+                out.push(u8::from_str_radix(hex, 16).unwrap_or(0));
+                i += 4;
+                continue;
             }
         }
+        out.push(bytes[i]);
+        i += 1;
     }
 
-    None
+    String::from_utf8_lossy(&out).to_string()
 }
 
 pub fn current_timestamp() -> u64 {
@@ -62,3 +249,102 @@ pub fn current_timestamp() -> u64 {
         .unwrap()
         .as_secs()
 }
+
+pub fn current_timestamp_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scanner_extracts_bel_and_st_sequences() {
+        let mut scanner = OscScanner::new();
+        let seqs = scanner.feed(b"hi\x1b]133;A\x07$ \x1b]633;E;ls\x1b\\");
+        assert_eq!(seqs.len(), 2);
+        assert_eq!(seqs[0].code, 133);
+        assert_eq!(seqs[0].payload, "A");
+        assert_eq!(seqs[0].start, 2);
+        assert_eq!(seqs[0].end, 10);
+        assert_eq!(seqs[1].code, 633);
+        assert_eq!(seqs[1].payload, "E;ls");
+    }
+
+    #[test]
+    fn test_scanner_handles_split_reads() {
+        let mut scanner = OscScanner::new();
+        assert!(scanner.feed(b"out\x1b]13").is_empty());
+        assert!(scanner.feed(b"3;D;").is_empty());
+        let seqs = scanner.feed(b"127\x07next");
+        assert_eq!(seqs.len(), 1);
+        assert_eq!(seqs[0].payload, "D;127");
+        assert_eq!(seqs[0].start, 3);
+        assert_eq!(seqs[0].end, 15);
+    }
+
+    #[test]
+    fn test_scanner_ignores_csi_and_aborted_osc() {
+        let mut scanner = OscScanner::new();
+        let seqs = scanner.feed(b"\x1b[31mred\x1b]133;A\x1b[0m\x1b]133;B\x07");
+        assert_eq!(seqs.len(), 1);
+        assert_eq!(seqs[0].payload, "B");
+    }
+
+    #[test]
+    fn test_parse_remote_host() {
+        assert_eq!(parse_remote_host("RemoteHost=;Depth=0"), Some(None));
+        assert_eq!(
+            parse_remote_host("RemoteHost=alice@login1:10.0.0.1;Depth=1"),
+            Some(Some(RemoteHost {
+                user: "alice".to_string(),
+                host: "login1".to_string(),
+                ip: Some("10.0.0.1".to_string()),
+                depth: Some(1),
+            }))
+        );
+        assert_eq!(
+            parse_remote_host("RemoteHost=bob@node042;Depth=2"),
+            Some(Some(RemoteHost {
+                user: "bob".to_string(),
+                host: "node042".to_string(),
+                ip: None,
+                depth: Some(2),
+            }))
+        );
+        assert_eq!(
+            parse_remote_host("RemoteHost=bob@node042")
+                .flatten()
+                .and_then(|remote| remote.depth),
+            None
+        );
+        assert_eq!(parse_remote_host("PythonREPL=1"), None);
+    }
+
+    #[test]
+    fn test_parse_cwd_url() {
+        assert_eq!(
+            parse_cwd_url("file://node042.cluster.edu/scratch/my%20job/%C3%A9t%C3%A9"),
+            Some((
+                "node042.cluster.edu".to_string(),
+                "/scratch/my job/été".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_cwd_url("file:///tmp"),
+            Some((String::new(), "/tmp".to_string()))
+        );
+        assert_eq!(parse_cwd_url("file://host"), None);
+        assert_eq!(parse_cwd_url("/tmp"), None);
+    }
+
+    #[test]
+    fn test_unescape_osc_value() {
+        assert_eq!(unescape_osc_value(r"echo a\x3bb"), "echo a;b");
+        assert_eq!(unescape_osc_value(r"C:\\tmp"), r"C:\tmp");
+        assert_eq!(unescape_osc_value(r"trailing\"), r"trailing\");
+    }
+}
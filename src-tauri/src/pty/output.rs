@@ -0,0 +1,620 @@
+use super::command_blocks::CommandBlock;
+use super::scrollback::ScrollbackBuffer;
+use super::triggers::TriggerHit;
+use crate::models::{
+    FLOW_ACK_TIMEOUT_MS, FLOW_HIGH_WATERMARK, FLOW_LOW_WATERMARK, OUTPUT_FRAME_MS,
+    OUTPUT_MAX_FRAME_BYTES, STORM_BYTES_PER_SEC, STORM_COOLDOWN_MS, STORM_SUSTAIN_MS,
+};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tauri::ipc::{Channel, InvokeResponseBody};
+use tauri::Emitter;
+
+/// Unacknowledged frames remembered before the frontend has ever acked;
+/// older entries are dropped so a frontend that never acks costs nothing
+const MAX_UNTRACKED_FRAMES: usize = 1024;
+/// Lines of output shown when an output storm ends
+const STORM_SUMMARY_LINES: usize = 5;
+/// How often the suppressed-bytes notice is refreshed during a storm
+const STORM_NOTICE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Work handed from the reader thread to the output thread, in stream order
+pub enum OutputMessage {
+    Data { bytes: Vec<u8>, text: String },
+    CommandFinished(CommandBlock),
+    Trigger(TriggerHit),
+}
+
+/// Flow-control and storm state reported by `check_pty_health`
+#[derive(Serialize, Debug, Clone)]
+pub struct OutputStatus {
+    /// Reading from the PTY is paused until the frontend catches up
+    pub paused: bool,
+    pub unacked_bytes: usize,
+    pub storm_mode: bool,
+    pub storm_active: bool,
+}
+
+#[derive(Debug)]
+struct FlowState {
+    /// Set by the first ack; frontends that never ack are never throttled
+    enabled: bool,
+    /// Sizes of frames sent but not yet acknowledged, oldest first
+    unacked: VecDeque<usize>,
+    unacked_bytes: usize,
+    paused: bool,
+    last_ack: Instant,
+    closed: bool,
+}
+
+impl FlowState {
+    fn frame_sent(&mut self, bytes: usize) {
+        self.unacked.push_back(bytes);
+        self.unacked_bytes += bytes;
+        if !self.enabled {
+            while self.unacked.len() > MAX_UNTRACKED_FRAMES {
+                if let Some(old) = self.unacked.pop_front() {
+                    self.unacked_bytes -= old;
+                }
+            }
+        } else if self.unacked_bytes > FLOW_HIGH_WATERMARK {
+            self.paused = true;
+        }
+    }
+
+    fn ack(&mut self, frames: usize, now: Instant) {
+        self.enabled = true;
+        self.last_ack = now;
+        for _ in 0..frames.min(self.unacked.len()) {
+            if let Some(bytes) = self.unacked.pop_front() {
+                self.unacked_bytes -= bytes;
+            }
+        }
+        if self.unacked_bytes <= FLOW_LOW_WATERMARK {
+            self.paused = false;
+        }
+    }
+
+    /// Stop throttling a frontend that has gone quiet (closed pane, reloaded webview)
+    fn check_stalled(&mut self, now: Instant) -> bool {
+        if self.paused
+            && now.duration_since(self.last_ack) > Duration::from_millis(FLOW_ACK_TIMEOUT_MS)
+        {
+            self.enabled = false;
+            self.paused = false;
+            self.unacked.clear();
+            self.unacked_bytes = 0;
+            return true;
+        }
+        false
+    }
+}
+
+/// Per-PTY output controls shared by the reader thread, the output thread and commands
+pub struct OutputControl {
+    flow: Mutex<FlowState>,
+    resume: Condvar,
+    storm_mode: AtomicBool,
+    storm_active: AtomicBool,
+}
+
+impl OutputControl {
+    pub fn new() -> Self {
+        Self {
+            flow: Mutex::new(FlowState {
+                enabled: false,
+                unacked: VecDeque::new(),
+                unacked_bytes: 0,
+                paused: false,
+                last_ack: Instant::now(),
+                closed: false,
+            }),
+            resume: Condvar::new(),
+            storm_mode: AtomicBool::new(false),
+            storm_active: AtomicBool::new(false),
+        }
+    }
+
+    /// Block the reader while the frontend is above the high watermark.
+    /// Not reading lets the kernel PTY buffer fill, which stalls the writer.
+    pub fn wait_for_capacity(&self, id: u32) {
+        let Ok(mut flow) = self.flow.lock() else {
+            return;
+        };
+        while flow.paused && !flow.closed {
+            flow = match self.resume.wait_timeout(flow, Duration::from_millis(500)) {
+                Ok((guard, _)) => guard,
+                Err(_) => return,
+            };
+            if flow.check_stalled(Instant::now()) {
+                eprintln!(
+                    "[PTY {id}] Frontend stopped acknowledging output, disabling flow control"
+                );
+            }
+        }
+    }
+
+    fn frame_sent(&self, bytes: usize) {
+        if let Ok(mut flow) = self.flow.lock() {
+            flow.frame_sent(bytes);
+        }
+    }
+
+    /// Record that the frontend has rendered `frames` more frames
+    pub fn ack(&self, frames: usize) {
+        if let Ok(mut flow) = self.flow.lock() {
+            flow.ack(frames, Instant::now());
+            if !flow.paused {
+                self.resume.notify_all();
+            }
+        }
+    }
+
+    /// Release a paused reader so it can observe EOF
+    pub fn close(&self) {
+        if let Ok(mut flow) = self.flow.lock() {
+            flow.closed = true;
+            flow.paused = false;
+        }
+        self.resume.notify_all();
+    }
+
+    pub fn set_storm_mode(&self, enabled: bool) {
+        self.storm_mode.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn status(&self) -> OutputStatus {
+        let (paused, unacked_bytes) = self
+            .flow
+            .lock()
+            .map(|flow| (flow.paused, flow.unacked_bytes))
+            .unwrap_or((false, 0));
+        OutputStatus {
+            paused,
+            unacked_bytes,
+            storm_mode: self.storm_mode.load(Ordering::Relaxed),
+            storm_active: self.storm_active.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for OutputControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum StormTransition {
+    None,
+    Started,
+    Ended,
+}
+
+/// Detects sustained throughput above `STORM_BYTES_PER_SEC` over a 1s window
+#[derive(Debug, Default)]
+struct StormDetector {
+    samples: VecDeque<(Instant, usize)>,
+    window_bytes: usize,
+    hot_since: Option<Instant>,
+    cool_since: Option<Instant>,
+    active_since: Option<Instant>,
+    suppressed_bytes: u64,
+}
+
+impl StormDetector {
+    fn observe(&mut self, bytes: usize, now: Instant, enabled: bool) -> StormTransition {
+        if bytes > 0 {
+            self.samples.push_back((now, bytes));
+            self.window_bytes += bytes;
+        }
+        while let Some(&(at, size)) = self.samples.front() {
+            if now.duration_since(at) < Duration::from_secs(1) {
+                break;
+            }
+            self.samples.pop_front();
+            self.window_bytes -= size;
+        }
+        let hot = self.window_bytes >= STORM_BYTES_PER_SEC;
+
+        if self.active_since.is_some() {
+            if !enabled {
+                self.active_since = None;
+                return StormTransition::Ended;
+            }
+            if hot {
+                self.cool_since = None;
+            } else {
+                let cool_since = *self.cool_since.get_or_insert(now);
+                if now.duration_since(cool_since) >= Duration::from_millis(STORM_COOLDOWN_MS) {
+                    self.active_since = None;
+                    self.hot_since = None;
+                    return StormTransition::Ended;
+                }
+            }
+            return StormTransition::None;
+        }
+
+        if !hot {
+            self.hot_since = None;
+            return StormTransition::None;
+        }
+        let hot_since = *self.hot_since.get_or_insert(now);
+        if enabled && now.duration_since(hot_since) >= Duration::from_millis(STORM_SUSTAIN_MS) {
+            self.active_since = Some(now);
+            self.cool_since = None;
+            self.suppressed_bytes = 0;
+            return StormTransition::Started;
+        }
+        StormTransition::None
+    }
+
+    fn is_active(&self) -> bool {
+        self.active_since.is_some()
+    }
+}
+
+/// Output accumulated during one frame budget
+#[derive(Default)]
+struct Frame {
+    bytes: Vec<u8>,
+    text: String,
+    blocks: Vec<CommandBlock>,
+    triggers: Vec<TriggerHit>,
+}
+
+impl Frame {
+    fn push(&mut self, message: OutputMessage) {
+        match message {
+            OutputMessage::Data { bytes, text } => {
+                self.bytes.extend_from_slice(&bytes);
+                self.text.push_str(&text);
+            }
+            OutputMessage::CommandFinished(block) => self.blocks.push(block),
+            OutputMessage::Trigger(hit) => self.triggers.push(hit),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+            && self.text.is_empty()
+            && self.blocks.is_empty()
+            && self.triggers.is_empty()
+    }
+}
+
+struct OutputEmitter {
+    window: tauri::Window,
+    id: u32,
+    control: Arc<OutputControl>,
+    pty_channels: Arc<Mutex<HashMap<u32, Channel<InvokeResponseBody>>>>,
+    pty_scrollback: Arc<Mutex<HashMap<u32, ScrollbackBuffer>>>,
+    storm: StormDetector,
+    storm_started_at: Option<Instant>,
+    last_notice: Option<Instant>,
+}
+
+impl OutputEmitter {
+    /// Send one frame to the frontend: raw bytes over the channel when attached,
+    /// otherwise a `pty-data:{id}` event (also the fallback if the channel fails)
+    fn send(&self, bytes: Vec<u8>, text: String) {
+        let size = if bytes.is_empty() {
+            text.len()
+        } else {
+            bytes.len()
+        };
+        if size == 0 {
+            return;
+        }
+
+        if !bytes.is_empty() {
+            if let Ok(mut channels) = self.pty_channels.lock() {
+                if let Some(channel) = channels.get(&self.id) {
+                    match channel.send(InvokeResponseBody::Raw(bytes)) {
+                        Ok(()) => {
+                            self.control.frame_sent(size);
+                            return;
+                        }
+                        Err(e) => {
+                            eprintln!(
+                                "[PTY {}] Output channel failed, falling back to events: {}",
+                                self.id, e
+                            );
+                            channels.remove(&self.id);
+                        }
+                    }
+                }
+            }
+        }
+
+        if text.is_empty() {
+            return;
+        }
+        match self.window.emit(&format!("pty-data:{}", self.id), text) {
+            Ok(()) => self.control.frame_sent(size),
+            Err(e) => eprintln!("Failed to emit pty-data: {}", e),
+        }
+    }
+
+    fn send_notice(&self, notice: String) {
+        self.send(notice.as_bytes().to_vec(), notice);
+    }
+
+    fn flush(&mut self, frame: Frame) {
+        let Frame {
+            bytes,
+            text,
+            blocks,
+            triggers,
+        } = frame;
+        let size = bytes.len().max(text.len());
+        let now = Instant::now();
+        let storm_mode = self.control.storm_mode.load(Ordering::Relaxed);
+
+        match self.storm.observe(size, now, storm_mode) {
+            StormTransition::Started => self.storm_started(now),
+            StormTransition::Ended => self.storm_ended(now),
+            StormTransition::None => {}
+        }
+
+        if self.storm.is_active() {
+            self.storm.suppressed_bytes += size as u64;
+            if self
+                .last_notice
+                .is_none_or(|at| now.duration_since(at) >= STORM_NOTICE_INTERVAL)
+            {
+                self.last_notice = Some(now);
+                self.send_notice(format!(
+                    "\r\x1b[2K\x1b[33m[aiterminal] output storm: {} suppressed\x1b[0m",
+                    format_bytes(self.storm.suppressed_bytes)
+                ));
+            }
+        } else {
+            self.send(bytes, text);
+        }
+
+        // Emit after the data so listeners see the output first
+        for block in blocks {
+            if let Err(e) = self
+                .window
+                .emit(&format!("pty-command-finished:{}", self.id), block)
+            {
+                eprintln!("Failed to emit pty-command-finished: {}", e);
+            }
+        }
+        for hit in triggers {
+            if let Err(e) = self.window.emit(&format!("pty-trigger:{}", self.id), hit) {
+                eprintln!("Failed to emit pty-trigger: {}", e);
+            }
+        }
+    }
+
+    /// Called when no output arrived for a while, so a storm can end on silence
+    fn idle(&mut self) {
+        let now = Instant::now();
+        let storm_mode = self.control.storm_mode.load(Ordering::Relaxed);
+        if self.storm.observe(0, now, storm_mode) == StormTransition::Ended {
+            self.storm_ended(now);
+        }
+    }
+
+    fn storm_started(&mut self, now: Instant) {
+        self.control.storm_active.store(true, Ordering::Relaxed);
+        self.storm_started_at = Some(now);
+        self.last_notice = None;
+        self.send_notice(
+            "\x1b[0m\r\n\x1b[33m[aiterminal] output storm detected; suppressing display \
+             (scrollback and recordings still receive everything)\x1b[0m\r\n"
+                .to_string(),
+        );
+        self.emit_storm_status(true);
+    }
+
+    fn storm_ended(&mut self, now: Instant) {
+        self.control.storm_active.store(false, Ordering::Relaxed);
+        let duration = self
+            .storm_started_at
+            .take()
+            .map(|at| now.duration_since(at))
+            .unwrap_or_default();
+
+        let tail = self
+            .pty_scrollback
+            .lock()
+            .ok()
+            .and_then(|scrollback| {
+                scrollback
+                    .get(&self.id)
+                    .map(|buffer| buffer.last_lines(STORM_SUMMARY_LINES))
+            })
+            .unwrap_or_default();
+
+        // The last line is usually the prompt, so leave the cursor after it
+        self.send_notice(format!(
+            "\x1b[0m\r\x1b[2K\x1b[33m[aiterminal] output storm ended: {} suppressed over {:.1}s. Last lines:\x1b[0m\r\n{}",
+            format_bytes(self.storm.suppressed_bytes),
+            duration.as_secs_f64(),
+            tail.join("\r\n")
+        ));
+        self.emit_storm_status(false);
+    }
+
+    fn emit_storm_status(&self, active: bool) {
+        let _ = self.window.emit(
+            &format!("pty-output-storm:{}", self.id),
+            serde_json::json!({
+                "active": active,
+                "suppressed_bytes": self.storm.suppressed_bytes,
+            }),
+        );
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    if bytes >= 1 << 30 {
+        format!("{:.1} GB", bytes as f64 / (1u64 << 30) as f64)
+    } else if bytes >= 1 << 20 {
+        format!("{:.1} MB", bytes as f64 / (1u64 << 20) as f64)
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
+}
+
+/// Spawn the thread that coalesces reader output into frames (at most one
+/// emit per `OUTPUT_FRAME_MS`) and delivers them to the frontend. Emits
+/// `pty-exit:{id}` once the reader hangs up and everything is flushed.
+pub fn spawn_output_thread(
+    window: tauri::Window,
+    id: u32,
+    rx: Receiver<OutputMessage>,
+    control: Arc<OutputControl>,
+    pty_channels: Arc<Mutex<HashMap<u32, Channel<InvokeResponseBody>>>>,
+    pty_scrollback: Arc<Mutex<HashMap<u32, ScrollbackBuffer>>>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let frame_budget = Duration::from_millis(OUTPUT_FRAME_MS);
+        let mut emitter = OutputEmitter {
+            window,
+            id,
+            control,
+            pty_channels,
+            pty_scrollback,
+            storm: StormDetector::default(),
+            storm_started_at: None,
+            last_notice: None,
+        };
+        let mut frame = Frame::default();
+        let mut last_emit = Instant::now() - frame_budget;
+
+        loop {
+            let deadline = last_emit + frame_budget;
+            let received = if !frame.is_empty() {
+                rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            } else if emitter.storm.is_active() {
+                rx.recv_timeout(STORM_NOTICE_INTERVAL)
+            } else {
+                rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            };
+
+            match received {
+                Ok(message) => {
+                    frame.push(message);
+                    // Drain whatever else is already queued
+                    while frame.bytes.len() < OUTPUT_MAX_FRAME_BYTES {
+                        match rx.try_recv() {
+                            Ok(message) => frame.push(message),
+                            Err(_) => break,
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            let now = Instant::now();
+            if frame.is_empty() {
+                emitter.idle();
+            } else if now >= deadline || frame.bytes.len() >= OUTPUT_MAX_FRAME_BYTES {
+                emitter.flush(std::mem::take(&mut frame));
+                last_emit = now;
+            }
+        }
+
+        if !frame.is_empty() {
+            emitter.flush(frame);
+        }
+        if emitter.storm.is_active() {
+            emitter.storm_ended(Instant::now());
+        }
+        if let Ok(mut channels) = emitter.pty_channels.lock() {
+            channels.remove(&id);
+        }
+        let _ = emitter.window.emit(&format!("pty-exit:{}", id), ());
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_pauses_above_high_and_resumes_below_low_watermark() {
+        let now = Instant::now();
+        let control = OutputControl::new();
+        let mut flow = control.flow.lock().unwrap();
+
+        // Not throttled until the frontend has acked once
+        flow.frame_sent(FLOW_HIGH_WATERMARK + 1);
+        assert!(!flow.paused);
+        flow.ack(1, now);
+
+        let frame = FLOW_HIGH_WATERMARK / 4;
+        for _ in 0..5 {
+            flow.frame_sent(frame);
+        }
+        assert!(flow.paused);
+
+        flow.ack(2, now);
+        assert!(flow.paused, "still above the low watermark");
+        flow.ack(3, now);
+        assert!(!flow.paused);
+        assert_eq!(flow.unacked_bytes, 0);
+    }
+
+    #[test]
+    fn test_flow_gives_up_on_silent_frontend() {
+        let control = OutputControl::new();
+        let mut flow = control.flow.lock().unwrap();
+        let start = Instant::now();
+        flow.ack(0, start);
+        flow.frame_sent(FLOW_HIGH_WATERMARK + 1);
+        assert!(flow.paused);
+
+        assert!(!flow.check_stalled(start + Duration::from_millis(10)));
+        assert!(flow.check_stalled(start + Duration::from_millis(FLOW_ACK_TIMEOUT_MS + 1)));
+        assert!(!flow.paused && !flow.enabled);
+    }
+
+    #[test]
+    fn test_storm_requires_sustained_throughput_and_cools_down() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        // Twice the storm threshold, 10 frames per second
+        let burst = STORM_BYTES_PER_SEC / 5;
+        let mut storm = StormDetector::default();
+
+        let mut ms = 0;
+        let mut started_at = None;
+        while ms <= STORM_SUSTAIN_MS + 1500 {
+            if storm.observe(burst, at(ms), true) == StormTransition::Started {
+                started_at = Some(ms);
+            }
+            ms += 100;
+        }
+        let started_at = started_at.expect("storm should start");
+        assert!(started_at >= STORM_SUSTAIN_MS);
+
+        // Silence: ends once the window empties and the cooldown passes
+        let mut ended = false;
+        for step in 1..=((STORM_COOLDOWN_MS + 2000) / 100) {
+            if storm.observe(0, at(ms + step * 100), true) == StormTransition::Ended {
+                ended = true;
+                break;
+            }
+        }
+        assert!(ended);
+
+        // Disabled: never starts
+        let mut storm = StormDetector::default();
+        for i in 0..100 {
+            assert_eq!(
+                storm.observe(burst, at(i * 100), false),
+                StormTransition::None
+            );
+        }
+    }
+}
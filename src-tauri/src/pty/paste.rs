@@ -0,0 +1,492 @@
+//! Large pastes.
+//!
+//! A multi-megabyte paste written in one go can overflow the tty input queue
+//! (or the remote one behind ssh) and arrive garbled. Writes larger than one
+//! chunk are queued per PTY and written in paced chunks by a worker thread;
+//! input that arrives meanwhile is queued behind them so keystrokes stay in
+//! order.
+
+use super::startup::SharedWriter;
+use crate::models::{
+    AppState, PASTE_CHUNK_BYTES, PASTE_CHUNK_DELAY_MS, PASTE_PROGRESS_INTERVAL_MS,
+    PASTE_PROGRESS_THRESHOLD_BYTES,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{Emitter, State};
+
+const BRACKET_START: &str = "\x1b[200~";
+const BRACKET_END: &str = "\x1b[201~";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ModeState {
+    Ground,
+    Escape,
+    Csi,
+}
+
+/// Follows DECSET/DECRST 2004 (bracketed paste) in PTY output
+pub struct BracketedPasteTracker {
+    state: ModeState,
+    private: bool,
+    params: String,
+}
+
+impl BracketedPasteTracker {
+    pub fn new() -> Self {
+        Self {
+            state: ModeState::Ground,
+            private: false,
+            params: String::new(),
+        }
+    }
+
+    /// Feed PTY output. Returns the new mode if this output switched it.
+    pub fn feed(&mut self, data: &[u8]) -> Option<bool> {
+        if self.state == ModeState::Ground && !data.contains(&0x1b) {
+            return None;
+        }
+
+        let mut changed = None;
+        for &byte in data {
+            match self.state {
+                ModeState::Ground => {
+                    if byte == 0x1b {
+                        self.state = ModeState::Escape;
+                    }
+                }
+                ModeState::Escape => match byte {
+                    b'[' => {
+                        self.state = ModeState::Csi;
+                        self.private = false;
+                        self.params.clear();
+                    }
+                    0x1b => {}
+                    _ => self.state = ModeState::Ground,
+                },
+                ModeState::Csi => match byte {
+                    b'?' if self.params.is_empty() => self.private = true,
+                    b'0'..=b'9' | b';' if self.params.len() < 32 => {
+                        self.params.push(byte as char);
+                    }
+                    // Final byte; `CSI ? 1049 ; 2004 h` sets several modes at once
+                    0x40..=0x7e => {
+                        if self.private
+                            && matches!(byte, b'h' | b'l')
+                            && self.params.split(';').any(|mode| mode == "2004")
+                        {
+                            changed = Some(byte == b'h');
+                        }
+                        self.state = ModeState::Ground;
+                    }
+                    0x1b => self.state = ModeState::Escape,
+                    _ => {}
+                },
+            }
+        }
+        changed
+    }
+}
+
+impl Default for BracketedPasteTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How `paste_to_pty` delivers text
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PasteMode {
+    /// Paste as if typed
+    #[default]
+    Text,
+    /// Run a heredoc that writes the text to `path` on the machine the shell
+    /// runs on (POSIX shells only)
+    Heredoc { path: String },
+}
+
+/// Result of `paste_to_pty`
+#[derive(Serialize, Debug, Clone)]
+pub struct PasteInfo {
+    /// Set when the paste was queued; progress events carry the same ID
+    pub paste_id: Option<String>,
+    pub bytes: usize,
+    pub bracketed: bool,
+}
+
+/// Progress of a large paste, emitted as `pty-paste-progress:{id}`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PasteProgress {
+    pub paste_id: String,
+    pub written: usize,
+    pub total: usize,
+    /// "writing", "done", "cancelled" or "failed"
+    pub status: String,
+    pub error: Option<String>,
+}
+
+type ProgressFn = Box<dyn Fn(&PasteProgress) + Send>;
+
+struct PasteJob {
+    paste_id: String,
+    bytes: Vec<u8>,
+    progress: Option<ProgressFn>,
+}
+
+impl PasteJob {
+    fn report(&self, written: usize, status: &str, error: Option<String>) {
+        if let Some(progress) = &self.progress {
+            progress(&PasteProgress {
+                paste_id: self.paste_id.clone(),
+                written,
+                total: self.bytes.len(),
+                status: status.to_string(),
+                error,
+            });
+        }
+    }
+}
+
+struct QueueState {
+    jobs: VecDeque<PasteJob>,
+    /// A worker thread is writing jobs
+    running: bool,
+    /// Stop the job being written
+    cancelled: bool,
+    closed: bool,
+}
+
+/// Ordered, paced input for one PTY
+pub struct PasteQueue {
+    writer: SharedWriter,
+    state: Mutex<QueueState>,
+}
+
+impl PasteQueue {
+    pub fn new(writer: SharedWriter) -> Arc<Self> {
+        Arc::new(Self {
+            writer,
+            state: Mutex::new(QueueState {
+                jobs: VecDeque::new(),
+                running: false,
+                cancelled: false,
+                closed: false,
+            }),
+        })
+    }
+
+    /// Write input to the PTY. Small input goes straight through unless a
+    /// paste is still being written; anything else is queued and the paste ID
+    /// returned. `progress` is called for pastes over the progress threshold.
+    pub fn write(
+        self: &Arc<Self>,
+        id: u32,
+        bytes: Vec<u8>,
+        progress: impl Fn(&PasteProgress) + Send + 'static,
+    ) -> Result<Option<String>, String> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|e| format!("Failed to acquire paste queue lock: {}", e))?;
+        if state.closed {
+            return Err(format!("PTY {} not found", id));
+        }
+
+        if !state.running && bytes.len() <= PASTE_CHUNK_BYTES {
+            // The lock keeps a worker from starting in between
+            let mut writer = self.writer.clone();
+            writer
+                .write_all(&bytes)
+                .map_err(|e| format!("Failed to write to PTY {}: {}", id, e))?;
+            writer
+                .flush()
+                .map_err(|e| format!("Failed to flush PTY {}: {}", id, e))?;
+            return Ok(None);
+        }
+
+        let paste_id = uuid::Uuid::new_v4().to_string();
+        let progress: Option<ProgressFn> = if bytes.len() > PASTE_PROGRESS_THRESHOLD_BYTES {
+            Some(Box::new(progress))
+        } else {
+            None
+        };
+        state.jobs.push_back(PasteJob {
+            paste_id: paste_id.clone(),
+            bytes,
+            progress,
+        });
+        if !state.running {
+            state.running = true;
+            let queue = self.clone();
+            std::thread::spawn(move || queue.run(id));
+        }
+        Ok(Some(paste_id))
+    }
+
+    /// Drop queued input and stop the paste being written. Returns false if
+    /// nothing was pending.
+    pub fn cancel(&self) -> bool {
+        let Ok(mut state) = self.state.lock() else {
+            return false;
+        };
+        state.jobs.clear();
+        if state.running {
+            state.cancelled = true;
+        }
+        state.running
+    }
+
+    /// Stop for good; the worker releases its writer at the next chunk
+    pub fn close(&self) {
+        self.cancel();
+        if let Ok(mut state) = self.state.lock() {
+            state.closed = true;
+        }
+    }
+
+    fn run(&self, id: u32) {
+        let mut writer = self.writer.clone();
+        loop {
+            let job = {
+                let Ok(mut state) = self.state.lock() else {
+                    return;
+                };
+                match state.jobs.pop_front() {
+                    Some(job) => job,
+                    None => {
+                        state.running = false;
+                        state.cancelled = false;
+                        return;
+                    }
+                }
+            };
+
+            let mut written = 0;
+            let mut last_report = Instant::now();
+            job.report(0, "writing", None);
+            let result = loop {
+                if written == job.bytes.len() {
+                    break Ok(());
+                }
+                match self.state.lock() {
+                    Ok(mut state) if state.cancelled => {
+                        state.cancelled = false;
+                        break Err(None);
+                    }
+                    Ok(_) => {}
+                    Err(e) => break Err(Some(e.to_string())),
+                }
+
+                let end = (written + PASTE_CHUNK_BYTES).min(job.bytes.len());
+                if let Err(e) = writer
+                    .write_all(&job.bytes[written..end])
+                    .and_then(|_| writer.flush())
+                {
+                    break Err(Some(format!("Failed to write to PTY {}: {}", id, e)));
+                }
+                written = end;
+
+                if last_report.elapsed() >= Duration::from_millis(PASTE_PROGRESS_INTERVAL_MS) {
+                    job.report(written, "writing", None);
+                    last_report = Instant::now();
+                }
+                std::thread::sleep(Duration::from_millis(PASTE_CHUNK_DELAY_MS));
+            };
+
+            match result {
+                Ok(()) => job.report(written, "done", None),
+                Err(None) => job.report(written, "cancelled", None),
+                Err(Some(e)) => {
+                    eprintln!("[PTY {id}] Paste failed: {}", e);
+                    job.report(written, "failed", Some(e));
+                }
+            }
+        }
+    }
+}
+
+/// Bytes typed for a paste. Line breaks become CR, as a terminal sends them.
+fn paste_payload(text: &str, mode: &PasteMode, bracketed: bool) -> String {
+    let text = text.replace("\r\n", "\r").replace('\n', "\r");
+    match mode {
+        PasteMode::Text => bracket(&text, bracketed),
+        PasteMode::Heredoc { path } => {
+            let delimiter = heredoc_delimiter(&text);
+            let mut heredoc = format!("cat > {} <<'{}'\r{}", shell_quote(path), delimiter, text);
+            if !text.is_empty() && !text.ends_with('\r') {
+                heredoc.push('\r');
+            }
+            heredoc.push_str(&delimiter);
+            // Bracketed, the shell takes the heredoc as one edit (tabs do not
+            // trigger completion) and the final Enter runs it
+            format!("{}\r", bracket(&heredoc, bracketed))
+        }
+    }
+}
+
+/// Wrap text in bracketed-paste markers, removing markers inside it so the
+/// pasted text cannot end the paste early and have the rest run as typed
+fn bracket(text: &str, bracketed: bool) -> String {
+    if bracketed {
+        let inner = text.replace(BRACKET_START, "").replace(BRACKET_END, "");
+        format!("{}{}{}", BRACKET_START, inner, BRACKET_END)
+    } else {
+        text.to_string()
+    }
+}
+
+/// A heredoc terminator that does not occur as a line of the text
+fn heredoc_delimiter(text: &str) -> String {
+    (0..)
+        .map(|n| match n {
+            0 => "AITERM_EOF".to_string(),
+            n => format!("AITERM_EOF_{}", n),
+        })
+        .find(|delimiter| !text.split('\r').any(|line| line == delimiter))
+        .unwrap_or_default()
+}
+
+/// Single-quote a string for POSIX shells
+pub(super) fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+pub(super) fn paste_queue(id: u32, state: &AppState) -> Result<Arc<PasteQueue>, String> {
+    state
+        .paste_queues
+        .lock()
+        .map_err(|e| format!("Failed to acquire paste queue lock: {}", e))?
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| format!("PTY {} not found", id))
+}
+
+/// Emits paste progress to the window that owns the PTY
+pub(super) fn progress_emitter(
+    window: tauri::Window,
+    id: u32,
+) -> impl Fn(&PasteProgress) + Send + 'static {
+    move |progress| {
+        let _ = window.emit(&format!("pty-paste-progress:{}", id), progress);
+    }
+}
+
+/// Tauri command: paste text into a PTY, bracketed when the running
+/// application enabled bracketed paste (mode 2004)
+#[tauri::command]
+pub fn paste_to_pty(
+    id: u32,
+    text: String,
+    mode: Option<PasteMode>,
+    window: tauri::Window,
+    state: State<AppState>,
+) -> Result<PasteInfo, String> {
+    let bracketed = state
+        .bracketed_paste
+        .lock()
+        .map_err(|e| format!("Failed to acquire bracketed paste lock: {}", e))?
+        .contains(&id);
+    let payload = paste_payload(&text, &mode.unwrap_or_default(), bracketed);
+    let bytes = payload.len();
+
+    let paste_id =
+        paste_queue(id, &state)?.write(id, payload.into_bytes(), progress_emitter(window, id))?;
+    Ok(PasteInfo {
+        paste_id,
+        bytes,
+        bracketed,
+    })
+}
+
+/// Tauri command: stop a paste in progress and drop input queued behind it
+#[tauri::command]
+pub fn cancel_paste(id: u32, state: State<AppState>) -> Result<bool, String> {
+    Ok(paste_queue(id, &state)?.cancel())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracks_mode_2004_across_reads() {
+        let mut tracker = BracketedPasteTracker::new();
+        assert_eq!(tracker.feed(b"plain output"), None);
+        assert_eq!(tracker.feed(b"\x1b[?20"), None);
+        assert_eq!(tracker.feed(b"04h$ "), Some(true));
+        // Other private modes and non-private CSI leave it alone
+        assert_eq!(tracker.feed(b"\x1b[?25l\x1b[2004l\x1b[31m"), None);
+        assert_eq!(tracker.feed(b"\x1b[?1049;2004l"), Some(false));
+        assert_eq!(tracker.feed(b"\x1b[?2004h\x1b[?2004l"), Some(false));
+    }
+
+    #[test]
+    fn test_paste_payloads() {
+        let text = "echo a\nevil\x1b[201~rm -rf ~\r\n";
+        assert_eq!(
+            paste_payload(text, &PasteMode::Text, true),
+            "\x1b[200~echo a\revilrm -rf ~\r\x1b[201~"
+        );
+        assert_eq!(paste_payload("x\ny", &PasteMode::Text, false), "x\ry");
+
+        let heredoc = PasteMode::Heredoc {
+            path: "/tmp/it's.txt".to_string(),
+        };
+        assert_eq!(
+            paste_payload("AITERM_EOF\n\tdone", &heredoc, false),
+            "cat > '/tmp/it'\\''s.txt' <<'AITERM_EOF_1'\rAITERM_EOF\r\tdone\rAITERM_EOF_1\r"
+        );
+        assert_eq!(
+            paste_payload("", &heredoc, true),
+            "\x1b[200~cat > '/tmp/it'\\''s.txt' <<'AITERM_EOF'\rAITERM_EOF\x1b[201~\r"
+        );
+    }
+
+    #[test]
+    fn test_large_writes_are_chunked_and_keep_order() {
+        struct Sink(Arc<Mutex<Vec<Vec<u8>>>>);
+        impl Write for Sink {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().push(buf.to_vec());
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let queue = PasteQueue::new(SharedWriter::new(Box::new(Sink(writes.clone()))));
+        let (tx, rx) = std::sync::mpsc::channel();
+        let big = vec![b'x'; PASTE_PROGRESS_THRESHOLD_BYTES + 10];
+        let paste_id = queue
+            .write(1, big.clone(), move |p| {
+                let _ = tx.send(p.clone());
+            })
+            .unwrap();
+        assert!(paste_id.is_some());
+        // A keystroke during the paste lands after it
+        assert!(queue.write(1, b"\r".to_vec(), |_| {}).unwrap().is_some());
+
+        let done = rx
+            .iter()
+            .find(|p| p.status != "writing")
+            .expect("paste finished");
+        assert_eq!(done.status, "done");
+        assert_eq!(done.written, big.len());
+        while queue.state.lock().unwrap().running {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+
+        let writes = writes.lock().unwrap();
+        assert!(writes.iter().all(|w| w.len() <= PASTE_CHUNK_BYTES));
+        assert_eq!(writes.concat(), [big, b"\r".to_vec()].concat());
+        // Idle again: small input is written directly
+        drop(writes);
+        assert_eq!(queue.write(1, b"ls".to_vec(), |_| {}).unwrap(), None);
+    }
+}
@@ -0,0 +1,308 @@
+use crate::models::{AppState, MAX_PROCESS_TREE_DEPTH, PROCESS_POLL_MS};
+use serde::Serialize;
+use std::time::Duration;
+use tauri::{Emitter, Manager, State};
+
+/// A process and its descendants
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    pub argv: Vec<String>,
+    /// Seconds since the process started (None if the platform doesn't report it)
+    pub elapsed_secs: Option<u64>,
+    pub children: Vec<ProcessInfo>,
+}
+
+/// The foreground process of a PTY
+#[derive(Serialize, Debug, Clone)]
+pub struct PtyProcess {
+    pub pty_id: u32,
+    pub shell_pid: Option<u32>,
+    /// True when the shell itself is in the foreground (nothing running)
+    pub is_shell: bool,
+    pub process: ProcessInfo,
+}
+
+/// One row of the process table
+#[derive(Debug, Clone)]
+struct ProcessEntry {
+    pid: u32,
+    ppid: u32,
+    name: String,
+    argv: Vec<String>,
+    elapsed_secs: Option<u64>,
+}
+
+/// Foreground process group (tcgetpgrp on the master) and shell PID for a PTY
+fn foreground_pids(state: &AppState, id: u32) -> Result<(Option<u32>, Option<u32>), String> {
+    let ptys = state
+        .ptys
+        .lock()
+        .map_err(|e| format!("Failed to acquire PTY lock: {}", e))?;
+    let session = ptys
+        .get(&id)
+        .ok_or_else(|| format!("PTY {} not found", id))?;
+
+    #[cfg(unix)]
+    let foreground = session
+        .master
+        .process_group_leader()
+        .and_then(|pgid| u32::try_from(pgid).ok());
+    #[cfg(not(unix))]
+    let foreground = None;
+
+    let shell_pid = session.child.as_ref().and_then(|child| child.process_id());
+    Ok((foreground, shell_pid))
+}
+
+fn resolve_pty_process(state: &AppState, id: u32) -> Result<PtyProcess, String> {
+    let (foreground, shell_pid) = foreground_pids(state, id)?;
+    let pid = foreground.or(shell_pid).ok_or_else(|| {
+        "Foreground process tracking is not supported on this platform".to_string()
+    })?;
+
+    let entries = list_processes();
+    let process =
+        build_tree(&entries, pid, 0).ok_or_else(|| format!("Failed to read process {}", pid))?;
+
+    Ok(PtyProcess {
+        pty_id: id,
+        shell_pid,
+        is_shell: Some(pid) == shell_pid,
+        process,
+    })
+}
+
+/// Tauri command: the program running in the foreground of a PTY, with its
+/// argv, elapsed time and child processes
+#[tauri::command]
+pub fn get_pty_process(id: u32, state: State<AppState>) -> Result<PtyProcess, String> {
+    resolve_pty_process(&state, id)
+}
+
+/// Poll the PTY's foreground process group and emit `pty-process-changed:{id}`
+/// when a different program takes the foreground. Exits once the PTY is closed.
+pub fn spawn_process_watcher(window: tauri::Window, id: u32) {
+    std::thread::spawn(move || {
+        let mut last: Option<(u32, Vec<String>)> = None;
+        loop {
+            std::thread::sleep(Duration::from_millis(PROCESS_POLL_MS));
+            let state = window.state::<AppState>();
+            let Ok((foreground, shell_pid)) = foreground_pids(&state, id) else {
+                break;
+            };
+            let Some(pid) = foreground.or(shell_pid) else {
+                continue;
+            };
+
+            // argv distinguishes an exec in the same process (e.g. `exec vim`)
+            let current = (pid, process_argv(pid).unwrap_or_default());
+            if last.as_ref() == Some(&current) {
+                continue;
+            }
+            last = Some(current);
+
+            if let Ok(process) = resolve_pty_process(&state, id) {
+                let _ = window.emit(&format!("pty-process-changed:{}", id), process);
+            }
+        }
+    });
+}
+
+fn build_tree(entries: &[ProcessEntry], pid: u32, depth: usize) -> Option<ProcessInfo> {
+    let entry = entries.iter().find(|entry| entry.pid == pid)?;
+    let children = if depth < MAX_PROCESS_TREE_DEPTH {
+        entries
+            .iter()
+            .filter(|child| child.ppid == pid && child.pid != pid)
+            .filter_map(|child| build_tree(entries, child.pid, depth + 1))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    Some(ProcessInfo {
+        pid: entry.pid,
+        name: entry.name.clone(),
+        argv: entry.argv.clone(),
+        elapsed_secs: entry.elapsed_secs,
+        children,
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn process_argv(pid: u32) -> Option<Vec<String>> {
+    let raw = std::fs::read(format!("/proc/{}/cmdline", pid)).ok()?;
+    Some(
+        raw.split(|b| *b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).to_string())
+            .collect(),
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn list_processes() -> Vec<ProcessEntry> {
+    let uptime_secs = std::fs::read_to_string("/proc/uptime")
+        .ok()
+        .and_then(|s| s.split_whitespace().next()?.parse::<f64>().ok());
+
+    let Ok(dir) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    dir.filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|pid| {
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+            let mut entry = parse_proc_stat(&stat, uptime_secs)?;
+            entry.argv = process_argv(pid).unwrap_or_default();
+            if entry.argv.is_empty() {
+                // Kernel threads and zombies have no cmdline
+                entry.argv = vec![entry.name.clone()];
+            }
+            Some(entry)
+        })
+        .collect()
+}
+
+/// Parse /proc/<pid>/stat. The command name is in parentheses and may itself
+/// contain spaces or parentheses, so fields are counted from the last `)`.
+#[cfg(any(target_os = "linux", test))]
+fn parse_proc_stat(stat: &str, uptime_secs: Option<f64>) -> Option<ProcessEntry> {
+    // Linux reports start time in clock ticks; USER_HZ is 100 on all mainstream
+    // architectures
+    const CLOCK_TICKS_PER_SEC: f64 = 100.0;
+
+    let open = stat.find('(')?;
+    let close = stat.rfind(')')?;
+    let pid = stat[..open].trim().parse().ok()?;
+    let name = stat[open + 1..close].to_string();
+    // Fields after the name start at field 3 (state)
+    let fields: Vec<&str> = stat[close + 1..].split_whitespace().collect();
+    let ppid = fields.get(1)?.parse().ok()?;
+    let start_ticks = fields.get(19).and_then(|s| s.parse::<f64>().ok());
+    let elapsed_secs = match (uptime_secs, start_ticks) {
+        (Some(uptime), Some(start)) => Some((uptime - start / CLOCK_TICKS_PER_SEC).max(0.0) as u64),
+        _ => None,
+    };
+
+    Some(ProcessEntry {
+        pid,
+        ppid,
+        name,
+        argv: Vec::new(),
+        elapsed_secs,
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn process_argv(pid: u32) -> Option<Vec<String>> {
+    let output = std::process::Command::new("ps")
+        .args(["-p", &pid.to_string(), "-o", "args="])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// No /proc (macOS, BSD): read the table from `ps`. Arguments are split on
+/// whitespace, so quoting inside an argument is lost.
+#[cfg(not(target_os = "linux"))]
+fn list_processes() -> Vec<ProcessEntry> {
+    let Ok(output) = std::process::Command::new("ps")
+        .args(["-A", "-o", "pid=,ppid=,etime=,args="])
+        .output()
+    else {
+        return Vec::new();
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let pid = parts.next()?.parse().ok()?;
+            let ppid = parts.next()?.parse().ok()?;
+            let elapsed_secs = parse_etime(parts.next()?);
+            let argv: Vec<String> = parts.map(str::to_string).collect();
+            let name = argv
+                .first()
+                .map(|arg0| arg0.rsplit('/').next().unwrap_or(arg0).to_string())
+                .unwrap_or_default();
+            Some(ProcessEntry {
+                pid,
+                ppid,
+                name,
+                argv,
+                elapsed_secs,
+            })
+        })
+        .collect()
+}
+
+/// Parse ps `etime` ([[dd-]hh:]mm:ss) into seconds
+#[cfg(any(not(target_os = "linux"), test))]
+fn parse_etime(etime: &str) -> Option<u64> {
+    let (days, clock) = match etime.split_once('-') {
+        Some((days, clock)) => (days.parse::<u64>().ok()?, clock),
+        None => (0, etime),
+    };
+    let mut secs = 0;
+    for part in clock.split(':') {
+        secs = secs * 60 + part.parse::<u64>().ok()?;
+    }
+    Some(days * 86_400 + secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_stat_handles_parens_in_name() {
+        let stat = "4242 (my (odd) prog) S 100 4242 4242 34816 4242 4194304 1 0 0 0 \
+                    0 0 0 0 20 0 1 0 50000 1000 100 18446744073709551615";
+        let entry = parse_proc_stat(stat, Some(1000.0)).unwrap();
+        assert_eq!(entry.pid, 4242);
+        assert_eq!(entry.ppid, 100);
+        assert_eq!(entry.name, "my (odd) prog");
+        assert_eq!(entry.elapsed_secs, Some(500));
+    }
+
+    #[test]
+    fn test_parse_etime() {
+        assert_eq!(parse_etime("05"), Some(5));
+        assert_eq!(parse_etime("01:05"), Some(65));
+        assert_eq!(parse_etime("02:01:05"), Some(7265));
+        assert_eq!(parse_etime("3-00:00:01"), Some(259_201));
+        assert_eq!(parse_etime("bad"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_process_tree_includes_child_argv() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let entries = list_processes();
+        let tree = build_tree(&entries, std::process::id(), 0);
+        let _ = child.kill();
+        let _ = child.wait();
+
+        let tree = tree.unwrap();
+        let sleep = tree
+            .children
+            .iter()
+            .find(|c| c.pid == child.id())
+            .expect("child process listed");
+        assert_eq!(sleep.argv, ["sleep", "30"]);
+        assert!(sleep.elapsed_secs.is_some());
+    }
+}
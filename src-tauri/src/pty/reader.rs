@@ -1,26 +1,112 @@
-use super::osc_parser::{current_timestamp, parse_remote_host_osc};
-use crate::health_check;
-use crate::models::{SshSessionInfo, PTY_BUFFER_SIZE};
-use std::io::Read;
+use super::command_blocks::CommandTracker;
+use super::context::{HostStack, PtyCwd};
+use super::latency::{start_latency_monitor, stop_latency_monitor, LatencyMonitor};
+use super::osc_parser::{current_timestamp, OscScanner};
+use super::output::{spawn_output_thread, OutputControl, OutputMessage};
+use super::paste::BracketedPasteTracker;
+use super::recording::PtyRecorder;
+use super::scrollback::ScrollbackBuffer;
+use super::startup::{SharedWriter, StartupInput};
+use super::tmux::TmuxClient;
+use super::triggers::{current_triggers, TriggerAction, TriggerMatcher, TriggerSet};
+use super::utf8_stream::Utf8StreamDecoder;
+use crate::models::{AppState, SshSessionInfo, TerminalContext, PTY_BUFFER_SIZE};
+use std::io::{Read, Write};
 use std::sync::{Arc, Mutex};
-use tauri::Emitter;
+use tauri::ipc::{Channel, InvokeResponseBody};
 
-/// Spawn thread to read PTY output and handle SSH detection
+/// Shared per-PTY state updated by the reader thread
+pub struct ReaderHandles {
+    pub ssh_sessions: Arc<Mutex<std::collections::HashMap<u32, SshSessionInfo>>>,
+    pub terminal_contexts: Arc<Mutex<std::collections::HashMap<u32, TerminalContext>>>,
+    pub pty_cwds: Arc<Mutex<std::collections::HashMap<u32, PtyCwd>>>,
+    pub latency_monitors: Arc<Mutex<std::collections::HashMap<u32, LatencyMonitor>>>,
+    pub bracketed_paste: Arc<Mutex<std::collections::HashSet<u32>>>,
+    pub pty_last_output: Arc<Mutex<std::collections::HashMap<u32, u64>>>,
+    pub command_blocks: Arc<Mutex<std::collections::HashMap<u32, CommandTracker>>>,
+    pub pty_scrollback: Arc<Mutex<std::collections::HashMap<u32, ScrollbackBuffer>>>,
+    pub pty_recordings: Arc<Mutex<std::collections::HashMap<u32, PtyRecorder>>>,
+    pub pty_channels: Arc<Mutex<std::collections::HashMap<u32, Channel<InvokeResponseBody>>>>,
+    pub pty_output: Arc<Mutex<std::collections::HashMap<u32, Arc<OutputControl>>>>,
+    pub triggers: Arc<Mutex<Arc<TriggerSet>>>,
+}
+
+impl ReaderHandles {
+    pub fn from_state(state: &AppState) -> Self {
+        Self {
+            ssh_sessions: state.ssh_sessions.clone(),
+            terminal_contexts: state.terminal_contexts.clone(),
+            pty_cwds: state.pty_cwds.clone(),
+            latency_monitors: state.latency_monitors.clone(),
+            bracketed_paste: state.bracketed_paste.clone(),
+            pty_last_output: state.pty_last_output.clone(),
+            command_blocks: state.command_blocks.clone(),
+            pty_scrollback: state.pty_scrollback.clone(),
+            pty_recordings: state.pty_recordings.clone(),
+            pty_channels: state.pty_channels.clone(),
+            pty_output: state.pty_output.clone(),
+            triggers: state.triggers.clone(),
+        }
+    }
+}
+
+/// Spawn thread to read PTY output and handle SSH detection and command tracking.
+/// Delivery to the frontend happens on a separate output thread that coalesces
+/// reads into frames; the reader pauses when the frontend falls behind.
+/// `startup` is typed into the shell at its first prompt mark (see
+/// `StartupInput` for remote stages); `writer` answers
+/// tmux when the PTY enters control mode and types trigger responses.
 pub fn spawn_reader_thread(
     mut reader: Box<dyn Read + Send>,
     window: tauri::Window,
     id: u32,
-    ssh_sessions: Arc<Mutex<std::collections::HashMap<u32, SshSessionInfo>>>,
-    pty_last_output: Arc<Mutex<std::collections::HashMap<u32, u64>>>,
+    handles: ReaderHandles,
+    writer: SharedWriter,
+    mut startup: Option<StartupInput>,
 ) -> std::thread::JoinHandle<()> {
+    let ReaderHandles {
+        ssh_sessions,
+        terminal_contexts,
+        pty_cwds,
+        latency_monitors,
+        bracketed_paste,
+        pty_last_output,
+        command_blocks,
+        pty_scrollback,
+        pty_recordings,
+        pty_channels,
+        pty_output,
+        triggers,
+    } = handles;
+
+    let control = Arc::new(OutputControl::new());
+    if let Ok(mut outputs) = pty_output.lock() {
+        outputs.insert(id, control.clone());
+    }
+
     std::thread::spawn(move || {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let output_handle = spawn_output_thread(
+            window.clone(),
+            id,
+            rx,
+            control.clone(),
+            pty_channels,
+            pty_scrollback.clone(),
+        );
+
         let mut buf = [0u8; PTY_BUFFER_SIZE];
+        let mut scanner = OscScanner::new();
+        let mut decoder = Utf8StreamDecoder::new();
+        let mut tmux = TmuxClient::new(id, writer.clone());
+        let mut responder = writer;
+        let mut matcher = TriggerMatcher::new(id);
+        let mut hosts = HostStack::new();
+        let mut paste_mode = BracketedPasteTracker::new();
         loop {
+            control.wait_for_capacity(id);
             match reader.read(&mut buf) {
                 Ok(n) if n > 0 => {
-                    let data = &buf[..n];
-                    let data_str = String::from_utf8_lossy(data).to_string();
-
                     // Update last output timestamp
                     if let Ok(mut last_output) = pty_last_output.lock() {
                         let now_ms = std::time::SystemTime::now()
@@ -30,85 +116,179 @@ pub fn spawn_reader_thread(
                         last_output.insert(id, now_ms);
                     }
 
-                    // Parse OSC sequences for SSH detection
-                    if let Some(remote_info) = parse_remote_host_osc(&data_str) {
-                        handle_ssh_detection(id, remote_info, &ssh_sessions);
+                    // tmux control-mode protocol goes to the tmux panes instead
+                    let passthrough = tmux.feed(&window, &buf[..n]);
+                    let data = passthrough.as_ref();
+                    if data.is_empty() {
+                        continue;
+                    }
+                    if let Some(enabled) = paste_mode.feed(data) {
+                        if let Ok(mut bracketed) = bracketed_paste.lock() {
+                            if enabled {
+                                bracketed.insert(id);
+                            } else {
+                                bracketed.remove(&id);
+                            }
+                        }
+                    }
+                    // Empty when the chunk ends inside a multibyte character
+                    let data_str = decoder.decode(data);
+
+                    // Lines completed by this read, for the trigger engine
+                    let active_triggers = current_triggers(&triggers);
+                    let mut first_line = 0;
+                    let mut lines = Vec::new();
+                    if let Ok(mut scrollback) = pty_scrollback.lock() {
+                        let buffer = scrollback.entry(id).or_default();
+                        first_line = buffer.next_line_number();
+                        buffer.push_str(&data_str);
+                        if !active_triggers.is_empty() {
+                            lines.extend(buffer.lines_from(first_line).cloned());
+                        }
+                    }
+
+                    if let Ok(mut recordings) = pty_recordings.lock() {
+                        if let Some(recorder) = recordings.get_mut(&id) {
+                            recorder.output(&data_str);
+                        }
+                    }
+
+                    // Parse OSC sequences for SSH detection and command boundaries
+                    let sequences = scanner.feed(data);
+                    let mut finished = Vec::new();
+                    if !sequences.is_empty() {
+                        if let Ok(mut trackers) = command_blocks.lock() {
+                            let tracker = trackers.entry(id).or_default();
+                            for seq in &sequences {
+                                if let Some(block) = tracker.handle_osc(seq) {
+                                    finished.push(block);
+                                }
+                            }
+                        }
+                    }
+                    let previous_top = hosts.top().map(|hop| (hop.user.clone(), hop.host.clone()));
+                    let mut context_changed = false;
+                    for seq in &sequences {
+                        context_changed |= hosts.handle_osc(seq);
+                    }
+                    if context_changed {
+                        if let Ok(mut contexts) = terminal_contexts.lock() {
+                            contexts.insert(id, hosts.context(id));
+                        }
+                        if let (Some(cwd), Ok(mut cwds)) = (hosts.cwd(), pty_cwds.lock()) {
+                            cwds.insert(id, cwd.clone());
+                        }
+                        let top = hosts.top().map(|hop| (hop.user.clone(), hop.host.clone()));
+                        if top != previous_top {
+                            handle_ssh_detection(id, &hosts, &ssh_sessions, &latency_monitors);
+                        }
                     }
+                    if !sequences.is_empty() {
+                        if let Some(input) = startup.take() {
+                            startup = input.feed(id, &sequences, hosts.top().is_some());
+                        }
+                    }
+
+                    let hits = if lines.is_empty() {
+                        Vec::new()
+                    } else {
+                        let host = if active_triggers.needs_host() {
+                            ssh_sessions.lock().ok().and_then(|sessions| {
+                                sessions.get(&id).map(|s| s.remote_host.clone())
+                            })
+                        } else {
+                            None
+                        };
+                        matcher.scan(
+                            &active_triggers,
+                            host.as_deref(),
+                            first_line,
+                            &lines,
+                            std::time::Instant::now(),
+                        )
+                    };
 
-                    if let Err(e) = window.emit(&format!("pty-data:{}", id), data_str) {
-                        eprintln!("Failed to emit pty-data: {}", e);
+                    let _ = tx.send(OutputMessage::Data {
+                        bytes: data.to_vec(),
+                        text: data_str,
+                    });
+                    for block in finished {
+                        let _ = tx.send(OutputMessage::CommandFinished(block));
+                    }
+                    for hit in hits {
+                        if let TriggerAction::Respond { input } = &hit.action {
+                            if let Err(e) = responder.write_all(input.as_bytes()) {
+                                eprintln!("[PTY {id}] Failed to send trigger response: {}", e);
+                            }
+                        }
+                        let _ = tx.send(OutputMessage::Trigger(hit));
                     }
                 }
                 _ => {
+                    tmux.finish(&window);
                     // Clean up last output tracking on exit
                     if let Ok(mut last_output) = pty_last_output.lock() {
                         last_output.remove(&id);
                     }
-                    let _ = window.emit(&format!("pty-exit:{}", id), ());
+                    let _ = tx.send(OutputMessage::Data {
+                        bytes: Vec::new(),
+                        text: decoder.finish(),
+                    });
+                    // Finish the recording when the shell exits
+                    if let Ok(mut recordings) = pty_recordings.lock() {
+                        if let Some(recorder) = recordings.remove(&id) {
+                            recorder.finish();
+                        }
+                    }
+                    if let Ok(mut outputs) = pty_output.lock() {
+                        outputs.remove(&id);
+                    }
+                    stop_latency_monitor(id, &latency_monitors);
+                    if let Ok(mut bracketed) = bracketed_paste.lock() {
+                        bracketed.remove(&id);
+                    }
                     break;
                 }
             }
         }
+
+        // The output thread flushes what is left and emits pty-exit
+        drop(tx);
+        let _ = output_handle.join();
     })
 }
 
-/// Handle SSH session detection and start latency monitoring
+/// Update SSH session tracking after the innermost host changed, and start
+/// or stop latency monitoring
 fn handle_ssh_detection(
     id: u32,
-    remote_info: Option<(String, String, Option<String>)>,
+    hosts: &HostStack,
     ssh_sessions: &Arc<Mutex<std::collections::HashMap<u32, SshSessionInfo>>>,
+    latency_monitors: &Arc<Mutex<std::collections::HashMap<u32, LatencyMonitor>>>,
 ) {
-    if let Some((user, host, ip_opt)) = remote_info {
-        // SSH session detected
-        let ssh_info = SshSessionInfo {
-            remote_host: host.clone(),
-            remote_user: Some(user.clone()),
-            remote_port: 22,
-            connection_time: current_timestamp(),
-            last_latency_ms: None,
-            latency_monitor_handle: None,
-        };
-
+    if let (Some(first), Some(top)) = (hosts.first(), hosts.top()) {
+        // SSH session detected. Latency is measured to the first hop, so it
+        // carries over when moving between nested hosts.
         if let Ok(mut sessions) = ssh_sessions.lock() {
+            let last_latency_ms = sessions.get(&id).and_then(|info| info.last_latency_ms);
+            let ssh_info = SshSessionInfo {
+                remote_host: top.host.clone(),
+                remote_user: Some(top.user.clone()),
+                remote_port: 22,
+                connection_time: current_timestamp(),
+                last_latency_ms,
+            };
             sessions.insert(id, ssh_info);
         }
 
-        // Start latency monitoring
-        start_latency_monitor(id, ip_opt.unwrap_or(host), ssh_sessions.clone());
+        // Only the first hop is reachable from here; nested hosts usually
+        // report addresses on a private network.
+        start_latency_monitor(id, first, &top.host, latency_monitors, ssh_sessions);
     } else {
         // Back to local (no remote host)
         if let Ok(mut sessions) = ssh_sessions.lock() {
             sessions.remove(&id);
         }
+        stop_latency_monitor(id, latency_monitors);
     }
 }
-
-/// Start background thread to monitor SSH latency
-fn start_latency_monitor(
-    id: u32,
-    target: String,
-    ssh_sessions: Arc<Mutex<std::collections::HashMap<u32, SshSessionInfo>>>,
-) {
-    std::thread::spawn(move || {
-        loop {
-            std::thread::sleep(std::time::Duration::from_secs(5));
-
-            match health_check::measure_tcp_latency(&target, 22, 5000) {
-                Ok(latency) => {
-                    let latency_ms = latency.as_millis() as u64;
-
-                    if let Ok(mut sessions) = ssh_sessions.lock() {
-                        if let Some(info) = sessions.get_mut(&id) {
-                            info.last_latency_ms = Some(latency_ms);
-                        } else {
-                            break; // Session closed, exit monitor
-                        }
-                    }
-                }
-                Err(_) => {
-                    // Silently continue on latency measurement failure
-                }
-            }
-        }
-    });
-}
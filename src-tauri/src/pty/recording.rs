@@ -0,0 +1,504 @@
+use super::osc_parser::current_timestamp;
+use crate::models::AppState;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, State};
+
+const MIN_REPLAY_SPEED: f64 = 0.1;
+const MAX_REPLAY_SPEED: f64 = 16.0;
+/// Longest single sleep during replay, so speed changes and stops apply promptly
+const REPLAY_TICK: Duration = Duration::from_millis(50);
+
+/// An asciicast event: seconds since start, event type (`o` or `r`) and data
+type CastEvent = (f64, String, String);
+
+/// Metadata for a `.cast` file in the recordings directory
+#[derive(Serialize, Debug, Clone)]
+pub struct RecordingInfo {
+    /// File name, used to refer to the recording in other commands
+    pub name: String,
+    pub path: String,
+    pub width: u16,
+    pub height: u16,
+    /// Start time in seconds since epoch
+    pub timestamp: u64,
+    pub title: Option<String>,
+    /// Time of the last event in seconds
+    pub duration_secs: f64,
+    pub size_bytes: u64,
+}
+
+/// Returned when a replay starts; `id` is a pseudo PTY ID whose
+/// `pty-data:{id}` events carry the recorded output
+#[derive(Serialize, Debug, Clone)]
+pub struct ReplayInfo {
+    pub id: u32,
+    pub width: u16,
+    pub height: u16,
+    pub duration_secs: f64,
+}
+
+/// Writes PTY output and resizes as asciicast v2 events
+pub struct PtyRecorder {
+    writer: BufWriter<File>,
+    started: Instant,
+    info: RecordingInfo,
+}
+
+impl PtyRecorder {
+    pub fn create(id: u32, width: u16, height: u16, title: Option<String>) -> Result<Self, String> {
+        let dir = get_recordings_dir()?;
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create recordings directory: {}", e))?;
+
+        // Milliseconds plus a counter keep two recordings of the same PTY
+        // from sharing a file; create_new never truncates an existing one
+        let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S-%3f").to_string();
+        let mut attempt = 0;
+        let (name, path, file) = loop {
+            let name = if attempt == 0 {
+                format!("pty{}-{}.cast", id, stamp)
+            } else {
+                format!("pty{}-{}-{}.cast", id, stamp, attempt)
+            };
+            let path = dir.join(&name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break (name, path, file),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => attempt += 1,
+                Err(e) => {
+                    return Err(format!(
+                        "Failed to create recording {}: {}",
+                        path.display(),
+                        e
+                    ))
+                }
+            }
+        };
+
+        let timestamp = current_timestamp();
+        let mut header = json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": timestamp,
+            "env": {
+                "SHELL": std::env::var("SHELL").unwrap_or_default(),
+                "TERM": "xterm-256color",
+            },
+        });
+        if let Some(title) = &title {
+            header["title"] = json!(title);
+        }
+
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{}", header)
+            .map_err(|e| format!("Failed to write recording header: {}", e))?;
+
+        Ok(Self {
+            writer,
+            started: Instant::now(),
+            info: RecordingInfo {
+                name,
+                path: path.to_string_lossy().to_string(),
+                width,
+                height,
+                timestamp,
+                title,
+                duration_secs: 0.0,
+                size_bytes: 0,
+            },
+        })
+    }
+
+    fn event(&mut self, kind: &str, data: &str) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        // Microsecond precision, as written by asciinema itself
+        let t = (elapsed * 1_000_000.0).round() / 1_000_000.0;
+        if let Err(e) = writeln!(self.writer, "{}", json!([t, kind, data])) {
+            eprintln!("Failed to write recording event: {}", e);
+        }
+        self.info.duration_secs = t;
+    }
+
+    pub fn output(&mut self, data: &str) {
+        if !data.is_empty() {
+            self.event("o", data);
+        }
+    }
+
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        self.event("r", &format!("{}x{}", cols, rows));
+        let _ = self.writer.flush();
+    }
+
+    pub fn finish(mut self) -> RecordingInfo {
+        if let Err(e) = self.writer.flush() {
+            eprintln!("Failed to flush recording: {}", e);
+        }
+        self.info.size_bytes = std::fs::metadata(&self.info.path)
+            .map(|m| m.len())
+            .unwrap_or(0);
+        self.info
+    }
+}
+
+/// Playback controls shared with a replay thread
+pub struct ReplayControl {
+    /// Playback speed as `f64` bits
+    speed: AtomicU64,
+    stopped: AtomicBool,
+}
+
+impl ReplayControl {
+    fn speed(&self) -> f64 {
+        f64::from_bits(self.speed.load(Ordering::Relaxed))
+    }
+
+    fn set_speed(&self, speed: f64) {
+        self.speed.store(
+            speed.clamp(MIN_REPLAY_SPEED, MAX_REPLAY_SPEED).to_bits(),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+pub fn get_recordings_dir() -> Result<PathBuf, String> {
+    let home = std::env::var("HOME").map_err(|_| "HOME not set".to_string())?;
+    Ok(Path::new(&home).join(".config/aiterminal/recordings"))
+}
+
+/// Resolve a recording name to a path inside the recordings directory
+fn recording_path(name: &str) -> Result<PathBuf, String> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(format!("Invalid recording name: {}", name));
+    }
+    if !name.ends_with(".cast") {
+        return Err(format!("Not an asciicast recording: {}", name));
+    }
+    Ok(get_recordings_dir()?.join(name))
+}
+
+/// Parse an asciicast v2 stream into its header and `(time, kind, data)` events
+fn parse_cast<R: BufRead>(reader: R) -> Result<(Value, Vec<CastEvent>), String> {
+    let mut lines = reader.lines();
+    let header_line = lines
+        .next()
+        .ok_or_else(|| "Recording is empty".to_string())?
+        .map_err(|e| format!("Failed to read recording: {}", e))?;
+    let header: Value = serde_json::from_str(&header_line)
+        .map_err(|e| format!("Invalid recording header: {}", e))?;
+    if header.get("version").and_then(Value::as_u64) != Some(2) {
+        return Err("Only asciicast v2 recordings are supported".to_string());
+    }
+
+    let mut events = Vec::new();
+    for line in lines {
+        let line = line.map_err(|e| format!("Failed to read recording: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // A truncated final line (e.g. the app was killed mid-write) is skipped
+        if let Ok((t, kind, data)) = serde_json::from_str::<CastEvent>(&line) {
+            events.push((t, kind, data));
+        }
+    }
+    Ok((header, events))
+}
+
+fn header_dimension(header: &Value, key: &str, default: u16) -> u16 {
+    header
+        .get(key)
+        .and_then(Value::as_u64)
+        .map(|v| v.min(u16::MAX as u64) as u16)
+        .unwrap_or(default)
+}
+
+fn parse_resize(data: &str) -> Option<(u16, u16)> {
+    let (cols, rows) = data.split_once('x')?;
+    Some((cols.trim().parse().ok()?, rows.trim().parse().ok()?))
+}
+
+#[tauri::command]
+pub fn start_pty_recording(
+    id: u32,
+    title: Option<String>,
+    state: State<AppState>,
+) -> Result<RecordingInfo, String> {
+    let size = {
+        let ptys = state
+            .ptys
+            .lock()
+            .map_err(|e| format!("Failed to acquire PTY lock: {}", e))?;
+        let session = ptys
+            .get(&id)
+            .ok_or_else(|| format!("PTY {} not found", id))?;
+        session
+            .master
+            .get_size()
+            .map_err(|e| format!("Failed to read PTY size: {}", e))?
+    };
+
+    let mut recordings = state
+        .pty_recordings
+        .lock()
+        .map_err(|e| format!("Failed to acquire recording lock: {}", e))?;
+    if recordings.contains_key(&id) {
+        return Err(format!("PTY {} is already being recorded", id));
+    }
+
+    let recorder = PtyRecorder::create(id, size.cols, size.rows, title)?;
+    let info = recorder.info.clone();
+    recordings.insert(id, recorder);
+    Ok(info)
+}
+
+#[tauri::command]
+pub fn stop_pty_recording(id: u32, state: State<AppState>) -> Result<RecordingInfo, String> {
+    let recorder = state
+        .pty_recordings
+        .lock()
+        .map_err(|e| format!("Failed to acquire recording lock: {}", e))?
+        .remove(&id)
+        .ok_or_else(|| format!("PTY {} is not being recorded", id))?;
+    Ok(recorder.finish())
+}
+
+/// List recordings, newest first
+#[tauri::command]
+pub fn list_recordings() -> Result<Vec<RecordingInfo>, String> {
+    let dir = get_recordings_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries =
+        std::fs::read_dir(&dir).map_err(|e| format!("Failed to read recordings: {}", e))?;
+    let mut recordings = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("cast") {
+            continue;
+        }
+        let file = match File::open(&path) {
+            Ok(file) => file,
+            Err(_) => continue,
+        };
+        let (header, events) = match parse_cast(BufReader::new(file)) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                eprintln!("Skipping recording {}: {}", path.display(), e);
+                continue;
+            }
+        };
+
+        recordings.push(RecordingInfo {
+            name: entry.file_name().to_string_lossy().to_string(),
+            path: path.to_string_lossy().to_string(),
+            width: header_dimension(&header, "width", 80),
+            height: header_dimension(&header, "height", 24),
+            timestamp: header.get("timestamp").and_then(Value::as_u64).unwrap_or(0),
+            title: header
+                .get("title")
+                .and_then(Value::as_str)
+                .map(|s| s.to_string()),
+            duration_secs: events.last().map(|(t, _, _)| *t).unwrap_or(0.0),
+            size_bytes: entry.metadata().map(|m| m.len()).unwrap_or(0),
+        });
+    }
+
+    recordings.sort_by_key(|r| std::cmp::Reverse(r.timestamp));
+    Ok(recordings)
+}
+
+/// Replay a recording into a read-only pane.
+///
+/// Output is emitted as `pty-data:{id}` and resizes as `pty-resize:{id}`, so
+/// the pane can reuse the normal terminal listeners; `pty-exit:{id}` marks the
+/// end. The ID never maps to a real PTY, so input to it is rejected.
+/// Pauses longer than `max_idle_secs` are shortened to that length.
+#[tauri::command]
+pub fn replay_recording(
+    name: String,
+    speed: Option<f64>,
+    max_idle_secs: Option<f64>,
+    window: tauri::Window,
+    state: State<AppState>,
+) -> Result<ReplayInfo, String> {
+    let path = recording_path(&name)?;
+    let file =
+        File::open(&path).map_err(|e| format!("Failed to open recording {}: {}", name, e))?;
+    let (header, events) = parse_cast(BufReader::new(file))?;
+
+    let id = {
+        let mut next_id = state
+            .next_id
+            .lock()
+            .map_err(|e| format!("Failed to acquire ID lock: {}", e))?;
+        let id = *next_id;
+        *next_id += 1;
+        id
+    };
+
+    let control = Arc::new(ReplayControl {
+        speed: AtomicU64::new(1.0f64.to_bits()),
+        stopped: AtomicBool::new(false),
+    });
+    control.set_speed(speed.unwrap_or(1.0));
+    state
+        .replays
+        .lock()
+        .map_err(|e| format!("Failed to acquire replay lock: {}", e))?
+        .insert(id, control.clone());
+
+    let info = ReplayInfo {
+        id,
+        width: header_dimension(&header, "width", 80),
+        height: header_dimension(&header, "height", 24),
+        duration_secs: events.last().map(|(t, _, _)| *t).unwrap_or(0.0),
+    };
+
+    let replays = state.replays.clone();
+    let max_idle = max_idle_secs.filter(|secs| *secs > 0.0);
+    std::thread::spawn(move || {
+        run_replay(id, &events, max_idle, &control, &window);
+        if let Ok(mut replays) = replays.lock() {
+            replays.remove(&id);
+        }
+        let _ = window.emit(&format!("pty-exit:{}", id), ());
+    });
+
+    Ok(info)
+}
+
+fn run_replay(
+    id: u32,
+    events: &[CastEvent],
+    max_idle: Option<f64>,
+    control: &ReplayControl,
+    window: &tauri::Window,
+) {
+    let mut previous = 0.0;
+    for (t, kind, data) in events {
+        // Recorded seconds still to wait before this event
+        let mut remaining = (t - previous).max(0.0);
+        if let Some(max_idle) = max_idle {
+            remaining = remaining.min(max_idle);
+        }
+        previous = *t;
+
+        while remaining > 0.0 {
+            if control.stopped.load(Ordering::Relaxed) {
+                return;
+            }
+            let speed = control.speed();
+            let sleep = Duration::from_secs_f64(remaining / speed).min(REPLAY_TICK);
+            std::thread::sleep(sleep);
+            remaining -= sleep.as_secs_f64() * speed;
+        }
+        if control.stopped.load(Ordering::Relaxed) {
+            return;
+        }
+
+        match kind.as_str() {
+            "o" => {
+                let _ = window.emit(&format!("pty-data:{}", id), data);
+            }
+            "r" => {
+                if let Some((cols, rows)) = parse_resize(data) {
+                    let _ = window.emit(
+                        &format!("pty-resize:{}", id),
+                        json!({ "cols": cols, "rows": rows }),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[tauri::command]
+pub fn set_replay_speed(id: u32, speed: f64, state: State<AppState>) -> Result<(), String> {
+    let replays = state
+        .replays
+        .lock()
+        .map_err(|e| format!("Failed to acquire replay lock: {}", e))?;
+    let control = replays
+        .get(&id)
+        .ok_or_else(|| format!("Replay {} not found", id))?;
+    if !speed.is_finite() || speed <= 0.0 {
+        return Err(format!("Invalid replay speed: {}", speed));
+    }
+    control.set_speed(speed);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn stop_replay(id: u32, state: State<AppState>) -> Result<(), String> {
+    let replays = state
+        .replays
+        .lock()
+        .map_err(|e| format!("Failed to acquire replay lock: {}", e))?;
+    if let Some(control) = replays.get(&id) {
+        control.stopped.store(true, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording_round_trips_as_asciicast_v2() {
+        let (_home_guard, _home_dir) = crate::tests::helpers::with_test_home();
+
+        let mut recorder = PtyRecorder::create(7, 120, 40, Some("demo".to_string())).unwrap();
+        recorder.output("hello \"world\"\r\n");
+        recorder.resize(100, 30);
+        recorder.output("\x1b[31mred\x1b[0m");
+        let info = recorder.finish();
+
+        let file = File::open(&info.path).unwrap();
+        let (header, events) = parse_cast(BufReader::new(file)).unwrap();
+        assert_eq!(header["width"], 120);
+        assert_eq!(header["height"], 40);
+        assert_eq!(header["title"], "demo");
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].1, "o");
+        assert_eq!(events[0].2, "hello \"world\"\r\n");
+        assert_eq!(parse_resize(&events[1].2), Some((100, 30)));
+        assert!(events[2].0 >= events[0].0);
+
+        assert!(list_recordings()
+            .unwrap()
+            .iter()
+            .any(|r| r.name == info.name && r.title.as_deref() == Some("demo")));
+        let _ = std::fs::remove_file(&info.path);
+    }
+
+    #[test]
+    fn test_recordings_started_together_get_distinct_files() {
+        let (_home_guard, _home_dir) = crate::tests::helpers::with_test_home();
+
+        let first = PtyRecorder::create(3, 80, 24, None).unwrap().finish();
+        let second = PtyRecorder::create(3, 80, 24, None).unwrap().finish();
+        assert_ne!(first.name, second.name);
+        assert!(Path::new(&first.path).exists());
+        assert!(Path::new(&second.path).exists());
+        let _ = std::fs::remove_file(&first.path);
+        let _ = std::fs::remove_file(&second.path);
+    }
+
+    #[test]
+    fn test_recording_path_rejects_traversal() {
+        assert!(recording_path("../secrets.cast").is_err());
+        assert!(recording_path("a/b.cast").is_err());
+        assert!(recording_path("notes.txt").is_err());
+    }
+}
@@ -0,0 +1,375 @@
+//! `MasterPty`/`Child` implementations for a shell channel on an in-process
+//! SSH connection, so a native SSH terminal is tracked like any local PTY.
+
+use crate::ssh::RemoteChannel;
+use portable_pty::{Child, ChildKiller, ExitStatus, MasterPty, PtySize};
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often `wait` checks whether the channel has closed
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct ChannelReader {
+    pub channel: Arc<dyn RemoteChannel>,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.channel.read(buf)
+    }
+}
+
+struct ChannelWriter {
+    channel: Arc<dyn RemoteChannel>,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.channel.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub struct ChannelMaster {
+    pub channel: Arc<dyn RemoteChannel>,
+    pub size: Mutex<PtySize>,
+}
+
+impl MasterPty for ChannelMaster {
+    fn resize(&self, size: PtySize) -> Result<(), anyhow::Error> {
+        if let Ok(mut current) = self.size.lock() {
+            *current = size;
+        }
+        self.channel.resize(size.cols, size.rows)?;
+        Ok(())
+    }
+
+    fn get_size(&self) -> Result<PtySize, anyhow::Error> {
+        Ok(*self
+            .size
+            .lock()
+            .map_err(|e| io::Error::other(e.to_string()))?)
+    }
+
+    /// The channel has one output stream, already owned by the reader thread
+    fn try_clone_reader(&self) -> Result<Box<dyn Read + Send>, anyhow::Error> {
+        Err(io::Error::other("SSH channels have a single reader").into())
+    }
+
+    fn take_writer(&self) -> Result<Box<dyn Write + Send>, anyhow::Error> {
+        Ok(Box::new(ChannelWriter {
+            channel: self.channel.clone(),
+        }))
+    }
+
+    /// The shell runs on the remote host
+    #[cfg(unix)]
+    fn process_group_leader(&self) -> Option<i32> {
+        None
+    }
+
+    #[cfg(unix)]
+    fn as_raw_fd(&self) -> Option<std::os::unix::io::RawFd> {
+        None
+    }
+
+    #[cfg(unix)]
+    fn tty_name(&self) -> Option<std::path::PathBuf> {
+        None
+    }
+}
+
+pub struct ChannelChild {
+    pub channel: Arc<dyn RemoteChannel>,
+}
+
+impl std::fmt::Debug for ChannelChild {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChannelChild")
+            .field("exit_status", &self.channel.exit_status())
+            .finish()
+    }
+}
+
+impl Child for ChannelChild {
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        Ok(self.channel.exit_status().map(ExitStatus::with_exit_code))
+    }
+
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        loop {
+            if let Some(code) = self.channel.exit_status() {
+                return Ok(ExitStatus::with_exit_code(code));
+            }
+            std::thread::sleep(EXIT_POLL_INTERVAL);
+        }
+    }
+
+    fn process_id(&self) -> Option<u32> {
+        None
+    }
+}
+
+impl ChildKiller for ChannelChild {
+    fn kill(&mut self) -> io::Result<()> {
+        self.channel.close()
+    }
+
+    fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+        Box::new(ChannelKiller {
+            channel: self.channel.clone(),
+        })
+    }
+}
+
+struct ChannelKiller {
+    channel: Arc<dyn RemoteChannel>,
+}
+
+impl std::fmt::Debug for ChannelKiller {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChannelKiller").finish()
+    }
+}
+
+impl ChildKiller for ChannelKiller {
+    fn kill(&mut self) -> io::Result<()> {
+        self.channel.close()
+    }
+
+    fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+        Box::new(ChannelKiller {
+            channel: self.channel.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{mpsc, Condvar};
+
+    /// Stand-in for the server end of an SSH session: a "shell" thread that
+    /// echoes input back as output until it reads `exit`
+    struct LoopbackChannel {
+        input: Mutex<Option<mpsc::Sender<Vec<u8>>>>,
+        output: Mutex<mpsc::Receiver<Vec<u8>>>,
+        size: Mutex<(u16, u16)>,
+        exit: Mutex<Option<u32>>,
+        exited: Condvar,
+    }
+
+    impl LoopbackChannel {
+        fn start() -> Arc<Self> {
+            let (input_tx, input_rx) = mpsc::channel::<Vec<u8>>();
+            let (output_tx, output_rx) = mpsc::channel();
+            let channel = Arc::new(Self {
+                input: Mutex::new(Some(input_tx)),
+                output: Mutex::new(output_rx),
+                size: Mutex::new((80, 24)),
+                exit: Mutex::new(None),
+                exited: Condvar::new(),
+            });
+            let server = channel.clone();
+            std::thread::spawn(move || {
+                let _ = output_tx.send(b"$ ".to_vec());
+                for line in input_rx {
+                    if line == b"exit\r" {
+                        break;
+                    }
+                    let _ = output_tx.send(line);
+                }
+                *server.exit.lock().unwrap() = Some(0);
+                server.exited.notify_all();
+            });
+            channel
+        }
+    }
+
+    impl RemoteChannel for LoopbackChannel {
+        fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.output.lock().unwrap().recv() {
+                Ok(chunk) => {
+                    buf[..chunk.len()].copy_from_slice(&chunk);
+                    Ok(chunk.len())
+                }
+                Err(_) => Ok(0),
+            }
+        }
+
+        fn write(&self, data: &[u8]) -> io::Result<usize> {
+            match self.input.lock().unwrap().as_ref() {
+                Some(input) => input
+                    .send(data.to_vec())
+                    .map(|_| data.len())
+                    .map_err(|_| io::ErrorKind::BrokenPipe.into()),
+                None => Err(io::ErrorKind::BrokenPipe.into()),
+            }
+        }
+
+        fn resize(&self, cols: u16, rows: u16) -> io::Result<()> {
+            *self.size.lock().unwrap() = (cols, rows);
+            Ok(())
+        }
+
+        fn exit_status(&self) -> Option<u32> {
+            *self.exit.lock().unwrap()
+        }
+
+        fn close(&self) -> io::Result<()> {
+            self.input.lock().unwrap().take();
+            let exit = self.exit.lock().unwrap();
+            drop(self.exited.wait_while(exit, |exit| exit.is_none()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_channel_behaves_like_a_pty() {
+        let channel = LoopbackChannel::start();
+        let master = ChannelMaster {
+            channel: channel.clone(),
+            size: Mutex::new(PtySize::default()),
+        };
+        let mut child = ChannelChild {
+            channel: channel.clone(),
+        };
+        let mut reader = ChannelReader {
+            channel: channel.clone(),
+        };
+        let mut writer = master.take_writer().unwrap();
+        let mut buf = [0u8; 64];
+
+        let n = reader.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"$ ");
+        writer.write_all(b"hostname\r").unwrap();
+        let n = reader.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"hostname\r");
+
+        master
+            .resize(PtySize {
+                rows: 40,
+                cols: 120,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .unwrap();
+        assert_eq!(*channel.size.lock().unwrap(), (120, 40));
+        assert_eq!(master.get_size().unwrap().cols, 120);
+
+        assert!(child.try_wait().unwrap().is_none());
+        writer.write_all(b"exit\r").unwrap();
+        assert!(child.wait().unwrap().success());
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        // Killing an already closed channel is harmless
+        child.clone_killer().kill().unwrap();
+        assert!(writer.write_all(b"late\r").is_err());
+    }
+
+    /// Output from `reader` until it contains `needle`, or None after 10s
+    #[cfg(feature = "native-ssh")]
+    fn read_until(output: &mpsc::Receiver<Vec<u8>>, needle: &str) -> Option<String> {
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        let mut seen = String::new();
+        while !seen.contains(needle) {
+            let left = deadline.saturating_duration_since(std::time::Instant::now());
+            seen.push_str(&String::from_utf8_lossy(&output.recv_timeout(left).ok()?));
+        }
+        Some(seen)
+    }
+
+    #[cfg(feature = "native-ssh")]
+    #[test]
+    fn test_native_ssh_session_against_in_process_server() {
+        let (_home_guard, home) = crate::tests::helpers::with_test_home();
+        let root = crate::tests::helpers::setup_test_dir();
+        std::fs::write(root.join("notes.txt"), "remote notes\n").unwrap();
+        let server = crate::ssh::test_server::start(&root, &home);
+
+        let profile: crate::ssh::SSHProfile = serde_json::from_value(serde_json::json!({
+            "id": "native-test",
+            "name": "In-process server",
+            "connectionType": "manual",
+            "manualConfig": {
+                "hostname": "127.0.0.1",
+                "username": "tester",
+                "port": server.port,
+                "identityFile": server.identity_file.to_string_lossy()
+            }
+        }))
+        .unwrap();
+        // Handshake, known_hosts check and public key auth
+        let connection = crate::ssh::connect_native(&profile, 100, 30).unwrap();
+        assert_eq!(
+            (connection.host.as_str(), connection.port),
+            ("127.0.0.1", server.port)
+        );
+
+        let master = ChannelMaster {
+            channel: connection.channel.clone(),
+            size: Mutex::new(PtySize {
+                rows: 30,
+                cols: 100,
+                pixel_width: 0,
+                pixel_height: 0,
+            }),
+        };
+        let mut child = ChannelChild {
+            channel: connection.channel.clone(),
+        };
+        let mut reader = ChannelReader {
+            channel: connection.channel.clone(),
+        };
+        let (tx, output) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 1024];
+            while let Ok(n) = reader.read(&mut buf) {
+                if n == 0 || tx.send(buf[..n].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut writer = master.take_writer().unwrap();
+
+        // PTY channel I/O, with the size from the PTY request and then a resize
+        assert!(read_until(&output, "$ ").is_some());
+        writer.write_all(b"size\r").unwrap();
+        assert!(read_until(&output, "100x30").is_some());
+        master
+            .resize(PtySize {
+                rows: 40,
+                cols: 120,
+                pixel_width: 0,
+                pixel_height: 0,
+            })
+            .unwrap();
+        writer.write_all(b"size\r").unwrap();
+        assert!(read_until(&output, "120x40").is_some());
+        writer.write_all(b"echo hi\r").unwrap();
+        assert!(read_until(&output, "echo hi").is_some());
+
+        // SFTP on the same connection sees what the server has on disk
+        let entries = connection.fs.list("/").unwrap();
+        assert!(entries
+            .iter()
+            .any(|entry| entry.name == "notes.txt" && !entry.is_dir && entry.size == 13));
+        assert_eq!(connection.fs.stat("/notes.txt").unwrap().size, 13);
+        assert_eq!(
+            connection.fs.read("/notes.txt", 1024).unwrap(),
+            b"remote notes\n"
+        );
+        assert_eq!(connection.fs.read("/notes.txt", 6).unwrap(), b"remote");
+        assert!(connection.fs.read("/missing.txt", 16).is_err());
+
+        writer.write_all(b"exit\r").unwrap();
+        assert!(child.wait().unwrap().success());
+
+        let _ = std::fs::remove_dir_all(home.join(".ssh"));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
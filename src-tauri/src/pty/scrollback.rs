@@ -0,0 +1,257 @@
+use crate::models::{MAX_SCROLLBACK_LINES, MAX_SCROLLBACK_LINE_LEN};
+use serde::Serialize;
+use std::collections::VecDeque;
+
+/// A scrollback line matching a search
+#[derive(Serialize, Debug, Clone)]
+pub struct ScrollbackMatch {
+    /// Absolute line number since the PTY was spawned
+    pub line_number: u64,
+    pub line: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AnsiState {
+    Ground,
+    Escape,
+    Csi,
+    /// OSC, DCS, SOS, PM and APC: swallowed until BEL or ST
+    String,
+    StringEscape,
+    /// ESC followed by intermediates (e.g. `ESC ( B`), waiting for the final byte
+    Intermediate,
+}
+
+/// Bounded, ANSI-aware line buffer of PTY output.
+///
+/// Escape sequences are stripped, `\r` and backspace overwrite the current
+/// line the way a terminal would, and only the last `MAX_SCROLLBACK_LINES`
+/// lines are kept.
+pub struct ScrollbackBuffer {
+    lines: VecDeque<String>,
+    current: Vec<char>,
+    cursor: usize,
+    state: AnsiState,
+    csi_params: String,
+    /// Absolute number of the oldest line still in `lines`
+    first_line_number: u64,
+}
+
+impl ScrollbackBuffer {
+    pub fn new() -> Self {
+        Self {
+            lines: VecDeque::new(),
+            current: Vec::new(),
+            cursor: 0,
+            state: AnsiState::Ground,
+            csi_params: String::new(),
+            first_line_number: 0,
+        }
+    }
+
+    /// Append decoded PTY output
+    pub fn push_str(&mut self, data: &str) {
+        for ch in data.chars() {
+            match self.state {
+                AnsiState::Ground => self.on_ground_char(ch),
+                AnsiState::Escape => match ch {
+                    '[' => {
+                        self.state = AnsiState::Csi;
+                        self.csi_params.clear();
+                    }
+                    ']' | 'P' | 'X' | '^' | '_' => self.state = AnsiState::String,
+                    ' '..='/' => self.state = AnsiState::Intermediate,
+                    '\x1b' => {}
+                    _ => self.state = AnsiState::Ground,
+                },
+                AnsiState::Csi => match ch {
+                    '\x40'..='\x7e' => {
+                        self.apply_csi(ch);
+                        self.state = AnsiState::Ground;
+                    }
+                    '\x1b' => self.state = AnsiState::Escape,
+                    _ => {
+                        if self.csi_params.len() < 32 {
+                            self.csi_params.push(ch);
+                        }
+                    }
+                },
+                AnsiState::String => match ch {
+                    '\x07' => self.state = AnsiState::Ground,
+                    '\x1b' => self.state = AnsiState::StringEscape,
+                    _ => {}
+                },
+                AnsiState::StringEscape => {
+                    self.state = if ch == '\\' {
+                        AnsiState::Ground
+                    } else {
+                        AnsiState::String
+                    };
+                }
+                AnsiState::Intermediate => {
+                    if !(' '..='/').contains(&ch) {
+                        self.state = AnsiState::Ground;
+                    }
+                }
+            }
+        }
+    }
+
+    fn on_ground_char(&mut self, ch: char) {
+        match ch {
+            '\x1b' => self.state = AnsiState::Escape,
+            '\n' => self.newline(),
+            '\r' => self.cursor = 0,
+            '\x08' => self.cursor = self.cursor.saturating_sub(1),
+            '\t' => self.put('\t'),
+            c if c.is_control() => {}
+            c => self.put(c),
+        }
+    }
+
+    fn put(&mut self, ch: char) {
+        if self.cursor < self.current.len() {
+            self.current[self.cursor] = ch;
+        } else if self.current.len() < MAX_SCROLLBACK_LINE_LEN {
+            self.current.push(ch);
+        } else {
+            return;
+        }
+        self.cursor += 1;
+    }
+
+    fn apply_csi(&mut self, final_byte: char) {
+        match final_byte {
+            // Erase in line: 0 (default) = to end, 1 = to start, 2 = whole line
+            'K' => match self.csi_params.as_str() {
+                "" | "0" => self.current.truncate(self.cursor),
+                "1" => {
+                    let end = self.cursor.min(self.current.len());
+                    self.current[..end].iter_mut().for_each(|c| *c = ' ');
+                }
+                "2" => self.current.clear(),
+                _ => {}
+            },
+            // Cursor horizontal absolute (1-based)
+            'G' => {
+                let col = self.csi_params.parse::<usize>().unwrap_or(1);
+                self.cursor = col.saturating_sub(1).min(MAX_SCROLLBACK_LINE_LEN);
+                while self.current.len() < self.cursor {
+                    self.current.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn newline(&mut self) {
+        let line: String = self.current.drain(..).collect();
+        self.cursor = 0;
+        self.lines.push_back(line.trim_end().to_string());
+        while self.lines.len() > MAX_SCROLLBACK_LINES {
+            self.lines.pop_front();
+            self.first_line_number += 1;
+        }
+    }
+
+    /// Last `count` lines, including the unterminated current line (usually the prompt)
+    pub fn last_lines(&self, count: usize) -> Vec<String> {
+        let has_partial = !self.current.is_empty();
+        let complete = count.saturating_sub(has_partial as usize);
+        let skip = self.lines.len().saturating_sub(complete);
+
+        let mut out: Vec<String> = self.lines.iter().skip(skip).cloned().collect();
+        if has_partial && count > 0 {
+            let partial: String = self.current.iter().collect();
+            out.push(partial.trim_end().to_string());
+        }
+        out
+    }
+
+    /// Absolute number the next completed line will get
+    pub fn next_line_number(&self) -> u64 {
+        self.first_line_number + self.lines.len() as u64
+    }
+
+    /// Complete lines numbered `from` or later that are still buffered
+    pub fn lines_from(&self, from: u64) -> impl Iterator<Item = &String> {
+        let skip = from.saturating_sub(self.first_line_number) as usize;
+        self.lines.iter().skip(skip)
+    }
+
+    /// Search complete lines, most recent first
+    pub fn search(&self, pattern: &regex::Regex, max_results: usize) -> Vec<ScrollbackMatch> {
+        self.lines
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, line)| pattern.is_match(line))
+            .take(max_results)
+            .map(|(idx, line)| ScrollbackMatch {
+                line_number: self.first_line_number + idx as u64,
+                line: line.clone(),
+            })
+            .collect()
+    }
+}
+
+impl Default for ScrollbackBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strips_ansi_and_osc() {
+        let mut buf = ScrollbackBuffer::new();
+        buf.push_str("\x1b]133;A\x07\x1b[1;32muser@host\x1b[0m:~$ ls\r\n");
+        buf.push_str("a.txt  b.txt\r\n");
+        assert_eq!(buf.last_lines(10), vec!["user@host:~$ ls", "a.txt  b.txt"]);
+    }
+
+    #[test]
+    fn test_carriage_return_overwrites_progress() {
+        let mut buf = ScrollbackBuffer::new();
+        buf.push_str("progress 10%\rprogress 100%\r\n");
+        buf.push_str("downloading\r\x1b[Kdone\n");
+        assert_eq!(buf.last_lines(2), vec!["progress 100%", "done"]);
+    }
+
+    #[test]
+    fn test_escape_split_across_chunks() {
+        let mut buf = ScrollbackBuffer::new();
+        buf.push_str("ok\x1b[3");
+        buf.push_str("1mred\x1b]0;ti");
+        buf.push_str("tle\x1b\\\n");
+        assert_eq!(buf.last_lines(1), vec!["okred"]);
+    }
+
+    #[test]
+    fn test_ring_is_bounded_and_search_numbers_lines() {
+        let mut buf = ScrollbackBuffer::new();
+        for i in 0..(MAX_SCROLLBACK_LINES + 5) {
+            buf.push_str(&format!("line {}\n", i));
+        }
+        assert_eq!(buf.last_lines(usize::MAX).len(), MAX_SCROLLBACK_LINES);
+        let next = buf.next_line_number();
+        assert_eq!(next, (MAX_SCROLLBACK_LINES + 5) as u64);
+        let recent: Vec<&String> = buf.lines_from(next - 2).collect();
+        assert_eq!(
+            recent,
+            vec![&format!("line {}", next - 2), &format!("line {}", next - 1)]
+        );
+        assert_eq!(buf.lines_from(0).count(), MAX_SCROLLBACK_LINES);
+
+        let re = regex::Regex::new(r"^line 7$").unwrap();
+        let matches = buf.search(&re, 10);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line_number, 7);
+        assert!(buf
+            .search(&regex::Regex::new(r"^line 2$").unwrap(), 10)
+            .is_empty());
+    }
+}
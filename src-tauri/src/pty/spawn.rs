@@ -1,63 +1,349 @@
-use super::integration::{configure_shell_command, setup_integration_scripts};
-use super::reader::spawn_reader_thread;
+use super::health::spawn_health_monitor;
+use super::integration::{configure_shell_launch, setup_integration_scripts, supports_integration};
+use super::paste::PasteQueue;
+use super::process::spawn_process_watcher;
+use super::osc_parser::current_timestamp;
+use super::reader::{spawn_reader_thread, ReaderHandles};
+use super::remote::{ChannelChild, ChannelMaster, ChannelReader};
 use super::shell::resolve_shell;
-use crate::models::{AppState, PtySession};
-use portable_pty::{CommandBuilder, NativePtySystem, PtySize, PtySystem};
+use super::startup::{SharedWriter, StartupInput};
+use crate::models::{AppState, PtySession, SshSessionInfo, MAX_TERMINAL_DIMENSION};
+use crate::ssh::{connect_native, find_ssh_profile, record_ssh_connection, ssh_command_line};
+use crate::terminal_profiles::{find_terminal_profile, PtySpawnOptions};
+use portable_pty::{Child, CommandBuilder, MasterPty, NativePtySystem, PtySize, PtySystem};
+use std::io::Read;
+use std::sync::Mutex;
 use tauri::State;
 
 #[tauri::command]
 pub fn spawn_pty(window: tauri::Window, state: State<AppState>) -> Result<u32, String> {
-    let id = {
-        let mut next_id = state
-            .next_id
-            .lock()
-            .map_err(|e| format!("Failed to acquire ID lock: {}", e))?;
-        let id = *next_id;
-        *next_id += 1;
-        id
+    spawn_pty_with_options(window, &state, PtySpawnOptions::default(), None, Vec::new())
+}
+
+/// Spawn a PTY from a saved terminal profile, inline options, or both
+/// (inline fields override the profile's)
+#[tauri::command]
+pub fn spawn_pty_with_profile(
+    window: tauri::Window,
+    state: State<AppState>,
+    profile_id: Option<String>,
+    options: Option<PtySpawnOptions>,
+    rows: Option<u16>,
+    cols: Option<u16>,
+) -> Result<u32, String> {
+    let base = match profile_id {
+        Some(id) => find_terminal_profile(&id)?.options,
+        None => PtySpawnOptions::default(),
     };
+    let options = base.merged(options.unwrap_or_default());
 
-    let pty_system = NativePtySystem::default();
+    spawn_pty_with_options(
+        window,
+        &state,
+        options,
+        requested_size(rows, cols),
+        Vec::new(),
+    )
+}
+
+/// Connect a saved SSH profile in a new PTY. The shell types the profile's
+/// ssh command at its first prompt and the startup commands at the first
+/// prompt on the remote host, which needs shell integration on both ends.
+#[tauri::command]
+pub fn connect_ssh_profile(
+    window: tauri::Window,
+    state: State<AppState>,
+    profile_id: String,
+    rows: Option<u16>,
+    cols: Option<u16>,
+) -> Result<u32, String> {
+    let profile = find_ssh_profile(&profile_id)?;
+    let options = PtySpawnOptions {
+        env_vars: profile.env_vars.clone(),
+        initial_command: Some(ssh_command_line(&profile)?),
+        ..Default::default()
+    };
+
+    let id = spawn_pty_with_options(
+        window.clone(),
+        &state,
+        options,
+        requested_size(rows, cols),
+        profile.startup_commands.clone().unwrap_or_default(),
+    )?;
+    if let Some(session) = state
+        .ptys
+        .lock()
+        .map_err(|e| format!("Failed to acquire PTY lock: {}", e))?
+        .get_mut(&id)
+    {
+        session.ssh_profile_id = Some(profile_id.clone());
+    }
+    spawn_health_monitor(window, id, &profile, false);
+
+    if let Err(e) = record_ssh_connection(&profile_id) {
+        eprintln!(
+            "[SSH] Failed to update connection stats for {}: {}",
+            profile_id, e
+        );
+    }
+    Ok(id)
+}
+
+/// Open an SSH profile with the in-process client instead of the ssh binary.
+/// The remote shell has no integration marks to wait for, so the profile's
+/// startup commands are typed right away and the remote tty buffers them.
+#[tauri::command]
+pub async fn connect_ssh_profile_native(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    profile_id: String,
+    rows: Option<u16>,
+    cols: Option<u16>,
+) -> Result<u32, String> {
+    let profile = find_ssh_profile(&profile_id)?;
+    let size = requested_size(rows, cols).unwrap_or_else(default_size);
+    let connection = {
+        let profile = profile.clone();
+        tokio::task::spawn_blocking(move || connect_native(&profile, size.cols, size.rows))
+            .await
+            .map_err(|e| format!("SSH connection task failed: {}", e))??
+    };
+
+    let id = next_pty_id(&state)?;
+    let startup_commands: Vec<String> = profile
+        .startup_commands
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|command| !command.trim().is_empty())
+        .collect();
+    register_pty(
+        window.clone(),
+        &state,
+        id,
+        Box::new(ChannelMaster {
+            channel: connection.channel.clone(),
+            size: Mutex::new(size),
+        }),
+        Box::new(ChannelChild {
+            channel: connection.channel.clone(),
+        }),
+        Box::new(ChannelReader {
+            channel: connection.channel,
+        }),
+        (!startup_commands.is_empty()).then(|| startup_commands.join("\r")),
+        Vec::new(),
+        false,
+    )?;
+
+    let ssh_info = SshSessionInfo {
+        remote_host: connection.host,
+        remote_user: Some(connection.user),
+        remote_port: connection.port,
+        connection_time: current_timestamp(),
+        last_latency_ms: None,
+    };
+    if let Some(session) = state
+        .ptys
+        .lock()
+        .map_err(|e| format!("Failed to acquire PTY lock: {}", e))?
+        .get_mut(&id)
+    {
+        session.remote_fs = Some(connection.fs);
+        session.ssh_session = Some(ssh_info.clone());
+        session.ssh_profile_id = Some(profile_id.clone());
+    }
+    if let Ok(mut sessions) = state.ssh_sessions.lock() {
+        sessions.insert(id, ssh_info);
+    }
+    spawn_health_monitor(window, id, &profile, true);
+
+    if let Err(e) = record_ssh_connection(&profile_id) {
+        eprintln!(
+            "[SSH] Failed to update connection stats for {}: {}",
+            profile_id, e
+        );
+    }
+    Ok(id)
+}
 
-    // Use more realistic default dimensions to minimize issues if resize is delayed
-    // Modern terminals are typically 100-200 cols × 30-50 rows
-    // Using larger defaults prevents line wrapping issues in SSH sessions
-    // The frontend will send the correct size immediately after connection
-    let pair = pty_system
-        .openpty(PtySize {
-            rows: 50,
-            cols: 200,
+fn requested_size(rows: Option<u16>, cols: Option<u16>) -> Option<PtySize> {
+    match (rows, cols) {
+        (Some(rows), Some(cols)) => Some(PtySize {
+            rows: rows.clamp(1, MAX_TERMINAL_DIMENSION),
+            cols: cols.clamp(1, MAX_TERMINAL_DIMENSION),
             pixel_width: 0,
             pixel_height: 0,
-        })
-        .map_err(|e| e.to_string())?;
+        }),
+        _ => None,
+    }
+}
+
+/// Use more realistic default dimensions to minimize issues if resize is delayed
+/// Modern terminals are typically 100-200 cols × 30-50 rows
+/// Using larger defaults prevents line wrapping issues in SSH sessions
+/// The frontend will send the correct size immediately after connection
+fn default_size() -> PtySize {
+    PtySize {
+        rows: 50,
+        cols: 200,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+/// `remote_commands` are typed at the first prompt on a remote host
+fn spawn_pty_with_options(
+    window: tauri::Window,
+    state: &AppState,
+    options: PtySpawnOptions,
+    size: Option<PtySize>,
+    remote_commands: Vec<String>,
+) -> Result<u32, String> {
+    let cwd = match options.cwd.as_deref() {
+        Some(dir) => {
+            let dir = shellexpand::tilde(dir).to_string();
+            if !std::path::Path::new(&dir).is_dir() {
+                return Err(format!("Working directory does not exist: {}", dir));
+            }
+            Some(dir)
+        }
+        None => None,
+    };
+
+    let id = next_pty_id(state)?;
+
+    let size = size.unwrap_or_else(default_size);
 
     // Detect user's preferred shell
-    let shell = if cfg!(target_os = "windows") {
-        std::env::var("SHELL").unwrap_or_else(|_| "powershell.exe".to_string())
-    } else {
-        resolve_shell()
+    let shell = match options.shell.as_deref() {
+        Some(shell) if !shell.trim().is_empty() => shellexpand::tilde(shell).to_string(),
+        _ if cfg!(target_os = "windows") => {
+            std::env::var("SHELL").unwrap_or_else(|_| "powershell.exe".to_string())
+        }
+        _ => resolve_shell(),
     };
 
     let mut cmd = CommandBuilder::new(&shell);
 
     // Setup shell integration
+    let interactive = options.interactive.unwrap_or(true);
     let config_dir = setup_integration_scripts();
-    configure_shell_command(&mut cmd, &shell, config_dir.as_ref());
+    configure_shell_launch(
+        &mut cmd,
+        &shell,
+        config_dir.as_ref(),
+        interactive,
+        options.login,
+    );
+    if let Some(args) = &options.args {
+        cmd.args(args);
+    }
+    if let Some(env_vars) = &options.env_vars {
+        for (key, value) in env_vars {
+            cmd.env(key, value);
+        }
+    }
+    if let Some(dir) = cwd {
+        cmd.cwd(dir);
+    }
+
+    // The initial command waits for the first prompt when the shell reports
+    // one; otherwise it is typed ahead immediately
+    let waits_for_prompt = interactive && config_dir.is_some() && supports_integration(&shell);
+
+    // With persistent sessions on, the daemon owns the PTY and we attach to it
+    #[cfg(unix)]
+    if let Some(daemon) = super::daemon::daemon_for_spawn(state)? {
+        let pty = daemon.spawn(&cmd, size)?;
+        register_pty(
+            window,
+            state,
+            id,
+            pty.master,
+            pty.child,
+            pty.reader,
+            options.initial_command,
+            remote_commands,
+            waits_for_prompt,
+        )?;
+        return Ok(id);
+    }
+
+    let pty_system = NativePtySystem::default();
+    let pair = pty_system.openpty(size).map_err(|e| e.to_string())?;
 
     let child = pair.slave.spawn_command(cmd).map_err(|e| e.to_string())?;
 
     let reader = pair.master.try_clone_reader().map_err(|e| e.to_string())?;
-    let master = pair.master;
-    let writer = master.take_writer().map_err(|e| e.to_string())?;
+    register_pty(
+        window,
+        state,
+        id,
+        pair.master,
+        child,
+        reader,
+        options.initial_command,
+        remote_commands,
+        waits_for_prompt,
+    )?;
+
+    Ok(id)
+}
+
+/// Reserve the ID for a new PTY
+pub(super) fn next_pty_id(state: &AppState) -> Result<u32, String> {
+    let mut next_id = state
+        .next_id
+        .lock()
+        .map_err(|e| format!("Failed to acquire ID lock: {}", e))?;
+    let id = *next_id;
+    *next_id += 1;
+    Ok(id)
+}
+
+/// Track a spawned or attached PTY: start its reader and process watcher and
+/// add it to `AppState.ptys`
+#[allow(clippy::too_many_arguments)]
+pub(super) fn register_pty(
+    window: tauri::Window,
+    state: &AppState,
+    id: u32,
+    master: Box<dyn MasterPty + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    reader: Box<dyn Read + Send>,
+    initial_command: Option<String>,
+    remote_commands: Vec<String>,
+    waits_for_prompt: bool,
+) -> Result<(), String> {
+    let writer = SharedWriter::new(master.take_writer().map_err(|e| e.to_string())?);
+    state
+        .paste_queues
+        .lock()
+        .map_err(|e| format!("Failed to acquire paste queue lock: {}", e))?
+        .insert(id, PasteQueue::new(writer.clone()));
+
+    let remote = StartupInput::remote(writer.clone(), remote_commands);
+    let mut startup = match StartupInput::new(writer.clone(), initial_command.into_iter().collect())
+    {
+        Some(input) => Some(input.then(remote)),
+        None => remote,
+    };
+    if !waits_for_prompt {
+        if let Some(input) = startup.take_if(|input| input.ready_without_prompt()) {
+            startup = input.send(id);
+        }
+    }
 
     // Spawn reader thread with SSH detection and output tracking
     let reader_handle = spawn_reader_thread(
-        reader, 
-        window, 
-        id, 
-        state.ssh_sessions.clone(),
-        state.pty_last_output.clone(),
+        reader,
+        window.clone(),
+        id,
+        ReaderHandles::from_state(state),
+        writer.clone(),
+        startup,
     );
 
     {
@@ -69,13 +355,18 @@ pub fn spawn_pty(window: tauri::Window, state: State<AppState>) -> Result<u32, S
             id,
             PtySession {
                 master,
-                writer,
+                writer: Box::new(writer),
                 child: Some(child),
                 reader_handle: Some(reader_handle),
                 ssh_session: None,
+                tmux_pane: None,
+                remote_fs: None,
+                ssh_profile_id: None,
             },
         );
     }
 
-    Ok(id)
+    spawn_process_watcher(window, id);
+
+    Ok(())
 }
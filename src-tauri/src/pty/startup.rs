@@ -0,0 +1,211 @@
+use super::osc_parser::{parse_remote_host, OscSequence};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+/// PTY writer shared between the session and the reader thread.
+///
+/// The underlying writer sends EOF to the shell when dropped, which now
+/// happens when the last clone goes away.
+#[derive(Clone)]
+pub struct SharedWriter(Arc<Mutex<Box<dyn Write + Send>>>);
+
+impl SharedWriter {
+    pub fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self(Arc::new(Mutex::new(writer)))
+    }
+}
+
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .lock()
+            .map_err(|e| std::io::Error::other(e.to_string()))?
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0
+            .lock()
+            .map_err(|e| std::io::Error::other(e.to_string()))?
+            .flush()
+    }
+}
+
+/// Commands typed into a new shell once it shows its first prompt (OSC 133;A),
+/// so they run after the user's rc files instead of racing them.
+///
+/// A remote stage waits for the first prompt that reports a remote host
+/// instead, e.g. the profile's startup commands after its ssh command
+/// connects. It is dropped if the shell reports a local prompt after a
+/// command started, i.e. the connection failed or ended first.
+pub struct StartupInput {
+    writer: SharedWriter,
+    commands: Vec<String>,
+    remote: bool,
+    /// Stage that starts waiting once this one was typed
+    next: Option<Box<StartupInput>>,
+    prompt_seen: bool,
+    command_started: bool,
+}
+
+impl StartupInput {
+    /// None when there is nothing to type
+    pub fn new(writer: SharedWriter, commands: Vec<String>) -> Option<Self> {
+        let commands: Vec<String> = commands
+            .into_iter()
+            .filter(|command| !command.trim().is_empty())
+            .collect();
+        if commands.is_empty() {
+            None
+        } else {
+            Some(Self {
+                writer,
+                commands,
+                remote: false,
+                next: None,
+                prompt_seen: false,
+                command_started: false,
+            })
+        }
+    }
+
+    /// Like `new`, but typed at the first prompt on a remote host
+    pub fn remote(writer: SharedWriter, commands: Vec<String>) -> Option<Self> {
+        Self::new(writer, commands).map(|input| Self {
+            remote: true,
+            ..input
+        })
+    }
+
+    /// Wait for `next` after this stage was typed
+    pub fn then(self, next: Option<Self>) -> Self {
+        Self {
+            next: next.map(Box::new),
+            ..self
+        }
+    }
+
+    /// Whether the stage can be typed before any prompt was seen
+    pub fn ready_without_prompt(&self) -> bool {
+        !self.remote
+    }
+
+    /// Track the OSC sequences of one read. Types the commands once their
+    /// prompt arrived and returns the stage still waiting, if any.
+    /// `on_remote_host` is the host stack after these sequences.
+    pub fn feed(
+        mut self,
+        id: u32,
+        sequences: &[OscSequence],
+        on_remote_host: bool,
+    ) -> Option<Self> {
+        for seq in sequences {
+            if is_prompt_start(seq) {
+                self.prompt_seen = true;
+            } else if matches!(seq.code, 133 | 633) && seq.payload == "C" {
+                self.command_started = true;
+            } else if self.remote
+                && self.command_started
+                && seq.code == 1337
+                && matches!(parse_remote_host(&seq.payload), Some(None))
+            {
+                eprintln!("[PTY {id}] Shell is local again; skipping remote startup commands");
+                return None;
+            }
+        }
+        if self.prompt_seen && (on_remote_host || !self.remote) {
+            self.send(id)
+        } else {
+            Some(self)
+        }
+    }
+
+    /// Type the commands and return the next stage. Consumes the input so
+    /// the writer clone is released.
+    pub fn send(mut self, id: u32) -> Option<Self> {
+        for command in &self.commands {
+            let line = format!("{}\r", command.trim_end_matches(['\r', '\n']));
+            if let Err(e) = self.writer.write_all(line.as_bytes()) {
+                eprintln!("[PTY {id}] Failed to send startup command: {}", e);
+                return None;
+            }
+        }
+        let _ = self.writer.flush();
+        self.next.map(|next| *next)
+    }
+}
+
+/// Plain prompt-start mark from the shell itself (REPL prompts carry extra fields)
+fn is_prompt_start(seq: &OscSequence) -> bool {
+    matches!(seq.code, 133 | 633) && seq.payload == "A"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Typed(Arc<Mutex<Vec<u8>>>);
+
+    impl Typed {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for Typed {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn osc(code: u32, payload: &str) -> OscSequence {
+        OscSequence {
+            code,
+            payload: payload.to_string(),
+            start: 0,
+            end: 0,
+        }
+    }
+
+    fn connect(typed: &Typed) -> Option<StartupInput> {
+        let writer = SharedWriter::new(Box::new(typed.clone()));
+        let remote = StartupInput::remote(writer.clone(), vec!["cd /scratch".to_string()]);
+        StartupInput::new(writer, vec!["ssh gpu7".to_string()]).map(|input| input.then(remote))
+    }
+
+    #[test]
+    fn test_remote_stage_waits_for_remote_prompt() {
+        let typed = Typed::default();
+
+        let startup = connect(&typed).unwrap();
+        assert!(startup.ready_without_prompt());
+        let startup = startup.feed(1, &[osc(133, "A")], false).unwrap();
+        assert_eq!(typed.text(), "ssh gpu7\r");
+        assert!(!startup.ready_without_prompt());
+
+        // Local host report of the same prompt, then the ssh command runs
+        let startup = startup.feed(1, &[osc(1337, "RemoteHost=;Depth=0")], false);
+        let startup = startup.unwrap().feed(1, &[osc(133, "C")], false).unwrap();
+        // Remote prompt mark and host report split across reads
+        let startup = startup.feed(1, &[osc(133, "A")], false).unwrap();
+        let remote = osc(1337, "RemoteHost=me@gpu7;Depth=1");
+        assert!(startup.feed(1, &[remote], true).is_none());
+        assert_eq!(typed.text(), "ssh gpu7\rcd /scratch\r");
+
+        // Connection failed: the local shell prompts again
+        let typed = Typed::default();
+        let startup = connect(&typed).unwrap().feed(1, &[osc(133, "A")], false);
+        let failed = [osc(133, "C"), osc(133, "D;255"), osc(133, "A")];
+        let startup = startup.unwrap().feed(1, &failed, false).unwrap();
+        assert!(startup
+            .feed(1, &[osc(1337, "RemoteHost=;Depth=0")], false)
+            .is_none());
+        assert_eq!(typed.text(), "ssh gpu7\r");
+    }
+}
@@ -0,0 +1,365 @@
+//! Parser for the tmux control-mode protocol.
+//!
+//! `tmux -CC` announces itself with the DCS sequence `ESC P 1000 p`, then
+//! writes one line per notification (`%output`, `%layout-change`, ...) and
+//! wraps command replies in `%begin`/`%end` (or `%error`) blocks. `%exit`
+//! followed by ST ends control mode and the terminal carries on as before.
+
+use std::borrow::Cow;
+
+const CONTROL_MODE_START: &[u8] = b"\x1bP1000p";
+const STRING_TERMINATOR: &[u8] = b"\x1b\\";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlEvent {
+    /// Control mode started; following lines are protocol, not terminal output
+    Started,
+    /// Reply to a command, in the order commands were sent
+    CommandResult {
+        success: bool,
+        lines: Vec<String>,
+    },
+    Output {
+        pane: u32,
+        data: Vec<u8>,
+    },
+    WindowAdd {
+        window: u32,
+    },
+    WindowClose {
+        window: u32,
+    },
+    WindowRenamed {
+        window: u32,
+        name: String,
+    },
+    LayoutChange {
+        window: u32,
+        layout: String,
+    },
+    /// The client now shows a different session; its windows must be listed again
+    SessionChanged,
+    Exited {
+        reason: Option<String>,
+    },
+}
+
+/// A `%begin` block waiting for its `%end`
+struct Block {
+    number: String,
+    lines: Vec<String>,
+}
+
+pub struct ControlParser {
+    active: bool,
+    /// Bytes that may be the start of the DCS (or ST after `%exit`)
+    held: Vec<u8>,
+    /// Partial protocol line
+    line: Vec<u8>,
+    block: Option<Block>,
+    /// Drop the ST that follows `%exit`
+    strip_terminator: bool,
+}
+
+impl ControlParser {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            held: Vec::new(),
+            line: Vec::new(),
+            block: None,
+            strip_terminator: false,
+        }
+    }
+
+    /// Feed PTY output. Returns the bytes that belong to the terminal itself
+    /// (everything outside control mode) and the protocol events.
+    pub fn feed<'a>(&mut self, data: &'a [u8]) -> (Cow<'a, [u8]>, Vec<ControlEvent>) {
+        // Fast path for ordinary terminal output
+        if !self.active && self.held.is_empty() && !self.strip_terminator && !data.contains(&0x1b) {
+            return (Cow::Borrowed(data), Vec::new());
+        }
+
+        let mut input = std::mem::take(&mut self.held);
+        input.extend_from_slice(data);
+
+        let mut passthrough = Vec::new();
+        let mut events = Vec::new();
+        let mut pos = 0;
+        while pos < input.len() {
+            let rest = &input[pos..];
+            if self.active {
+                match rest.iter().position(|&b| b == b'\n') {
+                    Some(end) => {
+                        self.line.extend_from_slice(&rest[..end]);
+                        pos += end + 1;
+                        let line = std::mem::take(&mut self.line);
+                        self.handle_line(&line, &mut events);
+                    }
+                    None => {
+                        self.line.extend_from_slice(rest);
+                        pos = input.len();
+                    }
+                }
+                continue;
+            }
+
+            if self.strip_terminator {
+                if rest.starts_with(STRING_TERMINATOR) {
+                    pos += STRING_TERMINATOR.len();
+                    self.strip_terminator = false;
+                    continue;
+                }
+                if rest != &STRING_TERMINATOR[..1] {
+                    self.strip_terminator = false;
+                }
+            }
+
+            if let Some(start) = find(rest, CONTROL_MODE_START) {
+                passthrough.extend_from_slice(&rest[..start]);
+                pos += start + CONTROL_MODE_START.len();
+                self.active = true;
+                self.block = None;
+                events.push(ControlEvent::Started);
+                continue;
+            }
+
+            // Hold back a trailing partial DCS (or ST) until the next read
+            let keep = partial_suffix(rest, CONTROL_MODE_START).max(if self.strip_terminator {
+                partial_suffix(rest, STRING_TERMINATOR)
+            } else {
+                0
+            });
+            passthrough.extend_from_slice(&rest[..rest.len() - keep]);
+            self.held = rest[rest.len() - keep..].to_vec();
+            pos = input.len();
+        }
+
+        (Cow::Owned(passthrough), events)
+    }
+
+    fn handle_line(&mut self, line: &[u8], events: &mut Vec<ControlEvent>) {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        // Pane output is raw bytes (only control characters are escaped), so it
+        // is handled before the line is decoded as text
+        if self.block.is_none() {
+            if let Some(event) = parse_output(line) {
+                events.push(event);
+                return;
+            }
+        }
+
+        let line = String::from_utf8_lossy(line);
+        if let Some(block) = self.block.as_mut() {
+            // `%end`/`%error` carry the same command number as `%begin`; anything
+            // else inside the block is reply text, even if it starts with `%`
+            let guard = line
+                .strip_prefix("%end ")
+                .map(|rest| (true, rest))
+                .or_else(|| line.strip_prefix("%error ").map(|rest| (false, rest)));
+            if let Some((success, rest)) = guard {
+                if rest.split(' ').nth(1) == Some(block.number.as_str()) {
+                    let lines = std::mem::take(&mut block.lines);
+                    self.block = None;
+                    events.push(ControlEvent::CommandResult { success, lines });
+                    return;
+                }
+            }
+            block.lines.push(line.to_string());
+            return;
+        }
+
+        let (name, args) = line.split_once(' ').unwrap_or((&line, ""));
+        match name {
+            "%begin" => {
+                self.block = Some(Block {
+                    number: args.split(' ').nth(1).unwrap_or_default().to_string(),
+                    lines: Vec::new(),
+                });
+            }
+            "%window-add" => {
+                if let Some(window) = parse_id(args, '@') {
+                    events.push(ControlEvent::WindowAdd { window });
+                }
+            }
+            "%window-close" => {
+                if let Some(window) = parse_id(args, '@') {
+                    events.push(ControlEvent::WindowClose { window });
+                }
+            }
+            "%window-renamed" => {
+                if let Some((window, name)) = args.split_once(' ') {
+                    if let Some(window) = parse_id(window, '@') {
+                        events.push(ControlEvent::WindowRenamed {
+                            window,
+                            name: name.to_string(),
+                        });
+                    }
+                }
+            }
+            "%layout-change" => {
+                let mut parts = args.split(' ');
+                if let (Some(window), Some(layout)) =
+                    (parts.next().and_then(|w| parse_id(w, '@')), parts.next())
+                {
+                    events.push(ControlEvent::LayoutChange {
+                        window,
+                        layout: layout.to_string(),
+                    });
+                }
+            }
+            "%session-changed" => events.push(ControlEvent::SessionChanged),
+            "%exit" => {
+                self.active = false;
+                self.block = None;
+                self.strip_terminator = true;
+                events.push(ControlEvent::Exited {
+                    reason: Some(args.to_string()).filter(|reason| !reason.is_empty()),
+                });
+            }
+            // Other notifications (%sessions-changed, %pane-mode-changed, ...) need no action
+            _ => {}
+        }
+    }
+}
+
+/// `%output %pane data`, or `%extended-output %pane age ... : data` when
+/// pause-after is set
+fn parse_output(line: &[u8]) -> Option<ControlEvent> {
+    let (args, extended) = match line.strip_prefix(b"%output ") {
+        Some(args) => (args, false),
+        None => (line.strip_prefix(b"%extended-output ")?, true),
+    };
+    let space = args.iter().position(|&b| b == b' ')?;
+    let pane = parse_id(std::str::from_utf8(&args[..space]).ok()?, '%')?;
+    let data = if extended {
+        &args[find(args, b" : ")? + 3..]
+    } else {
+        &args[space + 1..]
+    };
+    Some(ControlEvent::Output {
+        pane,
+        data: unescape_output(data),
+    })
+}
+
+/// Parse a tmux ID such as `%3`, `@1` or `$0`
+fn parse_id(id: &str, prefix: char) -> Option<u32> {
+    id.trim().strip_prefix(prefix)?.parse().ok()
+}
+
+/// `%output` escapes bytes below 0x20 and backslash as `\ooo` octal
+fn unescape_output(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() {
+            let octal = &bytes[i + 1..i + 4];
+            if octal.iter().all(|b| (b'0'..=b'7').contains(b)) {
+                let value = octal
+                    .iter()
+                    .fold(0u32, |acc, b| acc * 8 + u32::from(b - b'0'));
+                out.push(value as u8);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    out
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Length of the longest suffix of `data` that is a proper prefix of `pattern`
+fn partial_suffix(data: &[u8], pattern: &[u8]) -> usize {
+    (1..pattern.len().min(data.len() + 1))
+        .rev()
+        .find(|&len| data.ends_with(&pattern[..len]))
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(parser: &mut ControlParser, chunks: &[&[u8]]) -> (Vec<u8>, Vec<ControlEvent>) {
+        let mut passthrough = Vec::new();
+        let mut events = Vec::new();
+        for chunk in chunks {
+            let (bytes, new_events) = parser.feed(chunk);
+            passthrough.extend_from_slice(&bytes);
+            events.extend(new_events);
+        }
+        (passthrough, events)
+    }
+
+    #[test]
+    fn test_control_mode_session() {
+        let mut parser = ControlParser::new();
+        let (passthrough, events) = feed_all(
+            &mut parser,
+            &[
+                b"$ tmux -CC\r\n\x1bP10",
+                b"00p%begin 1700000000 12 0\r\n%end 1700000000 12 0\r\n",
+                b"%session-changed $0 main\r\n%output %1 ls\\015\\012a\\134b\r\n%lay",
+                b"out-change @2 b25d,80x24,0,0,1 b25d,80x24,0,0,1 *\r\n",
+                b"%begin 1700000001 13 1\r\n%end in text\r\nline two\r\n%error 1700000001 13 1\r\n",
+                b"%window-renamed @2 my editor\r\n%window-close @2\r\n",
+                b"%exit detached\r\n\x1b",
+                b"\\$ ",
+            ],
+        );
+
+        assert_eq!(passthrough, b"$ tmux -CC\r\n$ ");
+        assert_eq!(
+            events,
+            vec![
+                ControlEvent::Started,
+                ControlEvent::CommandResult {
+                    success: true,
+                    lines: vec![],
+                },
+                ControlEvent::SessionChanged,
+                ControlEvent::Output {
+                    pane: 1,
+                    data: b"ls\r\na\\b".to_vec(),
+                },
+                ControlEvent::LayoutChange {
+                    window: 2,
+                    layout: "b25d,80x24,0,0,1".to_string(),
+                },
+                ControlEvent::CommandResult {
+                    success: false,
+                    lines: vec!["%end in text".to_string(), "line two".to_string()],
+                },
+                ControlEvent::WindowRenamed {
+                    window: 2,
+                    name: "my editor".to_string(),
+                },
+                ControlEvent::WindowClose { window: 2 },
+                ControlEvent::Exited {
+                    reason: Some("detached".to_string()),
+                },
+            ]
+        );
+        assert!(!parser.active);
+    }
+
+    #[test]
+    fn test_plain_output_passes_through() {
+        let mut parser = ControlParser::new();
+        let (passthrough, events) = feed_all(&mut parser, &[b"\x1b[31mred\x1b", b"[0m\x1bP"]);
+        assert_eq!(passthrough, b"\x1b[31mred\x1b[0m");
+        assert!(events.is_empty());
+        // The held `ESC P` turns out to be some other DCS
+        let (passthrough, _) = feed_all(&mut parser, &[b"q\x1b\\"]);
+        assert_eq!(passthrough, b"\x1bPq\x1b\\");
+    }
+}
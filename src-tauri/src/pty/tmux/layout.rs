@@ -0,0 +1,197 @@
+//! tmux window layouts, as reported by `%layout-change` and `#{window_layout}`.
+//!
+//! A layout looks like `b25d,160x48,0,0{80x48,0,0,1,79x48,81,0,2}`: a checksum,
+//! then a cell `WxH,X,Y` that is either a pane (`,ID`) or a split into
+//! side-by-side (`{...}`) or stacked (`[...]`) cells.
+
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LayoutCell {
+    pub cols: u16,
+    pub rows: u16,
+    pub x: u16,
+    pub y: u16,
+    pub content: LayoutContent,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum LayoutContent {
+    /// tmux pane ID
+    Pane(u32),
+    /// Children side by side, left to right
+    Columns(Vec<LayoutCell>),
+    /// Children stacked, top to bottom
+    Rows(Vec<LayoutCell>),
+}
+
+impl LayoutCell {
+    pub fn parse(layout: &str) -> Option<Self> {
+        let (_checksum, cell) = layout.split_once(',')?;
+        let mut parser = Parser {
+            input: cell.as_bytes(),
+            pos: 0,
+        };
+        let cell = parser.cell()?;
+        (parser.pos == parser.input.len()).then_some(cell)
+    }
+
+    /// Pane IDs in layout order
+    pub fn panes(&self) -> Vec<u32> {
+        match &self.content {
+            LayoutContent::Pane(pane) => vec![*pane],
+            LayoutContent::Columns(children) | LayoutContent::Rows(children) => {
+                children.iter().flat_map(LayoutCell::panes).collect()
+            }
+        }
+    }
+
+    pub fn find_pane(&self, pane: u32) -> Option<&LayoutCell> {
+        match &self.content {
+            LayoutContent::Pane(id) => (*id == pane).then_some(self),
+            LayoutContent::Columns(children) | LayoutContent::Rows(children) => {
+                children.iter().find_map(|child| child.find_pane(pane))
+            }
+        }
+    }
+
+    /// Window size that gives each pane the size returned by `pane_size`
+    /// (falling back to its current size), with one-cell borders between
+    /// split children
+    pub fn fit_size(&self, pane_size: &impl Fn(u32) -> Option<(u16, u16)>) -> (u16, u16) {
+        match &self.content {
+            LayoutContent::Pane(pane) => pane_size(*pane).unwrap_or((self.cols, self.rows)),
+            LayoutContent::Columns(children) => {
+                let sizes: Vec<_> = children.iter().map(|c| c.fit_size(pane_size)).collect();
+                let cols = sizes.iter().map(|(cols, _)| *cols).sum::<u16>()
+                    + (sizes.len() as u16).saturating_sub(1);
+                let rows = sizes
+                    .iter()
+                    .map(|(_, rows)| *rows)
+                    .max()
+                    .unwrap_or(self.rows);
+                (cols, rows)
+            }
+            LayoutContent::Rows(children) => {
+                let sizes: Vec<_> = children.iter().map(|c| c.fit_size(pane_size)).collect();
+                let cols = sizes
+                    .iter()
+                    .map(|(cols, _)| *cols)
+                    .max()
+                    .unwrap_or(self.cols);
+                let rows = sizes.iter().map(|(_, rows)| *rows).sum::<u16>()
+                    + (sizes.len() as u16).saturating_sub(1);
+                (cols, rows)
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn cell(&mut self) -> Option<LayoutCell> {
+        let cols = self.number()?;
+        self.expect(b'x')?;
+        let rows = self.number()?;
+        self.expect(b',')?;
+        let x = self.number()?;
+        self.expect(b',')?;
+        let y = self.number()?;
+
+        let content = match self.input.get(self.pos)? {
+            b',' => {
+                self.pos += 1;
+                LayoutContent::Pane(self.number()?)
+            }
+            b'{' => LayoutContent::Columns(self.children(b'}')?),
+            b'[' => LayoutContent::Rows(self.children(b']')?),
+            _ => return None,
+        };
+        Some(LayoutCell {
+            cols: u16::try_from(cols).ok()?,
+            rows: u16::try_from(rows).ok()?,
+            x: u16::try_from(x).ok()?,
+            y: u16::try_from(y).ok()?,
+            content,
+        })
+    }
+
+    fn children(&mut self, close: u8) -> Option<Vec<LayoutCell>> {
+        self.pos += 1;
+        let mut children = vec![self.cell()?];
+        loop {
+            match self.input.get(self.pos)? {
+                b',' => {
+                    self.pos += 1;
+                    children.push(self.cell()?);
+                }
+                c if *c == close => {
+                    self.pos += 1;
+                    return Some(children);
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.input.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.input[start..self.pos])
+            .ok()?
+            .parse()
+            .ok()
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        (self.input.get(self.pos) == Some(&byte)).then(|| self.pos += 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nested_layout() {
+        let layout = LayoutCell::parse(
+            "5e1b,160x48,0,0{80x48,0,0,1,79x48,81,0[79x24,81,0,2,79x23,81,25,5]}",
+        )
+        .unwrap();
+        assert_eq!(layout.panes(), vec![1, 2, 5]);
+        assert_eq!((layout.cols, layout.rows), (160, 48));
+        let LayoutContent::Columns(columns) = &layout.content else {
+            panic!("expected columns: {:?}", layout);
+        };
+        assert!(matches!(columns[1].content, LayoutContent::Rows(_)));
+        assert_eq!(
+            layout.find_pane(5).map(|cell| (cell.x, cell.y)),
+            Some((81, 25))
+        );
+
+        assert_eq!(
+            LayoutCell::parse("b25d,80x24,0,0,7").unwrap().panes(),
+            vec![7]
+        );
+        assert!(LayoutCell::parse("b25d,80x24,0,0{80x24,0,0,1").is_none());
+        assert!(LayoutCell::parse("garbage").is_none());
+    }
+
+    #[test]
+    fn test_fit_size_adds_borders() {
+        let layout = LayoutCell::parse(
+            "5e1b,160x48,0,0{80x48,0,0,1,79x48,81,0[79x24,81,0,2,79x23,81,25,5]}",
+        )
+        .unwrap();
+        assert_eq!(layout.fit_size(&|_| None), (160, 48));
+        let wider = |pane| (pane == 1).then_some((100, 50));
+        assert_eq!(layout.fit_size(&wider), (180, 50));
+    }
+}
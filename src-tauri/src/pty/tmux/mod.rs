@@ -0,0 +1,608 @@
+//! tmux control mode (`tmux -CC`).
+//!
+//! When a PTY starts speaking the control-mode protocol, its reader hands the
+//! output to a `TmuxClient` instead of the terminal. Every tmux pane becomes a
+//! virtual PTY with its own ID (registered like any other PTY, so
+//! `write_to_pty`, `resize_pty` and `close_pty` work on it): input goes back
+//! as `send-keys`, resizes as `refresh-client -C`, and closing runs
+//! `kill-pane`. The PTY running tmux is the "gateway".
+//!
+//! Events, all keyed by the gateway PTY ID:
+//! - `tmux-control-mode:{id}` (bool) when control mode starts or ends
+//! - `tmux-window-changed:{id}` (`TmuxWindow`) when a window appears, is
+//!   renamed or changes layout
+//! - `tmux-window-closed:{id}` (tmux window ID)
+
+mod control;
+mod layout;
+mod pane;
+
+use super::spawn::{next_pty_id, register_pty};
+use super::startup::SharedWriter;
+use crate::models::AppState;
+use control::{ControlEvent, ControlParser};
+use layout::LayoutCell;
+use pane::{PaneChild, PaneEnd, PaneMaster, PaneReader};
+use portable_pty::PtySize;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::{self, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use tauri::{Emitter, Manager, State};
+
+/// Input bytes per `send-keys` command
+const SEND_KEYS_CHUNK: usize = 256;
+const LIST_WINDOWS: &str = "list-windows -F '#{window_id} #{window_layout} #{window_name}'";
+
+/// Marks a PTY as a tmux pane
+#[derive(Serialize, Debug, Clone, Copy)]
+pub struct TmuxPaneRef {
+    pub gateway_id: u32,
+    pub pane_id: u32,
+}
+
+/// A tmux window; the frontend shows it as a tab
+#[derive(Serialize, Debug, Clone)]
+pub struct TmuxWindow {
+    pub gateway_id: u32,
+    pub window_id: u32,
+    pub name: String,
+    pub layout: LayoutCell,
+    /// PTY IDs of the window's panes, in layout order
+    pub pty_ids: Vec<u32>,
+}
+
+/// What to do with the reply to a command we sent
+enum Pending {
+    ListWindows,
+    Capture(u32),
+    Ignore,
+}
+
+struct PaneLink {
+    pty_id: u32,
+    window: u32,
+    output: mpsc::Sender<Vec<u8>>,
+    end: Arc<PaneEnd>,
+    /// Live output held back until the pane's existing screen has been captured
+    held: Option<Vec<u8>>,
+}
+
+struct WindowState {
+    name: String,
+    layout: LayoutCell,
+}
+
+#[derive(Default)]
+struct GatewayState {
+    /// Command lines for the writer thread; None once control mode has ended
+    commands: Option<mpsc::Sender<String>>,
+    pending: VecDeque<Pending>,
+    windows: BTreeMap<u32, WindowState>,
+    panes: HashMap<u32, PaneLink>,
+    /// Pane sizes requested by the frontend
+    pane_sizes: HashMap<u32, (u16, u16)>,
+}
+
+impl GatewayState {
+    fn end_pane(&mut self, pane: u32) {
+        // Dropping the sender gives the pane's reader EOF
+        if let Some(link) = self.panes.remove(&pane) {
+            link.end.set();
+        }
+        self.pane_sizes.remove(&pane);
+    }
+
+    fn window_payload(&self, gateway_id: u32, window_id: u32) -> Option<TmuxWindow> {
+        let window = self.windows.get(&window_id)?;
+        Some(TmuxWindow {
+            gateway_id,
+            window_id,
+            name: window.name.clone(),
+            layout: window.layout.clone(),
+            pty_ids: window
+                .layout
+                .panes()
+                .iter()
+                .filter_map(|pane| self.panes.get(pane).map(|link| link.pty_id))
+                .collect(),
+        })
+    }
+}
+
+/// Control-mode connection to one tmux client
+pub struct TmuxGateway {
+    id: u32,
+    state: Mutex<GatewayState>,
+}
+
+impl TmuxGateway {
+    fn new(id: u32, writer: SharedWriter) -> Self {
+        let (commands, rx) = mpsc::channel();
+        std::thread::spawn(move || write_commands(id, writer, rx));
+        Self {
+            id,
+            state: Mutex::new(GatewayState {
+                commands: Some(commands),
+                ..GatewayState::default()
+            }),
+        }
+    }
+
+    fn lock(&self) -> io::Result<std::sync::MutexGuard<'_, GatewayState>> {
+        self.state
+            .lock()
+            .map_err(|e| io::Error::other(format!("Failed to acquire tmux lock: {}", e)))
+    }
+
+    /// Send a command line to tmux. Replies arrive in the order commands are
+    /// sent. The line is queued for the writer thread, so the PTY reader
+    /// thread never blocks on a PTY write while tmux is blocked on its output.
+    fn command(&self, command: &str, pending: Pending) -> io::Result<()> {
+        let mut state = self.lock()?;
+        let ended = || io::Error::new(io::ErrorKind::BrokenPipe, "tmux control mode has ended");
+        state
+            .commands
+            .as_ref()
+            .ok_or_else(ended)?
+            .send(format!("{}\n", command))
+            .map_err(|_| ended())?;
+        state.pending.push_back(pending);
+        Ok(())
+    }
+
+    fn send_keys(&self, pane: u32, bytes: &[u8]) -> io::Result<()> {
+        for chunk in bytes.chunks(SEND_KEYS_CHUNK) {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            self.command(
+                &format!("send-keys -t %{} -H {}", pane, hex.join(" ")),
+                Pending::Ignore,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// tmux sizes windows from the client size, so a pane resize becomes a
+    /// client size that fits every pane of its window at its requested size
+    fn resize_pane(&self, pane: u32, cols: u16, rows: u16) -> io::Result<()> {
+        let (cols, rows) = {
+            let mut state = self.lock()?;
+            state.pane_sizes.insert(pane, (cols, rows));
+            let Some(window) = state
+                .panes
+                .get(&pane)
+                .and_then(|link| state.windows.get(&link.window))
+            else {
+                return Ok(());
+            };
+            window
+                .layout
+                .fit_size(&|pane| state.pane_sizes.get(&pane).copied())
+        };
+        self.command(
+            &format!("refresh-client -C {},{}", cols, rows),
+            Pending::Ignore,
+        )
+    }
+
+    fn kill_pane(&self, pane: u32) -> io::Result<()> {
+        let result = self.command(&format!("kill-pane -t %{}", pane), Pending::Ignore);
+        self.lock()?.end_pane(pane);
+        match result {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            other => other,
+        }
+    }
+
+    pub fn windows(&self) -> Vec<TmuxWindow> {
+        let Ok(state) = self.lock() else {
+            return Vec::new();
+        };
+        state
+            .windows
+            .keys()
+            .filter_map(|window| state.window_payload(self.id, *window))
+            .collect()
+    }
+
+    fn list_windows(&self) {
+        if let Err(e) = self.command(LIST_WINDOWS, Pending::ListWindows) {
+            eprintln!("[tmux {}] Failed to list windows: {}", self.id, e);
+        }
+    }
+
+    /// Stop talking to tmux and end every pane
+    fn close(&self) {
+        if let Ok(mut state) = self.lock() {
+            state.commands = None;
+            state.pending.clear();
+            let panes: Vec<u32> = state.panes.keys().copied().collect();
+            for pane in panes {
+                state.end_pane(pane);
+            }
+            state.windows.clear();
+        }
+    }
+
+    fn handle(self: &Arc<Self>, window: &tauri::Window, event: ControlEvent) {
+        match event {
+            ControlEvent::CommandResult { success, lines } => {
+                let pending = self.lock().ok().and_then(|mut s| s.pending.pop_front());
+                match pending {
+                    Some(Pending::ListWindows) if success => self.apply_window_list(window, &lines),
+                    Some(Pending::Capture(pane)) => self.finish_capture(pane, success, &lines),
+                    _ if !success => {
+                        eprintln!("[tmux {}] Command failed: {}", self.id, lines.join(" "))
+                    }
+                    _ => {}
+                }
+            }
+            ControlEvent::Output { pane, data } => {
+                if let Ok(mut state) = self.lock() {
+                    if let Some(link) = state.panes.get_mut(&pane) {
+                        match link.held.as_mut() {
+                            Some(held) => held.extend_from_slice(&data),
+                            None => {
+                                let _ = link.output.send(data);
+                            }
+                        }
+                    }
+                }
+            }
+            ControlEvent::WindowAdd { .. } | ControlEvent::SessionChanged => self.list_windows(),
+            ControlEvent::WindowClose { window: window_id } => {
+                self.close_window(window, window_id);
+            }
+            ControlEvent::WindowRenamed {
+                window: window_id,
+                name,
+            } => {
+                let payload = self.lock().ok().and_then(|mut state| {
+                    state.windows.get_mut(&window_id)?.name = name;
+                    state.window_payload(self.id, window_id)
+                });
+                if let Some(payload) = payload {
+                    let _ = window.emit(&format!("tmux-window-changed:{}", self.id), payload);
+                }
+            }
+            ControlEvent::LayoutChange {
+                window: window_id,
+                layout,
+            } => match LayoutCell::parse(&layout) {
+                Some(layout) => self.apply_layout(window, window_id, None, layout),
+                None => eprintln!("[tmux {}] Unrecognized layout: {}", self.id, layout),
+            },
+            ControlEvent::Started | ControlEvent::Exited { .. } => {}
+        }
+    }
+
+    /// Reply to `LIST_WINDOWS`: `@id layout name` per line
+    fn apply_window_list(self: &Arc<Self>, window: &tauri::Window, lines: &[String]) {
+        let mut listed = Vec::new();
+        for line in lines {
+            let mut parts = line.splitn(3, ' ');
+            let (Some(window_id), Some(layout)) = (
+                parts
+                    .next()
+                    .and_then(|id| id.strip_prefix('@')?.parse::<u32>().ok()),
+                parts.next().and_then(LayoutCell::parse),
+            ) else {
+                continue;
+            };
+            let name = parts.next().unwrap_or_default().to_string();
+            listed.push(window_id);
+            self.apply_layout(window, window_id, Some(name), layout);
+        }
+
+        // Windows of a session we are no longer attached to
+        let stale: Vec<u32> = self
+            .lock()
+            .map(|state| {
+                state
+                    .windows
+                    .keys()
+                    .filter(|id| !listed.contains(id))
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default();
+        for window_id in stale {
+            self.close_window(window, window_id);
+        }
+    }
+
+    fn apply_layout(
+        self: &Arc<Self>,
+        window: &tauri::Window,
+        window_id: u32,
+        name: Option<String>,
+        layout: LayoutCell,
+    ) {
+        let panes = layout.panes();
+        let new_panes: Vec<u32> = {
+            let Ok(mut state) = self.lock() else {
+                return;
+            };
+            // Panes that left this window (closed, or moved by join-pane/break-pane)
+            let gone: Vec<u32> = state
+                .panes
+                .iter()
+                .filter(|(pane, link)| link.window == window_id && !panes.contains(pane))
+                .map(|(pane, _)| *pane)
+                .collect();
+            for pane in gone {
+                state.end_pane(pane);
+            }
+            for pane in &panes {
+                if let Some(link) = state.panes.get_mut(pane) {
+                    link.window = window_id;
+                }
+            }
+            panes
+                .iter()
+                .filter(|pane| !state.panes.contains_key(pane))
+                .copied()
+                .collect()
+        };
+
+        // Registering takes the PTY lock, so it happens without the tmux lock held
+        let mut links = Vec::new();
+        for pane in new_panes {
+            let size = layout
+                .find_pane(pane)
+                .map(|cell| (cell.cols, cell.rows))
+                .unwrap_or((80, 24));
+            match self.create_pane(window, window_id, pane, size) {
+                Ok(link) => links.push((pane, link)),
+                Err(e) => eprintln!("[tmux {}] Failed to open pane %{}: {}", self.id, pane, e),
+            }
+        }
+
+        let payload = {
+            let Ok(mut state) = self.lock() else {
+                return;
+            };
+            let created: Vec<u32> = links.iter().map(|(pane, _)| *pane).collect();
+            state.panes.extend(links);
+            let name = name
+                .or_else(|| state.windows.get(&window_id).map(|w| w.name.clone()))
+                .unwrap_or_default();
+            state
+                .windows
+                .insert(window_id, WindowState { name, layout });
+            drop(state);
+
+            // Show what is already on screen before live output
+            for pane in created {
+                if let Err(e) = self.command(
+                    &format!("capture-pane -p -e -t %{}", pane),
+                    Pending::Capture(pane),
+                ) {
+                    eprintln!("[tmux {}] Failed to capture pane %{}: {}", self.id, pane, e);
+                    self.finish_capture(pane, false, &[]);
+                }
+            }
+            self.lock()
+                .ok()
+                .and_then(|state| state.window_payload(self.id, window_id))
+        };
+        if let Some(payload) = payload {
+            let _ = window.emit(&format!("tmux-window-changed:{}", self.id), payload);
+        }
+    }
+
+    /// Register a virtual PTY for a pane
+    fn create_pane(
+        self: &Arc<Self>,
+        window: &tauri::Window,
+        window_id: u32,
+        pane: u32,
+        (cols, rows): (u16, u16),
+    ) -> Result<PaneLink, String> {
+        let state = window.state::<AppState>();
+        let id = next_pty_id(&state)?;
+        let (output, rx) = mpsc::channel();
+        let end = Arc::new(PaneEnd::default());
+
+        register_pty(
+            window.clone(),
+            &state,
+            id,
+            Box::new(PaneMaster {
+                gateway: self.clone(),
+                pane,
+                size: Mutex::new(PtySize {
+                    rows,
+                    cols,
+                    pixel_width: 0,
+                    pixel_height: 0,
+                }),
+            }),
+            Box::new(PaneChild {
+                gateway: self.clone(),
+                pane,
+                end: end.clone(),
+            }),
+            Box::new(PaneReader {
+                rx,
+                chunk: Vec::new(),
+                pos: 0,
+            }),
+            None,
+            Vec::new(),
+            false,
+        )?;
+        if let Some(session) = state
+            .ptys
+            .lock()
+            .map_err(|e| format!("Failed to acquire PTY lock: {}", e))?
+            .get_mut(&id)
+        {
+            session.tmux_pane = Some(TmuxPaneRef {
+                gateway_id: self.id,
+                pane_id: pane,
+            });
+        }
+
+        Ok(PaneLink {
+            pty_id: id,
+            window: window_id,
+            output,
+            end,
+            held: Some(Vec::new()),
+        })
+    }
+
+    /// Write the captured screen, then the output that arrived meanwhile
+    fn finish_capture(&self, pane: u32, success: bool, lines: &[String]) {
+        let Ok(mut state) = self.lock() else {
+            return;
+        };
+        let Some(link) = state.panes.get_mut(&pane) else {
+            return;
+        };
+        let mut screen = Vec::new();
+        if success {
+            // Trailing blank lines would leave the cursor below the prompt
+            let used = lines
+                .iter()
+                .rposition(|line| !line.trim().is_empty())
+                .map_or(0, |last| last + 1);
+            screen = lines[..used].join("\r\n").into_bytes();
+        }
+        screen.extend(link.held.take().unwrap_or_default());
+        if !screen.is_empty() {
+            let _ = link.output.send(screen);
+        }
+    }
+
+    fn close_window(&self, window: &tauri::Window, window_id: u32) {
+        if let Ok(mut state) = self.lock() {
+            let panes: Vec<u32> = state
+                .panes
+                .iter()
+                .filter(|(_, link)| link.window == window_id)
+                .map(|(pane, _)| *pane)
+                .collect();
+            for pane in panes {
+                state.end_pane(pane);
+            }
+            state.windows.remove(&window_id);
+        }
+        let _ = window.emit(&format!("tmux-window-closed:{}", self.id), window_id);
+    }
+}
+
+/// Write queued command lines to the gateway PTY until control mode ends
+fn write_commands(id: u32, mut writer: SharedWriter, commands: mpsc::Receiver<String>) {
+    for command in commands {
+        if let Err(e) = writer
+            .write_all(command.as_bytes())
+            .and_then(|_| writer.flush())
+        {
+            eprintln!("[tmux {}] Failed to send command: {}", id, e);
+            return;
+        }
+    }
+}
+
+/// tmux control-mode handling for one PTY's reader thread
+pub struct TmuxClient {
+    id: u32,
+    writer: SharedWriter,
+    parser: ControlParser,
+    gateway: Option<Arc<TmuxGateway>>,
+    /// Set once the reply to the command that started control mode is out of the way
+    synced: bool,
+}
+
+impl TmuxClient {
+    pub fn new(id: u32, writer: SharedWriter) -> Self {
+        Self {
+            id,
+            writer,
+            parser: ControlParser::new(),
+            gateway: None,
+            synced: false,
+        }
+    }
+
+    /// Handle control-mode output. Returns the bytes meant for the terminal
+    /// (everything outside control mode).
+    pub fn feed<'a>(&mut self, window: &tauri::Window, data: &'a [u8]) -> Cow<'a, [u8]> {
+        let (passthrough, events) = self.parser.feed(data);
+        for event in events {
+            self.handle(window, event);
+        }
+        passthrough
+    }
+
+    /// The PTY closed; end control mode if it was active
+    pub fn finish(&mut self, window: &tauri::Window) {
+        let Some(gateway) = self.gateway.take() else {
+            return;
+        };
+        gateway.close();
+        if let Ok(mut gateways) = window.state::<AppState>().tmux_gateways.lock() {
+            gateways.remove(&self.id);
+        }
+        let _ = window.emit(&format!("tmux-control-mode:{}", self.id), false);
+    }
+
+    fn handle(&mut self, window: &tauri::Window, event: ControlEvent) {
+        match event {
+            ControlEvent::Started => {
+                self.finish(window);
+                let gateway = Arc::new(TmuxGateway::new(self.id, self.writer.clone()));
+                if let Ok(mut gateways) = window.state::<AppState>().tmux_gateways.lock() {
+                    gateways.insert(self.id, gateway.clone());
+                }
+                self.gateway = Some(gateway);
+                self.synced = false;
+                let _ = window.emit(&format!("tmux-control-mode:{}", self.id), true);
+            }
+            ControlEvent::Exited { .. } => self.finish(window),
+            event => {
+                let Some(gateway) = self.gateway.clone() else {
+                    return;
+                };
+                // tmux may answer the command that started it (`tmux -CC attach`)
+                // before anything else. Our own commands wait until that reply
+                // has gone by, so replies and requests line up.
+                if !self.synced {
+                    self.synced = true;
+                    gateway.list_windows();
+                    if matches!(event, ControlEvent::CommandResult { .. }) {
+                        return;
+                    }
+                }
+                gateway.handle(window, event);
+            }
+        }
+    }
+}
+
+/// Tauri command: windows of the tmux session behind a gateway PTY (or behind
+/// one of its panes)
+#[tauri::command]
+pub fn get_tmux_windows(id: u32, state: State<AppState>) -> Result<Vec<TmuxWindow>, String> {
+    let gateway_id = {
+        let ptys = state
+            .ptys
+            .lock()
+            .map_err(|e| format!("Failed to acquire PTY lock: {}", e))?;
+        let session = ptys
+            .get(&id)
+            .ok_or_else(|| format!("PTY {} not found", id))?;
+        session.tmux_pane.map_or(id, |pane| pane.gateway_id)
+    };
+    let gateways = state
+        .tmux_gateways
+        .lock()
+        .map_err(|e| format!("Failed to acquire tmux lock: {}", e))?;
+    Ok(gateways
+        .get(&gateway_id)
+        .map(|gateway| gateway.windows())
+        .unwrap_or_default())
+}
@@ -0,0 +1,203 @@
+//! `MasterPty`/`Child` implementations for tmux panes, so the rest of the PTY
+//! module treats each pane like any other terminal.
+
+use super::TmuxGateway;
+use portable_pty::{Child, ChildKiller, ExitStatus, MasterPty, PtySize};
+use std::io::{self, Read, Write};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+
+/// Set once the pane is gone (closed in tmux, killed, or control mode ended)
+#[derive(Debug, Default)]
+pub struct PaneEnd {
+    ended: Mutex<bool>,
+    changed: Condvar,
+}
+
+impl PaneEnd {
+    pub fn set(&self) {
+        if let Ok(mut ended) = self.ended.lock() {
+            *ended = true;
+            self.changed.notify_all();
+        }
+    }
+
+    fn is_set(&self) -> bool {
+        self.ended.lock().map(|ended| *ended).unwrap_or(true)
+    }
+
+    fn wait(&self) {
+        let Ok(mut ended) = self.ended.lock() else {
+            return;
+        };
+        while !*ended {
+            match self.changed.wait(ended) {
+                Ok(next) => ended = next,
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+/// Pane output forwarded by the gateway; EOF once the pane's sender is dropped
+pub struct PaneReader {
+    pub rx: mpsc::Receiver<Vec<u8>>,
+    pub chunk: Vec<u8>,
+    pub pos: usize,
+}
+
+impl Read for PaneReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.chunk.len() {
+            match self.rx.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Input typed into the pane, sent as `send-keys`
+struct PaneWriter {
+    gateway: Arc<TmuxGateway>,
+    pane: u32,
+}
+
+impl Write for PaneWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.gateway.send_keys(self.pane, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub struct PaneMaster {
+    pub gateway: Arc<TmuxGateway>,
+    pub pane: u32,
+    pub size: Mutex<PtySize>,
+}
+
+impl MasterPty for PaneMaster {
+    fn resize(&self, size: PtySize) -> Result<(), anyhow::Error> {
+        if let Ok(mut current) = self.size.lock() {
+            *current = size;
+        }
+        self.gateway.resize_pane(self.pane, size.cols, size.rows)?;
+        Ok(())
+    }
+
+    fn get_size(&self) -> Result<PtySize, anyhow::Error> {
+        Ok(*self
+            .size
+            .lock()
+            .map_err(|e| io::Error::other(e.to_string()))?)
+    }
+
+    /// Output arrives through the gateway; a pane has exactly one reader
+    fn try_clone_reader(&self) -> Result<Box<dyn Read + Send>, anyhow::Error> {
+        Err(io::Error::other("tmux panes have a single reader").into())
+    }
+
+    fn take_writer(&self) -> Result<Box<dyn Write + Send>, anyhow::Error> {
+        Ok(Box::new(PaneWriter {
+            gateway: self.gateway.clone(),
+            pane: self.pane,
+        }))
+    }
+
+    /// The pane's processes run wherever tmux runs, possibly another host
+    #[cfg(unix)]
+    fn process_group_leader(&self) -> Option<i32> {
+        None
+    }
+
+    #[cfg(unix)]
+    fn as_raw_fd(&self) -> Option<std::os::unix::io::RawFd> {
+        None
+    }
+
+    #[cfg(unix)]
+    fn tty_name(&self) -> Option<std::path::PathBuf> {
+        None
+    }
+}
+
+pub struct PaneChild {
+    pub gateway: Arc<TmuxGateway>,
+    pub pane: u32,
+    pub end: Arc<PaneEnd>,
+}
+
+impl std::fmt::Debug for PaneChild {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PaneChild")
+            .field("pane", &self.pane)
+            .finish()
+    }
+}
+
+impl Child for PaneChild {
+    fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        Ok(self.end.is_set().then(|| ExitStatus::with_exit_code(0)))
+    }
+
+    fn wait(&mut self) -> io::Result<ExitStatus> {
+        self.end.wait();
+        Ok(ExitStatus::with_exit_code(0))
+    }
+
+    fn process_id(&self) -> Option<u32> {
+        None
+    }
+}
+
+impl ChildKiller for PaneChild {
+    fn kill(&mut self) -> io::Result<()> {
+        if self.end.is_set() {
+            return Ok(());
+        }
+        self.gateway.kill_pane(self.pane)
+    }
+
+    fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+        Box::new(PaneKiller {
+            gateway: self.gateway.clone(),
+            pane: self.pane,
+        })
+    }
+}
+
+struct PaneKiller {
+    gateway: Arc<TmuxGateway>,
+    pane: u32,
+}
+
+impl std::fmt::Debug for PaneKiller {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PaneKiller")
+            .field("pane", &self.pane)
+            .finish()
+    }
+}
+
+impl ChildKiller for PaneKiller {
+    fn kill(&mut self) -> io::Result<()> {
+        self.gateway.kill_pane(self.pane)
+    }
+
+    fn clone_killer(&self) -> Box<dyn ChildKiller + Send + Sync> {
+        Box::new(PaneKiller {
+            gateway: self.gateway.clone(),
+            pane: self.pane,
+        })
+    }
+}
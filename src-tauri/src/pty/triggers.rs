@@ -0,0 +1,408 @@
+//! Regex triggers on terminal output.
+//!
+//! Rules live in `~/.config/aiterminal/triggers.json`. Enabled rules are
+//! compiled once into a `RegexSet` and matched by the reader thread against
+//! complete output lines, as assembled by the scrollback buffer (escape
+//! sequences stripped, `\r` overwrites applied), never against raw reads.
+
+use crate::models::{AppState, MAX_TRIGGER_HITS_PER_READ, TRIGGER_DEFAULT_COOLDOWN_MS};
+use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::State;
+
+const TRIGGERS_FILE: &str = ".config/aiterminal/triggers.json";
+/// Compiled size limit for all trigger patterns together
+const TRIGGER_REGEX_SIZE_LIMIT: usize = 16 * 1024 * 1024;
+
+/// What happens when a rule matches. Every firing is also emitted as a
+/// `pty-trigger:{id}` event so the frontend can show or act on it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TriggerAction {
+    /// Raise an alert with the matched line
+    Notify,
+    /// Highlight the match in the terminal
+    Highlight {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        color: Option<String>,
+    },
+    /// Type `input` into the terminal (include `\r` to press Enter)
+    Respond { input: String },
+    /// Hand the line and recent output to the AI for analysis
+    Analyze {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        prompt: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TriggerRule {
+    pub id: String,
+    pub name: String,
+    /// Regex matched against each output line
+    pub pattern: String,
+    #[serde(default)]
+    pub case_insensitive: bool,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Only fire in SSH sessions to a matching host (`*` wildcards allowed)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Only fire in these PTYs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pty_ids: Option<Vec<u32>>,
+    pub action: TriggerAction,
+    /// Minimum time between firings on one PTY (default 1s)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cooldown_ms: Option<u64>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// A rule firing, emitted as `pty-trigger:{id}` after the output it matched
+#[derive(Debug, Clone, Serialize)]
+pub struct TriggerHit {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub action: TriggerAction,
+    pub line: String,
+    /// The part of `line` the pattern matched
+    pub matched: String,
+    /// Absolute scrollback line number (see `read_pty_scrollback`)
+    pub line_number: u64,
+}
+
+struct CompiledRule {
+    rule: TriggerRule,
+    regex: Regex,
+    host: Option<Regex>,
+    cooldown: Duration,
+}
+
+/// Enabled rules compiled for matching
+pub struct TriggerSet {
+    rules: Vec<CompiledRule>,
+    set: RegexSet,
+}
+
+impl TriggerSet {
+    pub fn empty() -> Self {
+        Self {
+            rules: Vec::new(),
+            set: RegexSet::empty(),
+        }
+    }
+
+    pub fn compile(rules: &[TriggerRule]) -> Result<Self, String> {
+        let enabled: Vec<&TriggerRule> = rules.iter().filter(|rule| rule.enabled).collect();
+
+        // Compile one by one first so an error names the rule
+        let rules = enabled
+            .iter()
+            .map(|rule| {
+                let regex = RegexBuilder::new(&rule.pattern)
+                    .case_insensitive(rule.case_insensitive)
+                    .build()
+                    .map_err(|e| format!("Invalid pattern in trigger '{}': {}", rule.name, e))?;
+                let host = rule.host.as_deref().map(host_pattern);
+                Ok(CompiledRule {
+                    rule: (*rule).clone(),
+                    regex,
+                    host,
+                    cooldown: Duration::from_millis(
+                        rule.cooldown_ms.unwrap_or(TRIGGER_DEFAULT_COOLDOWN_MS),
+                    ),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let set = RegexSetBuilder::new(enabled.iter().map(|rule| set_pattern(rule)))
+            .size_limit(TRIGGER_REGEX_SIZE_LIMIT)
+            .build()
+            .map_err(|e| format!("Failed to compile triggers: {}", e))?;
+
+        Ok(Self { rules, set })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Whether any rule is restricted to a host, so the caller needs to look it up
+    pub fn needs_host(&self) -> bool {
+        self.rules.iter().any(|compiled| compiled.host.is_some())
+    }
+}
+
+/// Per-PTY matcher owned by the reader thread
+pub struct TriggerMatcher {
+    id: u32,
+    /// Rule ID -> last firing on this PTY
+    last_fired: HashMap<String, Instant>,
+}
+
+impl TriggerMatcher {
+    pub fn new(id: u32) -> Self {
+        Self {
+            id,
+            last_fired: HashMap::new(),
+        }
+    }
+
+    /// Match complete lines, numbered from `first_line_number`. `host` is the
+    /// SSH host of the PTY, if any.
+    pub fn scan<'a>(
+        &mut self,
+        triggers: &TriggerSet,
+        host: Option<&str>,
+        first_line_number: u64,
+        lines: impl IntoIterator<Item = &'a String>,
+        now: Instant,
+    ) -> Vec<TriggerHit> {
+        let mut hits = Vec::new();
+        for (offset, line) in lines.into_iter().enumerate() {
+            for index in triggers.set.matches(line).iter() {
+                let compiled = &triggers.rules[index];
+                if !self.applies(compiled, host) {
+                    continue;
+                }
+                if self
+                    .last_fired
+                    .get(&compiled.rule.id)
+                    .is_some_and(|at| now.duration_since(*at) < compiled.cooldown)
+                {
+                    continue;
+                }
+                let Some(found) = compiled.regex.find(line) else {
+                    continue;
+                };
+                self.last_fired.insert(compiled.rule.id.clone(), now);
+                hits.push(TriggerHit {
+                    rule_id: compiled.rule.id.clone(),
+                    rule_name: compiled.rule.name.clone(),
+                    action: compiled.rule.action.clone(),
+                    line: line.clone(),
+                    matched: found.as_str().to_string(),
+                    line_number: first_line_number + offset as u64,
+                });
+                if hits.len() >= MAX_TRIGGER_HITS_PER_READ {
+                    return hits;
+                }
+            }
+        }
+        hits
+    }
+
+    fn applies(&self, compiled: &CompiledRule, host: Option<&str>) -> bool {
+        if let Some(ids) = &compiled.rule.pty_ids {
+            if !ids.contains(&self.id) {
+                return false;
+            }
+        }
+        match (&compiled.host, host) {
+            (None, _) => true,
+            (Some(pattern), Some(host)) => pattern.is_match(host),
+            (Some(_), None) => false,
+        }
+    }
+}
+
+fn set_pattern(rule: &TriggerRule) -> String {
+    if rule.case_insensitive {
+        format!("(?i:{})", rule.pattern)
+    } else {
+        rule.pattern.clone()
+    }
+}
+
+/// `*.cluster.edu` style host glob, matched case-insensitively
+fn host_pattern(glob: &str) -> Regex {
+    let pattern = glob
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(".*");
+    RegexBuilder::new(&format!("^{}$", pattern))
+        .case_insensitive(true)
+        .build()
+        .expect("escaped host glob is a valid regex")
+}
+
+fn get_triggers_path() -> Result<PathBuf, String> {
+    let home =
+        std::env::var("HOME").map_err(|_| "Could not determine HOME directory".to_string())?;
+    Ok(PathBuf::from(home).join(TRIGGERS_FILE))
+}
+
+fn read_triggers() -> Result<Vec<TriggerRule>, String> {
+    let path = get_triggers_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read triggers: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse triggers: {}", e))
+}
+
+fn install_triggers(rules: &[TriggerRule], state: &AppState) -> Result<(), String> {
+    let compiled = TriggerSet::compile(rules)?;
+    *state
+        .triggers
+        .lock()
+        .map_err(|e| format!("Failed to acquire trigger lock: {}", e))? = Arc::new(compiled);
+    Ok(())
+}
+
+/// Compile the saved rules at startup. Errors are logged, not fatal.
+pub fn init_triggers(state: &AppState) {
+    if let Err(e) = read_triggers().and_then(|rules| install_triggers(&rules, state)) {
+        eprintln!("[Triggers] {}", e);
+    }
+}
+
+/// Current rules for the reader thread; cheap to call per read
+pub fn current_triggers(triggers: &Mutex<Arc<TriggerSet>>) -> Arc<TriggerSet> {
+    triggers
+        .lock()
+        .map(|set| set.clone())
+        .unwrap_or_else(|_| Arc::new(TriggerSet::empty()))
+}
+
+/// Tauri command: Load trigger rules
+#[tauri::command]
+pub async fn load_triggers() -> Result<Vec<TriggerRule>, String> {
+    read_triggers()
+}
+
+/// Tauri command: Save trigger rules and start matching with them.
+/// Nothing is written if a pattern does not compile.
+#[tauri::command]
+pub fn save_triggers(rules: Vec<TriggerRule>, state: State<AppState>) -> Result<(), String> {
+    let compiled = TriggerSet::compile(&rules)?;
+    let path = get_triggers_path()?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+
+    let json = serde_json::to_string_pretty(&rules)
+        .map_err(|e| format!("Failed to serialize triggers: {}", e))?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write triggers: {}", e))?;
+
+    *state
+        .triggers
+        .lock()
+        .map_err(|e| format!("Failed to acquire trigger lock: {}", e))? = Arc::new(compiled);
+    Ok(())
+}
+
+/// Tauri command: Re-read triggers.json after it was edited by hand
+#[tauri::command]
+pub fn reload_triggers(state: State<AppState>) -> Result<Vec<TriggerRule>, String> {
+    let rules = read_triggers()?;
+    install_triggers(&rules, &state)?;
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(id: &str, pattern: &str, action: TriggerAction) -> TriggerRule {
+        TriggerRule {
+            id: id.to_string(),
+            name: id.to_string(),
+            pattern: pattern.to_string(),
+            case_insensitive: false,
+            enabled: true,
+            host: None,
+            pty_ids: None,
+            action,
+            cooldown_ms: None,
+        }
+    }
+
+    #[test]
+    fn test_rules_match_lines_with_filters_and_cooldown() {
+        let json = r#"[
+            {"id": "oom", "name": "Slurm OOM", "pattern": "slurmstepd: error: .* OUT OF MEMORY",
+             "host": "*.cluster.edu", "action": {"type": "notify"}},
+            {"id": "sudo", "name": "sudo", "pattern": "password for", "caseInsensitive": true,
+             "ptyIds": [2], "action": {"type": "respond", "input": "\r"}},
+            {"id": "off", "name": "off", "pattern": "(", "enabled": false, "action": {"type": "notify"}}
+        ]"#;
+        let rules: Vec<TriggerRule> = serde_json::from_str(json).unwrap();
+        let triggers = TriggerSet::compile(&rules).unwrap();
+        assert!(triggers.needs_host());
+
+        let lines: Vec<String> = vec![
+            "srun: job 42 queued".to_string(),
+            "slurmstepd: error: Detected 1 oom_kill event; task 0 OUT OF MEMORY".to_string(),
+            "[sudo] Password for me:".to_string(),
+        ];
+        let now = Instant::now();
+
+        let mut matcher = TriggerMatcher::new(1);
+        let hits = matcher.scan(&triggers, Some("Login1.Cluster.edu"), 100, &lines, now);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].rule_id, "oom");
+        assert_eq!(hits[0].line, lines[1]);
+        assert_eq!(hits[0].line_number, 101);
+        assert!(hits[0].matched.starts_with("slurmstepd: error:"));
+
+        // Wrong host, and PTY 1 is not in the sudo rule's list
+        assert!(matcher
+            .scan(
+                &triggers,
+                Some("laptop"),
+                0,
+                &lines,
+                now + Duration::from_secs(5)
+            )
+            .is_empty());
+
+        let mut matcher = TriggerMatcher::new(2);
+        let hits = matcher.scan(&triggers, None, 0, &lines, now);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(
+            hits[0].action,
+            TriggerAction::Respond {
+                input: "\r".to_string()
+            }
+        );
+        assert!(matcher.scan(&triggers, None, 3, &lines, now).is_empty());
+        assert_eq!(
+            matcher
+                .scan(&triggers, None, 3, &lines, now + Duration::from_secs(2))
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern_names_the_rule() {
+        let rules = vec![
+            rule("ok", "done", TriggerAction::Notify),
+            rule("bad", "([a-z", TriggerAction::Notify),
+        ];
+        let err = TriggerSet::compile(&rules).err().unwrap();
+        assert!(err.contains("trigger 'bad'"), "{}", err);
+
+        let mut disabled = rules[1].clone();
+        disabled.enabled = false;
+        let triggers = TriggerSet::compile(&[rules[0].clone(), disabled]).unwrap();
+        assert!(!triggers.is_empty());
+        assert!(!triggers.needs_host());
+        assert!(TriggerSet::compile(&[]).unwrap().is_empty());
+    }
+}
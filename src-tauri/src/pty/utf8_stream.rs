@@ -0,0 +1,98 @@
+/// Incremental UTF-8 decoder for PTY output.
+///
+/// `String::from_utf8_lossy` on each read turns a multibyte character split
+/// across two reads into two U+FFFD. This decoder holds back an incomplete
+/// trailing sequence and prepends it to the next chunk; only bytes that are
+/// invalid regardless of what follows are replaced.
+#[derive(Debug, Default)]
+pub struct Utf8StreamDecoder {
+    /// Incomplete sequence from the end of the previous chunk (at most 3 bytes)
+    pending: Vec<u8>,
+}
+
+impl Utf8StreamDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode a chunk, carrying any incomplete trailing sequence to the next call
+    pub fn decode(&mut self, chunk: &[u8]) -> String {
+        let joined;
+        let mut input: &[u8] = if self.pending.is_empty() {
+            chunk
+        } else {
+            let mut buf = std::mem::take(&mut self.pending);
+            buf.extend_from_slice(chunk);
+            joined = buf;
+            &joined
+        };
+
+        let mut out = String::with_capacity(input.len());
+        loop {
+            match std::str::from_utf8(input) {
+                Ok(valid) => {
+                    out.push_str(valid);
+                    break;
+                }
+                Err(e) => {
+                    let (valid, rest) = input.split_at(e.valid_up_to());
+                    // `valid_up_to` guarantees this prefix is UTF-8
+                    out.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            out.push(char::REPLACEMENT_CHARACTER);
+                            input = &rest[len..];
+                        }
+                        None => {
+                            // Truncated sequence at the end: wait for more bytes
+                            self.pending.extend_from_slice(rest);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Flush at end of stream; a dangling partial sequence becomes U+FFFD
+    pub fn finish(&mut self) -> String {
+        if self.pending.is_empty() {
+            return String::new();
+        }
+        self.pending.clear();
+        char::REPLACEMENT_CHARACTER.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multibyte_split_across_reads() {
+        let text = "héllo → 世界 🦀";
+        let bytes = text.as_bytes();
+        for split in 0..=bytes.len() {
+            let mut decoder = Utf8StreamDecoder::new();
+            let mut out = decoder.decode(&bytes[..split]);
+            out.push_str(&decoder.decode(&bytes[split..]));
+            out.push_str(&decoder.finish());
+            assert_eq!(out, text, "split at {}", split);
+        }
+
+        // One byte at a time
+        let mut decoder = Utf8StreamDecoder::new();
+        let out: String = bytes.iter().map(|b| decoder.decode(&[*b])).collect();
+        assert_eq!(out, text);
+    }
+
+    #[test]
+    fn test_invalid_bytes_are_replaced_once() {
+        let mut decoder = Utf8StreamDecoder::new();
+        assert_eq!(decoder.decode(b"a\xffb\xc3"), "a\u{fffd}b");
+        assert_eq!(decoder.decode(b"(x"), "\u{fffd}(x");
+        assert_eq!(decoder.decode(b"\xe4\xb8"), "");
+        assert_eq!(decoder.finish(), "\u{fffd}");
+    }
+}
@@ -186,8 +186,12 @@ pub fn is_sensitive_path(path: &Path) -> bool {
         Err(_) => return false, // Not under home, other checks handle this
     };
 
-    let relative_str = relative.to_string_lossy();
+    is_sensitive_relative(&relative.to_string_lossy())
+}
 
+/// Check a path given relative to a home directory against the sensitive
+/// lists. Shared with hosts whose home is not this machine's `HOME`.
+pub fn is_sensitive_relative(relative_str: &str) -> bool {
     // Check exact sensitive paths
     for sensitive in SENSITIVE_PATHS {
         if relative_str == *sensitive {
@@ -0,0 +1,580 @@
+//! OpenSSH client config parser.
+//!
+//! Follows `ssh_config(5)`: `Host` blocks with several patterns and `!`
+//! negation, `Match` blocks, `Include` with globs (relative to `~/.ssh`),
+//! `key value` and `key=value` syntax with quoting, and first-obtained-value
+//! wins. `resolve` evaluates the files for one alias the way `ssh -G` does, so
+//! wildcard blocks such as `Host *` are inherited.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Nesting limit for `Include`, as in OpenSSH
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Keywords whose values accumulate instead of the first one winning
+const MULTI_VALUE_KEYWORDS: &[&str] = &[
+    "identityfile",
+    "certificatefile",
+    "localforward",
+    "remoteforward",
+    "dynamicforward",
+    "sendenv",
+    "setenv",
+];
+
+/// Effective configuration for a host, like the output of `ssh -G`
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedSshHost {
+    pub alias: String,
+    pub hostname: String,
+    pub user: String,
+    pub port: u16,
+    /// `~` expanded; empty when the config names none (ssh then tries its defaults)
+    pub identity_files: Vec<String>,
+    pub proxy_jump: Option<String>,
+    /// Every option set for the host, keyword lowercased. Repeatable options
+    /// keep all their values in order.
+    pub options: BTreeMap<String, Vec<String>>,
+    /// Problems that did not stop evaluation, such as `Match` criteria this
+    /// parser cannot evaluate (those blocks are treated as not matching)
+    pub warnings: Vec<String>,
+}
+
+/// Where the config is read from; tests point this at fixtures
+pub struct ConfigSource {
+    pub path: PathBuf,
+    pub home: PathBuf,
+    pub local_user: String,
+}
+
+impl ConfigSource {
+    /// `~/.ssh/config` of the current user
+    pub fn user() -> Result<Self, String> {
+        let home =
+            std::env::var("HOME").map_err(|_| "Could not determine HOME directory".to_string())?;
+        let local_user = std::env::var("USER")
+            .or_else(|_| std::env::var("LOGNAME"))
+            .unwrap_or_else(|_| "unknown".to_string());
+        Ok(Self {
+            path: PathBuf::from(&home).join(".ssh/config"),
+            home: PathBuf::from(home),
+            local_user,
+        })
+    }
+
+    fn ssh_dir(&self) -> PathBuf {
+        self.home.join(".ssh")
+    }
+}
+
+/// Options collected so far; the first value of a keyword wins
+type Options = BTreeMap<String, Vec<String>>;
+
+struct Evaluator<'a> {
+    source: &'a ConfigSource,
+    alias: &'a str,
+    final_pass: bool,
+    /// A `Match canonical` or `Match final` was seen, so a second pass is needed
+    wants_final: bool,
+    options: Options,
+    warnings: Vec<String>,
+}
+
+impl Evaluator<'_> {
+    fn read_file(&mut self, path: &Path, depth: usize) -> Result<(), String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        // Host and Match lines only affect the rest of the file they are in
+        let mut active = true;
+        for (number, line) in content.lines().enumerate() {
+            let Some((keyword, args)) = split_line(line) else {
+                continue;
+            };
+            let at = || format!("{}:{}", path.display(), number + 1);
+            match keyword.as_str() {
+                "host" => active = host_matches(&args, self.alias),
+                "match" => {
+                    active = self
+                        .match_criteria(&args, &at())
+                        .map_err(|e| format!("{}: {}", at(), e))?
+                }
+                "include" => {
+                    if !active {
+                        continue;
+                    }
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        return Err(format!("{}: Include nested too deeply", at()));
+                    }
+                    for file in include_files(&args, self.source)? {
+                        self.read_file(&file, depth + 1)?;
+                    }
+                }
+                _ if active => self.set(keyword, args),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn set(&mut self, keyword: String, args: Vec<String>) {
+        if args.is_empty() {
+            return;
+        }
+        if MULTI_VALUE_KEYWORDS.contains(&keyword.as_str()) {
+            // A forward's listen and target arguments are one value
+            let args = match keyword.as_str() {
+                "localforward" | "remoteforward" => vec![args.join(" ")],
+                _ => args,
+            };
+            let values = self.options.entry(keyword).or_default();
+            for arg in args {
+                if !values.contains(&arg) {
+                    values.push(arg);
+                }
+            }
+        } else {
+            self.options
+                .entry(keyword)
+                .or_insert_with(|| vec![args.join(" ")]);
+        }
+    }
+
+    /// Evaluate `Match` criteria; all of them must hold. Criteria that cannot
+    /// be evaluated here count as not matched and leave a warning.
+    fn match_criteria(&mut self, args: &[String], at: &str) -> Result<bool, String> {
+        let mut args = args.iter();
+        let mut result = true;
+        while let Some(arg) = args.next() {
+            let (negate, criterion) = match arg.strip_prefix('!') {
+                Some(criterion) => (true, criterion.to_lowercase()),
+                None => (false, arg.to_lowercase()),
+            };
+            let matched = match criterion.as_str() {
+                "all" => true,
+                "canonical" | "final" => {
+                    self.wants_final = true;
+                    self.final_pass
+                }
+                // Would run a command just to read the config
+                "exec" => {
+                    args.next();
+                    false
+                }
+                "localnetwork" => {
+                    args.next();
+                    false
+                }
+                "host" | "originalhost" | "user" | "localuser" | "tagged" => {
+                    let list = args
+                        .next()
+                        .ok_or_else(|| format!("Match {} needs an argument", criterion))?;
+                    let value = match criterion.as_str() {
+                        "host" => self.hostname(),
+                        "originalhost" => self.alias.to_string(),
+                        "user" => self.user(),
+                        "localuser" => self.source.local_user.clone(),
+                        _ => self.first("tag").unwrap_or_default(),
+                    };
+                    let patterns: Vec<&str> = list.split(',').collect();
+                    pattern_list_matches(&patterns, &value)
+                }
+                other => {
+                    // Every other criterion takes one argument
+                    args.next();
+                    let warning = format!(
+                        "{}: Unsupported Match criterion '{}', block ignored",
+                        at, other
+                    );
+                    if !self.warnings.contains(&warning) {
+                        self.warnings.push(warning);
+                    }
+                    false
+                }
+            };
+            result &= matched != negate;
+        }
+        Ok(result)
+    }
+
+    fn first(&self, keyword: &str) -> Option<String> {
+        self.options
+            .get(keyword)
+            .and_then(|values| values.first().cloned())
+    }
+
+    /// HostName with `%h` expanded, or the alias itself
+    fn hostname(&self) -> String {
+        match self.first("hostname") {
+            Some(hostname) => expand_tokens(&hostname, &[('h', self.alias)]),
+            None => self.alias.to_string(),
+        }
+    }
+
+    fn user(&self) -> String {
+        self.first("user")
+            .unwrap_or_else(|| self.source.local_user.clone())
+    }
+}
+
+/// Split a config line into its lowercased keyword and arguments. Handles
+/// `key=value`, double quotes and trailing comments.
+fn split_line(line: &str) -> Option<(String, Vec<String>)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let end = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let keyword = line[..end].to_lowercase();
+    let rest = line[end..].trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest);
+
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quoted = false;
+    for c in rest.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_token = true;
+            }
+            '#' if !quoted && !in_token => break,
+            c if c.is_whitespace() && !quoted => {
+                if in_token {
+                    args.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+    if in_token {
+        args.push(current);
+    }
+    Some((keyword, args))
+}
+
+/// `Host` patterns are whitespace separated; a matching `!pattern` excludes the host
+fn host_matches(patterns: &[String], alias: &str) -> bool {
+    let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+    pattern_list_matches(&patterns, alias)
+}
+
+fn pattern_list_matches(patterns: &[&str], value: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns {
+        match pattern.strip_prefix('!') {
+            Some(negated) if wildcard_match(negated, value) => return false,
+            Some(_) => {}
+            None => matched |= wildcard_match(pattern, value),
+        }
+    }
+    matched
+}
+
+/// `*` and `?` wildcards, ASCII case-insensitive
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and the text position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            p += 1;
+            star = Some((p, t));
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Files named by an `Include` line, each glob sorted as OpenSSH does
+fn include_files(args: &[String], source: &ConfigSource) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for arg in args {
+        let path = match arg.strip_prefix("~/") {
+            Some(rest) => source.home.join(rest),
+            None if Path::new(arg).is_absolute() => PathBuf::from(arg),
+            None => source.ssh_dir().join(arg),
+        };
+        let pattern = path.to_string_lossy();
+        let mut matches: Vec<PathBuf> = glob::glob(&pattern)
+            .map_err(|e| format!("Invalid Include pattern {}: {}", arg, e))?
+            .filter_map(Result::ok)
+            .filter(|path| path.is_file())
+            .collect();
+        matches.sort();
+        files.extend(matches);
+    }
+    Ok(files)
+}
+
+/// Expand `%x` tokens; `%%` is a literal percent sign
+fn expand_tokens(value: &str, tokens: &[(char, &str)]) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => out.push('%'),
+            Some(token) => match tokens.iter().find(|(name, _)| *name == token) {
+                Some((_, replacement)) => out.push_str(replacement),
+                None => {
+                    out.push('%');
+                    out.push(token);
+                }
+            },
+            None => out.push('%'),
+        }
+    }
+    out
+}
+
+/// Options the config sets for `alias`, before defaults are applied, and
+/// any warnings raised while evaluating it
+fn evaluate(source: &ConfigSource, alias: &str) -> Result<(Options, Vec<String>), String> {
+    let mut evaluator = Evaluator {
+        source,
+        alias,
+        final_pass: false,
+        wants_final: false,
+        options: Options::new(),
+        warnings: Vec::new(),
+    };
+    if !source.path.exists() {
+        return Ok((evaluator.options, evaluator.warnings));
+    }
+    evaluator.read_file(&source.path, 0)?;
+    if evaluator.wants_final {
+        // `Match final` blocks apply on a second pass; earlier values still win
+        evaluator.final_pass = true;
+        evaluator.read_file(&source.path, 0)?;
+    }
+    Ok((evaluator.options, evaluator.warnings))
+}
+
+/// Effective configuration for `alias`, with the defaults ssh would use
+pub fn resolve(source: &ConfigSource, alias: &str) -> Result<ResolvedSshHost, String> {
+    let (options, warnings) = evaluate(source, alias)?;
+    let first = |keyword: &str| {
+        options
+            .get(keyword)
+            .and_then(|values| values.first().cloned())
+    };
+
+    let hostname = first("hostname")
+        .map(|hostname| expand_tokens(&hostname, &[('h', alias)]))
+        .unwrap_or_else(|| alias.to_string());
+    let user = first("user").unwrap_or_else(|| source.local_user.clone());
+    let port = match first("port") {
+        Some(port) => port
+            .parse()
+            .map_err(|_| format!("Invalid port '{}' for {}", port, alias))?,
+        None => 22,
+    };
+    let home = source.home.to_string_lossy();
+    let identity_files = options
+        .get("identityfile")
+        .map(|files| {
+            files
+                .iter()
+                .map(|file| expand_identity_file(file, &home, &hostname, &user, &source.local_user))
+                .collect()
+        })
+        .unwrap_or_default();
+    let proxy_jump = first("proxyjump").filter(|jump| !jump.eq_ignore_ascii_case("none"));
+
+    Ok(ResolvedSshHost {
+        alias: alias.to_string(),
+        hostname,
+        user,
+        port,
+        identity_files,
+        proxy_jump,
+        options,
+        warnings,
+    })
+}
+
+/// `~` and the `%d %h %r %u` tokens in an IdentityFile
+fn expand_identity_file(
+    file: &str,
+    home: &str,
+    hostname: &str,
+    user: &str,
+    local_user: &str,
+) -> String {
+    let file = match file.strip_prefix("~/") {
+        Some(rest) => format!("{}/{}", home, rest),
+        None => file.to_string(),
+    };
+    expand_tokens(
+        &file,
+        &[('d', home), ('h', hostname), ('r', user), ('u', local_user)],
+    )
+}
+
+/// Concrete aliases named in `Host` lines (no wildcards or negation), in
+/// file order, following every Include regardless of the block it is in
+pub fn host_aliases(source: &ConfigSource) -> Result<Vec<String>, String> {
+    fn collect(
+        path: &Path,
+        source: &ConfigSource,
+        depth: usize,
+        aliases: &mut Vec<String>,
+    ) -> Result<(), String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        for line in content.lines() {
+            match split_line(line) {
+                Some((keyword, args)) if keyword == "host" => {
+                    for pattern in args {
+                        let concrete = !pattern.contains(['*', '?', '!']);
+                        if concrete && !aliases.contains(&pattern) {
+                            aliases.push(pattern);
+                        }
+                    }
+                }
+                Some((keyword, args)) if keyword == "include" && depth < MAX_INCLUDE_DEPTH => {
+                    for file in include_files(&args, source)? {
+                        collect(&file, source, depth + 1, aliases)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    let mut aliases = Vec::new();
+    if source.path.exists() {
+        collect(&source.path, source, 0, &mut aliases)?;
+    }
+    Ok(aliases)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> ConfigSource {
+        let home = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/ssh/fixtures/home");
+        ConfigSource {
+            path: home.join(".ssh/config"),
+            home,
+            local_user: "alice".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_split_line_forms() {
+        assert_eq!(
+            split_line("  HostName=example.com"),
+            Some(("hostname".to_string(), vec!["example.com".to_string()]))
+        );
+        assert_eq!(
+            split_line("IdentityFile \"~/.ssh/my key\" # work key"),
+            Some((
+                "identityfile".to_string(),
+                vec!["~/.ssh/my key".to_string()]
+            ))
+        );
+        assert_eq!(
+            split_line("Host = a b\tc"),
+            Some((
+                "host".to_string(),
+                vec!["a".to_string(), "b".to_string(), "c".to_string()]
+            ))
+        );
+        assert_eq!(split_line("   # comment"), None);
+
+        assert!(wildcard_match("*.Example.com", "db1.example.COM"));
+        assert!(wildcard_match("web-??", "web-01"));
+        assert!(!wildcard_match("web-??", "web-001"));
+        assert!(!pattern_list_matches(
+            &["*.example.com", "!bastion.example.com"],
+            "bastion.example.com"
+        ));
+    }
+
+    #[test]
+    fn test_resolve_like_ssh_g() {
+        let source = fixtures();
+
+        // Multi-pattern Host, key=value, Host * inheritance and an Include glob
+        let web = resolve(&source, "web2").unwrap();
+        assert_eq!(web.hostname, "web2.prod.example.com");
+        assert_eq!(web.user, "deploy");
+        assert_eq!(web.port, 2222);
+        assert_eq!(web.proxy_jump.as_deref(), Some("bastion"));
+        assert_eq!(
+            web.identity_files,
+            vec![
+                format!("{}/.ssh/prod_ed25519", source.home.display()),
+                format!("{}/.ssh/id_ed25519", source.home.display()),
+            ]
+        );
+        assert_eq!(web.options["serveraliveinterval"], vec!["30"]);
+        assert_eq!(web.options["forwardagent"], vec!["no"]);
+
+        // First value wins over the later Host * block
+        let bastion = resolve(&source, "bastion").unwrap();
+        assert_eq!(bastion.hostname, "bastion.example.com");
+        assert_eq!(bastion.user, "ops");
+        assert_eq!(bastion.port, 22);
+        assert_eq!(bastion.proxy_jump, None);
+        assert_eq!(bastion.options["forwardagent"], vec!["yes"]);
+
+        // Included file with a Match block on the resolved hostname and a final pass
+        let gpu = resolve(&source, "gpu7").unwrap();
+        assert_eq!(gpu.hostname, "gpu7.cluster.internal");
+        assert_eq!(gpu.user, "alice");
+        assert_eq!(gpu.options["requesttty"], vec!["yes"]);
+        assert_eq!(gpu.options["compression"], vec!["yes"]);
+        assert_eq!(gpu.proxy_jump.as_deref(), Some("bastion"));
+
+        // Include with a ~ path; `none` disables the jump
+        let legacy = resolve(&source, "legacy").unwrap();
+        assert_eq!(legacy.hostname, "10.0.0.9");
+        assert_eq!(legacy.proxy_jump, None);
+        assert_eq!(legacy.options["proxyjump"], vec!["none"]);
+
+        // Unknown hosts still get the wildcard defaults
+        let other = resolve(&source, "elsewhere").unwrap();
+        assert_eq!(other.hostname, "elsewhere");
+        assert_eq!(other.user, "alice");
+        assert!(!other.options.contains_key("compression"));
+
+        // A criterion that cannot be evaluated skips its block with a warning
+        assert!(!bastion.options.contains_key("loglevel"));
+        assert_eq!(bastion.warnings.len(), 1);
+        assert!(bastion.warnings[0].contains("Unsupported Match criterion 'version'"));
+
+        assert_eq!(
+            host_aliases(&source).unwrap(),
+            vec!["gpu7", "legacy", "bastion", "web1", "web2"]
+        );
+    }
+}
@@ -0,0 +1,363 @@
+//! Turn an SSH profile into the ssh command line typed into a new PTY.
+
+use super::{ConnectionType, PortForward, PortForwardType, SSHProfile};
+
+/// Let ssh fall back to keyboard-interactive and password prompts in the PTY
+const AUTH_OPTIONS: &[&str] = &[
+    "-o",
+    "BatchMode=no",
+    "-o",
+    "PreferredAuthentications=publickey,keyboard-interactive,password",
+];
+
+/// Hostnames, users and config aliases
+fn is_valid_identifier(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '@' | '-'))
+}
+
+/// `-J` takes a comma-separated list of `[user@]host[:port]` hops
+fn is_valid_jump(value: &str) -> bool {
+    value.split(',').all(|hop| {
+        let host = hop.rsplit_once(':').map_or(hop, |(host, port)| {
+            if port.parse::<u16>().is_ok() {
+                host
+            } else {
+                hop
+            }
+        });
+        is_valid_identifier(host)
+    })
+}
+
+fn is_valid_env_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn check_port(port: u16, what: &str) -> Result<u16, String> {
+    if port == 0 {
+        return Err(format!("Invalid {}: {}", what, port));
+    }
+    Ok(port)
+}
+
+/// ssh flags that take a value (`-p 2222` or `-p2222`)
+const VALUE_FLAGS: &str = "BDEFIJLOQRSWbceilmopw";
+
+/// `-o` options whose value is a command, which may itself hold flags
+const COMMAND_OPTIONS: &[&str] = &[
+    "proxycommand",
+    "localcommand",
+    "remotecommand",
+    "knownhostscommand",
+];
+
+/// Split a flag's value off `rest`: it runs up to the next whitespace before
+/// a `-`, so `-i /keys/my key -A` keeps its spaces and still ends before `-A`
+fn split_value(rest: &str) -> (&str, &str) {
+    let end = rest
+        .char_indices()
+        .find(|&(i, c)| c.is_whitespace() && rest[i..].trim_start().starts_with('-'))
+        .map_or(rest.len(), |(i, _)| i);
+    (&rest[..end], rest[end..].trim_start())
+}
+
+/// The profile's custom flags. An entry may hold several flags ("-A -C") or
+/// flags with values ("-o ServerAliveInterval=30 -A"). A value ends at the
+/// next flag, except that a command option ("-o ProxyCommand=ssh -W %h:%p
+/// bastion") takes the rest of the entry.
+pub(super) fn custom_options(profile: &SSHProfile) -> Vec<String> {
+    let mut args = Vec::new();
+    for option in profile.ssh_options.iter().flatten() {
+        let mut rest = option.trim();
+        while !rest.is_empty() {
+            if !rest.starts_with('-') {
+                args.push(rest.to_string());
+                break;
+            }
+            let (flag, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            let takes_value = flag.len() == 2 && VALUE_FLAGS.contains(&flag[1..]);
+            let attached_option = flag.len() > 2 && flag.starts_with("-o");
+            if !takes_value && !attached_option {
+                // Flags without a value, or one attached like `-p2222`
+                args.push(flag.to_string());
+                rest = after.trim_start();
+                continue;
+            }
+            let (flag, after) = if attached_option {
+                ("-o", &rest[2..])
+            } else {
+                (flag, after.trim_start())
+            };
+            let keyword = after
+                .split(|c: char| c == '=' || c.is_whitespace())
+                .next()
+                .unwrap_or("")
+                .to_ascii_lowercase();
+            let (value, after) = if flag == "-o" && COMMAND_OPTIONS.contains(&keyword.as_str()) {
+                (after, "")
+            } else {
+                split_value(after)
+            };
+            if attached_option {
+                args.push(format!("-o{}", value));
+            } else {
+                args.push(flag.to_string());
+                if !value.is_empty() {
+                    args.push(value.to_string());
+                }
+            }
+            rest = after.trim_start();
+        }
+    }
+    args
+}
+
+/// `-L`, `-R` or `-D` with its spec
+pub(super) fn forward_args(forward: &PortForward) -> Result<Vec<String>, String> {
+    let local_port = check_port(forward.local_port, "local port")?;
+    if let Some(host) = &forward.remote_host {
+        if !is_valid_identifier(host) || host.contains('@') {
+            return Err(format!("Invalid remote host for port forward: {}", host));
+        }
+    }
+    let args = match (
+        &forward.forward_type,
+        &forward.remote_host,
+        forward.remote_port,
+    ) {
+        (PortForwardType::Local, Some(host), Some(port)) => {
+            let port = check_port(port, "remote port")?;
+            [
+                "-L".to_string(),
+                format!("{}:{}:{}", local_port, host, port),
+            ]
+        }
+        (PortForwardType::Remote, Some(host), Some(port)) => {
+            let port = check_port(port, "remote port")?;
+            [
+                "-R".to_string(),
+                format!("{}:{}:{}", port, host, local_port),
+            ]
+        }
+        (PortForwardType::Dynamic, _, _) => ["-D".to_string(), local_port.to_string()],
+        _ => {
+            return Err(format!(
+                "Port forward {} needs a remote host and port",
+                forward.id
+            ))
+        }
+    };
+    Ok(args.to_vec())
+}
+
+/// Identity, jump host and port flags followed by the destination
+pub(super) fn target_args(profile: &SSHProfile) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    match (
+        &profile.connection_type,
+        &profile.ssh_config_host,
+        &profile.manual_config,
+    ) {
+        (ConnectionType::SshConfig, Some(host), _) => {
+            if !is_valid_identifier(host) {
+                return Err(format!("Invalid SSH config host: {}", host));
+            }
+            args.push(host.clone());
+        }
+        (ConnectionType::Manual, _, Some(manual)) => {
+            if !is_valid_identifier(&manual.hostname) {
+                return Err(format!("Invalid hostname: {}", manual.hostname));
+            }
+            if !is_valid_identifier(&manual.username) {
+                return Err(format!("Invalid username: {}", manual.username));
+            }
+            if let Some(identity_file) = manual.identity_file.as_deref().filter(|f| !f.is_empty()) {
+                args.push("-i".to_string());
+                args.push(shellexpand::tilde(identity_file).to_string());
+            }
+            if let Some(jump) = manual.proxy_jump.as_deref().filter(|j| !j.is_empty()) {
+                if !is_valid_jump(jump) {
+                    return Err(format!("Invalid proxy jump: {}", jump));
+                }
+                args.push("-J".to_string());
+                args.push(jump.to_string());
+            }
+            if let Some(port) = manual.port.filter(|port| *port != 22) {
+                args.push("-p".to_string());
+                args.push(check_port(port, "port")?.to_string());
+            }
+            args.push(format!("{}@{}", manual.username, manual.hostname));
+        }
+        _ => return Err(format!("Invalid profile configuration: {}", profile.name)),
+    }
+    Ok(args)
+}
+
+/// Arguments after `ssh`, in the order ssh options, forwards, destination
+pub fn ssh_args(profile: &SSHProfile) -> Result<Vec<String>, String> {
+    let mut args: Vec<String> = AUTH_OPTIONS.iter().map(|arg| arg.to_string()).collect();
+    args.extend(custom_options(profile));
+
+    // The variables themselves are set in the local shell's environment
+    if let Some(env_vars) = &profile.env_vars {
+        let mut names: Vec<&String> = env_vars.keys().collect();
+        names.sort();
+        for name in names {
+            if !is_valid_env_name(name) {
+                return Err(format!("Invalid environment variable name: {}", name));
+            }
+            args.push("-o".to_string());
+            args.push(format!("SendEnv={}", name));
+        }
+    }
+
+    for forward in profile.port_forwards.iter().flatten() {
+        args.extend(forward_args(forward)?);
+    }
+    args.extend(target_args(profile)?);
+    Ok(args)
+}
+
+/// Quote an argument for a POSIX shell, leaving plain words as they are
+pub(super) fn shell_quote(arg: &str) -> String {
+    let plain = !arg.is_empty()
+        && arg.chars().all(|c| {
+            c.is_ascii_alphanumeric()
+                || matches!(c, '-' | '_' | '.' | '/' | ':' | ',' | '=' | '@' | '+')
+        });
+    if plain {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// The full `ssh ...` command line for a profile
+pub fn ssh_command_line(profile: &SSHProfile) -> Result<String, String> {
+    let args = ssh_args(profile)?;
+    let mut line = String::from("ssh");
+    for arg in &args {
+        line.push(' ');
+        line.push_str(&shell_quote(arg));
+    }
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh::ManualSSHConfig;
+    use std::collections::HashMap;
+
+    fn profile(json: serde_json::Value) -> SSHProfile {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_manual_profile_command_line() {
+        let mut profile = profile(serde_json::json!({
+            "id": "p1",
+            "name": "Cluster",
+            "connectionType": "manual",
+            "manualConfig": {
+                "hostname": "login.example.com",
+                "username": "me",
+                "port": 2222,
+                "identityFile": "/keys/my key",
+                "proxyJump": "ops@bastion:2200,gw"
+            },
+            "sshOptions": [
+                "-v -C",
+                "-o ServerAliveInterval=30",
+                "-o ProxyCommand=ssh -W %h:%p bastion"
+            ],
+            "envVars": {"LC_PROJECT": "x", "EDITOR": "vim"},
+            "portForwards": [
+                {"id": "f1", "type": "local", "localPort": 8080, "remoteHost": "localhost", "remotePort": 3000},
+                {"id": "f2", "type": "remote", "localPort": 22, "remoteHost": "localhost", "remotePort": 2022},
+                {"id": "f3", "type": "dynamic", "localPort": 1080}
+            ]
+        }));
+        assert_eq!(
+            ssh_command_line(&profile).unwrap(),
+            "ssh -o BatchMode=no -o PreferredAuthentications=publickey,keyboard-interactive,password \
+             -v -C -o ServerAliveInterval=30 -o 'ProxyCommand=ssh -W %h:%p bastion' -o SendEnv=EDITOR -o SendEnv=LC_PROJECT \
+             -L 8080:localhost:3000 -R 2022:localhost:22 -D 1080 \
+             -i '/keys/my key' -J ops@bastion:2200,gw -p 2222 me@login.example.com"
+        );
+
+        profile.manual_config = Some(ManualSSHConfig {
+            hostname: "host; rm -rf /".to_string(),
+            username: "me".to_string(),
+            port: None,
+            identity_file: None,
+            proxy_jump: None,
+        });
+        assert!(ssh_command_line(&profile).is_err());
+    }
+
+    #[test]
+    fn test_custom_option_values_end_at_the_next_flag() {
+        let profile = profile(serde_json::json!({
+            "id": "p3",
+            "name": "Options",
+            "connectionType": "ssh-config",
+            "sshConfigHost": "web1",
+            "sshOptions": [
+                "-o SetEnv=GREETING=hello there -A",
+                "-oServerAliveInterval=30 -i /keys/my key -C",
+                "-p2222 -o ProxyCommand=ssh -W %h:%p bastion -A"
+            ]
+        }));
+        assert_eq!(
+            custom_options(&profile),
+            vec![
+                "-o",
+                "SetEnv=GREETING=hello there",
+                "-A",
+                "-oServerAliveInterval=30",
+                "-i",
+                "/keys/my key",
+                "-C",
+                "-p2222",
+                "-o",
+                "ProxyCommand=ssh -W %h:%p bastion -A",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_config_host_profile_and_invalid_input() {
+        let mut profile = profile(serde_json::json!({
+            "id": "p2",
+            "name": "Web",
+            "connectionType": "ssh-config",
+            "sshConfigHost": "web1"
+        }));
+        assert_eq!(
+            ssh_args(&profile).unwrap().last().map(String::as_str),
+            Some("web1")
+        );
+
+        profile.env_vars = Some(HashMap::from([("BAD NAME".to_string(), "x".to_string())]));
+        assert!(ssh_args(&profile).is_err());
+        profile.env_vars = None;
+
+        profile.port_forwards = Some(vec![PortForward {
+            id: "f1".to_string(),
+            forward_type: PortForwardType::Local,
+            local_port: 8080,
+            remote_host: None,
+            remote_port: Some(80),
+            description: None,
+        }]);
+        assert!(ssh_args(&profile).is_err());
+
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+    }
+}
@@ -0,0 +1,971 @@
+//! Import and export of SSH profiles: `~/.ssh/config` fragments and portable
+//! JSON bundles.
+//!
+//! An import is planned against the saved profiles before anything is
+//! written, so a dry run returns the same per-field diff a real import
+//! applies. Conflicts are found by profile `id` or by `name`.
+
+use super::config::{self, ConfigSource, ResolvedSshHost};
+use super::connect::{custom_options, forward_args, ssh_args};
+use super::{
+    get_ssh_profiles_path, read_ssh_profiles, ConnectionType, ManualSSHConfig, PortForward,
+    PortForwardType, SSHProfile,
+};
+use crate::tools::commands::is_sensitive_env_var;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::Path;
+use tauri::{AppHandle, Emitter};
+
+/// ssh_config keywords whose value is a command line for the shell
+const COMMAND_KEYWORDS: &[&str] = &[
+    "knownhostscommand",
+    "localcommand",
+    "proxycommand",
+    "remotecommand",
+];
+
+/// `format` of a profile bundle, so other JSON is rejected up front
+const BUNDLE_FORMAT: &str = "aiterminal-ssh-profiles";
+const BUNDLE_VERSION: u32 = 1;
+
+/// ssh_config keywords that become profile fields on import rather than
+/// `-o` options
+const PROFILE_FIELD_KEYWORDS: &[&str] = &[
+    "hostname",
+    "user",
+    "port",
+    "identityfile",
+    "proxyjump",
+    "localforward",
+    "remoteforward",
+    "dynamicforward",
+];
+
+/// Bookkeeping of this machine, kept when an import replaces a profile
+const USAGE_FIELDS: &[&str] = &["createdAt", "lastConnectedAt", "connectionCount"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SshProfileBundle {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: String,
+    pub profiles: Vec<SSHProfile>,
+}
+
+/// Which field makes an imported profile the same as a saved one
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMatch {
+    #[default]
+    Id,
+    Name,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImportConflict {
+    /// Leave the saved profile as it is
+    #[default]
+    Skip,
+    /// Overwrite it, keeping its id and usage counters
+    Replace,
+    /// Add the import next to it, under a new id and a free name
+    KeepBoth,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Add,
+    Replace,
+    Skip,
+    Unchanged,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileChange {
+    pub action: ImportAction,
+    pub id: String,
+    pub name: String,
+    /// The saved profile the import matched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub existing_id: Option<String>,
+    /// What the import changes, or would change for a skipped conflict
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub profiles: Vec<ProfileChange>,
+    pub warnings: Vec<String>,
+}
+
+fn now_rfc3339() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
+/// `~/...` for a path inside `home`, so it works on another machine
+fn collapse_home(path: &str, home: &str) -> String {
+    match path.strip_prefix(home) {
+        Some(rest) if !home.is_empty() && rest.starts_with('/') => format!("~{}", rest),
+        _ => path.to_string(),
+    }
+}
+
+/// Quote a value ssh_config would otherwise split. Commands are the rest of
+/// the line as written, so quotes would reach the shell and they are left
+/// alone.
+fn config_value(keyword: &str, value: &str) -> String {
+    if COMMAND_KEYWORDS.contains(&keyword.to_lowercase().as_str()) {
+        value.to_string()
+    } else if value.contains(char::is_whitespace) {
+        format!("\"{}\"", value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// `Host` alias for a manual profile: its name lowercased, with anything
+/// ssh_config treats specially replaced by `-`
+fn host_alias(profile: &SSHProfile) -> String {
+    let alias: String = profile
+        .name
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let alias = alias.trim_matches('-');
+    if alias.is_empty() {
+        profile.id.clone()
+    } else {
+        alias.to_string()
+    }
+}
+
+/// ssh_config lines for the profile's custom flags. Flags without a config
+/// keyword are kept as comments.
+fn option_lines(profile: &SSHProfile) -> Vec<String> {
+    let flags = custom_options(profile);
+    let mut flags = flags.iter();
+    let mut lines = Vec::new();
+    while let Some(flag) = flags.next() {
+        let option = match flag.as_str() {
+            "-o" => flags.next().cloned(),
+            other => other.strip_prefix("-o").map(str::to_string),
+        };
+        if let Some(option) = option {
+            // `-o Keyword=value` or `-o "Keyword value"`
+            match option.split_once(|c: char| c == '=' || c.is_whitespace()) {
+                Some((keyword, value)) => {
+                    let value = value.trim_start_matches(|c: char| c == '=' || c.is_whitespace());
+                    lines.push(format!("{} {}", keyword, config_value(keyword, value)))
+                }
+                None => lines.push(format!("# Unsupported option: -o {}", option)),
+            }
+            continue;
+        }
+        let line = match flag.as_str() {
+            "-A" => "ForwardAgent yes",
+            "-a" => "ForwardAgent no",
+            "-C" => "Compression yes",
+            "-X" => "ForwardX11 yes",
+            "-x" => "ForwardX11 no",
+            "-Y" => "ForwardX11Trusted yes",
+            "-4" => "AddressFamily inet",
+            "-6" => "AddressFamily inet6",
+            "-T" => "RequestTTY no",
+            "-t" => "RequestTTY yes",
+            _ => {
+                lines.push(format!("# Unsupported flag: {}", flag));
+                continue;
+            }
+        };
+        lines.push(line.to_string());
+    }
+    lines
+}
+
+/// `LocalForward`, `RemoteForward` or `DynamicForward` for a forward, from
+/// the same spec `-L`, `-R` or `-D` would get
+fn forward_line(forward: &PortForward) -> Result<String, String> {
+    let args = forward_args(forward)?;
+    let spec = &args[1];
+    let keyword = match forward.forward_type {
+        PortForwardType::Local => "LocalForward",
+        PortForwardType::Remote => "RemoteForward",
+        PortForwardType::Dynamic => return Ok(format!("DynamicForward {}", spec)),
+    };
+    let (listen, target) = spec.split_once(':').unwrap_or((spec, ""));
+    Ok(format!("{} {} {}", keyword, listen, target))
+}
+
+/// One `Host` block. A profile built on an ssh_config alias only adds its
+/// own forwards and options to that alias.
+fn config_block(profile: &SSHProfile, alias: &str) -> Result<String, String> {
+    // The same checks connecting would run
+    ssh_args(profile)?;
+
+    let mut lines = vec![format!("# {}", profile.name), format!("Host {}", alias)];
+    if let (ConnectionType::Manual, Some(manual)) =
+        (&profile.connection_type, &profile.manual_config)
+    {
+        lines.push(format!("    HostName {}", manual.hostname));
+        lines.push(format!("    User {}", manual.username));
+        if let Some(port) = manual.port.filter(|port| *port != 22) {
+            lines.push(format!("    Port {}", port));
+        }
+        if let Some(file) = manual.identity_file.as_deref().filter(|f| !f.is_empty()) {
+            lines.push(format!(
+                "    IdentityFile {}",
+                config_value("IdentityFile", file)
+            ));
+        }
+        if let Some(jump) = manual.proxy_jump.as_deref().filter(|j| !j.is_empty()) {
+            lines.push(format!("    ProxyJump {}", jump));
+        }
+    }
+    for line in option_lines(profile) {
+        lines.push(format!("    {}", line));
+    }
+    if let Some(env_vars) = &profile.env_vars {
+        let mut names: Vec<&String> = env_vars.keys().collect();
+        names.sort();
+        for name in names {
+            lines.push(format!("    SendEnv {}", name));
+        }
+    }
+    for forward in profile.port_forwards.iter().flatten() {
+        lines.push(format!("    {}", forward_line(forward)?));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// An ssh_config fragment with a `Host` block per profile; profiles that
+/// would not connect are left out with a comment saying why
+pub fn profiles_to_config(profiles: &[SSHProfile]) -> String {
+    let mut blocks = vec![format!(
+        "# SSH profiles exported from AITerminal on {}",
+        now_rfc3339()
+    )];
+    let mut aliases = HashSet::new();
+    for profile in profiles {
+        let alias = match (&profile.connection_type, &profile.ssh_config_host) {
+            (ConnectionType::SshConfig, Some(alias)) => alias.clone(),
+            _ => {
+                // ssh uses the first block for an alias, so each gets its own
+                let base = host_alias(profile);
+                let mut alias = base.clone();
+                let mut n = 2;
+                while aliases.contains(&alias) {
+                    alias = format!("{}-{}", base, n);
+                    n += 1;
+                }
+                alias
+            }
+        };
+        match config_block(profile, &alias) {
+            Ok(block) => {
+                aliases.insert(alias);
+                blocks.push(block);
+            }
+            Err(e) => blocks.push(format!("# Skipped {}: {}", profile.name, e)),
+        }
+    }
+    blocks.join("\n\n") + "\n"
+}
+
+/// The port of a `[bind_address:]port` listen spec
+fn listen_port(spec: &str) -> Option<u16> {
+    spec.rsplit(':')
+        .next()?
+        .parse()
+        .ok()
+        .filter(|port| *port != 0)
+}
+
+/// Host and port of a `host:port` target, IPv6 hosts in brackets
+fn target_host_port(spec: &str) -> Option<(String, u16)> {
+    let (host, port) = spec.rsplit_once(':')?;
+    Some((
+        host.trim_matches(['[', ']']).to_string(),
+        port.parse().ok()?,
+    ))
+}
+
+/// A `LocalForward`, `RemoteForward` or `DynamicForward` value as a profile
+/// forward; None for the forms profiles cannot hold (Unix sockets)
+fn forward_from_config(keyword: &str, value: &str, id: String) -> Option<PortForward> {
+    let forward = match keyword {
+        "dynamicforward" => PortForward {
+            id,
+            forward_type: PortForwardType::Dynamic,
+            local_port: listen_port(value)?,
+            remote_host: None,
+            remote_port: None,
+            description: None,
+        },
+        _ => {
+            let (listen, target) = value.split_once(' ')?;
+            let (host, port) = target_host_port(target.trim())?;
+            let listen = listen_port(listen)?;
+            let (forward_type, local_port, remote_port) = if keyword == "localforward" {
+                (PortForwardType::Local, listen, port)
+            } else {
+                (PortForwardType::Remote, port, listen)
+            };
+            PortForward {
+                id,
+                forward_type,
+                local_port,
+                remote_host: Some(host),
+                remote_port: Some(remote_port),
+                description: None,
+            }
+        }
+    };
+    forward_args(&forward).ok().map(|_| forward)
+}
+
+/// A manual profile with everything ssh would use for the alias. Values
+/// a profile cannot hold are reported in `warnings`.
+fn profile_from_host(
+    resolved: &ResolvedSshHost,
+    home: &Path,
+    warnings: &mut Vec<String>,
+) -> SSHProfile {
+    let home = home.to_string_lossy();
+    let alias = &resolved.alias;
+    let mut ssh_options = Vec::new();
+    let mut port_forwards = Vec::new();
+    let mut add_option = |keyword: &str, value: &str| {
+        ssh_options.push(format!("-o {}={}", keyword, value));
+    };
+
+    for file in resolved.identity_files.iter().skip(1) {
+        add_option("identityfile", &collapse_home(file, &home));
+    }
+    for (keyword, values) in &resolved.options {
+        match keyword.as_str() {
+            "localforward" | "remoteforward" | "dynamicforward" => {
+                for value in values {
+                    let id = format!("forward-{}", port_forwards.len() + 1);
+                    match forward_from_config(keyword, value, id) {
+                        Some(forward) => port_forwards.push(forward),
+                        None => warnings.push(format!(
+                            "{}: skipped {} {} (only TCP port forwards are supported)",
+                            alias, keyword, value
+                        )),
+                    }
+                }
+            }
+            keyword if PROFILE_FIELD_KEYWORDS.contains(&keyword) => {}
+            _ => {
+                for value in values {
+                    add_option(keyword, value);
+                }
+            }
+        }
+    }
+
+    SSHProfile {
+        id: format!("ssh-config-{}", alias),
+        name: alias.clone(),
+        icon: None,
+        group: None,
+        tab_color: None,
+        connection_type: ConnectionType::Manual,
+        ssh_config_host: None,
+        manual_config: Some(ManualSSHConfig {
+            hostname: resolved.hostname.clone(),
+            username: resolved.user.clone(),
+            port: (resolved.port != 22).then_some(resolved.port),
+            identity_file: resolved
+                .identity_files
+                .first()
+                .map(|file| collapse_home(file, &home)),
+            proxy_jump: resolved.proxy_jump.clone(),
+        }),
+        startup_commands: None,
+        env_vars: None,
+        port_forwards: (!port_forwards.is_empty()).then_some(port_forwards),
+        ssh_options: (!ssh_options.is_empty()).then_some(ssh_options),
+        auto_connect: None,
+        health_check_interval: None,
+        alert_on_disconnect: None,
+        auto_reconnect: None,
+        created_at: None,
+        last_connected_at: None,
+        connection_count: None,
+    }
+}
+
+/// A command that assigns a variable with a credential-like name or hands
+/// sshpass a password
+fn mentions_secret(command: &str) -> bool {
+    let words: Vec<&str> = command
+        .split(|c: char| c.is_whitespace() || matches!(c, ';' | '&' | '|'))
+        .filter(|word| !word.is_empty())
+        .collect();
+    let assigns_secret = words
+        .iter()
+        .filter_map(|word| word.split_once('='))
+        .any(|(name, _)| is_sensitive_env_var(name));
+    let sshpass_password = words.windows(2).any(|pair| {
+        (pair[0] == "sshpass" || pair[0].ends_with("/sshpass")) && pair[1].starts_with("-p")
+    });
+    assigns_secret || sshpass_password
+}
+
+/// A profile fit to share: usage counters dropped, identity files relative
+/// to `~`, and environment variables, startup commands or custom options
+/// that look like they carry credentials removed
+fn strip_secrets(mut profile: SSHProfile, home: &str) -> SSHProfile {
+    profile.created_at = None;
+    profile.last_connected_at = None;
+    profile.connection_count = None;
+    if let Some(file) = profile
+        .manual_config
+        .as_mut()
+        .and_then(|manual| manual.identity_file.as_mut())
+    {
+        *file = collapse_home(file, home);
+    }
+    if let Some(env_vars) = profile.env_vars.as_mut() {
+        env_vars.retain(|name, _| !is_sensitive_env_var(name));
+    }
+    if let Some(commands) = profile.startup_commands.as_mut() {
+        commands.retain(|command| !mentions_secret(command));
+    }
+    if let Some(options) = profile.ssh_options.as_mut() {
+        // The command of `-o ProxyCommand=...` starts after the keyword
+        options.retain(|option| {
+            !mentions_secret(
+                option
+                    .split_once('=')
+                    .map_or(option.as_str(), |(_, value)| value),
+            )
+        });
+    }
+    profile
+}
+
+fn parse_bundle(bundle: &str) -> Result<Vec<SSHProfile>, String> {
+    let bundle: SshProfileBundle = serde_json::from_str(bundle)
+        .map_err(|e| format!("Failed to parse profile bundle: {}", e))?;
+    if bundle.format != BUNDLE_FORMAT {
+        return Err(format!("Not an SSH profile bundle: {}", bundle.format));
+    }
+    if bundle.version > BUNDLE_VERSION {
+        return Err(format!(
+            "Profile bundle version {} is newer than this app supports ({})",
+            bundle.version, BUNDLE_VERSION
+        ));
+    }
+    Ok(bundle.profiles)
+}
+
+/// Top-level fields that differ, in name order
+fn diff_fields(before: &Value, after: &Value) -> Vec<FieldChange> {
+    let empty = Map::new();
+    let before = before.as_object().unwrap_or(&empty);
+    let after = after.as_object().unwrap_or(&empty);
+    let fields: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    fields
+        .into_iter()
+        .filter(|field| before.get(*field) != after.get(*field))
+        .map(|field| FieldChange {
+            field: field.clone(),
+            before: before.get(field).cloned(),
+            after: after.get(field).cloned(),
+        })
+        .collect()
+}
+
+/// Fields of a saved profile this version does not know, which a replace
+/// carries over
+fn unknown_fields(saved: &Value) -> Map<String, Value> {
+    let known =
+        match serde_json::from_value::<SSHProfile>(saved.clone()).and_then(serde_json::to_value) {
+            Ok(known) => known,
+            Err(_) => return Map::new(),
+        };
+    saved
+        .as_object()
+        .map(|fields| {
+            fields
+                .iter()
+                .filter(|(field, _)| known.get(field.as_str()).is_none())
+                .map(|(field, value)| (field.clone(), value.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// `name (2)`, `name (3)`, ... whichever no saved profile uses
+fn free_name(saved: &[Value], name: &str) -> String {
+    let mut n = 2;
+    loop {
+        let candidate = format!("{} ({})", name, n);
+        if !saved
+            .iter()
+            .any(|profile| profile["name"] == candidate.as_str())
+        {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// Apply `incoming` to the saved profiles (raw JSON, so fields this version
+/// does not know survive) and describe each decision. Profiles are handled
+/// in order, so later imports also conflict with earlier ones.
+fn plan_import(
+    saved: &mut Vec<Value>,
+    incoming: Vec<SSHProfile>,
+    match_by: ImportMatch,
+    on_conflict: ImportConflict,
+) -> Result<Vec<ProfileChange>, String> {
+    let mut changes = Vec::new();
+    for mut profile in incoming {
+        let existing = saved.iter().position(|value| match match_by {
+            ImportMatch::Id => value["id"] == profile.id.as_str(),
+            ImportMatch::Name => value["name"] == profile.name.as_str(),
+        });
+        let existing_id =
+            existing.and_then(|index| saved[index]["id"].as_str().map(str::to_string));
+
+        if let (Some(index), false) = (existing, on_conflict == ImportConflict::KeepBoth) {
+            let before = saved[index].clone();
+            if let Some(id) = &existing_id {
+                profile.id = id.clone();
+            }
+            let mut after = serde_json::to_value(&profile)
+                .map_err(|e| format!("Failed to serialize profile: {}", e))?;
+            if let Some(fields) = after.as_object_mut() {
+                for field in USAGE_FIELDS {
+                    match before.get(*field) {
+                        Some(value) => fields.insert(field.to_string(), value.clone()),
+                        None => fields.remove(*field),
+                    };
+                }
+                for (field, value) in unknown_fields(&before) {
+                    fields.insert(field, value);
+                }
+            }
+            let diff = diff_fields(&before, &after);
+            let action = match (diff.is_empty(), on_conflict) {
+                (true, _) => ImportAction::Unchanged,
+                (false, ImportConflict::Replace) => ImportAction::Replace,
+                (false, _) => ImportAction::Skip,
+            };
+            if action == ImportAction::Replace {
+                saved[index] = after;
+            }
+            changes.push(ProfileChange {
+                action,
+                id: profile.id,
+                name: profile.name,
+                existing_id,
+                changes: diff,
+            });
+            continue;
+        }
+
+        if saved.iter().any(|value| value["id"] == profile.id.as_str()) {
+            profile.id = uuid::Uuid::new_v4().to_string();
+        }
+        if saved
+            .iter()
+            .any(|value| value["name"] == profile.name.as_str())
+        {
+            profile.name = free_name(saved, &profile.name);
+        }
+        profile.created_at = Some(now_rfc3339());
+        profile.last_connected_at = None;
+        profile.connection_count = None;
+        let after = serde_json::to_value(&profile)
+            .map_err(|e| format!("Failed to serialize profile: {}", e))?;
+        changes.push(ProfileChange {
+            action: ImportAction::Add,
+            id: profile.id,
+            name: profile.name,
+            existing_id,
+            changes: diff_fields(&Value::Null, &after),
+        });
+        saved.push(after);
+    }
+    Ok(changes)
+}
+
+fn run_import(
+    app: &AppHandle,
+    incoming: Vec<SSHProfile>,
+    match_by: Option<ImportMatch>,
+    on_conflict: Option<ImportConflict>,
+    dry_run: Option<bool>,
+    warnings: Vec<String>,
+) -> Result<ImportReport, String> {
+    let path = get_ssh_profiles_path()?;
+    let mut saved: Vec<Value> = if path.exists() {
+        let content =
+            fs::read_to_string(&path).map_err(|e| format!("Failed to read profiles: {}", e))?;
+        serde_json::from_str(&content).map_err(|e| format!("Failed to parse profiles: {}", e))?
+    } else {
+        Vec::new()
+    };
+
+    let profiles = plan_import(
+        &mut saved,
+        incoming,
+        match_by.unwrap_or_default(),
+        on_conflict.unwrap_or_default(),
+    )?;
+    let dry_run = dry_run.unwrap_or(false);
+    let changed = profiles
+        .iter()
+        .filter(|p| matches!(p.action, ImportAction::Add | ImportAction::Replace))
+        .count();
+
+    if !dry_run && changed > 0 {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        let json = serde_json::to_string_pretty(&saved)
+            .map_err(|e| format!("Failed to serialize profiles: {}", e))?;
+        fs::write(&path, json).map_err(|e| format!("Failed to write profiles: {}", e))?;
+        println!("[SSH] Imported {} profiles", changed);
+        let _ = app.emit("ssh-profiles-changed", ());
+    }
+
+    Ok(ImportReport {
+        dry_run,
+        profiles,
+        warnings,
+    })
+}
+
+/// Saved profiles with the given IDs, in that order; all of them when None
+fn selected_profiles(profile_ids: Option<Vec<String>>) -> Result<Vec<SSHProfile>, String> {
+    let profiles = read_ssh_profiles()?;
+    match profile_ids {
+        None => Ok(profiles),
+        Some(ids) => ids
+            .iter()
+            .map(|id| {
+                profiles
+                    .iter()
+                    .find(|profile| &profile.id == id)
+                    .cloned()
+                    .ok_or_else(|| format!("SSH profile not found: {}", id))
+            })
+            .collect(),
+    }
+}
+
+/// Tauri command: SSH profiles as an `~/.ssh/config` fragment
+#[tauri::command]
+pub async fn export_ssh_config(profile_ids: Option<Vec<String>>) -> Result<String, String> {
+    Ok(profiles_to_config(&selected_profiles(profile_ids)?))
+}
+
+/// Tauri command: SSH profiles as a JSON bundle to share, without usage
+/// counters or anything that looks like a credential
+#[tauri::command]
+pub async fn export_ssh_profile_bundle(profile_ids: Option<Vec<String>>) -> Result<String, String> {
+    let home = std::env::var("HOME").unwrap_or_default();
+    let bundle = SshProfileBundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        exported_at: now_rfc3339(),
+        profiles: selected_profiles(profile_ids)?
+            .into_iter()
+            .map(|profile| strip_secrets(profile, &home))
+            .collect(),
+    };
+    serde_json::to_string_pretty(&bundle).map_err(|e| format!("Failed to serialize bundle: {}", e))
+}
+
+/// Tauri command: Create or update profiles from `~/.ssh/config` hosts (all
+/// concrete aliases when `aliases` is None)
+#[tauri::command]
+pub async fn import_ssh_config_hosts(
+    app: AppHandle,
+    aliases: Option<Vec<String>>,
+    match_by: Option<ImportMatch>,
+    on_conflict: Option<ImportConflict>,
+    dry_run: Option<bool>,
+) -> Result<ImportReport, String> {
+    let source = ConfigSource::user()?;
+    let aliases = match aliases {
+        Some(aliases) => aliases,
+        None => config::host_aliases(&source)?,
+    };
+    let mut warnings = Vec::new();
+    let mut incoming = Vec::new();
+    for alias in &aliases {
+        let resolved = match config::resolve(&source, alias) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                warnings.push(format!("{}: skipped ({})", alias, e));
+                continue;
+            }
+        };
+        incoming.push(profile_from_host(&resolved, &source.home, &mut warnings));
+    }
+    run_import(&app, incoming, match_by, on_conflict, dry_run, warnings)
+}
+
+/// Tauri command: Create or update profiles from a bundle made by
+/// `export_ssh_profile_bundle`
+#[tauri::command]
+pub async fn import_ssh_profile_bundle(
+    app: AppHandle,
+    bundle: String,
+    match_by: Option<ImportMatch>,
+    on_conflict: Option<ImportConflict>,
+    dry_run: Option<bool>,
+) -> Result<ImportReport, String> {
+    run_import(
+        &app,
+        parse_bundle(&bundle)?,
+        match_by,
+        on_conflict,
+        dry_run,
+        Vec::new(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    fn profile(json: Value) -> SSHProfile {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn lab_profile() -> SSHProfile {
+        profile(serde_json::json!({
+            "id": "p1",
+            "name": "GPU Cluster",
+            "connectionType": "manual",
+            "manualConfig": {
+                "hostname": "gpu.lab.example.com",
+                "username": "ops",
+                "port": 2200,
+                "identityFile": "/home/alice/.ssh/lab_ed25519",
+                "proxyJump": "bastion"
+            },
+            "sshOptions": [
+                "-o ServerAliveInterval=30",
+                "-A",
+                "-v",
+                "-o SetEnv LAB_MODE=train",
+                "-o LocalCommand=echo connected to %n"
+            ],
+            "envVars": { "LAB_PROJECT": "vision", "HF_TOKEN": "hf_secret" },
+            "startupCommands": ["cd /data", "export WANDB_API_KEY=abc123"],
+            "portForwards": [
+                { "id": "f1", "type": "local", "localPort": 8888, "remoteHost": "localhost", "remotePort": 8888 },
+                { "id": "f2", "type": "remote", "localPort": 3000, "remoteHost": "localhost", "remotePort": 9000 },
+                { "id": "f3", "type": "dynamic", "localPort": 1080 }
+            ],
+            "connectionCount": 7
+        }))
+    }
+
+    #[test]
+    fn test_config_fragment_round_trips_through_import() {
+        let fragment = profiles_to_config(&[lab_profile()]);
+        assert!(fragment.contains("Host gpu-cluster\n"));
+        assert!(fragment.contains("    ForwardAgent yes\n"));
+        assert!(fragment.contains("    # Unsupported flag: -v\n"));
+        assert!(fragment.contains("    RemoteForward 9000 localhost:3000\n"));
+        assert!(fragment.contains("    SetEnv LAB_MODE=train\n"));
+        // Unquoted: ssh hands the rest of the line to the shell as is
+        assert!(fragment.contains("    LocalCommand echo connected to %n\n"));
+
+        let dir = std::env::temp_dir().join(format!("aiterminal_export_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config");
+        fs::write(&path, &fragment).unwrap();
+        let source = ConfigSource {
+            path,
+            home: PathBuf::from("/home/alice"),
+            local_user: "alice".to_string(),
+        };
+        assert_eq!(
+            config::host_aliases(&source).unwrap(),
+            vec!["gpu-cluster".to_string()]
+        );
+
+        let mut warnings = Vec::new();
+        let resolved = config::resolve(&source, "gpu-cluster").unwrap();
+        let imported = profile_from_host(&resolved, &source.home, &mut warnings);
+        let _ = fs::remove_dir_all(&dir);
+
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        let manual = imported.manual_config.as_ref().unwrap();
+        assert_eq!(manual.hostname, "gpu.lab.example.com");
+        assert_eq!(manual.username, "ops");
+        assert_eq!(manual.port, Some(2200));
+        assert_eq!(manual.identity_file.as_deref(), Some("~/.ssh/lab_ed25519"));
+        assert_eq!(manual.proxy_jump.as_deref(), Some("bastion"));
+        // The same flags the original forwards would pass to ssh
+        let flags = |forwards: &[PortForward]| {
+            let mut flags: Vec<Vec<String>> =
+                forwards.iter().map(|f| forward_args(f).unwrap()).collect();
+            flags.sort();
+            flags
+        };
+        assert_eq!(
+            flags(imported.port_forwards.as_ref().unwrap()),
+            flags(&lab_profile().port_forwards.unwrap())
+        );
+        let options = imported.ssh_options.unwrap();
+        assert!(options.contains(&"-o serveraliveinterval=30".to_string()));
+        assert!(options.contains(&"-o forwardagent=yes".to_string()));
+        assert!(options.contains(&"-o sendenv=HF_TOKEN".to_string()));
+        assert!(options.contains(&"-o setenv=LAB_MODE=train".to_string()));
+        assert!(options.contains(&"-o localcommand=echo connected to %n".to_string()));
+    }
+
+    #[test]
+    fn test_bundle_strips_secrets() {
+        let mut profile = lab_profile();
+        let options = profile.ssh_options.clone().unwrap();
+        profile.ssh_options.as_mut().unwrap().extend([
+            "-o ProxyCommand=sshpass -p hunter2 ssh -W %h:%p bastion".to_string(),
+            "-o RemoteCommand=DB_PASSWORD=hunter2 bash -l".to_string(),
+        ]);
+        let stripped = strip_secrets(profile.clone(), "/home/alice");
+        assert_eq!(
+            stripped.env_vars,
+            Some(HashMap::from([(
+                "LAB_PROJECT".to_string(),
+                "vision".to_string()
+            )]))
+        );
+        assert_eq!(
+            stripped.startup_commands,
+            Some(vec!["cd /data".to_string()])
+        );
+        assert_eq!(stripped.ssh_options, Some(options));
+        assert_eq!(stripped.connection_count, None);
+        assert_eq!(
+            stripped.manual_config.unwrap().identity_file.as_deref(),
+            Some("~/.ssh/lab_ed25519")
+        );
+
+        let json = serde_json::to_string(&SshProfileBundle {
+            format: BUNDLE_FORMAT.to_string(),
+            version: BUNDLE_VERSION,
+            exported_at: now_rfc3339(),
+            profiles: vec![strip_secrets(profile, "/home/alice")],
+        })
+        .unwrap();
+        assert!(!json.contains("hf_secret") && !json.contains("abc123"));
+        assert!(!json.contains("hunter2"));
+        assert_eq!(parse_bundle(&json).unwrap()[0].id, "p1");
+        assert!(
+            parse_bundle(r#"{"format":"other","version":1,"exportedAt":"","profiles":[]}"#)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_import_conflicts_and_dry_run_diff() {
+        let saved_profile = || {
+            let mut value = serde_json::to_value(lab_profile()).unwrap();
+            value["createdAt"] = "2026-01-01T00:00:00.000Z".into();
+            value["pinned"] = true.into(); // a field this version does not know
+            value
+        };
+        let mut renamed = lab_profile();
+        renamed.id = "from-elsewhere".to_string();
+        renamed.manual_config.as_mut().unwrap().port = Some(22);
+        renamed.connection_count = None;
+
+        // Matched by name, skipped: nothing changes, but the diff is reported
+        let mut saved = vec![saved_profile()];
+        let plan = plan_import(
+            &mut saved,
+            vec![renamed.clone()],
+            ImportMatch::Name,
+            ImportConflict::Skip,
+        )
+        .unwrap();
+        assert_eq!(plan[0].action, ImportAction::Skip);
+        assert_eq!(plan[0].existing_id.as_deref(), Some("p1"));
+        let fields: Vec<&str> = plan[0].changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(fields, vec!["manualConfig"]);
+        assert_eq!(saved, vec![saved_profile()]);
+
+        // Replaced: keeps the saved id, usage counters and unknown fields
+        let plan = plan_import(
+            &mut saved,
+            vec![renamed.clone()],
+            ImportMatch::Name,
+            ImportConflict::Replace,
+        )
+        .unwrap();
+        assert_eq!(plan[0].action, ImportAction::Replace);
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0]["id"], "p1");
+        assert_eq!(saved[0]["connectionCount"], 7);
+        assert_eq!(saved[0]["createdAt"], "2026-01-01T00:00:00.000Z");
+        assert_eq!(saved[0]["manualConfig"]["port"], 22);
+        assert_eq!(saved[0]["pinned"], true);
+
+        // Importing the same again changes nothing
+        let plan = plan_import(
+            &mut saved,
+            vec![renamed.clone()],
+            ImportMatch::Name,
+            ImportConflict::Replace,
+        )
+        .unwrap();
+        assert_eq!(plan[0].action, ImportAction::Unchanged);
+        assert!(plan[0].changes.is_empty());
+
+        // Kept both: a new name, and by id a new id as well
+        let plan = plan_import(
+            &mut saved,
+            vec![lab_profile(), lab_profile()],
+            ImportMatch::Id,
+            ImportConflict::KeepBoth,
+        )
+        .unwrap();
+        assert_eq!(plan[0].action, ImportAction::Add);
+        assert_eq!(plan[0].name, "GPU Cluster (2)");
+        assert_eq!(plan[1].name, "GPU Cluster (3)");
+        assert_ne!(plan[0].id, "p1");
+        assert_ne!(plan[0].id, plan[1].id);
+        assert_eq!(saved.len(), 3);
+        assert!(saved[1].get("connectionCount").is_none());
+    }
+}
@@ -0,0 +1,486 @@
+//! The agent's file tools on the host of an SSH pane.
+//!
+//! Every operation is a short POSIX shell script run over its own ssh
+//! connection (see `RemoteShell`). The path limits are checked on this side,
+//! against the remote home directory as the remote shell canonicalizes it,
+//! and batched so one tool call costs a connection or two rather than one
+//! per file.
+
+use super::transfer::{finish, remote_error, remote_path_arg, RemoteShell};
+use crate::models::AppState;
+use crate::security::path_validator::is_sensitive_relative;
+use crate::tools::filesystem::{FsMetadata, ToolFs, ToolPath};
+use base64::Engine;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, ChildStdout, Stdio};
+
+/// Prints the canonical home directory, then one tab-separated line per
+/// `info` call: kind (f, d, o, n for missing, x for missing parent), size,
+/// mtime and the canonical path (of the parent for n and x)
+const RESOLVE_PRELUDE: &str = r#"h=$(cd && pwd -P) || exit 1
+printf '%s\n' "$h"
+canon() { realpath -- "$1" 2>/dev/null || readlink -f -- "$1" 2>/dev/null; }
+info() {
+  if [ -e "$1" ]; then
+    c=$(canon "$1")
+    if [ -z "$c" ]; then printf 'x\t\t\t%s\n' "$1"; return; fi
+    if [ -d "$c" ]; then k=d; s=0; elif [ -f "$c" ]; then k=f; s=$(wc -c < "$c"); else k=o; s=0; fi
+    m=$(stat -c %Y -- "$c" 2>/dev/null || stat -f %m -- "$c" 2>/dev/null)
+    printf '%s\t%s\t%s\t%s\n' "$k" "$s" "$m" "$c"
+  elif [ -d "$(dirname -- "$1")" ]; then
+    printf 'n\t\t\t%s\n' "$(canon "$(dirname -- "$1")")"
+  else
+    printf 'x\t\t\t%s\n' "$(dirname -- "$1")"
+  fi
+}
+"#;
+
+/// Ends each file's base64 block in `read_all` output; a failed read gets
+/// `READ_FAILED` instead
+const READ_END: &str = ".";
+const READ_FAILED: &str = "!";
+
+pub struct SshFs {
+    shell: RemoteShell,
+    host: String,
+}
+
+impl SshFs {
+    pub fn for_pty(state: &AppState, pty_id: u32) -> Result<Self, String> {
+        let host = state
+            .ssh_sessions
+            .lock()
+            .map_err(|e| format!("Failed to acquire SSH session lock: {}", e))?
+            .get(&pty_id)
+            .map(|info| info.remote_host.clone())
+            .ok_or_else(|| format!("PTY {} is not an SSH session", pty_id))?;
+        Ok(Self {
+            shell: RemoteShell::for_pty(state, pty_id)?,
+            host,
+        })
+    }
+}
+
+/// Reject what the line-based replies of the scripts cannot carry
+fn check_path(path: &str) -> Result<(), String> {
+    if path.is_empty() || path.contains('\n') || path.contains('\0') {
+        return Err(format!("Invalid remote path: {:?}", path));
+    }
+    Ok(())
+}
+
+/// One `info` line checked against the remote home: the same rules as
+/// `PathValidator` and `validate_path_for_write` apply locally
+fn check_resolved(
+    requested: &str,
+    line: &str,
+    home: &Path,
+    for_write: bool,
+) -> Result<ToolPath, String> {
+    let fields: Vec<&str> = line.splitn(4, '\t').collect();
+    let [kind, size, modified, canonical] = fields[..] else {
+        return Err(format!("Unexpected reply from remote host: {}", line));
+    };
+    let (path, metadata) = match kind {
+        "x" => {
+            return Err(format!("Parent directory does not exist: {}", canonical));
+        }
+        "n" => {
+            let name = Path::new(requested)
+                .file_name()
+                .ok_or_else(|| "Invalid file path".to_string())?;
+            (Path::new(canonical).join(name), None)
+        }
+        _ => (
+            Path::new(canonical).to_path_buf(),
+            Some(FsMetadata {
+                is_file: kind == "f",
+                is_dir: kind == "d",
+                size: size.trim().parse().unwrap_or(0),
+                modified: modified.trim().parse().ok(),
+            }),
+        ),
+    };
+    let relative = path.strip_prefix(home).map_err(|_| {
+        format!(
+            "Access denied: path outside allowed base\nPath: {}\nAllowed base: {}",
+            path.display(),
+            home.display()
+        )
+    })?;
+    if for_write && is_sensitive_relative(&relative.to_string_lossy()) {
+        return Err(format!(
+            "Access denied: write to sensitive file is not allowed: {}",
+            path.display()
+        ));
+    }
+    Ok(ToolPath {
+        path: path.display().to_string(),
+        metadata,
+    })
+}
+
+/// Parse `read_all` output: a base64 block per file, ended by `READ_END`
+fn parse_read_all(output: &[u8], count: usize) -> Vec<Result<Vec<u8>, String>> {
+    let text = String::from_utf8_lossy(output);
+    let mut results = Vec::with_capacity(count);
+    let mut block = String::new();
+    for line in text.lines() {
+        match line {
+            READ_END => {
+                results.push(
+                    base64::engine::general_purpose::STANDARD
+                        .decode(&block)
+                        .map_err(|e| format!("Failed to decode remote file: {}", e)),
+                );
+                block.clear();
+            }
+            READ_FAILED => {
+                results.push(Err("Failed to read file".to_string()));
+                block.clear();
+            }
+            _ => block.push_str(line.trim()),
+        }
+    }
+    results.resize_with(count, || Err("No reply from remote host".to_string()));
+    results
+}
+
+/// Stdout of a remote command, ending in an error if the command failed.
+/// Dropping it early stops the command.
+struct RemoteStream {
+    child: Child,
+    stdout: ChildStdout,
+}
+
+impl RemoteStream {
+    fn spawn(shell: &RemoteShell, script: &str) -> Result<Self, String> {
+        let mut child = shell.spawn(script, Stdio::null(), Stdio::piped())?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| "Failed to capture ssh output".to_string())?;
+        Ok(Self { child, stdout })
+    }
+}
+
+impl Read for RemoteStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.stdout.read(buf)?;
+        if n == 0 && !buf.is_empty() && !self.child.wait()?.success() {
+            let mut stderr = Vec::new();
+            if let Some(mut pipe) = self.child.stderr.take() {
+                let _ = pipe.read_to_end(&mut stderr);
+            }
+            return Err(io::Error::other(remote_error(&stderr)));
+        }
+        Ok(n)
+    }
+}
+
+impl Drop for RemoteStream {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl ToolFs for SshFs {
+    fn host(&self) -> Option<&str> {
+        Some(&self.host)
+    }
+
+    /// Relative paths without a working directory stay relative: the remote
+    /// shell starts in the home directory
+    fn join(&self, working_directory: &Option<String>, path: &str) -> String {
+        match working_directory {
+            Some(wd) if !path.starts_with('/') && !path.starts_with('~') => {
+                format!("{}/{}", wd.trim_end_matches('/'), path)
+            }
+            _ => path.to_string(),
+        }
+    }
+
+    fn resolve(&self, path: &str, for_write: bool) -> Result<ToolPath, String> {
+        self.resolve_all(&[path.to_string()], for_write)
+            .pop()
+            .unwrap_or_else(|| Err("No reply from remote host".to_string()))
+    }
+
+    fn resolve_all(&self, paths: &[String], for_write: bool) -> Vec<Result<ToolPath, String>> {
+        let mut script = RESOLVE_PRELUDE.to_string();
+        for path in paths.iter().filter(|path| check_path(path).is_ok()) {
+            script.push_str(&format!("info {}\n", remote_path_arg(path)));
+        }
+        let reply = match self.shell.output(&script) {
+            Ok(reply) => reply,
+            Err(e) => return paths.iter().map(|_| Err(e.clone())).collect(),
+        };
+        let mut lines = reply.lines();
+        let home = Path::new(lines.next().unwrap_or_default());
+        // An empty base would let every path through
+        if !home.is_absolute() {
+            let e = format!("Could not determine remote home directory: {:?}", home);
+            return paths.iter().map(|_| Err(e.clone())).collect();
+        }
+        paths
+            .iter()
+            .map(|path| {
+                check_path(path)?;
+                let line = lines
+                    .next()
+                    .ok_or_else(|| "No reply from remote host".to_string())?;
+                check_resolved(path, line, home, for_write)
+            })
+            .collect()
+    }
+
+    fn open(&self, path: &str) -> Result<Box<dyn Read + Send>, String> {
+        check_path(path)?;
+        let stream =
+            RemoteStream::spawn(&self.shell, &format!("cat -- {}", remote_path_arg(path)))?;
+        Ok(Box::new(stream))
+    }
+
+    fn read(&self, path: &str, max_bytes: u64) -> Result<Vec<u8>, String> {
+        check_path(path)?;
+        self.shell.output_bytes(&format!(
+            "head -c {} -- {}",
+            max_bytes,
+            remote_path_arg(path)
+        ))
+    }
+
+    fn read_all(&self, paths: &[String], max_bytes: u64) -> Vec<Result<Vec<u8>, String>> {
+        let mut script = String::new();
+        for path in paths {
+            // Keep one block per path so the replies stay in order
+            if check_path(path).is_err() {
+                script.push_str(&format!("echo '{}'\n", READ_FAILED));
+                continue;
+            }
+            script.push_str(&format!(
+                "if [ -f {0} ] && [ -r {0} ]; then head -c {1} -- {0} | base64; echo '{2}'; else echo '{3}'; fi\n",
+                remote_path_arg(path),
+                max_bytes,
+                READ_END,
+                READ_FAILED
+            ));
+        }
+        match self.shell.output_bytes(&script) {
+            Ok(output) => parse_read_all(&output, paths.len()),
+            Err(e) => paths.iter().map(|_| Err(e.clone())).collect(),
+        }
+    }
+
+    fn list_dir(&self, path: &str) -> Result<Vec<(String, bool)>, String> {
+        check_path(path)?;
+        let reply = self.shell.output(&format!(
+            r#"cd -- {} || exit 1
+for f in * .[!.]* ..?*; do
+  [ -e "$f" ] || [ -L "$f" ] || continue
+  if [ -d "$f" ] && [ ! -L "$f" ]; then printf 'd\t%s\n' "$f"; else printf 'f\t%s\n' "$f"; fi
+done"#,
+            remote_path_arg(path)
+        ))?;
+        Ok(reply
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(kind, name)| (name.to_string(), kind == "d"))
+            .collect())
+    }
+
+    fn walk_files(
+        &self,
+        root: &str,
+        max_depth: usize,
+    ) -> Result<Box<dyn Iterator<Item = String> + Send>, String> {
+        check_path(root)?;
+        let stream = RemoteStream::spawn(
+            &self.shell,
+            &format!(
+                "find {} -maxdepth {} \\( -name '.*' -o -name node_modules -o -name target \\) -prune -o -type f -print",
+                remote_path_arg(root),
+                max_depth
+            ),
+        )?;
+        let prefix = format!("{}/", root.trim_end_matches('/'));
+        let files = BufReader::new(stream)
+            .lines()
+            .map_while(Result::ok)
+            .map(move |path| {
+                path.strip_prefix(&prefix)
+                    .map(str::to_string)
+                    .unwrap_or(path)
+            });
+        Ok(Box::new(files))
+    }
+
+    fn write(&self, path: &str, content: &[u8], append: bool) -> Result<(), String> {
+        use std::io::Write;
+
+        check_path(path)?;
+        let redirect = if append { ">>" } else { ">" };
+        let mut child = self.shell.spawn(
+            &format!("cat {} {}", redirect, remote_path_arg(path)),
+            Stdio::piped(),
+            Stdio::null(),
+        )?;
+        let written = match child.stdin.take() {
+            Some(mut stdin) => stdin.write_all(content),
+            None => Ok(()),
+        };
+        // The remote error says more than the broken pipe it caused
+        finish(child)?;
+        written.map_err(|e| {
+            if append {
+                format!("Failed to append to file: {}", e)
+            } else {
+                format!("Failed to write file: {}", e)
+            }
+        })
+    }
+
+    fn create_dir_all(&self, path: &str) -> Result<(), String> {
+        check_path(path)?;
+        self.shell
+            .output(&format!("mkdir -p -- {}", remote_path_arg(path)))
+            .map(|_| ())
+            .map_err(|e| format!("Failed to create directory: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// The "remote host" is a local shell with its own home directory,
+    /// started there the way an ssh session would be
+    fn local_host(home: &Path) -> SshFs {
+        SshFs {
+            shell: RemoteShell {
+                program: "env".to_string(),
+                args: vec![
+                    format!("HOME={}", home.display()),
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    "cd && eval \"$1\"".to_string(),
+                    "sh".to_string(),
+                ],
+            },
+            host: "build-box".to_string(),
+        }
+    }
+
+    fn temp_home(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("aiterminal_sshfs_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("project/src")).unwrap();
+        fs::create_dir_all(dir.join(".ssh")).unwrap();
+        fs::canonicalize(&dir).unwrap()
+    }
+
+    #[test]
+    fn test_remote_paths_stay_inside_remote_home() {
+        let home = temp_home("limits");
+        fs::write(home.join("project/notes.txt"), "hello").unwrap();
+        let remote = local_host(&home);
+
+        let results = remote.resolve_all(
+            &[
+                "project/notes.txt".to_string(),
+                "~/project/new.txt".to_string(),
+                "project/../../outside".to_string(),
+                "/etc/passwd".to_string(),
+                "missing/dir/file".to_string(),
+            ],
+            false,
+        );
+        let notes = results[0].as_ref().unwrap();
+        assert_eq!(
+            notes.path,
+            home.join("project/notes.txt").display().to_string()
+        );
+        assert!(notes.is_file());
+        assert_eq!(notes.size(), 5);
+        let new = results[1].as_ref().unwrap();
+        assert!(!new.exists());
+        assert_eq!(new.path, home.join("project/new.txt").display().to_string());
+        assert!(results[2].as_ref().unwrap_err().contains("Access denied"));
+        assert!(results[3].as_ref().unwrap_err().contains("Access denied"));
+        assert!(results[4]
+            .as_ref()
+            .unwrap_err()
+            .contains("Parent directory does not exist"));
+
+        // Credential files are readable but never writable
+        assert!(remote.resolve("~/.ssh/authorized_keys", false).is_ok());
+        let denied = remote.resolve("~/.ssh/authorized_keys", true).unwrap_err();
+        assert!(denied.contains("sensitive"));
+        assert!(remote.resolve("~/.bashrc", true).is_err());
+
+        let _ = fs::remove_dir_all(&home);
+    }
+
+    #[test]
+    fn test_remote_read_write_list_and_walk() {
+        let home = temp_home("io");
+        let remote = local_host(&home);
+        let main = home.join("project/src/main.rs").display().to_string();
+
+        remote.write(&main, b"fn main() {}\n", false).unwrap();
+        remote.write(&main, b"// end\n", true).unwrap();
+        assert_eq!(fs::read_to_string(&main).unwrap(), "fn main() {}\n// end\n");
+        assert_eq!(remote.read(&main, 7).unwrap(), b"fn main");
+        let mut streamed = String::new();
+        remote
+            .open(&main)
+            .unwrap()
+            .read_to_string(&mut streamed)
+            .unwrap();
+        assert!(streamed.ends_with("// end\n"));
+        assert!(remote
+            .open("~/project/nope.rs")
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .is_err());
+
+        let binary: Vec<u8> = (0..=255).collect();
+        fs::write(home.join("project/blob.bin"), &binary).unwrap();
+        let reads = remote.read_all(
+            &[
+                "~/project/blob.bin".to_string(),
+                "~/project/missing".to_string(),
+                main.clone(),
+            ],
+            1_000,
+        );
+        assert_eq!(reads[0].as_ref().unwrap(), &binary);
+        assert!(reads[1].is_err());
+        assert_eq!(reads[2].as_ref().unwrap().len(), 20);
+
+        remote.create_dir_all("~/project/target/debug").unwrap();
+        fs::write(home.join("project/target/debug/out"), "x").unwrap();
+        fs::write(home.join("project/.hidden"), "x").unwrap();
+        let mut listing = remote.list_dir("~/project").unwrap();
+        listing.sort();
+        assert_eq!(
+            listing,
+            vec![
+                (".hidden".to_string(), false),
+                ("blob.bin".to_string(), false),
+                ("src".to_string(), true),
+                ("target".to_string(), true),
+            ]
+        );
+        let root = home.join("project").display().to_string();
+        let mut files: Vec<String> = remote.walk_files(&root, 10).unwrap().collect();
+        files.sort();
+        assert_eq!(files, vec!["blob.bin", "src/main.rs"]);
+
+        let _ = fs::remove_dir_all(&home);
+    }
+}
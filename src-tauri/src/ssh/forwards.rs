@@ -0,0 +1,565 @@
+//! Port forwards of SSH profiles kept running in the background.
+//!
+//! Each forward gets a supervisor thread. When the profile's host already has
+//! a ControlMaster connection (`ssh -O check` succeeds), the forward is added
+//! to it with `ssh -O forward`; otherwise a dedicated `ssh -N` process carries
+//! it. Local and dynamic forwards count as active once their local port
+//! accepts connections. Forwards that die or stop accepting connections are
+//! restarted with exponential backoff. Every state change is emitted as a
+//! `port-forward-status` event.
+
+use super::connect::{custom_options, forward_args, target_args};
+use super::{find_ssh_profile, PortForward, PortForwardType, SSHProfile};
+use crate::models::{
+    AppState, PORT_FORWARD_BACKOFF_MAX_MS, PORT_FORWARD_BACKOFF_START_MS,
+    PORT_FORWARD_CHECK_INTERVAL_MS, PORT_FORWARD_STABLE_MS, PORT_FORWARD_START_TIMEOUT_MS,
+};
+use serde::Serialize;
+use std::io::Read;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+
+/// Options for the background ssh process. BatchMode makes a key that needs
+/// a password fail fast instead of hanging on a prompt nobody sees.
+const PROCESS_OPTIONS: &[&str] = &[
+    "-N",
+    "-o",
+    "BatchMode=yes",
+    "-o",
+    "ExitOnForwardFailure=yes",
+    "-o",
+    "ServerAliveInterval=15",
+    "-o",
+    "ServerAliveCountMax=3",
+    "-o",
+    "LogLevel=ERROR",
+    "-o",
+    "ControlMaster=no",
+    "-o",
+    "ControlPath=none",
+];
+
+/// How often a starting forward is polled and a stop request is noticed
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ForwardState {
+    Starting,
+    Active,
+    /// Waiting out the backoff before the next attempt
+    Restarting,
+    Stopped,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ForwardTransport {
+    /// Dedicated `ssh -N` process
+    Process,
+    /// Added to an existing ControlMaster connection
+    ControlMaster,
+}
+
+/// Status of a supervised forward, as listed and emitted
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PortForwardInfo {
+    pub profile_id: String,
+    pub forward: PortForward,
+    pub state: ForwardState,
+    pub transport: Option<ForwardTransport>,
+    /// PID of the `ssh -N` process
+    pub pid: Option<u32>,
+    pub restarts: u32,
+    pub last_error: Option<String>,
+    /// Delay before the next attempt while restarting
+    pub retry_in_ms: Option<u64>,
+    /// Unix time in milliseconds of the last state change
+    pub updated_at: u64,
+}
+
+pub struct PortForwardSupervisor {
+    info: Arc<Mutex<PortForwardInfo>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl PortForwardSupervisor {
+    fn start(app: AppHandle, profile: &SSHProfile, forward: PortForward) -> Result<Self, String> {
+        let forward_args = forward_args(&forward)?;
+        let target_args = target_args(profile)?;
+        let info = Arc::new(Mutex::new(PortForwardInfo {
+            profile_id: profile.id.clone(),
+            forward,
+            state: ForwardState::Starting,
+            transport: None,
+            pid: None,
+            restarts: 0,
+            last_error: None,
+            retry_in_ms: None,
+            updated_at: now_ms(),
+        }));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let worker = Worker {
+            app,
+            info: info.clone(),
+            stop: stop.clone(),
+            custom_options: custom_options(profile),
+            forward_args,
+            target_args,
+        };
+        let thread = std::thread::spawn(move || worker.run());
+
+        Ok(Self {
+            info,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn info(&self) -> PortForwardInfo {
+        self.info
+            .lock()
+            .map(|info| info.clone())
+            .unwrap_or_else(|e| e.into_inner().clone())
+    }
+
+    fn is_running(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_some_and(|thread| !thread.is_finished())
+    }
+
+    /// Stop the forward and wait until its ssh process is gone
+    pub fn stop(mut self) -> PortForwardInfo {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.info()
+    }
+}
+
+/// A launched forward
+enum Running {
+    Process(Child),
+    ControlMaster,
+}
+
+struct Worker {
+    app: AppHandle,
+    info: Arc<Mutex<PortForwardInfo>>,
+    stop: Arc<AtomicBool>,
+    custom_options: Vec<String>,
+    forward_args: Vec<String>,
+    target_args: Vec<String>,
+}
+
+impl Worker {
+    fn run(self) {
+        let mut backoff = PORT_FORWARD_BACKOFF_START_MS;
+        while !self.stopped() {
+            let mut active_since = None;
+            let error = match self.launch() {
+                Ok(mut running) => {
+                    let result = self.supervise(&mut running, &mut active_since);
+                    self.shutdown(running);
+                    match result {
+                        Ok(()) => break,
+                        Err(e) => e,
+                    }
+                }
+                Err(e) => e,
+            };
+            if self.stopped() {
+                break;
+            }
+
+            // A forward that stayed up for a while starts over with a short delay
+            if active_since.is_some_and(|since: Instant| {
+                since.elapsed() >= Duration::from_millis(PORT_FORWARD_STABLE_MS)
+            }) {
+                backoff = PORT_FORWARD_BACKOFF_START_MS;
+            }
+            eprintln!("[SSH] Port forward {}: {}", self.label(), error);
+            self.update(|info| {
+                info.state = ForwardState::Restarting;
+                info.pid = None;
+                info.restarts += 1;
+                info.last_error = Some(error);
+                info.retry_in_ms = Some(backoff);
+            });
+            self.sleep(Duration::from_millis(backoff));
+            backoff = (backoff * 2).min(PORT_FORWARD_BACKOFF_MAX_MS);
+        }
+
+        self.update(|info| {
+            info.state = ForwardState::Stopped;
+            info.pid = None;
+            info.retry_in_ms = None;
+        });
+    }
+
+    fn launch(&self) -> Result<Running, String> {
+        let port = self.local_port();
+        if port.is_some_and(port_accepts) {
+            return Err(format!(
+                "Local port {} is already in use",
+                port.unwrap_or_default()
+            ));
+        }
+
+        if self.master_running() {
+            self.control(&["-O", "forward"])?;
+            self.update(|info| {
+                info.state = ForwardState::Starting;
+                info.transport = Some(ForwardTransport::ControlMaster);
+                info.retry_in_ms = None;
+            });
+            return Ok(Running::ControlMaster);
+        }
+
+        let child = Command::new("ssh")
+            .args(PROCESS_OPTIONS)
+            .args(&self.custom_options)
+            .args(&self.forward_args)
+            .args(&self.target_args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start ssh: {}", e))?;
+        let pid = child.id();
+        self.update(|info| {
+            info.state = ForwardState::Starting;
+            info.transport = Some(ForwardTransport::Process);
+            info.pid = Some(pid);
+            info.retry_in_ms = None;
+        });
+        Ok(Running::Process(child))
+    }
+
+    /// Watch a launched forward until it fails (Err) or a stop is requested (Ok)
+    fn supervise(
+        &self,
+        running: &mut Running,
+        active_since: &mut Option<Instant>,
+    ) -> Result<(), String> {
+        let started = Instant::now();
+        let mut next_check = Instant::now();
+        loop {
+            if self.stopped() {
+                return Ok(());
+            }
+            if Instant::now() < next_check {
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+
+            let alive = match running {
+                Running::Process(child) => match child.try_wait() {
+                    Ok(Some(status)) => return Err(exit_error(child, status)),
+                    Ok(None) => true,
+                    Err(e) => return Err(format!("Failed to check ssh process: {}", e)),
+                },
+                Running::ControlMaster => self.master_running(),
+            };
+            if !alive {
+                return Err("ControlMaster connection closed".to_string());
+            }
+
+            // Remote forwards listen on the server; the live connection is all we can see
+            let bound = self.local_port().is_none_or(port_accepts);
+            match (bound, *active_since) {
+                (true, None) => {
+                    *active_since = Some(Instant::now());
+                    self.update(|info| {
+                        info.state = ForwardState::Active;
+                        info.last_error = None;
+                    });
+                }
+                (false, Some(_)) => {
+                    return Err(format!(
+                        "Local port {} stopped accepting connections",
+                        self.local_port().unwrap_or_default()
+                    ))
+                }
+                (false, None)
+                    if started.elapsed()
+                        >= Duration::from_millis(PORT_FORWARD_START_TIMEOUT_MS) =>
+                {
+                    return Err(format!(
+                        "Local port {} was not bound within {} s",
+                        self.local_port().unwrap_or_default(),
+                        PORT_FORWARD_START_TIMEOUT_MS / 1000
+                    ))
+                }
+                _ => {}
+            }
+
+            next_check = Instant::now()
+                + match active_since {
+                    Some(_) => Duration::from_millis(PORT_FORWARD_CHECK_INTERVAL_MS),
+                    None => POLL_INTERVAL,
+                };
+        }
+    }
+
+    fn shutdown(&self, running: Running) {
+        match running {
+            Running::Process(mut child) => {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            Running::ControlMaster => {
+                if let Err(e) = self.control(&["-O", "cancel"]) {
+                    eprintln!(
+                        "[SSH] Failed to cancel port forward {}: {}",
+                        self.label(),
+                        e
+                    );
+                }
+            }
+        }
+    }
+
+    /// Whether the host has a live ControlMaster connection to reuse
+    fn master_running(&self) -> bool {
+        Command::new("ssh")
+            .args(["-O", "check", "-o", "BatchMode=yes"])
+            .args(&self.custom_options)
+            .args(&self.target_args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    }
+
+    /// Run an `ssh -O` control command for this forward
+    fn control(&self, command: &[&str]) -> Result<(), String> {
+        let output = Command::new("ssh")
+            .args(command)
+            .args(["-o", "BatchMode=yes"])
+            .args(&self.custom_options)
+            .args(&self.forward_args)
+            .args(&self.target_args)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| format!("Failed to run ssh {}: {}", command.join(" "), e))?;
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "ssh {} failed: {}",
+                command.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+
+    fn local_port(&self) -> Option<u16> {
+        let info = self.info.lock().ok()?;
+        match info.forward.forward_type {
+            PortForwardType::Local | PortForwardType::Dynamic => Some(info.forward.local_port),
+            PortForwardType::Remote => None,
+        }
+    }
+
+    fn label(&self) -> String {
+        match self.info.lock() {
+            Ok(info) => format!("{}/{}", info.profile_id, info.forward.id),
+            Err(_) => self.forward_args.join(" "),
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::SeqCst)
+    }
+
+    /// Sleep unless a stop is requested first
+    fn sleep(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        while !self.stopped() && Instant::now() < until {
+            std::thread::sleep(POLL_INTERVAL.min(until - Instant::now()));
+        }
+    }
+
+    fn update(&self, change: impl FnOnce(&mut PortForwardInfo)) {
+        let snapshot = match self.info.lock() {
+            Ok(mut info) => {
+                change(&mut info);
+                info.updated_at = now_ms();
+                info.clone()
+            }
+            Err(_) => return,
+        };
+        let _ = self.app.emit("port-forward-status", snapshot);
+    }
+}
+
+/// Whether something accepts connections on the local port (IPv4 or IPv6 loopback)
+fn port_accepts(port: u16) -> bool {
+    let timeout = Duration::from_millis(500);
+    [
+        SocketAddr::from((Ipv4Addr::LOCALHOST, port)),
+        SocketAddr::from((Ipv6Addr::LOCALHOST, port)),
+    ]
+    .iter()
+    .any(|addr| TcpStream::connect_timeout(addr, timeout).is_ok())
+}
+
+fn exit_error(child: &mut Child, status: std::process::ExitStatus) -> String {
+    let mut stderr = String::new();
+    if let Some(pipe) = child.stderr.as_mut() {
+        let _ = pipe.read_to_string(&mut stderr);
+    }
+    match stderr.trim().lines().last() {
+        Some(line) => format!("ssh exited ({}): {}", status, line),
+        None => format!("ssh exited ({})", status),
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+fn forward_key(profile_id: &str, forward_id: &str) -> String {
+    format!("{}/{}", profile_id, forward_id)
+}
+
+/// Stop every forward, e.g. when the app exits
+pub fn stop_all_port_forwards(state: &AppState) {
+    let supervisors: Vec<PortForwardSupervisor> = match state.port_forwards.lock() {
+        Ok(mut forwards) => forwards.drain().map(|(_, supervisor)| supervisor).collect(),
+        Err(_) => return,
+    };
+    for supervisor in supervisors {
+        supervisor.stop();
+    }
+}
+
+/// Tauri command: Start a profile's port forwards (all of them, or the given IDs).
+/// Forwards that are already running are left alone.
+#[tauri::command]
+pub async fn start_port_forwards(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    profile_id: String,
+    forward_ids: Option<Vec<String>>,
+) -> Result<Vec<PortForwardInfo>, String> {
+    let profile = find_ssh_profile(&profile_id)?;
+    let forwards = profile.port_forwards.clone().unwrap_or_default();
+    if let Some(ids) = &forward_ids {
+        if let Some(missing) = ids.iter().find(|id| !forwards.iter().any(|f| &f.id == *id)) {
+            return Err(format!(
+                "Port forward {} not found in profile {}",
+                missing, profile_id
+            ));
+        }
+    }
+
+    let mut supervisors = state
+        .port_forwards
+        .lock()
+        .map_err(|e| format!("Failed to acquire port forward lock: {}", e))?;
+    let mut started = Vec::new();
+    for forward in forwards {
+        if forward_ids
+            .as_ref()
+            .is_some_and(|ids| !ids.contains(&forward.id))
+        {
+            continue;
+        }
+        let key = forward_key(&profile_id, &forward.id);
+        if let Some(supervisor) = supervisors.get(&key).filter(|s| s.is_running()) {
+            started.push(supervisor.info());
+            continue;
+        }
+        let supervisor = PortForwardSupervisor::start(app.clone(), &profile, forward)?;
+        started.push(supervisor.info());
+        supervisors.insert(key, supervisor);
+    }
+    Ok(started)
+}
+
+/// Tauri command: Stop a profile's port forwards (all of them, or the given IDs)
+#[tauri::command]
+pub async fn stop_port_forwards(
+    state: State<'_, AppState>,
+    profile_id: String,
+    forward_ids: Option<Vec<String>>,
+) -> Result<Vec<PortForwardInfo>, String> {
+    let prefix = forward_key(&profile_id, "");
+    let stopping: Vec<PortForwardSupervisor> = {
+        let mut supervisors = state
+            .port_forwards
+            .lock()
+            .map_err(|e| format!("Failed to acquire port forward lock: {}", e))?;
+        let keys: Vec<String> = supervisors
+            .keys()
+            .filter(|key| match (key.strip_prefix(&prefix), &forward_ids) {
+                (Some(id), Some(ids)) => ids.iter().any(|wanted| wanted == id),
+                (Some(_), None) => true,
+                (None, _) => false,
+            })
+            .cloned()
+            .collect();
+        keys.iter()
+            .filter_map(|key| supervisors.remove(key))
+            .collect()
+    };
+
+    // Joining waits for the ssh processes to exit, so do it without the lock
+    Ok(stopping
+        .into_iter()
+        .map(PortForwardSupervisor::stop)
+        .collect())
+}
+
+/// Tauri command: Status of every supervised port forward
+#[tauri::command]
+pub async fn list_port_forwards(
+    state: State<'_, AppState>,
+) -> Result<Vec<PortForwardInfo>, String> {
+    let supervisors = state
+        .port_forwards
+        .lock()
+        .map_err(|e| format!("Failed to acquire port forward lock: {}", e))?;
+    let mut forwards: Vec<PortForwardInfo> = supervisors
+        .values()
+        .map(PortForwardSupervisor::info)
+        .collect();
+    forwards.sort_by(|a, b| (&a.profile_id, &a.forward.id).cmp(&(&b.profile_id, &b.forward.id)));
+    Ok(forwards)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_port_accepts_sees_listeners() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(port_accepts(port));
+        drop(listener);
+        assert!(!port_accepts(port));
+
+        assert_eq!(forward_key("prod", "jupyter"), "prod/jupyter");
+        assert_eq!(
+            forward_key("prod", "jupyter").strip_prefix(&forward_key("prod", "")),
+            Some("jupyter")
+        );
+    }
+}
@@ -1,8 +1,37 @@
+mod config;
+mod connect;
+mod exchange;
+mod files;
+mod forwards;
+#[cfg(feature = "native-ssh")]
+mod native;
+mod remote;
+#[cfg(all(test, feature = "native-ssh"))]
+pub(crate) mod test_server;
+mod transfer;
+
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
+use config::ConfigSource;
+pub use config::ResolvedSshHost;
+pub use connect::ssh_command_line;
+pub use exchange::{
+    export_ssh_config, export_ssh_profile_bundle, import_ssh_config_hosts,
+    import_ssh_profile_bundle,
+};
+pub use files::SshFs;
+pub use forwards::{
+    list_port_forwards, start_port_forwards, stop_all_port_forwards, stop_port_forwards,
+    PortForwardSupervisor,
+};
+pub use remote::{
+    sftp_list_dir, sftp_read_file, NativeSshConnection, RemoteChannel, RemoteFs,
+};
+pub use transfer::{download_file, upload_file};
+
 const SSH_PROFILES_FILE: &str = ".config/aiterminal/ssh_profiles.json";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -38,6 +67,8 @@ pub struct SSHProfile {
     pub health_check_interval: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub alert_on_disconnect: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_reconnect: Option<bool>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub created_at: Option<String>,
@@ -106,120 +137,52 @@ pub struct SSHConfigHost {
     pub proxy_jump: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub options: Option<HashMap<String, String>>,
+    /// Config problems met while resolving this host
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warnings: Option<Vec<String>>,
 }
 
-/// Parse SSH config file and extract Host entries
+/// Parse SSH config file and extract Host entries.
+///
+/// Each concrete alias is resolved with Include, Match and wildcard blocks
+/// applied, so values inherited from e.g. `Host *` show up too.
 pub fn parse_ssh_config() -> Result<Vec<SSHConfigHost>, String> {
-    let home =
-        std::env::var("HOME").map_err(|_| "Could not determine HOME directory".to_string())?;
-
-    let config_path = PathBuf::from(home).join(".ssh/config");
-
-    if !config_path.exists() {
-        return Ok(Vec::new());
-    }
-
-    let content = fs::read_to_string(&config_path)
-        .map_err(|e| format!("Failed to read SSH config: {}", e))?;
+    let source = ConfigSource::user()?;
 
     let mut hosts = Vec::new();
-    let mut current_host: Option<SSHConfigHost> = None;
-    let mut current_options: HashMap<String, String> = HashMap::new();
-
-    for line in content.lines() {
-        let line = line.trim();
-
-        // Skip comments and empty lines
-        if line.is_empty() || line.starts_with('#') {
-            continue;
-        }
-
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.is_empty() {
-            continue;
-        }
-
-        let key = parts[0].to_lowercase();
-        let value = parts.get(1..).map(|v| v.join(" "));
-
-        match key.as_str() {
-            "host" => {
-                // Save previous host if exists
-                if let Some(mut host) = current_host.take() {
-                    if !current_options.is_empty() {
-                        host.options = Some(current_options.clone());
-                        current_options.clear();
-                    }
-                    hosts.push(host);
-                }
-
-                // Start new host
-                if let Some(host_name) = value {
-                    // Skip wildcard patterns
-                    if !host_name.contains('*') && !host_name.contains('?') {
-                        current_host = Some(SSHConfigHost {
-                            host: host_name,
-                            hostname: None,
-                            user: None,
-                            port: None,
-                            identity_file: None,
-                            proxy_jump: None,
-                            options: None,
-                        });
-                    }
-                }
-            }
-            "hostname" => {
-                if let Some(ref mut host) = current_host {
-                    host.hostname = value;
-                }
+    for alias in config::host_aliases(&source)? {
+        // One broken block should not hide every other host
+        let resolved = match config::resolve(&source, &alias) {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                eprintln!("[SSH] Skipping config host {}: {}", alias, e);
+                continue;
             }
-            "user" => {
-                if let Some(ref mut host) = current_host {
-                    host.user = value;
-                }
-            }
-            "port" => {
-                if let Some(ref mut host) = current_host {
-                    if let Some(port_str) = value {
-                        host.port = port_str.parse().ok();
-                    }
-                }
-            }
-            "identityfile" => {
-                if let Some(ref mut host) = current_host {
-                    if let Some(mut path) = value {
-                        // Expand ~ to home directory
-                        if path.starts_with("~/") {
-                            path =
-                                path.replacen("~", &std::env::var("HOME").unwrap_or_default(), 1);
-                        }
-                        host.identity_file = Some(path);
-                    }
-                }
-            }
-            "proxyjump" => {
-                if let Some(ref mut host) = current_host {
-                    host.proxy_jump = value;
-                }
-            }
-            _ => {
-                // Store other options
-                if current_host.is_some() {
-                    if let Some(val) = value {
-                        current_options.insert(key.to_string(), val);
-                    }
-                }
-            }
-        }
-    }
-
-    // Don't forget the last host
-    if let Some(mut host) = current_host {
-        if !current_options.is_empty() {
-            host.options = Some(current_options);
-        }
-        hosts.push(host);
+        };
+        let is_set = |key: &str| resolved.options.contains_key(key);
+
+        let options: HashMap<String, String> = resolved
+            .options
+            .iter()
+            .filter(|(key, _)| {
+                !matches!(
+                    key.as_str(),
+                    "hostname" | "user" | "port" | "identityfile" | "proxyjump"
+                )
+            })
+            .map(|(key, values)| (key.clone(), values.join(" ")))
+            .collect();
+
+        hosts.push(SSHConfigHost {
+            hostname: is_set("hostname").then(|| resolved.hostname.clone()),
+            user: is_set("user").then(|| resolved.user.clone()),
+            port: is_set("port").then_some(resolved.port),
+            identity_file: resolved.identity_files.first().cloned(),
+            proxy_jump: resolved.proxy_jump.clone(),
+            options: (!options.is_empty()).then_some(options),
+            warnings: (!resolved.warnings.is_empty()).then(|| resolved.warnings.clone()),
+            host: alias,
+        });
     }
 
     Ok(hosts)
@@ -238,6 +201,12 @@ pub async fn get_ssh_config_hosts() -> Result<Vec<SSHConfigHost>, String> {
     parse_ssh_config()
 }
 
+/// Tauri command: Effective SSH configuration for a host alias, like `ssh -G`
+#[tauri::command]
+pub async fn resolve_ssh_host(alias: String) -> Result<ResolvedSshHost, String> {
+    config::resolve(&ConfigSource::user()?, &alias)
+}
+
 /// Tauri command: Save SSH profiles
 #[tauri::command]
 pub async fn save_ssh_profiles(profiles: Vec<SSHProfile>) -> Result<(), String> {
@@ -260,24 +229,81 @@ pub async fn save_ssh_profiles(profiles: Vec<SSHProfile>) -> Result<(), String>
     Ok(())
 }
 
-/// Tauri command: Load SSH profiles
-#[tauri::command]
-pub async fn load_ssh_profiles() -> Result<Vec<SSHProfile>, String> {
+/// Read saved SSH profiles (empty if none were saved yet)
+pub fn read_ssh_profiles() -> Result<Vec<SSHProfile>, String> {
     let path = get_ssh_profiles_path()?;
-
-    println!("[SSH] Loading profiles from {:?}", path);
-
     if !path.exists() {
-        println!("[SSH] Profiles file doesn't exist, returning empty list");
         return Ok(Vec::new());
     }
 
     let content =
         fs::read_to_string(&path).map_err(|e| format!("Failed to read profiles: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse profiles: {}", e))
+}
 
-    let profiles: Vec<SSHProfile> =
+/// Saved SSH profile by ID
+pub fn find_ssh_profile(id: &str) -> Result<SSHProfile, String> {
+    read_ssh_profiles()?
+        .into_iter()
+        .find(|profile| profile.id == id)
+        .ok_or_else(|| format!("SSH profile not found: {}", id))
+}
+
+/// Set `lastConnectedAt` and bump `connectionCount` of a saved profile.
+/// Edits the JSON in place so fields this struct does not know survive.
+pub fn record_ssh_connection(id: &str) -> Result<(), String> {
+    let path = get_ssh_profiles_path()?;
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read profiles: {}", e))?;
+    let mut profiles: Vec<serde_json::Value> =
         serde_json::from_str(&content).map_err(|e| format!("Failed to parse profiles: {}", e))?;
 
+    let profile = profiles
+        .iter_mut()
+        .find(|profile| profile["id"] == id)
+        .and_then(|profile| profile.as_object_mut())
+        .ok_or_else(|| format!("SSH profile not found: {}", id))?;
+    let count = profile
+        .get("connectionCount")
+        .and_then(|count| count.as_u64())
+        .unwrap_or(0);
+    profile.insert(
+        "lastConnectedAt".to_string(),
+        chrono::Utc::now()
+            .to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+            .into(),
+    );
+    profile.insert("connectionCount".to_string(), (count + 1).into());
+
+    let json = serde_json::to_string_pretty(&profiles)
+        .map_err(|e| format!("Failed to serialize profiles: {}", e))?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write profiles: {}", e))
+}
+
+/// Open a profile with the in-process SSH client: a shell with a
+/// `cols` x `rows` PTY and SFTP on the same connection
+#[cfg(feature = "native-ssh")]
+pub fn connect_native(
+    profile: &SSHProfile,
+    cols: u16,
+    rows: u16,
+) -> Result<NativeSshConnection, String> {
+    native::connect(profile, cols, rows)
+}
+
+#[cfg(not(feature = "native-ssh"))]
+pub fn connect_native(
+    _profile: &SSHProfile,
+    _cols: u16,
+    _rows: u16,
+) -> Result<NativeSshConnection, String> {
+    Err("Native SSH support is not compiled in (build with --features native-ssh)".to_string())
+}
+
+/// Tauri command: Load SSH profiles
+#[tauri::command]
+pub async fn load_ssh_profiles() -> Result<Vec<SSHProfile>, String> {
+    let profiles = read_ssh_profiles()?;
     println!("[SSH] Loaded {} profiles", profiles.len());
     Ok(profiles)
 }
@@ -0,0 +1,604 @@
+//! In-process SSH client on libssh2: a shell channel with a remote PTY and
+//! SFTP on the same connection, authenticated with the agent or the
+//! profile's identity files, optionally through one jump host.
+
+use super::config::{self, ConfigSource};
+use super::remote::{NativeSshConnection, RemoteChannel, RemoteEntry, RemoteFs};
+use super::{ConnectionType, SSHProfile};
+use crate::models::{NATIVE_SSH_CONNECT_TIMEOUT_MS, NATIVE_SSH_POLL_MAX_MS};
+use ssh2::{CheckResult, ErrorCode, KnownHostFileKind, Session, Sftp};
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// libssh2's "would block" result in non-blocking mode
+const LIBSSH2_ERROR_EAGAIN: i32 = -37;
+/// End of a directory listing
+const LIBSSH2_ERROR_FILE: i32 = -16;
+/// Tried in order when neither the profile nor ssh_config names a key
+const DEFAULT_IDENTITY_FILES: &[&str] = &["~/.ssh/id_ed25519", "~/.ssh/id_ecdsa", "~/.ssh/id_rsa"];
+
+/// Where to connect and as whom
+#[derive(Debug, Clone)]
+struct Endpoint {
+    host: String,
+    port: u16,
+    user: String,
+    identity_files: Vec<String>,
+}
+
+fn would_block(e: &ssh2::Error) -> bool {
+    e.code() == ErrorCode::Session(LIBSSH2_ERROR_EAGAIN)
+}
+
+/// Sleeps between polls of an idle non-blocking session, growing up to
+/// `NATIVE_SSH_POLL_MAX_MS` and starting over once data moves
+struct Backoff(u64);
+
+impl Backoff {
+    fn new() -> Self {
+        Self(1)
+    }
+
+    fn wait(&mut self) {
+        std::thread::sleep(Duration::from_millis(self.0));
+        self.0 = (self.0 * 2).min(NATIVE_SSH_POLL_MAX_MS);
+    }
+
+    fn reset(&mut self) {
+        self.0 = 1;
+    }
+}
+
+/// Repeat a libssh2 call until it stops reporting EAGAIN
+fn retry<T>(mut call: impl FnMut() -> Result<T, ssh2::Error>) -> Result<T, ssh2::Error> {
+    let mut backoff = Backoff::new();
+    loop {
+        match call() {
+            Err(e) if would_block(&e) => backoff.wait(),
+            result => return result,
+        }
+    }
+}
+
+/// Split `[user@]host[:port]`
+fn parse_hop(spec: &str) -> (Option<&str>, &str, Option<u16>) {
+    let (user, rest) = match spec.split_once('@') {
+        Some((user, rest)) => (Some(user), rest),
+        None => (None, spec),
+    };
+    match rest.rsplit_once(':') {
+        Some((host, port)) => match port.parse() {
+            Ok(port) => (user, host, Some(port)),
+            Err(_) => (user, rest, None),
+        },
+        None => (user, rest, None),
+    }
+}
+
+/// An ssh_config alias expanded, falling back to the literal host
+fn resolve_alias(source: &ConfigSource, alias: &str) -> Result<Endpoint, String> {
+    let resolved = config::resolve(source, alias)?;
+    Ok(Endpoint {
+        host: resolved.hostname,
+        port: resolved.port,
+        user: resolved.user,
+        identity_files: resolved.identity_files,
+    })
+}
+
+/// The destination and the jump host in front of it, if any
+fn endpoints(
+    profile: &SSHProfile,
+    source: &ConfigSource,
+) -> Result<(Endpoint, Option<Endpoint>), String> {
+    let (target, proxy_jump) = match (
+        &profile.connection_type,
+        &profile.ssh_config_host,
+        &profile.manual_config,
+    ) {
+        (ConnectionType::SshConfig, Some(alias), _) => {
+            let resolved = config::resolve(source, alias)?;
+            let target = Endpoint {
+                host: resolved.hostname,
+                port: resolved.port,
+                user: resolved.user,
+                identity_files: resolved.identity_files,
+            };
+            (target, resolved.proxy_jump)
+        }
+        (ConnectionType::Manual, _, Some(manual)) => {
+            let target = Endpoint {
+                host: manual.hostname.clone(),
+                port: manual.port.unwrap_or(22),
+                user: manual.username.clone(),
+                identity_files: manual
+                    .identity_file
+                    .iter()
+                    .filter(|file| !file.is_empty())
+                    .map(|file| shellexpand::tilde(file).to_string())
+                    .collect(),
+            };
+            (target, manual.proxy_jump.clone())
+        }
+        _ => return Err(format!("Invalid profile configuration: {}", profile.name)),
+    };
+
+    let jump = match proxy_jump
+        .as_deref()
+        .map(str::trim)
+        .filter(|jump| !jump.is_empty() && !jump.eq_ignore_ascii_case("none"))
+    {
+        Some(jump) if jump.contains(',') => {
+            return Err(format!(
+                "Native SSH supports a single jump host, not {}",
+                jump
+            ))
+        }
+        Some(jump) => {
+            let (user, host, port) = parse_hop(jump);
+            let mut hop = resolve_alias(source, host)?;
+            if let Some(user) = user {
+                hop.user = user.to_string();
+            }
+            if let Some(port) = port {
+                hop.port = port;
+            }
+            Some(hop)
+        }
+        None => None,
+    };
+    Ok((target, jump))
+}
+
+fn connect_tcp(host: &str, port: u16) -> Result<TcpStream, String> {
+    let timeout = Duration::from_millis(NATIVE_SSH_CONNECT_TIMEOUT_MS);
+    let addrs = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve {}: {}", host, e))?;
+    let mut last_error = format!("No addresses found for {}", host);
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = format!("Failed to connect to {}:{}: {}", host, port, e),
+        }
+    }
+    Err(last_error)
+}
+
+/// Refuse hosts that are missing from, or disagree with, ~/.ssh/known_hosts
+fn verify_host_key(session: &Session, endpoint: &Endpoint, home: &Path) -> Result<(), String> {
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| format!("{} sent no host key", endpoint.host))?;
+    let mut known_hosts = session
+        .known_hosts()
+        .map_err(|e| format!("Failed to initialise known hosts: {}", e))?;
+    let path = home.join(".ssh/known_hosts");
+    if path.exists() {
+        known_hosts
+            .read_file(&path, KnownHostFileKind::OpenSSH)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    }
+    match known_hosts.check_port(&endpoint.host, endpoint.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(format!(
+            "Host key for {} does not match ~/.ssh/known_hosts; refusing to connect",
+            endpoint.host
+        )),
+        CheckResult::NotFound => Err(format!(
+            "{} is not in ~/.ssh/known_hosts; connect once with ssh to verify its host key",
+            endpoint.host
+        )),
+        CheckResult::Failure => Err(format!("Failed to check the host key of {}", endpoint.host)),
+    }
+}
+
+/// Handshake, host key check and authentication, in blocking mode
+fn open_session(stream: TcpStream, endpoint: &Endpoint, home: &Path) -> Result<Session, String> {
+    let mut session = Session::new().map_err(|e| format!("Failed to create SSH session: {}", e))?;
+    session.set_timeout(NATIVE_SSH_CONNECT_TIMEOUT_MS as u32);
+    session.set_tcp_stream(stream);
+    session
+        .handshake()
+        .map_err(|e| format!("SSH handshake with {} failed: {}", endpoint.host, e))?;
+    verify_host_key(&session, endpoint, home)?;
+
+    if session.userauth_agent(&endpoint.user).is_ok() {
+        return Ok(session);
+    }
+
+    let identity_files: Vec<String> = if endpoint.identity_files.is_empty() {
+        DEFAULT_IDENTITY_FILES
+            .iter()
+            .map(|file| shellexpand::tilde(file).to_string())
+            .filter(|file| Path::new(file).exists())
+            .collect()
+    } else {
+        endpoint.identity_files.clone()
+    };
+    let mut last_error = String::from("no agent identity or key file was accepted");
+    for file in &identity_files {
+        match session.userauth_pubkey_file(&endpoint.user, None, Path::new(file), None) {
+            Ok(()) => return Ok(session),
+            Err(e) => last_error = format!("{}: {}", file, e),
+        }
+    }
+    Err(format!(
+        "Authentication as {}@{} failed ({}). Encrypted keys must be loaded into ssh-agent.",
+        endpoint.user, endpoint.host, last_error
+    ))
+}
+
+/// Copy bytes between a local socket and a direct-tcpip channel on the jump
+/// host until either side closes
+fn pump_tunnel(jump: Session, mut channel: ssh2::Channel, mut socket: TcpStream) {
+    let mut buf = vec![0u8; 32 * 1024];
+    let mut backoff = Backoff::new();
+    'pump: loop {
+        let mut moved = false;
+        match channel.read(&mut buf) {
+            Ok(0) if channel.eof() => break,
+            Ok(0) => {}
+            Ok(n) => {
+                let mut pending = &buf[..n];
+                while !pending.is_empty() {
+                    match socket.write(pending) {
+                        Ok(written) => pending = &pending[written..],
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => backoff.wait(),
+                        Err(_) => break 'pump,
+                    }
+                }
+                moved = true;
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(_) => break,
+        }
+        match socket.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                let mut pending = &buf[..n];
+                while !pending.is_empty() {
+                    match channel.write(pending) {
+                        Ok(written) => pending = &pending[written..],
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => backoff.wait(),
+                        Err(_) => break 'pump,
+                    }
+                }
+                moved = true;
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+            Err(_) => break,
+        }
+        if moved {
+            backoff.reset();
+        } else {
+            backoff.wait();
+        }
+    }
+    let _ = retry(|| channel.close());
+    drop(jump);
+}
+
+/// A TCP stream to `target` carried through the jump host
+fn tunnel(jump: &Endpoint, target: &Endpoint, home: &Path) -> Result<TcpStream, String> {
+    let session = open_session(connect_tcp(&jump.host, jump.port)?, jump, home)?;
+    let channel = session
+        .channel_direct_tcpip(&target.host, target.port, None)
+        .map_err(|e| {
+            format!(
+                "Jump host {} could not reach {}:{}: {}",
+                jump.host, target.host, target.port, e
+            )
+        })?;
+
+    // libssh2 needs a real socket, so the channel is bridged to a loopback pair
+    let listener = TcpListener::bind(("127.0.0.1", 0))
+        .map_err(|e| format!("Failed to open tunnel socket: {}", e))?;
+    let addr = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to open tunnel socket: {}", e))?;
+    let local =
+        TcpStream::connect(addr).map_err(|e| format!("Failed to open tunnel socket: {}", e))?;
+    let expected_peer = local
+        .local_addr()
+        .map_err(|e| format!("Failed to open tunnel socket: {}", e))?;
+    let (remote, peer) = listener
+        .accept()
+        .map_err(|e| format!("Failed to open tunnel socket: {}", e))?;
+    if peer != expected_peer {
+        return Err("Unexpected connection on the tunnel socket".to_string());
+    }
+    remote
+        .set_nonblocking(true)
+        .map_err(|e| format!("Failed to open tunnel socket: {}", e))?;
+
+    session.set_blocking(false);
+    std::thread::spawn(move || pump_tunnel(session, channel, remote));
+    Ok(local)
+}
+
+struct NativeChannel {
+    // Keeps the connection (and any tunnel) open while the channel is used
+    _session: Session,
+    channel: Mutex<ssh2::Channel>,
+    exit: Mutex<Option<u32>>,
+}
+
+impl NativeChannel {
+    fn finish(&self, channel: &ssh2::Channel) {
+        if let Ok(mut exit) = self.exit.lock() {
+            if exit.is_none() {
+                *exit = Some(channel.exit_status().unwrap_or(0).max(0) as u32);
+            }
+        }
+    }
+}
+
+impl RemoteChannel for NativeChannel {
+    fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut backoff = Backoff::new();
+        loop {
+            if self.exit_status().is_some() {
+                return Ok(0);
+            }
+            {
+                let mut channel = self
+                    .channel
+                    .lock()
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+                match channel.read(buf) {
+                    Ok(0) if channel.eof() => {
+                        self.finish(&channel);
+                        return Ok(0);
+                    }
+                    Ok(0) => {}
+                    Ok(n) => return Ok(n),
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(e) => {
+                        self.finish(&channel);
+                        return Err(e);
+                    }
+                }
+            }
+            backoff.wait();
+        }
+    }
+
+    fn write(&self, data: &[u8]) -> io::Result<usize> {
+        let mut backoff = Backoff::new();
+        loop {
+            {
+                let mut channel = self
+                    .channel
+                    .lock()
+                    .map_err(|e| io::Error::other(e.to_string()))?;
+                match channel.write(data) {
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    result => return result,
+                }
+            }
+            backoff.wait();
+        }
+    }
+
+    fn resize(&self, cols: u16, rows: u16) -> io::Result<()> {
+        let mut channel = self
+            .channel
+            .lock()
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        retry(|| channel.request_pty_size(cols as u32, rows as u32, None, None))?;
+        Ok(())
+    }
+
+    fn exit_status(&self) -> Option<u32> {
+        self.exit.lock().ok().and_then(|exit| *exit)
+    }
+
+    fn close(&self) -> io::Result<()> {
+        let mut channel = self
+            .channel
+            .lock()
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        let result = retry(|| channel.close());
+        self.finish(&channel);
+        result.map_err(io::Error::from)
+    }
+}
+
+struct NativeFs {
+    sftp: Sftp,
+}
+
+/// Join a directory listing name onto its remote (always `/`-separated) parent
+fn join_remote(dir: &str, name: &str) -> String {
+    if dir.is_empty() || dir == "." {
+        name.to_string()
+    } else if dir.ends_with('/') {
+        format!("{}{}", dir, name)
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+fn entry(path: &str, name: String, stat: &ssh2::FileStat) -> RemoteEntry {
+    RemoteEntry {
+        name,
+        path: path.to_string(),
+        is_dir: stat.is_dir(),
+        size: stat.size.unwrap_or(0),
+        modified: stat.mtime,
+    }
+}
+
+impl RemoteFs for NativeFs {
+    fn list(&self, path: &str) -> Result<Vec<RemoteEntry>, String> {
+        let mut dir = retry(|| self.sftp.opendir(path))
+            .map_err(|e| format!("Failed to open directory {}: {}", path, e))?;
+        let mut entries = Vec::new();
+        loop {
+            match retry(|| dir.readdir()) {
+                Ok((name, stat)) => {
+                    let name = name.to_string_lossy().into_owned();
+                    if name != "." && name != ".." {
+                        entries.push(entry(&join_remote(path, &name), name, &stat));
+                    }
+                }
+                Err(e) if e.code() == ErrorCode::Session(LIBSSH2_ERROR_FILE) => break,
+                Err(e) => return Err(format!("Failed to list directory {}: {}", path, e)),
+            }
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+
+    fn stat(&self, path: &str) -> Result<RemoteEntry, String> {
+        let stat = retry(|| self.sftp.stat(Path::new(path)))
+            .map_err(|e| format!("Failed to stat {}: {}", path, e))?;
+        let name = path
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or(path)
+            .to_string();
+        Ok(entry(path, name, &stat))
+    }
+
+    fn read(&self, path: &str, max_bytes: usize) -> Result<Vec<u8>, String> {
+        let mut file = retry(|| self.sftp.open(path))
+            .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+        let mut content = vec![0u8; max_bytes];
+        let mut filled = 0;
+        let mut backoff = Backoff::new();
+        while filled < max_bytes {
+            match file.read(&mut content[filled..]) {
+                Ok(0) => break,
+                Ok(n) => {
+                    filled += n;
+                    backoff.reset();
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => backoff.wait(),
+                Err(e) => return Err(format!("Failed to read {}: {}", path, e)),
+            }
+        }
+        content.truncate(filled);
+        Ok(content)
+    }
+}
+
+/// Connect, then open a shell with a `cols` x `rows` PTY and an SFTP session
+pub fn connect(profile: &SSHProfile, cols: u16, rows: u16) -> Result<NativeSshConnection, String> {
+    let source = ConfigSource::user()?;
+    let (target, jump) = endpoints(profile, &source)?;
+    let stream = match &jump {
+        Some(jump) => tunnel(jump, &target, &source.home)?,
+        None => connect_tcp(&target.host, target.port)?,
+    };
+    let session = open_session(stream, &target, &source.home)?;
+
+    let mut channel = session
+        .channel_session()
+        .map_err(|e| format!("Failed to open SSH channel: {}", e))?;
+    // Servers only accept names listed in their AcceptEnv, so refusals are expected
+    let mut env_vars: Vec<_> = profile.env_vars.iter().flatten().collect();
+    env_vars.sort();
+    for (name, value) in env_vars {
+        let _ = channel.setenv(name, value);
+    }
+    channel
+        .request_pty(
+            "xterm-256color",
+            None,
+            Some((cols as u32, rows as u32, 0, 0)),
+        )
+        .map_err(|e| format!("Failed to request remote PTY: {}", e))?;
+    channel
+        .shell()
+        .map_err(|e| format!("Failed to start remote shell: {}", e))?;
+    let sftp = session
+        .sftp()
+        .map_err(|e| format!("Failed to start SFTP: {}", e))?;
+
+    // Reads poll so that input and SFTP requests never wait behind them
+    session.set_blocking(false);
+
+    Ok(NativeSshConnection {
+        host: target.host,
+        user: target.user,
+        port: target.port,
+        channel: Arc::new(NativeChannel {
+            _session: session,
+            channel: Mutex::new(channel),
+            exit: Mutex::new(None),
+        }),
+        fs: Arc::new(NativeFs { sftp }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_endpoints_from_manual_and_config_profiles() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/ssh/fixtures/home");
+        let source = ConfigSource {
+            path: dir.join(".ssh/config"),
+            home: dir.clone(),
+            local_user: "me".to_string(),
+        };
+        let profile: SSHProfile = serde_json::from_value(serde_json::json!({
+            "id": "p1",
+            "name": "Cluster",
+            "connectionType": "manual",
+            "manualConfig": {
+                "hostname": "login.example.com",
+                "username": "alice",
+                "port": 2222,
+                "identityFile": "/keys/id_ed25519",
+                "proxyJump": "bastion:2200"
+            }
+        }))
+        .unwrap();
+        let (target, jump) = endpoints(&profile, &source).unwrap();
+        assert_eq!(
+            (target.host.as_str(), target.port, target.user.as_str()),
+            ("login.example.com", 2222, "alice")
+        );
+        assert_eq!(target.identity_files, vec!["/keys/id_ed25519".to_string()]);
+        let jump = jump.unwrap();
+        assert_eq!(
+            (jump.host.as_str(), jump.port, jump.user.as_str()),
+            ("bastion.example.com", 2200, "ops")
+        );
+
+        // Aliases are expanded through ssh_config, the jump host's too
+        let mut config_profile = profile.clone();
+        config_profile.connection_type = ConnectionType::SshConfig;
+        config_profile.ssh_config_host = Some("web1".to_string());
+        let (target, jump) = endpoints(&config_profile, &source).unwrap();
+        assert_eq!(
+            (target.host.as_str(), target.port, target.user.as_str()),
+            ("web1.prod.example.com", 2222, "deploy")
+        );
+        assert_eq!(
+            target.identity_files.first().map(String::as_str),
+            Some(dir.join(".ssh/prod_ed25519").to_str().unwrap())
+        );
+        let jump = jump.unwrap();
+        assert_eq!(
+            (jump.host.as_str(), jump.port, jump.user.as_str()),
+            ("bastion.example.com", 22, "ops")
+        );
+
+        let mut two_hops = profile.clone();
+        two_hops.manual_config.as_mut().unwrap().proxy_jump = Some("a,b".to_string());
+        assert!(endpoints(&two_hops, &source).is_err());
+
+        assert_eq!(parse_hop("gw"), (None, "gw", None));
+        assert_eq!(parse_hop("u@gw:bad"), (Some("u"), "gw:bad", None));
+    }
+}
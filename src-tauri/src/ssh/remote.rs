@@ -0,0 +1,85 @@
+//! Transport-neutral view of an in-process SSH connection: the shell channel
+//! behind a PTY and the SFTP subsystem on the same connection.
+
+use crate::models::AppState;
+use serde::Serialize;
+use std::io;
+use std::sync::Arc;
+use tauri::State;
+
+/// An interactive shell channel with a remote PTY
+pub trait RemoteChannel: Send + Sync {
+    /// Block until output arrives; `Ok(0)` once the remote side closes
+    fn read(&self, buf: &mut [u8]) -> io::Result<usize>;
+    fn write(&self, data: &[u8]) -> io::Result<usize>;
+    fn resize(&self, cols: u16, rows: u16) -> io::Result<()>;
+    /// Set once the channel has closed, from either side
+    fn exit_status(&self) -> Option<u32>;
+    fn close(&self) -> io::Result<()>;
+}
+
+/// Read-only file access on the remote host
+pub trait RemoteFs: Send + Sync {
+    fn list(&self, path: &str) -> Result<Vec<RemoteEntry>, String>;
+    fn stat(&self, path: &str) -> Result<RemoteEntry, String>;
+    /// At most `max_bytes` from the start of the file
+    fn read(&self, path: &str, max_bytes: usize) -> Result<Vec<u8>, String>;
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoteEntry {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<u64>, // Unix timestamp
+}
+
+/// A connected shell channel and the SFTP session next to it
+pub struct NativeSshConnection {
+    pub host: String,
+    pub user: String,
+    pub port: u16,
+    pub channel: Arc<dyn RemoteChannel>,
+    pub fs: Arc<dyn RemoteFs>,
+}
+
+fn pty_remote_fs(state: &AppState, pty_id: u32) -> Result<Arc<dyn RemoteFs>, String> {
+    state
+        .ptys
+        .lock()
+        .map_err(|e| format!("Failed to acquire PTY lock: {}", e))?
+        .get(&pty_id)
+        .and_then(|session| session.remote_fs.clone())
+        .ok_or_else(|| format!("PTY {} has no native SSH connection", pty_id))
+}
+
+/// List a directory over the SFTP session of a native SSH terminal
+#[tauri::command]
+pub async fn sftp_list_dir(
+    state: State<'_, AppState>,
+    pty_id: u32,
+    path: String,
+) -> Result<Vec<RemoteEntry>, String> {
+    let fs = pty_remote_fs(&state, pty_id)?;
+    tokio::task::spawn_blocking(move || fs.list(&path))
+        .await
+        .map_err(|e| format!("SFTP task failed: {}", e))?
+}
+
+/// Read up to `max_bytes` of a text file over the SFTP session of a native
+/// SSH terminal
+#[tauri::command]
+pub async fn sftp_read_file(
+    state: State<'_, AppState>,
+    pty_id: u32,
+    path: String,
+    max_bytes: usize,
+) -> Result<String, String> {
+    let fs = pty_remote_fs(&state, pty_id)?;
+    let content = tokio::task::spawn_blocking(move || fs.read(&path, max_bytes))
+        .await
+        .map_err(|e| format!("SFTP task failed: {}", e))??;
+    String::from_utf8(content).map_err(|_| "File contains invalid UTF-8 (binary file?)".to_string())
+}
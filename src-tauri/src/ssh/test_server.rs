@@ -0,0 +1,293 @@
+//! In-process SSH server for the native client tests: public key auth for one
+//! key, a shell channel that echoes input and reports its PTY size, and a
+//! read-only SFTP subsystem over a local directory.
+
+use russh::keys::ssh_key::private::Ed25519Keypair;
+use russh::keys::ssh_key::LineEnding;
+use russh::keys::{PrivateKey, PublicKey};
+use russh::server::{Auth, ChannelOpenHandle, Msg, Session};
+use russh::{Channel, ChannelId};
+use russh_sftp::protocol::{
+    Attrs, Data, File, FileAttributes, Handle, Name, OpenFlags, Status, StatusCode,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A running server and the client side files it was set up with
+pub(crate) struct TestServer {
+    pub port: u16,
+    /// Unencrypted OpenSSH private key the server accepts
+    pub identity_file: PathBuf,
+}
+
+/// Start a server on a loopback port that serves `root` over SFTP. Writes a
+/// client key and a known_hosts entry for the server into `home/.ssh`.
+pub(crate) fn start(root: &Path, home: &Path) -> TestServer {
+    let host_key = PrivateKey::from(Ed25519Keypair::from_seed(&[7; 32]));
+    let client_key = PrivateKey::from(Ed25519Keypair::from_seed(&[9; 32]));
+
+    let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let ssh_dir = home.join(".ssh");
+    std::fs::create_dir_all(&ssh_dir).unwrap();
+    let identity_file = ssh_dir.join("native_test_ed25519");
+    std::fs::write(
+        &identity_file,
+        client_key.to_openssh(LineEnding::LF).unwrap().as_bytes(),
+    )
+    .unwrap();
+    std::fs::write(
+        ssh_dir.join("known_hosts"),
+        format!(
+            "[127.0.0.1]:{} {}\n",
+            port,
+            host_key.public_key().to_openssh().unwrap()
+        ),
+    )
+    .unwrap();
+
+    let config = Arc::new(russh::server::Config {
+        keys: vec![host_key],
+        auth_rejection_time: std::time::Duration::from_millis(10),
+        ..Default::default()
+    });
+    let allowed = client_key.public_key().clone();
+    let root = root.to_path_buf();
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async move {
+            listener.set_nonblocking(true).unwrap();
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            while let Ok((stream, _)) = listener.accept().await {
+                let handler = ShellHandler {
+                    allowed: allowed.clone(),
+                    root: root.clone(),
+                    size: (0, 0),
+                    channels: HashMap::new(),
+                };
+                let config = config.clone();
+                tokio::spawn(async move {
+                    if let Ok(session) = russh::server::run_stream(config, stream, handler).await {
+                        let _ = session.await;
+                    }
+                });
+            }
+        });
+    });
+
+    TestServer {
+        port,
+        identity_file,
+    }
+}
+
+struct ShellHandler {
+    allowed: PublicKey,
+    root: PathBuf,
+    size: (u32, u32),
+    /// Session channels not yet handed to the SFTP subsystem
+    channels: HashMap<ChannelId, Channel<Msg>>,
+}
+
+impl russh::server::Handler for ShellHandler {
+    type Error = russh::Error;
+
+    async fn auth_publickey(&mut self, _user: &str, key: &PublicKey) -> Result<Auth, Self::Error> {
+        if key.key_data() == self.allowed.key_data() {
+            Ok(Auth::Accept)
+        } else {
+            Ok(Auth::reject())
+        }
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        channel: Channel<Msg>,
+        reply: ChannelOpenHandle,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.channels.insert(channel.id(), channel);
+        reply.accept().await;
+        Ok(())
+    }
+
+    async fn pty_request(
+        &mut self,
+        channel: ChannelId,
+        _term: &str,
+        cols: u32,
+        rows: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _modes: &[(russh::Pty, u32)],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.size = (cols, rows);
+        session.channel_success(channel)
+    }
+
+    async fn window_change_request(
+        &mut self,
+        _channel: ChannelId,
+        cols: u32,
+        rows: u32,
+        _pix_width: u32,
+        _pix_height: u32,
+        _session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        self.size = (cols, rows);
+        Ok(())
+    }
+
+    async fn shell_request(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        session.channel_success(channel)?;
+        session.data(channel, b"$ ".to_vec())
+    }
+
+    /// `size` prints the PTY size, `exit` ends the shell, anything else is echoed
+    async fn data(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        match data {
+            b"exit\r" => {
+                session.exit_status_request(channel, 0)?;
+                session.eof(channel)?;
+                session.close(channel)
+            }
+            b"size\r" => {
+                let (cols, rows) = self.size;
+                session.data(channel, format!("{}x{}\r\n", cols, rows).into_bytes())
+            }
+            _ => session.data(channel, data.to_vec()),
+        }
+    }
+
+    async fn subsystem_request(
+        &mut self,
+        channel_id: ChannelId,
+        name: &str,
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        match self.channels.remove(&channel_id) {
+            Some(channel) if name == "sftp" => {
+                session.channel_success(channel_id)?;
+                let sftp = SftpHandler {
+                    root: self.root.clone(),
+                    listed: Vec::new(),
+                };
+                russh_sftp::server::run(channel.into_stream(), sftp).await;
+                Ok(())
+            }
+            _ => session.channel_failure(channel_id),
+        }
+    }
+}
+
+/// Serves `root` as the remote filesystem; handles are the requested paths
+struct SftpHandler {
+    root: PathBuf,
+    /// Directory handles whose entries were already sent
+    listed: Vec<String>,
+}
+
+impl SftpHandler {
+    fn local(&self, path: &str) -> PathBuf {
+        self.root.join(path.trim_start_matches('/'))
+    }
+}
+
+impl russh_sftp::server::Handler for SftpHandler {
+    type Error = StatusCode;
+
+    fn unimplemented(&self) -> Self::Error {
+        StatusCode::OpUnsupported
+    }
+
+    async fn open(
+        &mut self,
+        id: u32,
+        filename: String,
+        _pflags: OpenFlags,
+        _attrs: FileAttributes,
+    ) -> Result<Handle, Self::Error> {
+        if !self.local(&filename).is_file() {
+            return Err(StatusCode::NoSuchFile);
+        }
+        Ok(Handle {
+            id,
+            handle: filename,
+        })
+    }
+
+    async fn read(
+        &mut self,
+        id: u32,
+        handle: String,
+        offset: u64,
+        len: u32,
+    ) -> Result<Data, Self::Error> {
+        let content = std::fs::read(self.local(&handle)).map_err(|_| StatusCode::Failure)?;
+        let start = (offset as usize).min(content.len());
+        let end = (start + len as usize).min(content.len());
+        if start == end {
+            return Err(StatusCode::Eof);
+        }
+        Ok(Data {
+            id,
+            data: content[start..end].to_vec(),
+        })
+    }
+
+    async fn close(&mut self, id: u32, handle: String) -> Result<Status, Self::Error> {
+        self.listed.retain(|listed| *listed != handle);
+        Ok(Status {
+            id,
+            status_code: StatusCode::Ok,
+            error_message: "Ok".to_string(),
+            language_tag: "en-US".to_string(),
+        })
+    }
+
+    async fn stat(&mut self, id: u32, path: String) -> Result<Attrs, Self::Error> {
+        let metadata = std::fs::metadata(self.local(&path)).map_err(|_| StatusCode::NoSuchFile)?;
+        Ok(Attrs {
+            id,
+            attrs: FileAttributes::from(&metadata),
+        })
+    }
+
+    async fn opendir(&mut self, id: u32, path: String) -> Result<Handle, Self::Error> {
+        if !self.local(&path).is_dir() {
+            return Err(StatusCode::NoSuchFile);
+        }
+        Ok(Handle { id, handle: path })
+    }
+
+    async fn readdir(&mut self, id: u32, handle: String) -> Result<Name, Self::Error> {
+        if self.listed.contains(&handle) {
+            return Err(StatusCode::Eof);
+        }
+        self.listed.push(handle.clone());
+        let files = std::fs::read_dir(self.local(&handle))
+            .map_err(|_| StatusCode::Failure)?
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                Some(File::new(
+                    entry.file_name().to_string_lossy(),
+                    FileAttributes::from(&metadata),
+                ))
+            })
+            .collect();
+        Ok(Name { id, files })
+    }
+}
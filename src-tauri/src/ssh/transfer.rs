@@ -0,0 +1,672 @@
+//! File transfer between this machine and the host an SSH pane is logged into.
+//!
+//! Each transfer opens its own ssh connection to the pane's target (the saved
+//! profile it was opened from, or else the host from `SshSessionInfo` behind
+//! the outer hops of a nested session) and streams the file through `cat` or
+//! `tail -c` on the remote side. That gives exact byte progress. The copy
+//! goes to `<name>.part` next to the destination, so an interrupted one can
+//! resume from there, and replaces the destination only once both sides'
+//! SHA-256 match. Progress is emitted as `transfer-progress` events.
+
+use super::connect::{shell_quote, target_args};
+use super::find_ssh_profile;
+use crate::models::{AppState, TRANSFER_CHUNK_BYTES, TRANSFER_PROGRESS_INTERVAL_MS};
+use crate::security::path_validator::{validate_path, validate_path_for_write};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+
+/// A key that needs a password would otherwise hang on a prompt nobody sees;
+/// an existing ControlMaster connection is still reused
+const SSH_OPTIONS: &[&str] = &["-o", "BatchMode=yes", "-o", "ConnectTimeout=15"];
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferDirection {
+    Upload,
+    Download,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferState {
+    Running,
+    Verifying,
+    Done,
+    Failed,
+}
+
+/// Payload of `transfer-progress` events
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferProgress {
+    pub transfer_id: String,
+    pub pty_id: u32,
+    pub direction: TransferDirection,
+    pub local_path: String,
+    pub remote_path: String,
+    pub bytes_done: u64,
+    pub total_bytes: u64,
+    pub state: TransferState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferResult {
+    pub transfer_id: String,
+    pub total_bytes: u64,
+    /// Bytes already on the receiving side that were kept
+    pub resumed_from: u64,
+    /// Hex SHA-256 both sides agreed on; None when verification was skipped
+    pub sha256: Option<String>,
+}
+
+/// Runs commands on the remote host: over ssh for a pane, through a local
+/// `sh -c` in tests
+pub(super) struct RemoteShell {
+    pub(super) program: String,
+    pub(super) args: Vec<String>,
+}
+
+impl RemoteShell {
+    /// ssh to the host of the profile a pane was opened from. Only the
+    /// profile's target is used: its custom flags may ask for a tty or
+    /// forwards, which would get in the way of the streams. A pane without a
+    /// profile goes to its innermost detected host, jumping through the
+    /// outer ones.
+    pub(super) fn for_pty(state: &AppState, pty_id: u32) -> Result<Self, String> {
+        let profile_id = state
+            .ptys
+            .lock()
+            .map_err(|e| format!("Failed to acquire PTY lock: {}", e))?
+            .get(&pty_id)
+            .and_then(|session| session.ssh_profile_id.clone());
+        if let Some(profile_id) = profile_id {
+            let mut args: Vec<String> = SSH_OPTIONS.iter().map(|arg| arg.to_string()).collect();
+            args.extend(target_args(&find_ssh_profile(&profile_id)?)?);
+            return Ok(Self {
+                program: "ssh".to_string(),
+                args,
+            });
+        }
+
+        let info = state
+            .ssh_sessions
+            .lock()
+            .map_err(|e| format!("Failed to acquire SSH session lock: {}", e))?
+            .get(&pty_id)
+            .cloned()
+            .ok_or_else(|| format!("PTY {} is not an SSH session", pty_id))?;
+        let hops = state
+            .terminal_contexts
+            .lock()
+            .map_err(|e| format!("Failed to acquire context lock: {}", e))?
+            .get(&pty_id)
+            .map(|context| context.hops.clone())
+            .unwrap_or_default();
+
+        let mut args: Vec<String> = SSH_OPTIONS.iter().map(|arg| arg.to_string()).collect();
+        if hops.len() > 1 {
+            let jumps: Vec<String> = hops[..hops.len() - 1]
+                .iter()
+                .map(|hop| format!("{}@{}", hop.user, hop.host))
+                .collect();
+            args.push("-J".to_string());
+            args.push(jumps.join(","));
+        }
+        if info.remote_port != 22 {
+            args.push("-p".to_string());
+            args.push(info.remote_port.to_string());
+        }
+        args.push(match &info.remote_user {
+            Some(user) => format!("{}@{}", user, info.remote_host),
+            None => info.remote_host.clone(),
+        });
+        Ok(Self {
+            program: "ssh".to_string(),
+            args,
+        })
+    }
+
+    fn command(&self, script: &str) -> Command {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args).arg(script);
+        command
+    }
+
+    pub(super) fn spawn(&self, script: &str, stdin: Stdio, stdout: Stdio) -> Result<Child, String> {
+        self.command(script)
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", self.program, e))
+    }
+
+    /// Run a command to completion and return its stdout as is
+    pub(super) fn output_bytes(&self, script: &str) -> Result<Vec<u8>, String> {
+        let output = self
+            .command(script)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| format!("Failed to start {}: {}", self.program, e))?;
+        if !output.status.success() {
+            return Err(remote_error(&output.stderr));
+        }
+        Ok(output.stdout)
+    }
+
+    /// Run a command to completion and return its trimmed stdout
+    pub(super) fn output(&self, script: &str) -> Result<String, String> {
+        let stdout = self.output_bytes(script)?;
+        Ok(String::from_utf8_lossy(&stdout).trim().to_string())
+    }
+
+    /// Size of a regular remote file, None when there is none
+    fn file_size(&self, path: &str) -> Result<Option<u64>, String> {
+        let path = remote_path_arg(path);
+        let reply = self.output(&format!(
+            "if [ -f {0} ]; then wc -c < {0}; else echo none; fi",
+            path
+        ))?;
+        if reply == "none" {
+            return Ok(None);
+        }
+        reply
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Unexpected size from remote host: {}", reply))
+    }
+
+    fn sha256(&self, path: &str) -> Result<String, String> {
+        let path = remote_path_arg(path);
+        let reply = self.output(&format!(
+            "sha256sum {0} 2>/dev/null || shasum -a 256 {0}",
+            path
+        ))?;
+        // GNU sha256sum prefixes the line with `\` when the name needed escaping
+        let digest = reply
+            .split_whitespace()
+            .next()
+            .unwrap_or("")
+            .trim_start_matches('\\');
+        if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("No SHA-256 tool on the remote host: {}", reply));
+        }
+        Ok(digest.to_ascii_lowercase())
+    }
+}
+
+pub(super) fn remote_error(stderr: &[u8]) -> String {
+    let stderr = String::from_utf8_lossy(stderr).trim().to_string();
+    if stderr.contains("Permission denied") || stderr.contains("Host key verification failed") {
+        format!(
+            "Remote command failed: {} (file access opens its own connection, so it needs key-based auth or a ControlMaster)",
+            stderr
+        )
+    } else {
+        format!("Remote command failed: {}", stderr)
+    }
+}
+
+/// Quote a remote path, keeping a leading `~/` expandable
+pub(super) fn remote_path_arg(path: &str) -> String {
+    match path.strip_prefix("~/") {
+        Some(rest) => format!("\"$HOME\"/{}", shell_quote(rest)),
+        None if path == "~" => "\"$HOME\"".to_string(),
+        None => shell_quote(path),
+    }
+}
+
+fn local_sha256(path: &Path) -> Result<String, String> {
+    let mut file =
+        File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; TRANSFER_CHUNK_BYTES];
+    loop {
+        let n = file
+            .read(&mut buf)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Copy everything from `from` into `to`, reporting the running total
+/// (starting at `offset`) at most every `TRANSFER_PROGRESS_INTERVAL_MS`
+fn copy_with_progress(
+    from: &mut dyn Read,
+    to: &mut dyn Write,
+    offset: u64,
+    progress: &mut dyn FnMut(u64),
+) -> std::io::Result<u64> {
+    let interval = Duration::from_millis(TRANSFER_PROGRESS_INTERVAL_MS);
+    let mut buf = vec![0u8; TRANSFER_CHUNK_BYTES];
+    let mut done = offset;
+    let mut last_report = Instant::now();
+    loop {
+        let n = from.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        to.write_all(&buf[..n])?;
+        done += n as u64;
+        if last_report.elapsed() >= interval {
+            progress(done);
+            last_report = Instant::now();
+        }
+    }
+    to.flush()?;
+    progress(done);
+    Ok(done)
+}
+
+pub(super) fn finish(mut child: Child) -> Result<(), String> {
+    let mut stderr = Vec::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_end(&mut stderr);
+    }
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to wait for ssh: {}", e))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(remote_error(&stderr))
+    }
+}
+
+struct Transfer<'a> {
+    shell: &'a RemoteShell,
+    local: &'a Path,
+    remote: &'a str,
+    resume: bool,
+    verify: bool,
+}
+
+/// Bytes on the receiving side and the total, as the copy progresses
+type Progress<'a> = &'a mut dyn FnMut(TransferState, u64, u64);
+
+/// Where a copy is written until it is complete and verified
+fn part_name(path: &str) -> String {
+    format!("{}.part", path)
+}
+
+impl Transfer<'_> {
+    fn upload(&self, progress: Progress) -> Result<(u64, u64, Option<String>), String> {
+        let total = fs::metadata(self.local)
+            .map_err(|e| format!("Failed to read {}: {}", self.local.display(), e))?
+            .len();
+        let part = part_name(self.remote);
+        let existing = if self.resume {
+            self.shell.file_size(&part)?.unwrap_or(0)
+        } else {
+            0
+        };
+        // A larger partial file is not a partial copy of this one
+        let offset = if existing <= total { existing } else { 0 };
+
+        let mut file = File::open(self.local)
+            .map_err(|e| format!("Failed to open {}: {}", self.local.display(), e))?;
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|e| format!("Failed to read {}: {}", self.local.display(), e))?;
+        let redirect = if offset > 0 { ">>" } else { ">" };
+        let mut child = self.shell.spawn(
+            &format!("cat {} {}", redirect, remote_path_arg(&part)),
+            Stdio::piped(),
+            Stdio::null(),
+        )?;
+        let copied = match child.stdin.take() {
+            Some(mut stdin) => copy_with_progress(&mut file, &mut stdin, offset, &mut |done| {
+                progress(TransferState::Running, done, total)
+            }),
+            None => Ok(offset),
+        };
+        // The remote error says more than the broken pipe it caused
+        finish(child)?;
+        copied.map_err(|e| format!("Upload failed: {}", e))?;
+
+        let sha256 = match self.verify(progress, total, self.local, &part) {
+            Ok(sha256) => sha256,
+            Err(e) => {
+                // Resuming from a corrupt copy could never succeed
+                let _ = self
+                    .shell
+                    .output(&format!("rm -f {}", remote_path_arg(&part)));
+                return Err(e);
+            }
+        };
+        self.shell.output(&format!(
+            "mv -f {} {}",
+            remote_path_arg(&part),
+            remote_path_arg(self.remote)
+        ))?;
+        Ok((total, offset, sha256))
+    }
+
+    fn download(&self, progress: Progress) -> Result<(u64, u64, Option<String>), String> {
+        let total = self
+            .shell
+            .file_size(self.remote)?
+            .ok_or_else(|| format!("Remote file does not exist: {}", self.remote))?;
+        let part = self.local.with_file_name(part_name(
+            &self.local.file_name().unwrap_or_default().to_string_lossy(),
+        ));
+        let existing = if self.resume {
+            fs::metadata(&part).map(|m| m.len()).unwrap_or(0)
+        } else {
+            0
+        };
+        let offset = if existing <= total { existing } else { 0 };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(offset > 0)
+            .truncate(offset == 0)
+            .open(&part)
+            .map_err(|e| format!("Failed to open {}: {}", part.display(), e))?;
+        let mut child = self.shell.spawn(
+            &format!("tail -c +{} {}", offset + 1, remote_path_arg(self.remote)),
+            Stdio::null(),
+            Stdio::piped(),
+        )?;
+        let copied = match child.stdout.take() {
+            Some(mut stdout) => copy_with_progress(&mut stdout, &mut file, offset, &mut |done| {
+                progress(TransferState::Running, done, total)
+            }),
+            None => Ok(offset),
+        };
+        drop(file);
+        finish(child)?;
+        let copied = copied.map_err(|e| format!("Download failed: {}", e))?;
+        if copied != total {
+            return Err(format!(
+                "Download incomplete: {} of {} bytes (the remote file changed?)",
+                copied, total
+            ));
+        }
+
+        let sha256 = match self.verify(progress, total, &part, self.remote) {
+            Ok(sha256) => sha256,
+            Err(e) => {
+                // Resuming from a corrupt copy could never succeed
+                let _ = fs::remove_file(&part);
+                return Err(e);
+            }
+        };
+        fs::rename(&part, self.local)
+            .map_err(|e| format!("Failed to replace {}: {}", self.local.display(), e))?;
+        Ok((total, offset, sha256))
+    }
+
+    /// Compare a local and a remote file, when verification is on
+    fn verify(
+        &self,
+        progress: Progress,
+        total: u64,
+        local: &Path,
+        remote: &str,
+    ) -> Result<Option<String>, String> {
+        if !self.verify {
+            return Ok(None);
+        }
+        progress(TransferState::Verifying, total, total);
+        let local = local_sha256(local)?;
+        let remote = self.shell.sha256(remote)?;
+        if local != remote {
+            return Err(format!(
+                "Checksum mismatch: local {} but remote {}",
+                local, remote
+            ));
+        }
+        Ok(Some(local))
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_transfer(
+    app: AppHandle,
+    state: &AppState,
+    pty_id: u32,
+    direction: TransferDirection,
+    local: String,
+    remote: String,
+    resume: Option<bool>,
+    verify: Option<bool>,
+) -> Result<TransferResult, String> {
+    let shell = RemoteShell::for_pty(state, pty_id)?;
+    // The same rules as the local file tools: inside the home directory, and
+    // no downloads over credential or shell startup files
+    let local_path = match direction {
+        TransferDirection::Upload => validate_path(Path::new(&local))?,
+        TransferDirection::Download => validate_path_for_write(Path::new(&local))?,
+    };
+    let transfer_id = uuid::Uuid::new_v4().to_string();
+    let event = TransferProgress {
+        transfer_id: transfer_id.clone(),
+        pty_id,
+        direction,
+        local_path: local_path.display().to_string(),
+        remote_path: remote.clone(),
+        bytes_done: 0,
+        total_bytes: 0,
+        state: TransferState::Running,
+        error: None,
+    };
+
+    let result = {
+        let app = app.clone();
+        let mut event = event.clone();
+        tokio::task::spawn_blocking(move || {
+            let transfer = Transfer {
+                shell: &shell,
+                local: &local_path,
+                remote: &remote,
+                resume: resume.unwrap_or(true),
+                verify: verify.unwrap_or(true),
+            };
+            let mut progress = |state, done, total| {
+                event.state = state;
+                event.bytes_done = done;
+                event.total_bytes = total;
+                let _ = app.emit("transfer-progress", &event);
+            };
+            match direction {
+                TransferDirection::Upload => transfer.upload(&mut progress),
+                TransferDirection::Download => transfer.download(&mut progress),
+            }
+        })
+        .await
+        .map_err(|e| format!("Transfer task failed: {}", e))?
+    };
+
+    match result {
+        Ok((total_bytes, resumed_from, sha256)) => {
+            let _ = app.emit(
+                "transfer-progress",
+                TransferProgress {
+                    bytes_done: total_bytes,
+                    total_bytes,
+                    state: TransferState::Done,
+                    ..event
+                },
+            );
+            Ok(TransferResult {
+                transfer_id,
+                total_bytes,
+                resumed_from,
+                sha256,
+            })
+        }
+        Err(e) => {
+            let _ = app.emit(
+                "transfer-progress",
+                TransferProgress {
+                    state: TransferState::Failed,
+                    error: Some(e.clone()),
+                    ..event
+                },
+            );
+            Err(e)
+        }
+    }
+}
+
+/// Tauri command: Copy a local file to the host of an SSH pane. With `resume`
+/// (default on) the `.part` file of an interrupted upload is continued; with
+/// `verify` (default on) both sides' SHA-256 must match before the remote
+/// file is replaced.
+#[tauri::command]
+pub async fn upload_file(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    pty_id: u32,
+    local: String,
+    remote: String,
+    resume: Option<bool>,
+    verify: Option<bool>,
+) -> Result<TransferResult, String> {
+    run_transfer(
+        app,
+        &state,
+        pty_id,
+        TransferDirection::Upload,
+        local,
+        remote,
+        resume,
+        verify,
+    )
+    .await
+}
+
+/// Tauri command: Copy a file from the host of an SSH pane to this machine,
+/// with the same `resume` and `verify` options as `upload_file`
+#[tauri::command]
+pub async fn download_file(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    pty_id: u32,
+    remote: String,
+    local: String,
+    resume: Option<bool>,
+    verify: Option<bool>,
+) -> Result<TransferResult, String> {
+    run_transfer(
+        app,
+        &state,
+        pty_id,
+        TransferDirection::Download,
+        local,
+        remote,
+        resume,
+        verify,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The "remote host" is a local shell, so whole transfers run end to end
+    fn local_shell() -> RemoteShell {
+        RemoteShell {
+            program: "sh".to_string(),
+            args: vec!["-c".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_upload_and_download_resume_and_verify() {
+        let dir = std::env::temp_dir().join(format!("aiterminal-transfer-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let local = dir.join("job output.bin");
+        let remote = dir.join("remote copy.bin");
+        let back = dir.join("back.bin");
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&local, &content).unwrap();
+        // An unrelated file has the name, and an interrupted earlier upload
+        // left the first part behind
+        fs::write(&remote, b"someone else's data").unwrap();
+        fs::write(dir.join("remote copy.bin.part"), &content[..70_000]).unwrap();
+
+        let shell = local_shell();
+        let remote_str = remote.to_str().unwrap();
+        let mut seen = Vec::new();
+        let transfer = Transfer {
+            shell: &shell,
+            local: &local,
+            remote: remote_str,
+            resume: true,
+            verify: true,
+        };
+        let (total, resumed_from, sha256) = transfer
+            .upload(&mut |state, done, total| seen.push((state, done, total)))
+            .unwrap();
+        assert_eq!((total, resumed_from), (200_000, 70_000));
+        assert_eq!(fs::read(&remote).unwrap(), content);
+        assert!(!dir.join("remote copy.bin.part").exists());
+        assert_eq!(sha256, Some(local_sha256(&local).unwrap()));
+        assert_eq!(
+            seen.last(),
+            Some(&(TransferState::Verifying, 200_000, 200_000))
+        );
+        assert!(seen.iter().all(|&(_, done, _)| done >= 70_000));
+
+        let back_part = dir.join("back.bin.part");
+        fs::write(&back, b"older, longer than the partial copy").unwrap();
+        fs::write(&back_part, &content[..1_000]).unwrap();
+        let download = Transfer {
+            local: &back,
+            ..transfer
+        };
+        let (_, resumed_from, _) = download.download(&mut |_, _, _| {}).unwrap();
+        assert_eq!(resumed_from, 1_000);
+        assert_eq!(fs::read(&back).unwrap(), content);
+        assert!(!back_part.exists());
+
+        // A corrupted prefix is caught by the checksum, and the destination
+        // is left alone
+        let mut corrupt = content[..5_000].to_vec();
+        corrupt[10] ^= 0xff;
+        fs::write(&back, b"keep me").unwrap();
+        fs::write(&back_part, &corrupt).unwrap();
+        let err = download.download(&mut |_, _, _| {}).unwrap_err();
+        assert!(err.starts_with("Checksum mismatch"), "{}", err);
+        assert_eq!(fs::read(&back).unwrap(), b"keep me");
+        assert!(!back_part.exists());
+
+        let missing = Transfer {
+            remote: "/nonexistent/file",
+            ..download
+        };
+        assert!(missing.download(&mut |_, _, _| {}).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_remote_path_quoting() {
+        assert_eq!(
+            remote_path_arg("~/runs/out 1.nc"),
+            "\"$HOME\"/'runs/out 1.nc'"
+        );
+        assert_eq!(remote_path_arg("/scratch/x.csv"), "/scratch/x.csv");
+        assert_eq!(remote_path_arg("it's"), r"'it'\''s'");
+    }
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+const TERMINAL_PROFILES_FILE: &str = ".config/aiterminal/terminal_profiles.json";
+
+/// How a local shell is launched. Every field is optional; unset fields fall
+/// back to the defaults used by `spawn_pty`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PtySpawnOptions {
+    /// Shell executable (defaults to `resolve_shell()`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<String>,
+    /// Extra arguments appended after the shell integration arguments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+    /// Starting directory (`~` is expanded)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_vars: Option<HashMap<String, String>>,
+    /// Start a login shell. Bash integration already sources /etc/profile and
+    /// ~/.bash_profile, so this mainly affects zsh and other shells.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub login: Option<bool>,
+    /// Interactive shell with integration (default true). Non-interactive
+    /// shells get no OSC 133 marks, so command tracking is unavailable.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interactive: Option<bool>,
+    /// Command typed at the first prompt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initial_command: Option<String>,
+}
+
+impl PtySpawnOptions {
+    /// Fields set in `overrides` replace those in `self`
+    pub fn merged(self, overrides: PtySpawnOptions) -> Self {
+        Self {
+            shell: overrides.shell.or(self.shell),
+            args: overrides.args.or(self.args),
+            cwd: overrides.cwd.or(self.cwd),
+            env_vars: match (self.env_vars, overrides.env_vars) {
+                (Some(mut base), Some(extra)) => {
+                    base.extend(extra);
+                    Some(base)
+                }
+                (base, extra) => extra.or(base),
+            },
+            login: overrides.login.or(self.login),
+            interactive: overrides.interactive.or(self.interactive),
+            initial_command: overrides.initial_command.or(self.initial_command),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TerminalProfile {
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tab_color: Option<String>,
+
+    #[serde(flatten)]
+    pub options: PtySpawnOptions,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+}
+
+fn get_terminal_profiles_path() -> Result<PathBuf, String> {
+    let home =
+        std::env::var("HOME").map_err(|_| "Could not determine HOME directory".to_string())?;
+    Ok(PathBuf::from(home).join(TERMINAL_PROFILES_FILE))
+}
+
+fn read_terminal_profiles() -> Result<Vec<TerminalProfile>, String> {
+    let path = get_terminal_profiles_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read profiles: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("Failed to parse profiles: {}", e))
+}
+
+/// Look up a saved profile by ID
+pub fn find_terminal_profile(id: &str) -> Result<TerminalProfile, String> {
+    read_terminal_profiles()?
+        .into_iter()
+        .find(|profile| profile.id == id)
+        .ok_or_else(|| format!("Terminal profile {} not found", id))
+}
+
+/// Tauri command: Save terminal profiles
+#[tauri::command]
+pub async fn save_terminal_profiles(profiles: Vec<TerminalProfile>) -> Result<(), String> {
+    let path = get_terminal_profiles_path()?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create config directory: {}", e))?;
+    }
+
+    let json = serde_json::to_string_pretty(&profiles)
+        .map_err(|e| format!("Failed to serialize profiles: {}", e))?;
+
+    fs::write(&path, json).map_err(|e| format!("Failed to write profiles: {}", e))
+}
+
+/// Tauri command: Load terminal profiles
+#[tauri::command]
+pub async fn load_terminal_profiles() -> Result<Vec<TerminalProfile>, String> {
+    read_terminal_profiles()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_round_trip_and_merge() {
+        let json = r#"{
+            "id": "conda-proj",
+            "name": "Conda: project",
+            "shell": "/bin/zsh",
+            "cwd": "~/projects/y",
+            "envVars": {"CONDA_ENV": "x"},
+            "login": true,
+            "initialCommand": "conda activate x"
+        }"#;
+        let profile: TerminalProfile = serde_json::from_str(json).unwrap();
+        assert_eq!(profile.options.shell.as_deref(), Some("/bin/zsh"));
+        assert_eq!(
+            profile.options.initial_command.as_deref(),
+            Some("conda activate x")
+        );
+
+        let serialized = serde_json::to_value(&profile).unwrap();
+        assert_eq!(serialized["envVars"]["CONDA_ENV"], "x");
+        assert!(serialized.get("args").is_none());
+
+        let merged = profile.options.merged(PtySpawnOptions {
+            cwd: Some("/tmp".to_string()),
+            env_vars: Some(HashMap::from([("EXTRA".to_string(), "1".to_string())])),
+            ..Default::default()
+        });
+        assert_eq!(merged.cwd.as_deref(), Some("/tmp"));
+        assert_eq!(merged.shell.as_deref(), Some("/bin/zsh"));
+        assert_eq!(merged.env_vars.unwrap().len(), 2);
+    }
+}
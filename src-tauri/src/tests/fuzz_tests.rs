@@ -6,7 +6,8 @@
 #[cfg(test)]
 mod fuzz_tests {
     use crate::tests::helpers::*;
-    use crate::tools::commands::{read_file_tool, write_file_impl};
+    use crate::tools::commands::{read_file_impl, write_file_impl};
+    use crate::tools::filesystem::LocalFs;
     use crate::security::path_validator::PathValidator;
     use std::env;
     use std::path::PathBuf;
@@ -41,7 +42,7 @@ mod fuzz_tests {
 
         for pattern in traversal_patterns {
             let test_path = home_path.join(pattern).join("etc/passwd");
-            let result = read_file_tool(test_path.to_string_lossy().to_string(), 1000).await;
+            let result = read_file_impl(test_path.to_string_lossy().to_string(), 1000, &LocalFs);
             
             // All traversal attempts should fail
             assert!(
@@ -151,10 +152,11 @@ mod fuzz_tests {
             let repeated = pattern.repeat(count);
             let test_path = home_path.join(repeated).join("etc/passwd");
             
-            let result = read_file_tool(
+            let result = read_file_impl(
                 test_path.to_string_lossy().to_string(),
-                1000
-            ).await;
+                1000,
+                &LocalFs
+            );
             
             assert!(
                 result.is_err(),
@@ -187,10 +189,11 @@ mod fuzz_tests {
         for pattern in encoded_patterns {
             let test_path = home_path.join(pattern).join("etc/passwd");
             
-            let result = read_file_tool(
+            let result = read_file_impl(
                 test_path.to_string_lossy().to_string(),
-                1000
-            ).await;
+                1000,
+                &LocalFs
+            );
             
             // All encoding attempts should fail
             assert!(
@@ -220,7 +223,7 @@ mod fuzz_tests {
         ];
         
         for path in sensitive_paths {
-            let result = read_file_tool(path.to_string(), 1000).await;
+            let result = read_file_impl(path.to_string(), 1000, &LocalFs);
             
             assert!(
                 result.is_err(),
@@ -285,7 +288,7 @@ mod fuzz_tests {
         ];
         
         for path in case_variations {
-            let result = read_file_tool(path.to_string(), 1000).await;
+            let result = read_file_impl(path.to_string(), 1000, &LocalFs);
             
             // Should be blocked (case-sensitive on Unix, outside home on any OS)
             assert!(
@@ -311,7 +314,7 @@ mod fuzz_tests {
         ];
         
         for path in edge_cases {
-            let result = read_file_tool(path.to_string(), 1000).await;
+            let result = read_file_impl(path.to_string(), 1000, &LocalFs);
             
             // Should fail gracefully (invalid path)
             assert!(result.is_err(), "Empty/whitespace path should fail");
@@ -334,10 +337,11 @@ mod fuzz_tests {
         for pattern in symlink_patterns {
             let test_path = home_path.join(pattern);
             
-            let result = read_file_tool(
+            let result = read_file_impl(
                 test_path.to_string_lossy().to_string(),
-                1000
-            ).await;
+                1000,
+                &LocalFs
+            );
             
             assert!(
                 result.is_err(),
@@ -380,10 +384,11 @@ mod fuzz_tests {
             // May hit OS limits for very large files
             if write_result.is_ok() {
                 // Try to read it back
-                let read_result = read_file_tool(
+                let read_result = read_file_impl(
                     test_file.to_string_lossy().to_string(),
-                    usize::MAX
-                ).await;
+                    usize::MAX,
+                    &LocalFs
+                );
                 
                 // Reading should work
                 assert!(read_result.is_ok() || read_result.is_err());
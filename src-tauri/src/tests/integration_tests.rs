@@ -10,9 +10,10 @@
 mod integration_tests {
     use crate::tests::helpers::*;
     use crate::tools::commands::{
-        read_file_tool, write_file_impl,
-        append_to_file_impl, tail_file_tool
+        read_file_impl, write_file_impl,
+        append_to_file_impl, tail_file_impl
     };
+    use crate::tools::filesystem::LocalFs;
     use crate::security::path_validator::PathValidator;
     use std::env;
     use std::fs;
@@ -31,7 +32,7 @@ mod integration_tests {
         // Attacker tries to inject command AND traverse path
         let malicious_path = format!("{}/../../../etc/passwd; cat /etc/passwd", test_dir.display());
         
-        let result = read_file_tool(malicious_path, 1000).await;
+        let result = read_file_impl(malicious_path, 1000, &LocalFs);
         
         // Should fail due to path traversal detection
         assert!(result.is_err());
@@ -99,14 +100,14 @@ mod integration_tests {
         assert!(append_result.is_ok(), "Append failed: {}", append_result.unwrap_err());
         
         // 3. Read file
-        let read_result = read_file_tool(test_file_str.clone(), 1000).await;
+        let read_result = read_file_impl(test_file_str.clone(), 1000, &LocalFs);
         assert!(read_result.is_ok());
         let content = read_result.unwrap();
         assert!(content.contains("Line 1"));
         assert!(content.contains("Line 2"));
         
         // 4. Tail file
-        let tail_result = tail_file_tool(test_file_str.clone(), 10, None).await;
+        let tail_result = tail_file_impl(test_file_str.clone(), 10, None, &LocalFs);
         assert!(tail_result.is_ok());
         
         // Cleanup
@@ -178,7 +179,7 @@ mod integration_tests {
                 filename
             );
             
-            let read_result = read_file_tool(test_file_str, 1000).await;
+            let read_result = read_file_impl(test_file_str, 1000, &LocalFs);
             if filename.is_ascii() {
                 assert!(read_result.is_ok(), "{}: Read failed", description);
             }
@@ -212,7 +213,7 @@ mod integration_tests {
         assert!(write_result.is_ok());
         
         // Try to read with small limit
-        let read_result = read_file_tool(test_file_str.clone(), 100).await;
+        let read_result = read_file_impl(test_file_str.clone(), 100, &LocalFs);
         assert!(read_result.is_ok());
         let content = read_result.unwrap();
         
@@ -257,7 +258,7 @@ mod integration_tests {
                 assert!(write_result.is_ok());
                 
                 // Read
-                let read_result = read_file_tool(test_file_str.clone(), 1000).await;
+                let read_result = read_file_impl(test_file_str.clone(), 1000, &LocalFs);
                 assert!(read_result.is_ok());
                 
                 // Append
@@ -298,7 +299,7 @@ mod integration_tests {
         let malicious_input = "/etc/passwd; cat /etc/shadow";
         
         // Should fail at path validation layer
-        let result = read_file_tool(malicious_input.to_string(), 1000).await;
+        let result = read_file_impl(malicious_input.to_string(), 1000, &LocalFs);
         assert!(result.is_err());
         
         let error_msg = result.unwrap_err();
@@ -339,10 +340,11 @@ mod integration_tests {
             
             if symlink_file.exists() {
                 // Try to read through symlink
-                let result = read_file_tool(
+                let result = read_file_impl(
                     symlink_file.to_str().unwrap().to_string(),
-                    1000
-                ).await;
+                    1000,
+                    &LocalFs
+                );
                 
                 // Should fail due to path validation resolving the symlink
                 assert!(
@@ -375,7 +377,7 @@ mod integration_tests {
         
         // Attack vector 2: Path traversal
         let attack2 = format!("{}/../../../etc/passwd", test_dir.display());
-        let result2 = read_file_tool(attack2, 1000).await;
+        let result2 = read_file_impl(attack2, 1000, &LocalFs);
         assert!(result2.is_err(), "Path traversal should fail");
         
         // Attack vector 3: Null byte injection
@@ -414,7 +416,7 @@ mod integration_tests {
         
         // Cause an error
         let bad_path = "/etc/passwd";
-        let result1 = read_file_tool(bad_path.to_string(), 1000).await;
+        let result1 = read_file_impl(bad_path.to_string(), 1000, &LocalFs);
         assert!(result1.is_err());
         
         // Verify system can recover and continue with valid operations
@@ -427,10 +429,11 @@ mod integration_tests {
         ).await;
         assert!(result2.is_ok(), "Should recover from previous error");
         
-        let result3 = read_file_tool(
+        let result3 = read_file_impl(
             good_file.to_str().unwrap().to_string(),
-            1000
-        ).await;
+            1000,
+            &LocalFs
+        );
         assert!(result3.is_ok());
         assert_eq!(result3.unwrap(), "recovered");
         
@@ -1,7 +1,8 @@
 #[cfg(test)]
 mod security_tests {
     use crate::tests::helpers::*;
-    use crate::tools::commands::{calculate_tool, read_file_tool, write_file_impl, append_to_file_impl, tail_file_tool};
+    use crate::tools::commands::{calculate_tool, read_file_impl, write_file_impl, append_to_file_impl, tail_file_impl};
+    use crate::tools::filesystem::LocalFs;
     use crate::security::path_validator::{PathValidator, validate_path_for_write};
     use std::path::{Path, PathBuf};
     use std::env;
@@ -47,10 +48,11 @@ mod security_tests {
 
     #[tokio::test]
     async fn test_read_file_blocks_parent_traversal() {
-        let result = read_file_tool(
+        let result = read_file_impl(
             "../../../etc/passwd".to_string(),
-            1000000
-        ).await;
+            1000000,
+            &LocalFs
+        );
 
         assert!(result.is_err(), "Should reject path with ..");
         let error_msg = result.unwrap_err();
@@ -59,10 +61,11 @@ mod security_tests {
 
     #[tokio::test]
     async fn test_read_file_blocks_absolute_path_outside_home() {
-        let result = read_file_tool(
+        let result = read_file_impl(
             "/etc/passwd".to_string(),
-            1000000
-        ).await;
+            1000000,
+            &LocalFs
+        );
 
         assert!(result.is_err(), "Should reject absolute path outside home");
         assert!(result.unwrap_err().contains("Access denied"));
@@ -112,11 +115,12 @@ mod security_tests {
 
     #[tokio::test]
     async fn test_tail_file_blocks_parent_traversal() {
-        let result = tail_file_tool(
+        let result = tail_file_impl(
             "../../../etc/passwd".to_string(),
             10,
-            None
-        ).await;
+            None,
+            &LocalFs
+        );
 
         assert!(result.is_err(), "Should reject path with ..");
         assert!(is_traversal_error(&result.unwrap_err()));
@@ -131,10 +135,11 @@ mod security_tests {
         file.write_all(b"test content").unwrap();
         drop(file);
 
-        let result = read_file_tool(
+        let result = read_file_impl(
             test_file.to_string_lossy().to_string(),
-            1000000
-        ).await;
+            1000000,
+            &LocalFs
+        );
 
         let _ = fs::remove_file(&test_file);
 
@@ -241,10 +246,11 @@ mod security_tests {
 
     #[tokio::test]
     async fn test_hidden_traversal_in_middle() {
-        let result = read_file_tool(
+        let result = read_file_impl(
             "docs/../../../etc/passwd".to_string(),
-            1000000
-        ).await;
+            1000000,
+            &LocalFs
+        );
 
         assert!(result.is_err(), "Should reject hidden traversal in path");
         assert!(is_traversal_error(&result.unwrap_err()));
@@ -1,59 +1,35 @@
+use super::filesystem::{display_path, run_blocking, tool_fs, ToolFs, ToolPath};
 use crate::models::AppState;
-use crate::security::path_validator::{validate_path, validate_path_for_write};
+use crate::security::path_validator::validate_path;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::io::Read;
+use std::path::Path;
 use std::process::Command;
-use walkdir::WalkDir;
+use std::sync::Arc;
 
 const GIT_NOT_FOUND_ERR: &str = "Not a git repository or git not installed";
 
-/// Resolve working_directory to a PathBuf, with tilde expansion.
-/// Falls back to the current directory if not provided.
-fn resolve_base_dir(working_directory: &Option<String>) -> PathBuf {
-    working_directory
-        .as_deref()
-        .and_then(|wd| shellexpand::tilde(wd).parse::<PathBuf>().ok())
-        .unwrap_or_else(|| std::env::current_dir().unwrap_or_default())
-}
-
-/// Resolve a path relative to an optional working directory.
-/// If the path is absolute, returns it as-is; otherwise joins with cwd.
-fn resolve_path(path: &str, working_directory: &Option<String>) -> PathBuf {
-    if Path::new(path).is_absolute() {
-        PathBuf::from(path)
-    } else if let Some(ref cwd) = working_directory {
-        Path::new(cwd).join(path)
-    } else {
-        PathBuf::from(path)
-    }
-}
+/// Files larger than this are only searched up to this point
+const MAX_GREP_FILE_BYTES: u64 = 10 * 1024 * 1024;
 
-#[tauri::command]
-pub async fn read_file_tool(path: String, max_bytes: usize) -> Result<String, String> {
+pub(crate) fn read_file_impl(
+    path: String,
+    max_bytes: usize,
+    fs: &dyn ToolFs,
+) -> Result<String, String> {
     // SECURITY: Validate path to prevent traversal attacks
-    let safe_path = validate_path(Path::new(&path))?;
+    let safe_path = fs.resolve(&path, false)?;
 
     if !safe_path.exists() {
-        return Err(format!("File does not exist: {}", safe_path.display()));
+        return Err(format!("File does not exist: {}", safe_path.path));
     }
 
     if !safe_path.is_file() {
-        return Err(format!("Path is not a file: {}", safe_path.display()));
+        return Err(format!("Path is not a file: {}", safe_path.path));
     }
 
     // Read file with size limit
-    let metadata =
-        fs::metadata(&safe_path).map_err(|e| format!("Failed to read file metadata: {}", e))?;
-
-    let file_size = metadata.len() as usize;
-    let bytes_to_read = file_size.min(max_bytes);
-
-    let mut file = fs::File::open(&safe_path).map_err(|e| format!("Failed to open file: {}", e))?;
-
-    use std::io::Read;
-    let mut buffer = vec![0u8; bytes_to_read];
-    file.read_exact(&mut buffer)
-        .map_err(|e| format!("Failed to read file: {}", e))?;
+    let buffer = fs.read(&safe_path.path, max_bytes as u64)?;
 
     // Try to convert to UTF-8
     match String::from_utf8(buffer) {
@@ -79,37 +55,30 @@ pub struct FileInfo {
 pub async fn get_file_info_tool(
     path: String,
     working_directory: Option<String>,
+    state: tauri::State<'_, AppState>,
 ) -> Result<FileInfo, String> {
-    let base_dir = resolve_base_dir(&working_directory);
+    let fs = tool_fs(&state)?;
 
     // Resolve path relative to working directory
-    let file_path = if Path::new(&path).is_absolute() {
-        Path::new(&path).to_path_buf()
-    } else {
-        base_dir.join(&path)
-    };
+    let file_path = fs.join(&working_directory, &path);
 
     // SECURITY: Validate path
-    let safe_path = validate_path(&file_path)?;
+    let safe_path = fs.resolve(&file_path, false)?;
 
     if !safe_path.exists() {
-        return Err(format!("File does not exist: {}", safe_path.display()));
+        return Err(format!("File does not exist: {}", safe_path.path));
     }
 
     if !safe_path.is_file() {
-        return Err(format!("Path is not a file: {}", safe_path.display()));
+        return Err(format!("Path is not a file: {}", safe_path.path));
     }
 
-    // Get file metadata
-    let metadata =
-        fs::metadata(&safe_path).map_err(|e| format!("Failed to read file metadata: {}", e))?;
-
-    let size_bytes = metadata.len();
+    let size_bytes = safe_path.size();
     
     let size_human = format_file_size(size_bytes);
 
     // Get extension
-    let extension = safe_path
+    let extension = Path::new(&safe_path.path)
         .extension()
         .and_then(|e| e.to_str())
         .map(|s| s.to_string());
@@ -139,39 +108,35 @@ pub async fn get_file_info_tool(
     .to_string();
 
     // Get last modified time
-    let last_modified = metadata
-        .modified()
-        .ok()
+    let last_modified = safe_path
+        .metadata
+        .as_ref()
+        .and_then(|metadata| metadata.modified)
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs as i64, 0))
         .map(|time| {
             let datetime: chrono::DateTime<chrono::Local> = time.into();
             datetime.format("%Y-%m-%d %H:%M:%S").to_string()
         });
 
     // Try to detect if file is text or binary by reading first 8KB
-    let sample_size = 8192.min(size_bytes as usize);
-    let mut file = fs::File::open(&safe_path).map_err(|e| format!("Failed to open file: {}", e))?;
-    
-    use std::io::Read;
-    let mut buffer = vec![0u8; sample_size];
-    let bytes_read = file.read(&mut buffer).unwrap_or(0);
-    buffer.truncate(bytes_read);
+    let buffer = fs.read(&safe_path.path, 8192).unwrap_or_default();
 
-    let is_text = String::from_utf8(buffer.clone()).is_ok();
+    let is_text = String::from_utf8(buffer).is_ok();
     let is_binary = !is_text;
 
     // Count lines if it's a text file and not too large
     let line_count = if is_text && size_bytes < 10 * 1024 * 1024 {
         // Only count lines for files under 10MB
-        match fs::read_to_string(&safe_path) {
-            Ok(content) => Some(content.lines().count()),
-            Err(_) => None,
+        match fs.read(&safe_path.path, size_bytes).map(String::from_utf8) {
+            Ok(Ok(content)) => Some(content.lines().count()),
+            _ => None,
         }
     } else {
         None
     };
 
     Ok(FileInfo {
-        path: safe_path.display().to_string(),
+        path: safe_path.path.clone(),
         size_bytes,
         size_human,
         line_count,
@@ -195,51 +160,37 @@ pub async fn list_directory_tool(
     path: Option<String>,
     show_hidden: Option<bool>,
     working_directory: Option<String>,
+    state: tauri::State<'_, AppState>,
 ) -> Result<DirectoryListing, String> {
+    let fs = tool_fs(&state)?;
     let dir_path = path.as_deref().unwrap_or(".");
-    let base_dir = resolve_base_dir(&working_directory);
-
-    let full_path = if Path::new(dir_path).is_absolute() {
-        Path::new(dir_path).to_path_buf()
-    } else {
-        base_dir.join(dir_path)
-    };
+    let full_path = fs.join(&working_directory, dir_path);
 
     // SECURITY: Validate path to prevent directory traversal
-    let safe_path = validate_path(&full_path)?;
+    let safe_path = fs.resolve(&full_path, false)?;
     let show_hidden = show_hidden.unwrap_or(false);
 
     if !safe_path.exists() {
-        return Err(format!("Directory does not exist: {}", safe_path.display()));
+        return Err(format!("Directory does not exist: {}", safe_path.path));
     }
 
     if !safe_path.is_dir() {
-        return Err(format!("Path is not a directory: {}", safe_path.display()));
+        return Err(format!("Path is not a directory: {}", safe_path.path));
     }
 
-    let entries = fs::read_dir(&safe_path).map_err(|e| format!("Failed to read directory: {}", e))?;
-
     let mut files = Vec::new();
     let mut directories = Vec::new();
 
-    for entry in entries {
-        let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
-        let file_name = entry.file_name();
-        let name = file_name.to_string_lossy();
-
+    for (name, is_dir) in fs.list_dir(&safe_path.path)? {
         // Skip hidden files unless requested
         if !show_hidden && name.starts_with('.') {
             continue;
         }
 
-        let metadata = entry
-            .metadata()
-            .map_err(|e| format!("Failed to read metadata: {}", e))?;
-
-        if metadata.is_dir() {
-            directories.push(name.to_string());
+        if is_dir {
+            directories.push(name);
         } else {
-            files.push(name.to_string());
+            files.push(name);
         }
     }
 
@@ -259,45 +210,29 @@ pub async fn search_files_tool(
     pattern: String,
     max_results: usize,
     working_directory: Option<String>,
+    state: tauri::State<'_, AppState>,
 ) -> Result<Vec<String>, String> {
-    let base_dir = resolve_base_dir(&working_directory);
+    let fs = tool_fs(&state)?;
+    let base_dir = fs.join(&working_directory, ".");
 
     // SECURITY: Validate the search root directory
-    let safe_dir = validate_path(&base_dir)?;
+    let safe_dir = fs.resolve(&base_dir, false)?;
 
     let max_results = max_results.min(500);
 
     let mut results = Vec::new();
-    let walker = WalkDir::new(&safe_dir)
-        .max_depth(10)
-        .into_iter()
-        .filter_entry(|e| {
-            let name = e.file_name().to_string_lossy();
-            !name.starts_with('.') && name != "node_modules" && name != "target"
-        });
-
-    for entry in walker {
+    for relative in fs.walk_files(&safe_dir.path, 10)? {
         if results.len() >= max_results {
             break;
         }
 
-        let Some(entry) = entry.ok() else {
-            continue;
-        };
-
-        if !entry.file_type().is_file() {
-            continue;
-        }
-
-        let path = entry.path();
-        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let file_name = Path::new(&relative)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("");
 
         if matches_pattern(file_name, &pattern) {
-            if let Ok(relative) = path.strip_prefix(&safe_dir) {
-                results.push(relative.to_string_lossy().to_string());
-            } else {
-                results.push(path.to_string_lossy().to_string());
-            }
+            results.push(relative);
         }
     }
 
@@ -310,7 +245,18 @@ pub async fn get_current_directory_tool(
     state: tauri::State<'_, AppState>,
 ) -> Result<String, String> {
     if let Some(id) = terminal_id {
-        return crate::pty::get_pty_cwd(id, state);
+        let cwd = crate::pty::get_pty_cwd(id, state)?;
+        return Ok(match (cwd.remote, cwd.host) {
+            (true, Some(host)) => format!(
+                "{} (on remote host {}; not on this machine's filesystem)",
+                cwd.path, host
+            ),
+            (true, None) => format!(
+                "{} (on a remote host; not on this machine's filesystem)",
+                cwd.path
+            ),
+            (false, _) => cwd.path,
+        });
     }
 
     let cwd =
@@ -364,7 +310,7 @@ const SENSITIVE_ENV_VARS: &[&str] = &[
 ];
 
 /// Check if an environment variable name matches any sensitive pattern.
-fn is_sensitive_env_var(name: &str) -> bool {
+pub(crate) fn is_sensitive_env_var(name: &str) -> bool {
     let upper = name.to_uppercase();
 
     // Check exact matches
@@ -406,33 +352,30 @@ pub(crate) async fn write_file_impl(
     working_directory: Option<String>,
     state: &AppState,
 ) -> Result<String, String> {
-    use std::fs;
-    use std::io::Write;
-
-    let full_path = resolve_path(&path, &working_directory);
+    let fs = tool_fs(state)?;
+    let full_path = fs.join(&working_directory, &path);
 
     // SECURITY: Validate path for write (prevents traversal + blocks sensitive files)
-    let safe_path = validate_path_for_write(&full_path)?;
+    let safe_path = fs.resolve(&full_path, true)?;
 
     // Create backup before modifying (if file exists)
     if safe_path.exists() {
-        if let Err(e) = create_file_backup(state, &safe_path) {
-            eprintln!("[Backup] Failed to backup {}: {}", safe_path.display(), e);
+        if let Err(e) = create_file_backup(state, fs.as_ref(), &safe_path) {
+            eprintln!("[Backup] Failed to backup {}: {}", safe_path.path, e);
         }
     }
 
-    if let Some(parent) = safe_path.parent() {
-        fs::create_dir_all(parent)
+    if let Some(parent) = Path::new(&safe_path.path).parent() {
+        fs.create_dir_all(&parent.to_string_lossy())
             .map_err(|e| format!("Failed to create parent directory: {}", e))?;
     }
 
-    let mut file =
-        fs::File::create(&safe_path).map_err(|e| format!("Failed to open file: {}", e))?;
-
-    file.write_all(content.as_bytes())
-        .map_err(|e| format!("Failed to write file: {}", e))?;
+    fs.write(&safe_path.path, content.as_bytes(), false)?;
 
-    Ok(format!("Successfully wrote to {}", safe_path.display()))
+    Ok(format!(
+        "Successfully wrote to {}",
+        display_path(fs.as_ref(), &safe_path.path)
+    ))
 }
 
 #[tauri::command]
@@ -452,31 +395,25 @@ pub(crate) async fn append_to_file_impl(
     working_directory: Option<String>,
     state: &AppState,
 ) -> Result<String, String> {
-    use std::fs::OpenOptions;
-    use std::io::Write;
-
-    let full_path = resolve_path(&path, &working_directory);
+    let fs = tool_fs(state)?;
+    let full_path = fs.join(&working_directory, &path);
 
     // SECURITY: Validate path for write (prevents traversal + blocks sensitive files)
-    let safe_path = validate_path_for_write(&full_path)?;
+    let safe_path = fs.resolve(&full_path, true)?;
 
     // Create backup before modifying (if file exists)
     if safe_path.exists() {
-        if let Err(e) = create_file_backup(state, &safe_path) {
-            eprintln!("[Backup] Failed to backup {}: {}", safe_path.display(), e);
+        if let Err(e) = create_file_backup(state, fs.as_ref(), &safe_path) {
+            eprintln!("[Backup] Failed to backup {}: {}", safe_path.path, e);
         }
     }
 
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&safe_path)
-        .map_err(|e| format!("Failed to open file: {}", e))?;
-
-    file.write_all(content.as_bytes())
-        .map_err(|e| format!("Failed to append to file: {}", e))?;
+    fs.write(&safe_path.path, content.as_bytes(), true)?;
 
-    Ok(format!("Successfully appended to {}", safe_path.display()))
+    Ok(format!(
+        "Successfully appended to {}",
+        display_path(fs.as_ref(), &safe_path.path)
+    ))
 }
 
 #[tauri::command]
@@ -499,28 +436,27 @@ pub async fn replace_in_file_tool(
     working_directory: Option<String>,
     state: tauri::State<'_, AppState>,
 ) -> Result<String, String> {
-    use std::fs;
-
-    let full_path = resolve_path(&path, &working_directory);
+    let fs = tool_fs(&state)?;
+    let full_path = fs.join(&working_directory, &path);
 
     // SECURITY: Validate path for write (prevents traversal + blocks sensitive files)
-    let safe_path = validate_path_for_write(&full_path)?;
+    let safe_path = fs.resolve(&full_path, true)?;
 
     if !safe_path.exists() {
-        return Err(format!("File does not exist: {}", safe_path.display()));
+        return Err(format!("File does not exist: {}", safe_path.path));
     }
 
     if !safe_path.is_file() {
-        return Err(format!("Path is not a file: {}", safe_path.display()));
+        return Err(format!("Path is not a file: {}", safe_path.path));
     }
 
     // Create backup before modifying
-    if let Err(e) = create_file_backup(&state, &safe_path) {
-        eprintln!("[Backup] Failed to backup {}: {}", safe_path.display(), e);
+    if let Err(e) = create_file_backup(&state, fs.as_ref(), &safe_path) {
+        eprintln!("[Backup] Failed to backup {}: {}", safe_path.path, e);
     }
 
     // Read the file content
-    let content = fs::read_to_string(&safe_path)
+    let content = String::from_utf8(fs.read(&safe_path.path, safe_path.size())?)
         .map_err(|e| format!("Failed to read file: {}", e))?;
 
     // Perform replacement
@@ -546,15 +482,14 @@ pub async fn replace_in_file_tool(
     }
 
     // Write back to file
-    fs::write(&safe_path, new_content)
-        .map_err(|e| format!("Failed to write file: {}", e))?;
+    fs.write(&safe_path.path, new_content.as_bytes(), false)?;
 
     let occurrence_text = if count == 1 { "occurrence" } else { "occurrences" };
     Ok(format!(
         "Successfully replaced {} {} in {}",
         count,
         occurrence_text,
-        safe_path.display()
+        display_path(fs.as_ref(), &safe_path.path)
     ))
 }
 
@@ -657,19 +592,21 @@ pub async fn get_system_info_tool() -> Result<String, String> {
 }
 
 // Read last N lines of a file (tail)
-#[tauri::command]
-pub async fn tail_file_tool(
+pub(crate) fn tail_file_impl(
     path: String,
     lines: usize,
     working_directory: Option<String>,
+    fs: &dyn ToolFs,
 ) -> Result<String, String> {
-    let full_path = resolve_path(&path, &working_directory);
+    let full_path = fs.join(&working_directory, &path);
 
     // SECURITY: Validate path to prevent traversal attacks
-    let safe_path = validate_path(&full_path)?;
+    let safe_path = fs.resolve(&full_path, false)?;
 
-    let content =
-        fs::read_to_string(&safe_path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let mut content = String::new();
+    fs.open(&safe_path.path)?
+        .read_to_string(&mut content)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
 
     let all_lines: Vec<&str> = content.lines().collect();
     let start = all_lines.len().saturating_sub(lines);
@@ -684,29 +621,23 @@ pub async fn tail_file_tool(
 pub async fn make_directory_tool(
     path: String,
     working_directory: Option<String>,
+    state: tauri::State<'_, AppState>,
 ) -> Result<String, String> {
-    // Determine base directory
-    let base_dir = if let Some(cwd) = working_directory.as_ref() {
-        Path::new(cwd)
-    } else {
-        Path::new(".")
-    };
-    
+    let fs = tool_fs(&state)?;
+
     // Construct full path
-    let full_path = if Path::new(&path).is_absolute() {
-        Path::new(&path).to_path_buf()
-    } else {
-        base_dir.join(&path)
-    };
+    let full_path = fs.join(&working_directory, &path);
 
     // Validate path to prevent directory traversal
-    let safe_path = validate_path(&full_path)?;
+    let safe_path = fs.resolve(&full_path, false)?;
 
     // Create directory with parents (equivalent to mkdir -p)
-    fs::create_dir_all(&safe_path)
-        .map_err(|e| format!("Failed to create directory: {}", e))?;
+    fs.create_dir_all(&safe_path.path)?;
 
-    Ok(format!("Successfully created directory: {}", safe_path.display()))
+    Ok(format!(
+        "Successfully created directory: {}",
+        display_path(fs.as_ref(), &safe_path.path)
+    ))
 }
 
 // Get git diff
@@ -918,6 +849,7 @@ pub async fn read_multiple_files_tool(
     paths: Vec<String>,
     max_bytes_per_file: Option<usize>,
     working_directory: Option<String>,
+    state: tauri::State<'_, AppState>,
 ) -> Result<String, String> {
     if paths.is_empty() {
         return Err("No paths provided".to_string());
@@ -928,18 +860,27 @@ pub async fn read_multiple_files_tool(
     }
 
     let max_bytes = max_bytes_per_file.unwrap_or(50000);
-    let base_dir = resolve_base_dir(&working_directory);
+    let fs = tool_fs(&state)?;
 
-    let mut results = Vec::new();
+    let full_paths: Vec<String> = paths
+        .iter()
+        .map(|path| fs.join(&working_directory, path))
+        .collect();
+    let resolved = fs.resolve_all(&full_paths, false);
+
+    // Read every file that passed validation in one batch
+    let readable: Vec<String> = resolved
+        .iter()
+        .flatten()
+        .filter(|safe_path| safe_path.is_file())
+        .map(|safe_path| safe_path.path.clone())
+        .collect();
+    let mut contents = fs.read_all(&readable, max_bytes as u64).into_iter();
 
-    for path in paths {
-        let file_path = if Path::new(&path).is_absolute() {
-            Path::new(&path).to_path_buf()
-        } else {
-            base_dir.join(&path)
-        };
+    let mut results = Vec::new();
 
-        let safe_path = match validate_path(&file_path) {
+    for (path, safe_path) in paths.iter().zip(resolved) {
+        let safe_path = match safe_path {
             Ok(p) => p,
             Err(e) => {
                 results.push(format!("=== {} ===\nError: {}\n", path, e));
@@ -957,46 +898,26 @@ pub async fn read_multiple_files_tool(
             continue;
         }
 
-        // Read file with size limit
-        match fs::metadata(&safe_path) {
-            Ok(metadata) => {
-                let file_size = metadata.len() as usize;
-                let bytes_to_read = file_size.min(max_bytes);
-
-                match fs::File::open(&safe_path) {
-                    Ok(mut file) => {
-                        use std::io::Read;
-                        let mut buffer = vec![0u8; bytes_to_read];
-
-                        match file.read(&mut buffer) {
-                            Ok(bytes_read) => {
-                                buffer.truncate(bytes_read);
-                                match String::from_utf8(buffer) {
-                                    Ok(content) => {
-                                        let truncated = if file_size > max_bytes {
-                                            format!(" (truncated from {} bytes)", file_size)
-                                        } else {
-                                            String::new()
-                                        };
-                                        results.push(format!("=== {}{} ===\n{}\n", path, truncated, content));
-                                    }
-                                    Err(_) => {
-                                        results.push(format!("=== {} ===\nError: Binary file\n", path));
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                results.push(format!("=== {} ===\nError reading: {}\n", path, e));
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        results.push(format!("=== {} ===\nError opening: {}\n", path, e));
-                    }
+        let file_size = safe_path.size() as usize;
+        match contents.next() {
+            Some(Ok(buffer)) => match String::from_utf8(buffer) {
+                Ok(content) => {
+                    let truncated = if file_size > max_bytes {
+                        format!(" (truncated from {} bytes)", file_size)
+                    } else {
+                        String::new()
+                    };
+                    results.push(format!("=== {}{} ===\n{}\n", path, truncated, content));
+                }
+                Err(_) => {
+                    results.push(format!("=== {} ===\nError: Binary file\n", path));
                 }
+            },
+            Some(Err(e)) => {
+                results.push(format!("=== {} ===\nError reading: {}\n", path, e));
             }
-            Err(e) => {
-                results.push(format!("=== {} ===\nError getting metadata: {}\n", path, e));
+            None => {
+                results.push(format!("=== {} ===\nError reading: no content\n", path));
             }
         }
     }
@@ -1011,6 +932,7 @@ pub async fn grep_in_files_tool(
     paths: Vec<String>,
     case_sensitive: Option<bool>,
     working_directory: Option<String>,
+    state: tauri::State<'_, AppState>,
 ) -> Result<String, String> {
     if paths.is_empty() {
         return Err("No paths provided".to_string());
@@ -1027,29 +949,30 @@ pub async fn grep_in_files_tool(
         pattern.to_lowercase()
     };
 
-    let base_dir = resolve_base_dir(&working_directory);
+    // Search only files that pass validation, read in one batch
+    let (safe_paths, contents) = run_blocking(tool_fs(&state)?.into(), move |fs| {
+        let full_paths: Vec<String> = paths
+            .iter()
+            .map(|path| fs.join(&working_directory, path))
+            .collect();
+        let safe_paths: Vec<String> = fs
+            .resolve_all(&full_paths, false)
+            .into_iter()
+            .flatten()
+            .filter(|safe_path| safe_path.is_file())
+            .map(|safe_path| safe_path.path)
+            .collect();
+        let contents = fs.read_all(&safe_paths, MAX_GREP_FILE_BYTES);
+        Ok((safe_paths, contents))
+    })
+    .await?;
 
     let mut results = Vec::new();
     let mut match_count = 0;
 
-    for path in paths {
-        let file_path = if Path::new(&path).is_absolute() {
-            Path::new(&path).to_path_buf()
-        } else {
-            base_dir.join(&path)
-        };
-
-        let safe_path = match validate_path(&file_path) {
-            Ok(p) => p,
-            Err(_) => continue,
-        };
-
-        if !safe_path.is_file() {
-            continue;
-        }
-
-        match fs::read_to_string(&safe_path) {
-            Ok(content) => {
+    for (safe_path, content) in safe_paths.iter().zip(contents) {
+        match content.map(String::from_utf8) {
+            Ok(Ok(content)) => {
                 let lines: Vec<&str> = content.lines().collect();
                 let mut file_matches = Vec::new();
 
@@ -1061,7 +984,7 @@ pub async fn grep_in_files_tool(
                     };
 
                     if matched {
-                        file_matches.push(format!("  {}:{}: {}", safe_path.display(), line_num + 1, line));
+                        file_matches.push(format!("  {}:{}: {}", safe_path, line_num + 1, line));
                         match_count += 1;
 
                         if match_count >= 100 {
@@ -1071,7 +994,7 @@ pub async fn grep_in_files_tool(
                 }
 
                 if !file_matches.is_empty() {
-                    results.push(format!("{}:\n{}", safe_path.display(), file_matches.join("\n")));
+                    results.push(format!("{}:\n{}", safe_path, file_matches.join("\n")));
                 }
 
                 if match_count >= 100 {
@@ -1079,7 +1002,7 @@ pub async fn grep_in_files_tool(
                     break;
                 }
             }
-            Err(_) => continue,
+            _ => continue,
         }
     }
 
@@ -1095,6 +1018,7 @@ pub async fn grep_in_files_tool(
 pub async fn analyze_error_tool(
     error_text: String,
     working_directory: Option<String>,
+    state: tauri::State<'_, AppState>,
 ) -> Result<String, String> {
     let mut analysis = Vec::new();
     
@@ -1163,17 +1087,18 @@ pub async fn analyze_error_tool(
 
     if !mentioned_files.is_empty() {
         analysis.push("FILES MENTIONED:".to_string());
-        for file in mentioned_files.iter().take(10) {
-            // Check if file exists
-            let base_dir = resolve_base_dir(&working_directory);
-            
-            let file_path = if Path::new(file).is_absolute() {
-                Path::new(file).to_path_buf()
-            } else {
-                base_dir.join(file)
-            };
-
-            let exists = file_path.exists();
+        let files: Vec<&String> = mentioned_files.iter().take(10).collect();
+
+        // Check which files exist, all at once
+        let fs = tool_fs(&state)?;
+        let full_paths: Vec<String> = files
+            .iter()
+            .map(|file| fs.join(&working_directory, file))
+            .collect();
+        let found = fs.resolve_all(&full_paths, false);
+
+        for (file, found) in files.iter().zip(found) {
+            let exists = found.is_ok_and(|safe_path| safe_path.exists());
             let status = if exists { "✓" } else { "✗" };
             analysis.push(format!("  {} {}", status, file));
         }
@@ -1351,34 +1276,28 @@ pub async fn find_errors_in_file_tool(
     context_lines: Option<usize>,
     max_matches: Option<usize>,
     custom_patterns: Option<Vec<String>>,
+    state: tauri::State<'_, AppState>,
 ) -> Result<String, String> {
     use std::collections::VecDeque;
     use std::io::{BufRead, BufReader};
 
     // Resolve path
-    let base_dir = resolve_base_dir(&working_directory);
-
-    let file_path = if Path::new(&path).is_absolute() {
-        Path::new(&path).to_path_buf()
-    } else {
-        base_dir.join(&path)
-    };
+    let fs = tool_fs(&state)?;
+    let file_path = fs.join(&working_directory, &path);
 
     // SECURITY: Validate path
-    let safe_path = validate_path(&file_path)?;
+    let safe_path = fs.resolve(&file_path, false)?;
 
     if !safe_path.exists() {
-        return Err(format!("File does not exist: {}", safe_path.display()));
+        return Err(format!("File does not exist: {}", safe_path.path));
     }
 
     if !safe_path.is_file() {
-        return Err(format!("Path is not a file: {}", safe_path.display()));
+        return Err(format!("Path is not a file: {}", safe_path.path));
     }
 
     // Get file metadata for summary
-    let metadata = fs::metadata(&safe_path)
-        .map_err(|e| format!("Failed to read file metadata: {}", e))?;
-    let file_size = metadata.len();
+    let file_size = safe_path.size();
     let size_human = format_file_size(file_size);
 
     // Configuration
@@ -1410,8 +1329,7 @@ pub async fn find_errors_in_file_tool(
         .unwrap_or_else(|| default_patterns.iter().map(|s| s.to_string()).collect());
 
     // Open file for streaming read
-    let file = fs::File::open(&safe_path)
-        .map_err(|e| format!("Failed to open file: {}", e))?;
+    let file = fs.open(&safe_path.path)?;
     let reader = BufReader::with_capacity(64 * 1024, file); // 64KB buffer for efficiency
 
     // Circular buffer for context lines before match
@@ -1489,7 +1407,7 @@ pub async fn find_errors_in_file_tool(
     if matches.is_empty() {
         output.push(format!(
             "No errors found in {} ({}, {} lines scanned)",
-            safe_path.display(),
+            display_path(fs.as_ref(), &safe_path.path),
             size_human,
             total_lines
         ));
@@ -1499,7 +1417,7 @@ pub async fn find_errors_in_file_tool(
         output.push(format!(
             "Found {} error(s) in {} ({}, {} lines):",
             matches.len(),
-            safe_path.display(),
+            display_path(fs.as_ref(), &safe_path.path),
             size_human,
             total_lines
         ));
@@ -1555,27 +1473,23 @@ pub async fn file_sections_tool(
     start_line: usize,
     end_line: Option<usize>,
     max_lines: Option<usize>,
+    state: tauri::State<'_, AppState>,
 ) -> Result<String, String> {
     use std::io::{BufRead, BufReader};
 
     // Resolve path
-    let base_dir = resolve_base_dir(&working_directory);
-
-    let file_path = if Path::new(&path).is_absolute() {
-        Path::new(&path).to_path_buf()
-    } else {
-        base_dir.join(&path)
-    };
+    let fs = tool_fs(&state)?;
+    let file_path = fs.join(&working_directory, &path);
 
     // SECURITY: Validate path
-    let safe_path = validate_path(&file_path)?;
+    let safe_path = fs.resolve(&file_path, false)?;
 
     if !safe_path.exists() {
-        return Err(format!("File does not exist: {}", safe_path.display()));
+        return Err(format!("File does not exist: {}", safe_path.path));
     }
 
     if !safe_path.is_file() {
-        return Err(format!("Path is not a file: {}", safe_path.display()));
+        return Err(format!("Path is not a file: {}", safe_path.path));
     }
 
     // Validate start_line (1-indexed)
@@ -1584,9 +1498,7 @@ pub async fn file_sections_tool(
     }
 
     // Get file metadata
-    let metadata = fs::metadata(&safe_path)
-        .map_err(|e| format!("Failed to read file metadata: {}", e))?;
-    let file_size = metadata.len();
+    let file_size = safe_path.size();
     let size_human = format_file_size(file_size);
 
     // Configuration
@@ -1599,8 +1511,7 @@ pub async fn file_sections_tool(
     let actual_end = start_line + lines_to_read - 1;
 
     // Open file for streaming read
-    let file = fs::File::open(&safe_path)
-        .map_err(|e| format!("Failed to open file: {}", e))?;
+    let file = fs.open(&safe_path.path)?;
     let reader = BufReader::with_capacity(64 * 1024, file); // 64KB buffer
 
     let mut output_lines: Vec<String> = Vec::new();
@@ -1660,7 +1571,7 @@ pub async fn file_sections_tool(
             "Lines {}-{} of {} ({}):",
             start_line,
             showing_end,
-            safe_path.display(),
+            display_path(fs.as_ref(), &safe_path.path),
             total_info
         ));
         output.push(String::new());
@@ -1703,20 +1614,22 @@ fn truncate_line(line: &str, max_len: usize) -> String {
 /// Helper: Create a backup of a file before modifying it
 pub fn create_file_backup(
     state: &AppState,
-    path: &Path,
+    fs: &dyn ToolFs,
+    path: &ToolPath,
 ) -> Result<(), String> {
     use crate::models::{FileBackup, MAX_BACKUPS_PER_FILE, MAX_TOTAL_BACKUPS};
     
     // Only backup if file exists
-    if !path.exists() || !path.is_file() {
+    if !path.is_file() {
         return Ok(());
     }
     
     // Read current content
-    let content = fs::read_to_string(path)
+    let content = String::from_utf8(fs.read(&path.path, path.size())?)
         .map_err(|e| format!("Failed to read file for backup: {}", e))?;
     
-    let path_str = path.to_string_lossy().to_string();
+    let path_str = path.path.clone();
+    let host = fs.host().map(str::to_string);
     let timestamp = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .unwrap()
@@ -1724,6 +1637,7 @@ pub fn create_file_backup(
     
     let backup = FileBackup {
         path: path_str.clone(),
+        host: host.clone(),
         content,
         timestamp,
     };
@@ -1732,11 +1646,12 @@ pub fn create_file_backup(
         .map_err(|e| format!("Failed to lock backups: {}", e))?;
     
     // Count existing backups for this file
-    let file_backup_count = backups.iter().filter(|b| b.path == path_str).count();
+    let is_this_file = |b: &FileBackup| b.path == path_str && b.host == host;
+    let file_backup_count = backups.iter().filter(|b| is_this_file(b)).count();
     
     // If too many backups for this file, remove oldest
     if file_backup_count >= MAX_BACKUPS_PER_FILE {
-        if let Some(idx) = backups.iter().position(|b| b.path == path_str) {
+        if let Some(idx) = backups.iter().position(is_this_file) {
             backups.remove(idx);
         }
     }
@@ -1757,41 +1672,61 @@ pub async fn undo_file_change_tool(
     working_directory: Option<String>,
     state: tauri::State<'_, AppState>,
 ) -> Result<String, String> {
-    let mut backups = state.file_backups.lock()
-        .map_err(|e| format!("Failed to lock backups: {}", e))?;
-    
-    if backups.is_empty() {
-        return Err("No file backups available to restore".to_string());
-    }
-    
-    // If path specified, find backup for that specific file
-    let backup = if let Some(ref p) = path {
-        let full_path = resolve_path(p, &working_directory);
-        
-        let safe_path = validate_path(&full_path)?;
-        let path_str = safe_path.to_string_lossy().to_string();
+    let fs: Arc<dyn ToolFs> = tool_fs(&state)?.into();
+    let host = fs.host().map(str::to_string);
+
+    // Resolve the requested file before taking the lock; on an SSH host
+    // that is a round trip
+    let path_str = match path {
+        Some(p) => Some(
+            run_blocking(fs.clone(), move |fs| {
+                Ok(fs.resolve(&fs.join(&working_directory, &p), false)?.path)
+            })
+            .await?,
+        ),
+        None => None,
+    };
+
+    let backup = {
+        let mut backups = state.file_backups.lock()
+            .map_err(|e| format!("Failed to lock backups: {}", e))?;
         
-        // Find most recent backup for this file
-        let idx = backups.iter().rposition(|b| b.path == path_str)
-            .ok_or_else(|| format!("No backup found for: {}", path_str))?;
+        if backups.is_empty() {
+            return Err("No file backups available to restore".to_string());
+        }
         
-        backups.remove(idx)
-    } else {
-        // No path specified, restore most recent backup
-        backups.pop().ok_or_else(|| "No backups remaining".to_string())?
+        // If path specified, find backup for that specific file
+        if let Some(path_str) = path_str {
+            // Find most recent backup for this file
+            let idx = backups.iter().rposition(|b| b.path == path_str && b.host == host)
+                .ok_or_else(|| format!("No backup found for: {}", path_str))?;
+            
+            backups.remove(idx)
+        } else {
+            // No path specified, restore the most recent backup taken on this host
+            let idx = backups.iter().rposition(|b| b.host == host)
+                .ok_or_else(|| "No backups remaining".to_string())?;
+            backups.remove(idx)
+        }
     };
     
-    // SECURITY: Re-validate the restore path before writing
-    let restore_path = validate_path_for_write(Path::new(&backup.path))?;
-
-    // Create parent directories if needed
-    if let Some(parent) = restore_path.parent() {
-        fs::create_dir_all(parent)
-            .map_err(|e| format!("Failed to create parent directory: {}", e))?;
-    }
+    // Write the backup with the lock released
+    let (backup, shown_path) = run_blocking(fs, move |fs| {
+        // SECURITY: Re-validate the restore path before writing
+        let restore_path = fs.resolve(&backup.path, true)?;
+
+        // Create parent directories if needed
+        if let Some(parent) = Path::new(&restore_path.path).parent() {
+            fs.create_dir_all(&parent.to_string_lossy())
+                .map_err(|e| format!("Failed to create parent directory: {}", e))?;
+        }
 
-    fs::write(&restore_path, &backup.content)
-        .map_err(|e| format!("Failed to restore file: {}", e))?;
+        fs.write(&restore_path.path, backup.content.as_bytes(), false)
+            .map_err(|e| format!("Failed to restore file: {}", e))?;
+        let shown_path = display_path(fs, &backup.path);
+        Ok((backup, shown_path))
+    })
+    .await?;
     
     // Format timestamp
     let datetime = chrono::DateTime::from_timestamp(backup.timestamp as i64, 0)
@@ -1800,7 +1735,7 @@ pub async fn undo_file_change_tool(
     
     Ok(format!(
         "Restored {} to version from {} ({} bytes)",
-        backup.path,
+        shown_path,
         datetime,
         backup.content.len()
     ))
@@ -1822,12 +1757,16 @@ pub async fn list_file_backups_tool(
     
     // Filter by path if specified
     let filtered: Vec<_> = if let Some(ref p) = path {
-        let full_path = resolve_path(p, &working_directory);
+        let fs = tool_fs(&state)?;
+        let full_path = fs.join(&working_directory, p);
         
-        let safe_path = validate_path(&full_path)?;
-        let path_str = safe_path.to_string_lossy().to_string();
+        let safe_path = fs.resolve(&full_path, false)?;
+        let host = fs.host();
         
-        backups.iter().filter(|b| b.path == path_str).collect()
+        backups
+            .iter()
+            .filter(|b| b.path == safe_path.path && b.host.as_deref() == host)
+            .collect()
     } else {
         backups.iter().collect()
     };
@@ -1849,7 +1788,11 @@ pub async fn list_file_backups_tool(
             format!("{:.1} KB", backup.content.len() as f64 / 1024.0)
         };
         
-        output.push(format!("  {}. {} ({}) - {}", i + 1, backup.path, size, datetime));
+        let location = match &backup.host {
+            Some(host) => format!("{}:{}", host, backup.path),
+            None => backup.path.clone(),
+        };
+        output.push(format!("  {}. {} ({}) - {}", i + 1, location, size, datetime));
     }
     
     Ok(output.join("\n"))
@@ -1863,15 +1806,16 @@ pub async fn diff_files_tool(
     working_directory: Option<String>,
     state: tauri::State<'_, AppState>,
 ) -> Result<String, String> {
-    let base_dir = resolve_base_dir(&working_directory);
+    let fs = tool_fs(&state)?;
+    let read_text = |safe_path: &ToolPath, label: &str| -> Result<String, String> {
+        fs.read(&safe_path.path, safe_path.size())
+            .and_then(|bytes| String::from_utf8(bytes).map_err(|e| e.to_string()))
+            .map_err(|e| format!("Failed to read {}: {}", label, e))
+    };
     
     // Resolve file1 path
-    let path1 = if Path::new(&file1).is_absolute() {
-        Path::new(&file1).to_path_buf()
-    } else {
-        base_dir.join(&file1)
-    };
-    let safe_path1 = validate_path(&path1)?;
+    let path1 = fs.join(&working_directory, &file1);
+    let safe_path1 = fs.resolve(&path1, false)?;
     
     // Get content1 - either from file or backup
     let (content1, label1) = if file2.is_none() {
@@ -1879,44 +1823,38 @@ pub async fn diff_files_tool(
         let backups = state.file_backups.lock()
             .map_err(|e| format!("Failed to lock backups: {}", e))?;
         
-        let path_str = safe_path1.to_string_lossy().to_string();
-        let backup = backups.iter().rev().find(|b| b.path == path_str)
+        let path_str = safe_path1.path.clone();
+        let host = fs.host();
+        let backup = backups.iter().rev().find(|b| b.path == path_str && b.host.as_deref() == host)
             .ok_or_else(|| format!("No backup found for {}. Cannot show diff without a previous version.", path_str))?;
         
         (backup.content.clone(), format!("{} (backup)", file1))
     } else {
         // Read file1
         if !safe_path1.exists() {
-            return Err(format!("File does not exist: {}", safe_path1.display()));
+            return Err(format!("File does not exist: {}", safe_path1.path));
         }
-        let content = fs::read_to_string(&safe_path1)
-            .map_err(|e| format!("Failed to read {}: {}", file1, e))?;
+        let content = read_text(&safe_path1, &file1)?;
         (content, file1.clone())
     };
     
     // Get content2
     let (content2, label2) = if let Some(ref f2) = file2 {
-        let path2 = if Path::new(f2).is_absolute() {
-            Path::new(f2).to_path_buf()
-        } else {
-            base_dir.join(f2)
-        };
-        let safe_path2 = validate_path(&path2)?;
+        let path2 = fs.join(&working_directory, f2);
+        let safe_path2 = fs.resolve(&path2, false)?;
         
         if !safe_path2.exists() {
-            return Err(format!("File does not exist: {}", safe_path2.display()));
+            return Err(format!("File does not exist: {}", safe_path2.path));
         }
         
-        let content = fs::read_to_string(&safe_path2)
-            .map_err(|e| format!("Failed to read {}: {}", f2, e))?;
+        let content = read_text(&safe_path2, f2)?;
         (content, f2.clone())
     } else {
         // Compare with current file content
         if !safe_path1.exists() {
-            return Err(format!("File does not exist: {}", safe_path1.display()));
+            return Err(format!("File does not exist: {}", safe_path1.path));
         }
-        let content = fs::read_to_string(&safe_path1)
-            .map_err(|e| format!("Failed to read {}: {}", file1, e))?;
+        let content = read_text(&safe_path1, &file1)?;
         (content, format!("{} (current)", file1))
     };
     
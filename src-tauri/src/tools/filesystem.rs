@@ -0,0 +1,288 @@
+//! The filesystem the agent's file tools work on.
+//!
+//! Tools follow the focused terminal: when it is an SSH session they read and
+//! write on that host, otherwise on this machine. Both implementations apply
+//! the same limits. Paths must canonicalize inside the home directory of the
+//! host they are on, and writes to credential and shell startup files are
+//! refused.
+
+use super::commands::{read_file_impl, tail_file_impl};
+use crate::models::AppState;
+use crate::security::path_validator::{validate_path, validate_path_for_write};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FsMetadata {
+    pub is_file: bool,
+    pub is_dir: bool,
+    pub size: u64,
+    pub modified: Option<u64>, // Unix timestamp
+}
+
+/// A path that passed validation, and what is at it right now
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolPath {
+    pub path: String,                 // Canonical path on the host
+    pub metadata: Option<FsMetadata>, // None when nothing exists there yet
+}
+
+impl ToolPath {
+    pub fn exists(&self) -> bool {
+        self.metadata.is_some()
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.metadata.as_ref().is_some_and(|m| m.is_file)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.metadata.as_ref().is_some_and(|m| m.is_dir)
+    }
+
+    pub fn size(&self) -> u64 {
+        self.metadata.as_ref().map_or(0, |m| m.size)
+    }
+}
+
+pub trait ToolFs: Send + Sync {
+    /// The remote host the files are on; None for this machine
+    fn host(&self) -> Option<&str>;
+
+    /// `path` as the tools should look it up, relative paths being taken from
+    /// the working directory
+    fn join(&self, working_directory: &Option<String>, path: &str) -> String;
+
+    /// Check `path` against the home directory limit (and for writes, the
+    /// sensitive file list) and stat it
+    fn resolve(&self, path: &str, for_write: bool) -> Result<ToolPath, String>;
+
+    /// `resolve` for several paths, in order
+    fn resolve_all(&self, paths: &[String], for_write: bool) -> Vec<Result<ToolPath, String>> {
+        paths
+            .iter()
+            .map(|path| self.resolve(path, for_write))
+            .collect()
+    }
+
+    /// Stream a file from the start
+    fn open(&self, path: &str) -> Result<Box<dyn Read + Send>, String>;
+
+    /// At most `max_bytes` from the start of a file
+    fn read(&self, path: &str, max_bytes: u64) -> Result<Vec<u8>, String> {
+        let mut buffer = Vec::new();
+        self.open(path)?
+            .take(max_bytes)
+            .read_to_end(&mut buffer)
+            .map_err(|e| format!("Failed to read file: {}", e))?;
+        Ok(buffer)
+    }
+
+    /// `read` for several files, in order
+    fn read_all(&self, paths: &[String], max_bytes: u64) -> Vec<Result<Vec<u8>, String>> {
+        paths
+            .iter()
+            .map(|path| self.read(path, max_bytes))
+            .collect()
+    }
+
+    /// Names in a directory, each with whether it is a directory itself
+    fn list_dir(&self, path: &str) -> Result<Vec<(String, bool)>, String>;
+
+    /// Files below `root`, relative to it, skipping hidden, `node_modules`
+    /// and `target` directories
+    fn walk_files(
+        &self,
+        root: &str,
+        max_depth: usize,
+    ) -> Result<Box<dyn Iterator<Item = String> + Send>, String>;
+
+    fn write(&self, path: &str, content: &[u8], append: bool) -> Result<(), String>;
+
+    fn create_dir_all(&self, path: &str) -> Result<(), String>;
+}
+
+/// This machine's filesystem, limited to `$HOME` by the path validator
+pub struct LocalFs;
+
+/// Resolve working_directory to a PathBuf, with tilde expansion.
+/// Falls back to the current directory if not provided.
+pub(crate) fn resolve_base_dir(working_directory: &Option<String>) -> PathBuf {
+    working_directory
+        .as_deref()
+        .and_then(|wd| shellexpand::tilde(wd).parse::<PathBuf>().ok())
+        .unwrap_or_else(|| std::env::current_dir().unwrap_or_default())
+}
+
+impl ToolFs for LocalFs {
+    fn host(&self) -> Option<&str> {
+        None
+    }
+
+    fn join(&self, working_directory: &Option<String>, path: &str) -> String {
+        // The validator expands `~` itself
+        if path.starts_with('~') || Path::new(path).is_absolute() {
+            return path.to_string();
+        }
+        resolve_base_dir(working_directory)
+            .join(path)
+            .to_string_lossy()
+            .to_string()
+    }
+
+    fn resolve(&self, path: &str, for_write: bool) -> Result<ToolPath, String> {
+        let safe_path = if for_write {
+            validate_path_for_write(Path::new(path))?
+        } else {
+            validate_path(Path::new(path))?
+        };
+        let metadata = fs::metadata(&safe_path).ok().map(|metadata| FsMetadata {
+            is_file: metadata.is_file(),
+            is_dir: metadata.is_dir(),
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|elapsed| elapsed.as_secs()),
+        });
+        Ok(ToolPath {
+            path: safe_path.display().to_string(),
+            metadata,
+        })
+    }
+
+    fn open(&self, path: &str) -> Result<Box<dyn Read + Send>, String> {
+        let file = fs::File::open(path).map_err(|e| format!("Failed to open file: {}", e))?;
+        Ok(Box::new(file))
+    }
+
+    fn list_dir(&self, path: &str) -> Result<Vec<(String, bool)>, String> {
+        let entries = fs::read_dir(path).map_err(|e| format!("Failed to read directory: {}", e))?;
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
+            let metadata = entry
+                .metadata()
+                .map_err(|e| format!("Failed to read metadata: {}", e))?;
+            names.push((
+                entry.file_name().to_string_lossy().to_string(),
+                metadata.is_dir(),
+            ));
+        }
+        Ok(names)
+    }
+
+    fn walk_files(
+        &self,
+        root: &str,
+        max_depth: usize,
+    ) -> Result<Box<dyn Iterator<Item = String> + Send>, String> {
+        let root = PathBuf::from(root);
+        let walker = WalkDir::new(&root)
+            .max_depth(max_depth)
+            .into_iter()
+            .filter_entry(|e| {
+                let name = e.file_name().to_string_lossy();
+                !name.starts_with('.') && name != "node_modules" && name != "target"
+            })
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(move |entry| {
+                let path = entry.path();
+                path.strip_prefix(&root)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .to_string()
+            });
+        Ok(Box::new(walker))
+    }
+
+    fn write(&self, path: &str, content: &[u8], append: bool) -> Result<(), String> {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+            .map_err(|e| format!("Failed to open file: {}", e))?;
+        file.write_all(content).map_err(|e| {
+            if append {
+                format!("Failed to append to file: {}", e)
+            } else {
+                format!("Failed to write file: {}", e)
+            }
+        })
+    }
+
+    fn create_dir_all(&self, path: &str) -> Result<(), String> {
+        fs::create_dir_all(path).map_err(|e| format!("Failed to create directory: {}", e))
+    }
+}
+
+/// The filesystem of the focused terminal: its remote host when it is an SSH
+/// session, this machine otherwise
+pub fn tool_fs(state: &AppState) -> Result<Box<dyn ToolFs>, String> {
+    let pty_id = state.active_terminal.load(Ordering::Acquire);
+    let is_ssh = pty_id != 0
+        && state
+            .ssh_sessions
+            .lock()
+            .map_err(|e| format!("Failed to acquire SSH session lock: {}", e))?
+            .contains_key(&pty_id);
+    if is_ssh {
+        Ok(Box::new(crate::ssh::SshFs::for_pty(state, pty_id)?))
+    } else {
+        Ok(Box::new(LocalFs))
+    }
+}
+
+/// Run `op` on a blocking thread. On an SSH host every filesystem call waits
+/// on ssh, which would otherwise stall the async runtime
+pub async fn run_blocking<T, F>(fs: Arc<dyn ToolFs>, op: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&dyn ToolFs) -> Result<T, String> + Send + 'static,
+{
+    tokio::task::spawn_blocking(move || op(fs.as_ref()))
+        .await
+        .map_err(|e| format!("File tool task failed: {}", e))?
+}
+
+#[tauri::command]
+pub async fn read_file_tool(
+    path: String,
+    max_bytes: usize,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    run_blocking(tool_fs(&state)?.into(), move |fs| {
+        read_file_impl(path, max_bytes, fs)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn tail_file_tool(
+    path: String,
+    lines: usize,
+    working_directory: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    run_blocking(tool_fs(&state)?.into(), move |fs| {
+        tail_file_impl(path, lines, working_directory, fs)
+    })
+    .await
+}
+
+/// Name a tool result after the host its files are on, when that is not
+/// this machine
+pub fn display_path(fs: &dyn ToolFs, path: &str) -> String {
+    match fs.host() {
+        Some(host) => format!("{}:{}", host, path),
+        None => path.to_string(),
+    }
+}
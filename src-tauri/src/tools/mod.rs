@@ -1,5 +1,6 @@
 // Tools module - System interaction tools
 pub mod commands;
+pub mod filesystem;
 
 // Re-export all tool commands for easier access
 pub use commands::*;
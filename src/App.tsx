@@ -20,6 +20,7 @@ import { useKeyboardShortcuts } from "./hooks/useKeyboardShortcuts";
 import { useCommandTracking } from "./hooks/useCommandTracking";
 import { useWindowCloseHandler } from "./hooks/useWindowCloseHandler";
 import { useAIPanelAutoOpen } from "./hooks/useAIPanelAutoOpen";
+import { useTmuxIntegration } from "./hooks/useTmuxIntegration";
 import { detectWindowType } from "./utils/windowDetection";
 import { openAIPanelWindow, openSSHPanelWindow } from "./utils/windowManagement";
 import { initializeActions } from "./actions/actions";
@@ -33,7 +34,7 @@ function AppContent() {
   const [isInitialized, setIsInitialized] = useState(false);
   const [isSettingsOpen, setIsSettingsOpen] = useState(false);
   const [isCommandPaletteOpen, setIsCommandPaletteOpen] = useState(false);
-  const { updateProfile, updateConnection, getProfileById } = useSSHProfiles();
+  const { loadProfiles, updateConnection, getProfileById } = useSSHProfiles();
   
   // Detect window type
   const windowType = detectWindowType();
@@ -58,7 +59,11 @@ function AppContent() {
     updateTabRemoteState,
     addSSHTab,
     addLocalTab,
+    syncTmuxWindow,
   } = useTabManagement(isInitialized, ptyToProfileMap, updateConnection);
+
+  // Show tmux control-mode windows as tabs
+  useTmuxIntegration({ tabs, syncTmuxWindow });
   
   // Use SSH connection hook
   const { connectSSHProfile } = useSSHConnection({
@@ -66,7 +71,7 @@ function AppContent() {
     ptyToProfileMap,
     setPtyToProfileMap,
     addSSHTab,
-    updateProfile,
+    loadProfiles,
     updateConnection,
   });
   
@@ -168,6 +168,7 @@ CORE PRINCIPLES:
 YOUR CAPABILITIES:
 - \`get_current_directory\`: Check where you are
 - \`execute_command\`: Run shell commands (pwd, ls, cat, etc.)
+- \`read_terminal_output\` / \`search_terminal_output\`: Read or regex-search what the terminal already printed, without re-running anything
 - \`read_file\`: Read file contents directly
 - \`get_file_info\`: Get file metadata (size, type, line count) BEFORE reading - use this to avoid reading huge/binary files
 - \`read_multiple_files\`: Read up to 20 files at once (useful for errors spanning multiple files)
@@ -423,6 +423,62 @@ Examples:
       },
     }),
 
+    read_terminal_output: tool({
+      description: `Read recent output from the active terminal (ANSI codes stripped), without running a command.
+Use this to see what the user just ran or what a command printed, e.g. an error message.
+
+Examples:
+- Last screen of output: lines=50
+- Longer history: lines=300`,
+      inputSchema: z.object({
+        lines: z.number().optional().describe('Number of lines to read from the end (default: 100)'),
+      }),
+      execute: async ({ lines }) => {
+        try {
+          const terminalId = await getActiveTerminalId();
+          const output = await invoke<string>('read_pty_scrollback', {
+            id: terminalId,
+            lastLines: lines || 100,
+          });
+          if (!output.trim()) {
+            return 'No terminal output captured yet';
+          }
+          return truncateToolResult(output);
+        } catch (error) {
+          return `Error reading terminal output: ${error}`;
+        }
+      },
+    }),
+
+    search_terminal_output: tool({
+      description: `Search the active terminal's output history with a regular expression. Returns matching lines, most recent first.
+
+Examples:
+- Find errors: pattern="(?i)error|failed"
+- Find a job ID: pattern="Submitted batch job \\d+"`,
+      inputSchema: z.object({
+        pattern: z.string().describe('Regular expression (Rust regex syntax)'),
+        max_results: z.number().optional().describe('Maximum matches to return (default: 50)'),
+      }),
+      execute: async ({ pattern, max_results }) => {
+        try {
+          const terminalId = await getActiveTerminalId();
+          const matches = await invoke<Array<{ line_number: number; line: string }>>('search_pty_scrollback', {
+            id: terminalId,
+            regex: pattern,
+            maxResults: max_results || 50,
+          });
+          if (matches.length === 0) {
+            return `No lines matching "${pattern}" in terminal output`;
+          }
+          const formatted = matches.map((m) => `${m.line_number}: ${m.line}`).join('\n');
+          return truncateToolResult(`Found ${matches.length} matching line(s):\n\n${formatted}`);
+        } catch (error) {
+          return `Error searching terminal output: ${error}`;
+        }
+      },
+    }),
+
     read_file: tool({
       description: `Read the contents of a file. Only works with text files. Large files will be truncated to ~3000 chars.
 
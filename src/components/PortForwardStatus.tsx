@@ -1,10 +1,13 @@
 import { useEffect, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import { PortForward, PortForwardHealth } from '../types/ssh';
+import { listen } from '@tauri-apps/api/event';
+import { PortForward, PortForwardHealth, PortForwardInfo } from '../types/ssh';
 import './PortForwardStatus.css';
 
 interface PortForwardStatusProps {
   portForwards: PortForward[];
+  /** Profile the forwards belong to; forwards the backend supervises show its status */
+  profileId?: string;
   /** Check interval in milliseconds (default: 10000 = 10s) */
   checkInterval?: number;
 }
@@ -12,8 +15,37 @@ interface PortForwardStatusProps {
 /**
  * Component that displays port forward status with health checks
  */
-export function PortForwardStatus({ portForwards, checkInterval = 10000 }: PortForwardStatusProps) {
+export function PortForwardStatus({ portForwards, profileId, checkInterval = 10000 }: PortForwardStatusProps) {
   const [healthStatus, setHealthStatus] = useState<Map<string, PortForwardHealth>>(new Map());
+  const [supervised, setSupervised] = useState<Map<string, PortForwardInfo>>(new Map());
+
+  useEffect(() => {
+    if (!profileId) {
+      return;
+    }
+
+    const track = (info: PortForwardInfo) => {
+      if (info.profileId !== profileId) return;
+      setSupervised(prev => {
+        const next = new Map(prev);
+        if (info.state === 'stopped') {
+          next.delete(info.forward.id);
+        } else {
+          next.set(info.forward.id, info);
+        }
+        return next;
+      });
+    };
+
+    invoke<PortForwardInfo[]>('list_port_forwards')
+      .then(forwards => forwards.forEach(track))
+      .catch(() => {});
+    const unlistenPromise = listen<PortForwardInfo>('port-forward-status', event => track(event.payload));
+
+    return () => {
+      unlistenPromise.then(unlisten => unlisten());
+    };
+  }, [profileId]);
 
   useEffect(() => {
     if (!portForwards || portForwards.length === 0) {
@@ -74,7 +106,15 @@ export function PortForwardStatus({ portForwards, checkInterval = 10000 }: PortF
       </div>
       <div className="port-forward-list">
         {portForwards.map(forward => {
-          const health = healthStatus.get(forward.id);
+          const info = supervised.get(forward.id);
+          const health: PortForwardHealth | undefined = info
+            ? {
+                forwardId: forward.id,
+                isActive: info.state === 'active',
+                lastChecked: new Date(info.updatedAt),
+                error: info.state === 'active' ? undefined : info.lastError || `Forward ${info.state}`,
+              }
+            : healthStatus.get(forward.id);
           const statusIcon = health?.isActive ? '🟢' : '🔴';
           const statusClass = health?.isActive ? 'active' : 'inactive';
 
@@ -54,6 +54,7 @@ export const SSHProfileEditor: React.FC<SSHProfileEditorProps> = ({
   const [autoConnect, setAutoConnect] = useState(false);
   const [healthCheckInterval, setHealthCheckInterval] = useState('30');
   const [alertOnDisconnect, setAlertOnDisconnect] = useState(false);
+  const [autoReconnect, setAutoReconnect] = useState(false);
 
 
   // Load profile data when editing
@@ -79,6 +80,7 @@ export const SSHProfileEditor: React.FC<SSHProfileEditorProps> = ({
       setAutoConnect(profile.autoConnect || false);
       setHealthCheckInterval(String(profile.healthCheckInterval || 30));
       setAlertOnDisconnect(profile.alertOnDisconnect || false);
+      setAutoReconnect(profile.autoReconnect || false);
     }
   }, [profile]);
 
@@ -106,6 +108,7 @@ export const SSHProfileEditor: React.FC<SSHProfileEditorProps> = ({
       autoConnect,
       healthCheckInterval: parseInt(healthCheckInterval) || 30,
       alertOnDisconnect,
+      autoReconnect,
       createdAt: profile?.createdAt || new Date().toISOString(),
       lastConnectedAt: profile?.lastConnectedAt,
       connectionCount: profile?.connectionCount || 0,
@@ -624,6 +627,17 @@ export const SSHProfileEditor: React.FC<SSHProfileEditorProps> = ({
                 Alert on disconnect
               </label>
             </div>
+            <div style={sshProfileEditorStyles.checkboxRow}>
+              <label style={sshProfileEditorStyles.checkboxLabel}>
+                <input
+                  type="checkbox"
+                  checked={autoReconnect}
+                  onChange={e => setAutoReconnect(e.target.checked)}
+                  style={sshProfileEditorStyles.checkboxInput}
+                />
+                Reconnect automatically when the connection drops
+              </label>
+            </div>
           </section>
         </div>
 
@@ -317,6 +317,19 @@ const SettingsModal: React.FC<SettingsModalProps> = ({ isOpen, onClose }) => {
                                         style={settingsModalStyles.formInput}
                                     />
                                 </div>
+                                <div style={settingsModalStyles.formGroup}>
+                                    <label style={settingsModalStyles.checkboxLabel}>
+                                        <input
+                                            type="checkbox"
+                                            checked={localSettings.terminal.persistent_sessions ?? false}
+                                            onChange={(e) => handleChange('terminal', 'persistent_sessions', e.target.checked as any)}
+                                        />
+                                        <span>Keep shells running after the app closes</span>
+                                    </label>
+                                    <div style={settingsModalStyles.formHint}>
+                                        New terminals are owned by a background session daemon and are reattached on the next launch. macOS and Linux only.
+                                    </div>
+                                </div>
                             </>
                         )}
 
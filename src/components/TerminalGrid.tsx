@@ -103,7 +103,7 @@ export function TerminalGrid(props: TerminalGridProps) {
                     {tab.profileId && (() => {
                       const profile = getProfileById(tab.profileId);
                       return profile?.portForwards && profile.portForwards.length > 0 ? (
-                        <PortForwardStatus portForwards={profile.portForwards} />
+                        <PortForwardStatus portForwards={profile.portForwards} profileId={profile.id} />
                       ) : null;
                     })()}
                     <TerminalErrorBoundary 
@@ -21,6 +21,8 @@ function getToolDisplayName(toolName: string): string {
   const names: Record<string, string> = {
     get_current_directory: 'Getting current directory',
     execute_command: 'Executing command',
+    read_terminal_output: 'Reading terminal output',
+    search_terminal_output: 'Searching terminal output',
     read_file: 'Reading file',
     list_directory: 'Listing directory',
     search_files: 'Searching files',
@@ -6,6 +6,7 @@
 
 import React, { createContext, useContext, useEffect, useState, useCallback, useRef, useMemo } from 'react';
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 import { SSHProfile, SSHConfigHost, ConnectionHealth, GroupOrder } from '../types/ssh';
 import { createLogger } from '../utils/logger';
 import { ContextErrorBoundary } from '../components/ContextErrorBoundary';
@@ -370,6 +371,16 @@ const SSHProfilesProviderInner: React.FC<SSHProfilesProviderProps> = ({ children
     init();
   }, [loadProfiles, loadSSHConfig]);
 
+  // Reload when the backend writes profiles itself (imports)
+  useEffect(() => {
+    const unlistenPromise = listen('ssh-profiles-changed', () => {
+      loadProfiles();
+    });
+    return () => {
+      unlistenPromise.then(unlisten => unlisten());
+    };
+  }, [loadProfiles]);
+
   const value = useMemo<SSHProfilesContextType>(() => ({
     profiles,
     loadProfiles,
@@ -54,6 +54,7 @@ export interface AiSettings {
 
 export interface TerminalSettings {
     max_markers: number;
+    persistent_sessions?: boolean;
 }
 
 export interface AutocompleteSettings {
@@ -27,12 +27,12 @@ interface UseSSHConnectionOptions {
   ptyToProfileMap: Map<number, string>;
   setPtyToProfileMap: (update: (prev: Map<number, string>) => Map<number, string>) => void;
   addSSHTab: (ptyId: number, displayName: string, profileId: string) => void;
-  updateProfile: (profileId: string, updates: Partial<SSHProfile>) => Promise<void>;
+  loadProfiles: () => Promise<void>;
   updateConnection: (ptyId: string, updates: any) => void;
 }
 
 export function useSSHConnection(options: UseSSHConnectionOptions) {
-  const { tabs, ptyToProfileMap, setPtyToProfileMap, addSSHTab, updateProfile, updateConnection } = options;
+  const { tabs, ptyToProfileMap, setPtyToProfileMap, addSSHTab, loadProfiles, updateConnection } = options;
 
   /**
    * Connect to an SSH profile and create a new tab
@@ -67,15 +67,12 @@ export function useSSHConnection(options: UseSSHConnectionOptions) {
         tabId: String(ptyId),
       });
       
-      // Update profile connection stats
-      await updateProfile(profile.id, {
-        lastConnectedAt: new Date().toISOString(),
-        connectionCount: (profile.connectionCount || 0) + 1,
-      });
+      // Pick up the connection stats the backend saved
+      await loadProfiles();
     } catch (error) {
       log.error("Failed to connect SSH profile", error);
     }
-  }, [addSSHTab, setPtyToProfileMap, updateConnection, updateProfile]);
+  }, [addSSHTab, setPtyToProfileMap, updateConnection, loadProfiles]);
 
   /**
    * Monitor connection health for SSH sessions
@@ -6,6 +6,43 @@ import type { SessionState } from "../types/session";
 
 const log = createLogger('useSessionRestoration');
 
+/** A shell kept running by the persistent PTY daemon */
+interface DaemonSession {
+  session: number;
+  argv: string[];
+  cwd: string | null;
+  attached_clients: number;
+}
+
+/**
+ * Reattach shells that outlived the previous app run. Returns how many tabs
+ * were opened.
+ */
+async function reattachDaemonSessions(
+  addLocalTab: (ptyId: number, title: string) => void
+): Promise<number> {
+  let sessions: DaemonSession[];
+  try {
+    sessions = await invoke<DaemonSession[]>("list_daemon_sessions");
+  } catch {
+    // Not available on this platform
+    return 0;
+  }
+
+  let attached = 0;
+  for (const session of sessions.filter(s => s.attached_clients === 0)) {
+    try {
+      const ptyId = await invoke<number>("attach_daemon_session", { session: session.session });
+      const program = session.argv[0]?.split('/').pop() || 'Terminal';
+      addLocalTab(ptyId, program);
+      attached++;
+    } catch (error) {
+      log.error(`Failed to reattach daemon session ${session.session}:`, error);
+    }
+  }
+  return attached;
+}
+
 interface UseSessionRestorationProps {
   profiles: SSHProfile[];
   connectSSHProfile: (profile: SSHProfile) => Promise<void>;
@@ -33,6 +70,14 @@ export function useSessionRestoration(props: UseSessionRestorationProps): void {
   useEffect(() => {
     const restoreOrCreateSession = async () => {
       try {
+        // Shells that kept running in the daemon replace the saved layout
+        const reattached = await reattachDaemonSessions(addLocalTab);
+        if (reattached > 0) {
+          log.info(`Reattached ${reattached} persistent sessions`);
+          setIsInitialized(true);
+          return;
+        }
+
         const sessionState = await loadSession();
         
         if (!sessionState || sessionState.tabs.length === 0) {
@@ -70,18 +115,24 @@ export function useSessionRestoration(props: UseSessionRestorationProps): void {
               // Restore local terminal
               log.info(`Restoring local terminal (cwd: ${sessionPane.workingDirectory || 'default'})`);
               try {
-                const ptyId = await invoke<number>("spawn_pty");
+                // Start the shell directly in the saved working directory;
+                // fall back to the default if it no longer exists
+                let ptyId: number;
+                if (sessionPane.workingDirectory) {
+                  try {
+                    ptyId = await invoke<number>("spawn_pty_with_profile", {
+                      options: { cwd: sessionPane.workingDirectory },
+                    });
+                  } catch (error) {
+                    log.warn(`Could not restore cwd ${sessionPane.workingDirectory}:`, error);
+                    ptyId = await invoke<number>("spawn_pty");
+                  }
+                } else {
+                  ptyId = await invoke<number>("spawn_pty");
+                }
 
                 // Create local tab with restored title
                 addLocalTab(ptyId, sessionTab.customName || sessionTab.title);
-
-                // Change to working directory if available
-                if (sessionPane.workingDirectory) {
-                  await invoke('write_to_pty', {
-                    id: ptyId,
-                    data: `cd "${sessionPane.workingDirectory}"\n`,
-                  });
-                }
               } catch (error) {
                 log.error('Failed to restore local terminal:', error);
               }
@@ -4,6 +4,7 @@ import { getCurrentWindow } from '@tauri-apps/api/window';
 import { emitTo } from '@tauri-apps/api/event';
 import { createLogger } from '../utils/logger';
 import type { ConnectionStatus } from '../app/sshIntegration';
+import type { TmuxWindow } from '../types/tmux';
 
 const log = createLogger('TabManagement');
 
@@ -39,6 +40,7 @@ interface UseTabManagementReturn {
   updateTabRemoteState: (tabId: number, paneId: number, isRemote: boolean, remoteHost?: string) => void;
   addSSHTab: (ptyId: number, displayName: string, profileId: string) => void;
   addLocalTab: (ptyId: number, title: string) => void;
+  syncTmuxWindow: (window: TmuxWindow) => void;
 }
 
 export function useTabManagement(
@@ -100,6 +102,42 @@ export function useTabManagement(
     setActiveTabId(ptyId);
   }, []);
 
+  const syncTmuxWindow = useCallback((window: TmuxWindow) => {
+    if (window.pty_ids.length === 0) return;
+    const { content, cols, rows } = window.layout;
+    // The grid splits once; deeper tmux splits are laid out along the top-level direction
+    const splitLayout: Tab['splitLayout'] =
+      content.type === 'columns' ? 'vertical' : content.type === 'rows' ? 'horizontal' : 'single';
+    const splitRatio =
+      content.type === 'columns' ? Math.round((content.value[0].cols / cols) * 100)
+      : content.type === 'rows' ? Math.round((content.value[0].rows / rows) * 100)
+      : 50;
+
+    setTabs((prev) => {
+      const existing = prev.find(t => t.panes.some(p => window.pty_ids.includes(p.id)));
+      const panes = window.pty_ids.map(id => existing?.panes.find(p => p.id === id) ?? { id });
+      if (existing) {
+        return prev.map((tab) => tab !== existing ? tab : {
+          ...tab,
+          title: tab.customName || window.name || tab.title,
+          panes,
+          focusedPaneId: panes.some(p => p.id === tab.focusedPaneId) ? tab.focusedPaneId : panes[0].id,
+          splitLayout,
+          splitRatio,
+        });
+      }
+      const newTab: Tab = {
+        id: window.pty_ids[0],
+        title: window.name || 'tmux',
+        panes,
+        focusedPaneId: panes[0].id,
+        splitLayout,
+        splitRatio,
+      };
+      return [...prev, newTab];
+    });
+  }, []);
+
   const closeTab = useCallback((tabId: number) => {
     // Use functional state update to avoid depending on tabs in closure
     setTabs((prevTabs) => {
@@ -299,5 +337,6 @@ export function useTabManagement(
     updateTabRemoteState,
     addSSHTab,
     addLocalTab,
+    syncTmuxWindow,
   };
 }
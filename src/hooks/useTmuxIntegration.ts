@@ -0,0 +1,41 @@
+/**
+ * Hook that mirrors tmux control-mode windows as tabs.
+ * The backend creates a PTY per tmux pane and reports window layouts on
+ * `tmux-window-changed:{gatewayId}`; panes closed in tmux end their PTYs, which
+ * closes them here like any exited shell.
+ */
+
+import { useEffect, useMemo } from 'react';
+import { listen } from '@tauri-apps/api/event';
+import { createLogger } from '../utils/logger';
+import type { Tab } from './useTabManagement';
+import type { TmuxWindow } from '../types/tmux';
+
+const log = createLogger('TmuxIntegration');
+
+interface UseTmuxIntegrationProps {
+  tabs: Tab[];
+  syncTmuxWindow: (window: TmuxWindow) => void;
+}
+
+export function useTmuxIntegration({ tabs, syncTmuxWindow }: UseTmuxIntegrationProps): void {
+  // Any pane may be a gateway; re-subscribe only when the set of panes changes
+  const paneKey = useMemo(
+    () => tabs.flatMap(tab => tab.panes.map(pane => pane.id)).sort((a, b) => a - b).join(','),
+    [tabs]
+  );
+
+  useEffect(() => {
+    if (!paneKey) return;
+    const unlistenPromises = paneKey.split(',').map(id =>
+      listen<TmuxWindow>(`tmux-window-changed:${id}`, (event) => {
+        log.debug(`tmux window @${event.payload.window_id} changed`, event.payload);
+        syncTmuxWindow(event.payload);
+      })
+    );
+
+    return () => {
+      unlistenPromises.forEach(promise => promise.then(unlisten => unlisten()));
+    };
+  }, [paneKey, syncTmuxWindow]);
+}
@@ -1,5 +1,6 @@
 import { invoke } from '@tauri-apps/api/core';
-import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+import { type UnlistenFn } from '@tauri-apps/api/event';
+import { listenPtyText } from './ptyListeners';
 
 const MAX_OUTPUT_BYTES = 2 * 1024 * 1024; // 2MB cap to prevent UI freeze
 
@@ -73,8 +74,7 @@ export async function executeInPty(options: ExecuteInPtyOptions): Promise<Execut
     
     // Listen for PTY output
     try {
-      unlisten = await listen<string>(`pty-data:${terminalId}`, (event) => {
-        const data = event.payload;
+      unlisten = await listenPtyText(terminalId, (data) => {
 
         // Guard against runaway output consuming memory and freezing the UI
         if (outputBuffer.length + data.length > MAX_OUTPUT_BYTES) {
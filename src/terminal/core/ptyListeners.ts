@@ -1,23 +1,111 @@
-import { listen } from '@tauri-apps/api/event';
+import { Channel, invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 
 export interface PtyListenerHandle {
   cleanup: () => void;
 }
 
+type PtyTextListener = (data: string) => void;
+
+// Decoded-text subscribers for PTYs whose output arrives over a raw channel
+const textListeners = new Map<number, Set<PtyTextListener>>();
+
+/**
+ * Listen for PTY output as text, regardless of transport.
+ * The backend sends each chunk either as a `pty-data:{id}` event or as a raw
+ * channel frame (never both), so listening on both paths yields no duplicates.
+ */
+export async function listenPtyText(id: number, onText: PtyTextListener): Promise<UnlistenFn> {
+  let listeners = textListeners.get(id);
+  if (!listeners) {
+    listeners = new Set();
+    textListeners.set(id, listeners);
+  }
+  listeners.add(onText);
+
+  const unlistenEvent = await listen<string>(`pty-data:${id}`, (event) => {
+    if (typeof event.payload === 'string') {
+      onText(event.payload);
+    }
+  });
+
+  return () => {
+    listeners.delete(onText);
+    if (listeners.size === 0 && textListeners.get(id) === listeners) {
+      textListeners.delete(id);
+    }
+    unlistenEvent();
+  };
+}
+
+// Batch acks so a busy terminal sends at most one per interval
+const ACK_INTERVAL_MS = 16;
+
+const noAck = () => {};
+
+/**
+ * Deliver PTY output to `onData`. Call `written` once a frame has been rendered
+ * (e.g. from xterm's write callback); the backend pauses reading when too many
+ * channel frames are outstanding. Event frames are not counted, so their
+ * `written` does nothing.
+ */
 export function attachPtyDataListener(params: {
   id: number;
-  onData: (data: string) => void;
+  onData: (data: string | Uint8Array, written: () => void) => void;
 }): PtyListenerHandle {
+  let pendingAcks = 0;
+  let ackTimer: ReturnType<typeof setTimeout> | null = null;
+  const written = () => {
+    pendingAcks += 1;
+    if (ackTimer === null) {
+      ackTimer = setTimeout(() => {
+        ackTimer = null;
+        const frames = pendingAcks;
+        pendingAcks = 0;
+        invoke('ack_pty_output', { id: params.id, frames }).catch(() => {});
+      }, ACK_INTERVAL_MS);
+    }
+  };
+
+  // Event path: output emitted before the channel is attached, replay panes,
+  // and the fallback if the channel fails
   const unlistenPromise = listen<string>(`pty-data:${params.id}`, (event) => {
     // Validate payload is a string before processing
     if (typeof event.payload === 'string') {
-      params.onData(event.payload);
+      params.onData(event.payload, noAck);
     }
   });
 
+  // Raw byte frames; xterm decodes UTF-8 across frame boundaries itself
+  const decoder = new TextDecoder();
+  const channel = new Channel<ArrayBuffer | number[]>();
+  channel.onmessage = (message) => {
+    const bytes = new Uint8Array(message);
+    params.onData(bytes, written);
+
+    const listeners = textListeners.get(params.id);
+    if (listeners && listeners.size > 0) {
+      const text = decoder.decode(bytes, { stream: true });
+      listeners.forEach((listener) => listener(text));
+    }
+  };
+
+  const attached = invoke('attach_pty_channel', { id: params.id, channel }).then(
+    () => true,
+    () => false,
+  );
+
   return {
     cleanup: () => {
+      if (ackTimer !== null) {
+        clearTimeout(ackTimer);
+      }
       unlistenPromise.then((f) => f());
+      attached.then((ok) => {
+        if (ok) {
+          invoke('detach_pty_channel', { id: params.id }).catch(() => {});
+        }
+      });
     },
   };
 }
@@ -126,7 +126,7 @@ export function createTerminalWiring(params: {
 
     const ptyDataListener = attachPtyDataListener({
         id,
-        onData: (data) => term.write(data),
+        onData: (data, written) => term.write(data, written),
     });
 
     const focusTimeoutId = window.setTimeout(() => {
@@ -84,6 +84,9 @@ export interface SSHProfile {
   /** Show alert when connection drops */
   alertOnDisconnect?: boolean;
   
+  /** Run the ssh command again when health checks find the connection lost, then return to the last remote directory */
+  autoReconnect?: boolean;
+  
   // Metadata
   
   /** Display order within the group (for drag/drop sorting) */
@@ -152,6 +155,9 @@ export interface SSHConfigHost {
   
   /** Other SSH options */
   options?: Record<string, string>;
+  
+  /** Config problems met while resolving this host (e.g. unsupported Match criteria) */
+  warnings?: string[];
 }
 
 /**
@@ -199,6 +205,46 @@ export interface PortForwardHealth {
   error?: string;
 }
 
+/**
+ * Status of a port forward run by the backend supervisor
+ * (`list_port_forwards`, `port-forward-status` events)
+ */
+export interface PortForwardInfo {
+  profileId: string;
+  forward: PortForward;
+  state: 'starting' | 'active' | 'restarting' | 'stopped';
+  /** Dedicated `ssh -N` process or an existing ControlMaster connection */
+  transport?: 'process' | 'control-master';
+  pid?: number;
+  restarts: number;
+  lastError?: string;
+  /** Delay before the next attempt while restarting */
+  retryInMs?: number;
+  /** Unix time in milliseconds of the last state change */
+  updatedAt: number;
+}
+
+/**
+ * Payload of the `ssh-degraded`, `ssh-recovered`, `ssh-disconnected` and
+ * `ssh-reconnected` events sent by health checks of profile sessions
+ */
+export interface SshHealthEvent {
+  ptyId: number;
+  profileId: string;
+  /**
+   * `host:port` the checks connect to (the first jump host, if any); unset
+   * when the profile connects through a ProxyCommand, which is not probed
+   */
+  target?: string;
+  reason?: string;
+  consecutiveFailures: number;
+  lastLatencyMs?: number;
+  /** The profile has `alertOnDisconnect` set */
+  alert: boolean;
+  /** The profile's ssh command is being run again */
+  reconnecting: boolean;
+}
+
 /**
  * Profile groups for organization
  */
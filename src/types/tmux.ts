@@ -0,0 +1,26 @@
+/**
+ * tmux control-mode (`tmux -CC`) types, mirroring src-tauri/src/pty/tmux
+ */
+
+/** A cell of a tmux window layout */
+export interface TmuxLayoutCell {
+  cols: number;
+  rows: number;
+  x: number;
+  y: number;
+  content:
+    | { type: 'pane'; value: number }
+    | { type: 'columns'; value: TmuxLayoutCell[] }
+    | { type: 'rows'; value: TmuxLayoutCell[] };
+}
+
+/** A tmux window, shown as a tab */
+export interface TmuxWindow {
+  /** PTY running `tmux -CC` */
+  gateway_id: number;
+  window_id: number;
+  name: string;
+  layout: TmuxLayoutCell;
+  /** Virtual PTY IDs of the window's panes, in layout order */
+  pty_ids: number[];
+}